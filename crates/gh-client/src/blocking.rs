@@ -0,0 +1,181 @@
+//! A synchronous wrapper over [`crate::GithubEnvClient`] for callers that are
+//! not running inside an async runtime, such as build scripts.
+//!
+//! Rather than a second implementation of every request on
+//! `reqwest::blocking`, each client owns a single-threaded Tokio runtime which
+//! drives the async client to completion, so that pagination, retries, rate
+//! limiting and cassettes behave exactly as they do for the async client.
+//! `reqwest::blocking` itself works the same way, running a Tokio runtime on
+//! a background thread.
+//!
+//! Blocking on a runtime from within another one panics in Tokio, so these
+//! functions return an error instead when called from async code; use the
+//! async client there.  A client must also not be dropped inside an async
+//! runtime, which panics when its own runtime shuts down.
+
+use std::{collections::HashMap, future::Future};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::{
+    EnvironmentVariable, HttpOptions, OrganizationRepository, OrganizationSecretVisibility,
+    ProtectionRule, ProtectionRuleApp, PublicKey, PublicKeyCache, RateLimitStatus, Repository,
+    RequestStats, Reviewer, Secret,
+};
+
+/// Blocking version of [`crate::list_organization_repositories`].
+pub fn list_organization_repositories(
+    username: Option<String>,
+    token: &str,
+    organization: &str,
+    http_options: &HttpOptions,
+) -> Result<Vec<OrganizationRepository>> {
+    block_on(
+        &new_runtime()?,
+        crate::list_organization_repositories(username, token, organization, http_options),
+    )
+}
+
+/// Creates the runtime a blocking client drives requests on, failing if
+/// called from within another runtime.
+fn new_runtime() -> Result<Runtime> {
+    check_not_in_runtime()?;
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+/// Runs a request to completion on `runtime`, failing rather than panicking
+/// if called from within another runtime.
+fn block_on<T>(runtime: &Runtime, future: impl Future<Output = Result<T>>) -> Result<T> {
+    check_not_in_runtime()?;
+    runtime.block_on(future)
+}
+
+fn check_not_in_runtime() -> Result<()> {
+    match Handle::try_current() {
+        Ok(_) => Err(eyre!(
+            "The blocking client can't be used from within an async runtime; use gh_client::GithubEnvClient instead"
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Generates blocking versions of methods on the async client.
+macro_rules! blocking_methods {
+    ($(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            #[doc = concat!("Blocking version of [`crate::GithubEnvClient::", stringify!($name), "`].")]
+            pub fn $name(&self $(, $arg: $ty)*) -> $ret {
+                block_on(&self.runtime, self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Blocking client over Github's environment and actions APIs.
+#[derive(Debug)]
+pub struct GithubEnvClient {
+    inner: crate::GithubEnvClient,
+    runtime: Runtime,
+}
+
+impl GithubEnvClient {
+    /// Blocking version of [`crate::GithubEnvClient::init`].
+    pub fn init(
+        username: Option<String>,
+        token: String,
+        repository_owner: &str,
+        repository_name: &str,
+        http_options: &HttpOptions,
+    ) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let inner = block_on(
+            &runtime,
+            crate::GithubEnvClient::init(
+                username,
+                token,
+                repository_owner,
+                repository_name,
+                http_options,
+            ),
+        )?;
+
+        Ok(Self { inner, runtime })
+    }
+
+    /// The id sent with every request as `X-Correlation-Id`, if any.
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.inner.correlation_id()
+    }
+
+    /// The repository the client was initialized for.
+    pub fn repository(&self) -> &Repository {
+        self.inner.repository()
+    }
+
+    /// The login of the repository owner.
+    pub fn repository_owner(&self) -> &str {
+        self.inner.repository_owner()
+    }
+
+    /// The name of the repository.
+    pub fn repository_name(&self) -> &str {
+        self.inner.repository_name()
+    }
+
+    /// The repository's default branch.
+    pub fn default_branch(&self) -> &str {
+        self.inner.default_branch()
+    }
+
+    /// Blocking version of [`crate::GithubEnvClient::request_stats`].
+    pub fn request_stats(&self) -> RequestStats {
+        self.inner.request_stats()
+    }
+
+    /// The secrets public keys the client has fetched.
+    pub fn public_key_cache(&self) -> &PublicKeyCache {
+        self.inner.public_key_cache()
+    }
+
+    blocking_methods! {
+        fn list_environments(&self) -> Result<Vec<String>>;
+        fn list_environments_graphql(&self) -> Result<Vec<String>>;
+        fn upsert_environment(&self, environment_name: &str) -> Result<()>;
+        fn delete_environment(&self, environment_name: &str) -> Result<()>;
+        fn list_protection_rules(&self, environment_name: &str) -> Result<Vec<ProtectionRule>>;
+        fn list_protection_rule_apps(&self, environment_name: &str) -> Result<Vec<ProtectionRuleApp>>;
+        fn enable_protection_rule(&self, environment_name: &str, integration_id: usize) -> Result<ProtectionRule>;
+        fn disable_protection_rule(&self, environment_name: &str, rule_id: usize) -> Result<()>;
+        fn create_environment_variable(&self, environment_name: &str, key: &str, value: &str) -> Result<()>;
+        fn get_environment_variable(&self, environment_name: &str, key: &str) -> Result<Option<String>>;
+        fn get_environment_variable_details(&self, environment_name: &str, key: &str) -> Result<Option<EnvironmentVariable>>;
+        fn list_environment_variables(&self, environment_name: &str) -> Result<HashMap<String, String>>;
+        fn list_environment_variable_details(&self, environment_name: &str) -> Result<Vec<EnvironmentVariable>>;
+        fn update_environment_variable(&self, environment_name: &str, key: &str, value: &str) -> Result<()>;
+        fn upsert_environment_variable(&self, environment_name: &str, key: &str, value: &str) -> Result<()>;
+        fn delete_environment_variable(&self, environment_name: &str, key: &str) -> Result<()>;
+        fn get_environment_public_key(&self, environment_name: &str) -> Result<PublicKey>;
+        fn list_environment_secrets(&self, environment_name: &str) -> Result<Vec<Secret>>;
+        fn put_environment_secret(&self, environment_name: &str, name: &str, value: &str) -> Result<()>;
+        fn get_environment_secret(&self, environment_name: &str, name: &str) -> Result<Option<Secret>>;
+        fn environment_secret_exists(&self, environment_name: &str, name: &str) -> Result<bool>;
+        fn delete_environment_secret(&self, environment_name: &str, name: &str) -> Result<()>;
+        fn get_repository_id(&self, owner: &str, name: &str) -> Result<usize>;
+        fn get_repository_file(&self, owner: &str, name: &str, path: &str, reference: &str) -> Result<Option<String>>;
+        fn get_organization_public_key(&self) -> Result<PublicKey>;
+        fn get_organization_secret(&self, name: &str) -> Result<Option<Secret>>;
+        fn put_organization_secret(&self, name: &str, value: &str, visibility: OrganizationSecretVisibility) -> Result<()>;
+        fn set_organization_secret_repositories(&self, name: &str, repository_ids: &[usize]) -> Result<()>;
+        fn dispatch_workflow(&self, workflow: &str, reference: &str, inputs: &HashMap<String, String>) -> Result<()>;
+        fn create_repository_dispatch(&self, event_type: &str, client_payload: &serde_json::Value) -> Result<()>;
+        fn get_repository_variable(&self, key: &str) -> Result<Option<String>>;
+        fn create_repository_variable(&self, key: &str, value: &str) -> Result<()>;
+        fn delete_repository_variable(&self, key: &str) -> Result<()>;
+        fn list_repository_variables(&self) -> Result<HashMap<String, String>>;
+        fn list_organization_variables(&self) -> Result<HashMap<String, String>>;
+        fn get_rate_limit(&self) -> Result<RateLimitStatus>;
+        fn resolve_reviewer(&self, name: &str) -> Result<Reviewer>;
+        fn get_authenticated_login(&self) -> Result<String>;
+    }
+}
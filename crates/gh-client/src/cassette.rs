@@ -0,0 +1,206 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use reqwest::{Client, Request, Response};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Whether a cassette captures live interactions or serves recorded ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send every request to Github and append the interaction to the
+    /// cassette file.
+    Record,
+    /// Never touch the network; answer every request from the cassette file.
+    Replay,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
+}
+
+impl RecordedRequest {
+    /// Captures the parts of a request used to match it against a recording.
+    /// Headers are deliberately left out so that tokens never end up in a
+    /// cassette file.
+    fn from_request(request: &Request) -> Self {
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|bytes| serde_json::from_slice(bytes).ok());
+
+        Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Result<Response> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+
+        Ok(Response::from(builder.body(self.body.clone())?))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Default)]
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    replayed: Vec<bool>,
+}
+
+/// A JSON file of recorded Github API interactions.  Cloning a cassette
+/// shares the underlying recording.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Arc<Mutex<CassetteState>>,
+}
+
+impl Cassette {
+    /// Creates a cassette that records every interaction to the file at
+    /// `path`, replacing whatever it contained before.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Record,
+            state: Arc::default(),
+        }
+    }
+
+    /// Loads the interactions recorded in the file at `path` for replay.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| eyre!("Error reading cassette {}: {}", path.display(), e))?;
+        let interactions: Vec<Interaction> = serde_json::from_str(&contents)
+            .map_err(|e| eyre!("Error parsing cassette {}: {}", path.display(), e))?;
+
+        debug!(
+            "Loaded {} interactions from cassette {}",
+            interactions.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            path,
+            mode: CassetteMode::Replay,
+            state: Arc::new(Mutex::new(CassetteState {
+                replayed: vec![false; interactions.len()],
+                interactions,
+            })),
+        })
+    }
+
+    /// The mode this cassette was opened in.
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub(crate) async fn send(&self, client: &Client, request: Request) -> Result<Response> {
+        let recorded_request = RecordedRequest::from_request(&request);
+
+        match self.mode {
+            CassetteMode::Record => {
+                let response = client.execute(request).await?;
+                let headers = response
+                    .headers()
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.to_string()))
+                    })
+                    .collect();
+                let recorded_response = RecordedResponse {
+                    status: response.status().as_u16(),
+                    headers,
+                    body: response.text().await?,
+                };
+
+                self.append(Interaction {
+                    request: recorded_request,
+                    response: recorded_response.clone(),
+                })?;
+
+                recorded_response.to_response()
+            }
+            CassetteMode::Replay => {
+                let response = self.next_matching(&recorded_request)?;
+                response.to_response()
+            }
+        }
+    }
+
+    /// Appends an interaction and rewrites the cassette file, so that a
+    /// recording is usable even if the run fails part way through.
+    fn append(&self, interaction: Interaction) -> Result<()> {
+        let mut state = self.state.lock().expect("cassette lock poisoned");
+        debug!(
+            "Recording {} {} to cassette {}",
+            interaction.request.method,
+            interaction.request.url,
+            self.path.display()
+        );
+        state.interactions.push(interaction);
+
+        let contents = serde_json::to_string_pretty(&state.interactions)?;
+        std::fs::write(&self.path, contents)
+            .map_err(|e| eyre!("Error writing cassette {}: {}", self.path.display(), e))
+    }
+
+    /// Finds the first interaction matching the request that has not already
+    /// been replayed.  Matching on content rather than position keeps replays
+    /// stable even though environments are synced in hash map order.
+    fn next_matching(&self, request: &RecordedRequest) -> Result<RecordedResponse> {
+        let mut state = self.state.lock().expect("cassette lock poisoned");
+        let CassetteState {
+            interactions,
+            replayed,
+        } = &mut *state;
+
+        let index = interactions
+            .iter()
+            .zip(replayed.iter())
+            .position(|(interaction, replayed)| !replayed && &interaction.request == request)
+            .ok_or_else(|| {
+                eyre!(
+                    "No unplayed interaction in cassette {} matches {} {}",
+                    self.path.display(),
+                    request.method,
+                    request.url
+                )
+            })?;
+
+        debug!("Replaying {} {} from cassette", request.method, request.url);
+        replayed[index] = true;
+
+        Ok(interactions[index].response.clone())
+    }
+}
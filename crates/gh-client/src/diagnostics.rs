@@ -0,0 +1,151 @@
+use color_eyre::{eyre::eyre, Report};
+use reqwest::{header::HeaderMap, Method, Response, Url};
+
+use crate::error::ErrorResponse;
+
+/// The kind of credential a token is, from its prefix.  See:
+/// https://github.blog/2021-04-05-behind-githubs-new-authentication-token-formats/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum TokenKind {
+    /// `github_pat_`
+    FineGrained,
+    /// `ghp_`
+    Classic,
+    /// `ghs_`, a Github App installation token.
+    App,
+    /// `gho_`
+    OAuth,
+    #[default]
+    Unknown,
+}
+
+impl TokenKind {
+    pub(crate) fn of(token: &str) -> Self {
+        match token {
+            token if token.starts_with("github_pat_") => TokenKind::FineGrained,
+            token if token.starts_with("ghp_") => TokenKind::Classic,
+            token if token.starts_with("ghs_") => TokenKind::App,
+            token if token.starts_with("gho_") => TokenKind::OAuth,
+            _ => TokenKind::Unknown,
+        }
+    }
+}
+
+/// Turns a 403 from Github into an error naming what the token is missing,
+/// rather than just the status code.
+pub(crate) async fn forbidden_error(
+    method: &Method,
+    url: &Url,
+    token_kind: TokenKind,
+    response: Response,
+) -> Report {
+    let headers = response.headers().clone();
+    let message = response
+        .json::<ErrorResponse>()
+        .await
+        .unwrap_or_default()
+        .message;
+
+    let request = format!("{} {}", method, url.path());
+    if header(&headers, "x-ratelimit-remaining").as_deref() == Some("0") {
+        return eyre!(
+            "Github rate limit exceeded for {} (resets at unix time {}): {}",
+            request,
+            header(&headers, "x-ratelimit-reset").unwrap_or_default(),
+            message
+        );
+    }
+
+    let hint = match token_kind {
+        TokenKind::FineGrained | TokenKind::App => {
+            let permissions = header(&headers, "x-accepted-github-permissions")
+                .map(|accepted| describe_permissions(&accepted))
+                .unwrap_or_else(|| guess_permission(url.path()).to_string());
+            let holder = if token_kind == TokenKind::App {
+                "Github App installation"
+            } else {
+                "fine-grained token"
+            };
+            format!("The {} needs the {} permission.", holder, permissions)
+        }
+        TokenKind::Classic | TokenKind::OAuth => {
+            let scopes = header(&headers, "x-accepted-oauth-scopes")
+                .filter(|scopes| !scopes.is_empty())
+                .unwrap_or_else(|| "repo".to_string());
+            format!("The token needs one of these scopes: {}.", scopes)
+        }
+        TokenKind::Unknown => format!(
+            "The token may be missing the {} permission.",
+            guess_permission(url.path())
+        ),
+    };
+
+    eyre!(
+        "Github denied {} (403 Forbidden): {}. {}",
+        request,
+        message,
+        hint
+    )
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Describes an `X-Accepted-GitHub-Permissions` header, such as
+/// `actions_variables=write,environments=write; actions_variables=admin`,
+/// in the terms used by Github's token settings page.  Sets separated by `;`
+/// are alternatives, and only the first is described.
+fn describe_permissions(accepted: &str) -> String {
+    let first = accepted.split(';').next().unwrap_or_default();
+
+    first
+        .split(',')
+        .filter_map(|permission| {
+            let (name, level) = permission.trim().split_once('=')?;
+            let level = match level {
+                "read" => "Read-only",
+                "write" | "admin" => "Read and write",
+                other => other,
+            };
+            Some(format!("\"{}: {}\"", permission_name(name), level))
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn permission_name(name: &str) -> String {
+    match name {
+        "actions" => "Actions".to_string(),
+        "actions_variables" => "Variables".to_string(),
+        "administration" => "Administration".to_string(),
+        "contents" => "Contents".to_string(),
+        "environments" => "Environments".to_string(),
+        "metadata" => "Metadata".to_string(),
+        "organization_actions_variables" => "Organization variables".to_string(),
+        "organization_secrets" => "Organization secrets".to_string(),
+        "secrets" => "Secrets".to_string(),
+        other => other.replace('_', " "),
+    }
+}
+
+/// A best guess at the permission an endpoint needs, for when Github doesn't
+/// say.
+fn guess_permission(path: &str) -> &'static str {
+    if path.starts_with("/orgs/") && path.contains("/secrets") {
+        "\"Organization secrets: Read and write\""
+    } else if path.contains("/secrets") {
+        "\"Secrets: Read and write\""
+    } else if path.contains("/variables") {
+        "\"Variables: Read and write\""
+    } else if path.contains("/environments") {
+        "\"Environments: Read and write\""
+    } else if path.contains("/dispatches") {
+        "\"Actions: Read and write\" or \"Contents: Read and write\""
+    } else {
+        "\"Metadata: Read-only\""
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use reqwest::{Method, Response, StatusCode};
+use serde::Deserialize;
+
+/// The body Github returns with an error status.  See:
+/// https://docs.github.com/en/rest/using-the-rest-api/troubleshooting-the-rest-api
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ErrorResponse {
+    #[serde(default)]
+    pub(crate) message: String,
+    #[serde(default)]
+    pub(crate) documentation_url: Option<String>,
+}
+
+/// An error status returned by the Github API, along with the reason Github
+/// gave for it, such as "Variables quota exceeded".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhApiError {
+    pub status: StatusCode,
+    /// The path of the request, without the host or query string.
+    pub path: String,
+    /// Github's explanation of the error, if the body had one.
+    pub message: Option<String>,
+    /// A link to the documentation of the endpoint or error.
+    pub documentation_url: Option<String>,
+}
+
+impl fmt::Display for GhApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Github returned {} for {}", self.status, self.path)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let Some(documentation_url) = &self.documentation_url {
+            write!(f, " (see {})", documentation_url)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for GhApiError {}
+
+/// A write refused by a client built with [`crate::HttpOptions::read_only`],
+/// before anything was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyError {
+    pub method: Method,
+    /// The path of the request, without the host or query string.
+    pub path: String,
+}
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Refusing to {} {} in read-only mode",
+            self.method, self.path
+        )
+    }
+}
+
+impl std::error::Error for ReadOnlyError {}
+
+/// Like [`Response::error_for_status`], but reads Github's error body into
+/// the error rather than discarding it.
+pub(crate) async fn error_for_status(response: Response) -> Result<Response, GhApiError> {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(response);
+    }
+
+    let path = response.url().path().to_string();
+    let body = response.json::<ErrorResponse>().await.unwrap_or_default();
+    Err(GhApiError {
+        status,
+        path,
+        message: Some(body.message).filter(|message| !message.is_empty()),
+        documentation_url: body.documentation_url,
+    })
+}
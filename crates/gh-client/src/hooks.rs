@@ -0,0 +1,127 @@
+use std::fmt;
+
+use color_eyre::{eyre::eyre, Result};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Request, Url,
+};
+
+/// The host of github.com's API, which the client's requests are written
+/// against.
+const GITHUB_API_HOST: &str = "api.github.com";
+
+/// Changes every request a client sends, e.g. to add the authentication
+/// headers of an egress proxy or an audit id.  Hooks see a request once the
+/// client's own headers are set, and run in the order they are given in
+/// [`crate::HttpOptions::request_hooks`].  An error aborts the request.
+///
+/// Requests made with a token carry it in the `Authorization` header, which
+/// hooks can read, so only install hooks you trust with it.
+pub trait RequestHook: fmt::Debug + Send + Sync {
+    fn on_request(&self, request: &mut Request) -> Result<()>;
+}
+
+/// Adds the same headers to every request, replacing any of the same name.
+/// Values are left out of its `Debug` output, as they are often
+/// credentials.
+#[derive(Clone, Default)]
+pub struct StaticHeaders {
+    headers: HeaderMap,
+}
+
+impl StaticHeaders {
+    /// Validates the header names and values.
+    pub fn new<N, V>(headers: impl IntoIterator<Item = (N, V)>) -> Result<Self>
+    where
+        N: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            let (name, value) = (name.as_ref(), value.as_ref());
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| eyre!("Invalid request header name '{}'", name))?;
+            let mut header_value = HeaderValue::from_str(value)
+                .map_err(|_| eyre!("Invalid value for request header '{}'", name))?;
+            header_value.set_sensitive(true);
+            map.append(header_name, header_value);
+        }
+
+        Ok(Self { headers: map })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+impl RequestHook for StaticHeaders {
+    fn on_request(&self, request: &mut Request) -> Result<()> {
+        let headers = request.headers_mut();
+        for name in self.headers.keys() {
+            headers.remove(name);
+        }
+        for (name, value) in &self.headers {
+            headers.append(name.clone(), value.clone());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for StaticHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticHeaders")
+            .field("names", &self.headers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Sends requests to the API of a Github Enterprise Server instead of
+/// github.com's: REST requests go to `https://HOST/api/v3/...` and GraphQL
+/// ones to `https://HOST/api/graphql`.  Requests for other hosts, such as
+/// the next pages that a server links to, are left alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiHost {
+    host: String,
+}
+
+impl ApiHost {
+    /// A hook for `host`, e.g. `github.example.com` as given in `GH_HOST` or
+    /// its API URL `https://github.example.com/api/v3`, or `None` for
+    /// github.com, whose requests need no change.
+    pub fn new(host: &str) -> Option<Self> {
+        let host = host.trim().trim_end_matches('/');
+        let host = host.strip_prefix("https://").unwrap_or(host);
+        let host = host.strip_suffix("/api/v3").unwrap_or(host);
+        let github = host.is_empty()
+            || host.eq_ignore_ascii_case("github.com")
+            || host.eq_ignore_ascii_case(GITHUB_API_HOST);
+        (!github).then(|| Self {
+            host: host.to_lowercase(),
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+}
+
+impl RequestHook for ApiHost {
+    fn on_request(&self, request: &mut Request) -> Result<()> {
+        let url = request.url();
+        if url.host_str() != Some(GITHUB_API_HOST) {
+            return Ok(());
+        }
+
+        let path = match url.path() {
+            "/graphql" => "/api/graphql".to_string(),
+            path => format!("/api/v3{}", path),
+        };
+        let mut rewritten = Url::parse(&format!("https://{}{}", self.host, path))
+            .map_err(|e| eyre!("Invalid Github host '{}': {}", self.host, e))?;
+        rewritten.set_query(url.query());
+        *request.url_mut() = rewritten;
+        Ok(())
+    }
+}
@@ -0,0 +1,2048 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{eyre::eyre, Result};
+use diagnostics::TokenKind;
+use error::error_for_status;
+use pagination::PageBody;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use reviewers::ReviewerCache;
+use serde::Deserialize;
+use stats::{RateLimitResponse, RequestCounter};
+use tracing::debug;
+
+#[cfg(feature = "cassette")]
+pub use cassette::{Cassette, CassetteMode};
+pub use error::{GhApiError, ReadOnlyError};
+pub use hooks::{ApiHost, RequestHook, StaticHeaders};
+pub use pagination::{Page, Paginator};
+pub use path::encode_path_segment;
+pub use public_keys::{PublicKeyCache, PublicKeyScope};
+pub use releases::{download_release_asset, get_release, Release, ReleaseAsset};
+pub use response_cache::ResponseCache;
+pub use reviewers::{Reviewer, ReviewerType};
+pub use secrets::{encrypt_secret, OrganizationSecretVisibility, PublicKey};
+pub use stats::{RateLimit, RateLimitResource, RateLimitStatus, RequestStats};
+pub use throttle::Throttle;
+pub use types::{EnvironmentVariable, ProtectionRule, ProtectionRuleApp, Repository, Secret, User};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "cassette")]
+mod cassette;
+mod diagnostics;
+mod error;
+mod hooks;
+mod pagination;
+mod path;
+mod public_keys;
+mod releases;
+mod response_cache;
+mod reviewers;
+mod secrets;
+mod stats;
+mod throttle;
+mod timer;
+mod types;
+
+/// Maximum page size accepted by the environments list endpoint.
+const ENVIRONMENTS_PER_PAGE: usize = 100;
+
+/// Maximum page size accepted by the environment variables list endpoint.
+const VARIABLES_PER_PAGE: usize = 30;
+
+/// Maximum page size accepted by the environment secrets list endpoint.
+const SECRETS_PER_PAGE: usize = 100;
+
+/// Maximum page size accepted by the organization repositories list endpoint.
+const REPOSITORIES_PER_PAGE: usize = 100;
+
+/// Maximum page size accepted by the available protection rule apps list
+/// endpoint.
+const PROTECTION_RULE_APPS_PER_PAGE: usize = 100;
+
+/// GraphQL query listing every environment of a repository, one page at a time.
+const LIST_ENVIRONMENTS_QUERY: &str = r#"
+query($owner: String!, $name: String!, $cursor: String) {
+  repository(owner: $owner, name: $name) {
+    environments(first: 100, after: $cursor) {
+      nodes {
+        name
+      }
+      pageInfo {
+        hasNextPage
+        endCursor
+      }
+    }
+  }
+}
+"#;
+
+/// GraphQL query for the account a token belongs to.
+const VIEWER_QUERY: &str = "query { viewer { login } }";
+
+/// The GraphQL documents the client sends that only read, which are the only
+/// ones allowed in [`HttpOptions::read_only`] mode.
+const READ_QUERIES: [&str; 2] = [LIST_ENVIRONMENTS_QUERY, VIEWER_QUERY];
+
+/// The id of a user or team.
+#[derive(Debug, Deserialize)]
+struct AccountResponse {
+    id: usize,
+}
+
+/// A repository listed by [`list_organization_repositories`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OrganizationRepository {
+    pub name: String,
+    /// The `owner/name` pair.
+    pub full_name: String,
+    pub archived: bool,
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListEnvironmentsResponse {
+    environments: Vec<Environment>,
+}
+
+impl PageBody for ListEnvironmentsResponse {
+    type Item = String;
+
+    fn into_items(self) -> (Vec<String>, Option<usize>) {
+        let names = self.environments.into_iter().map(|env| env.name).collect();
+        (names, None)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Environment {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileContentsResponse {
+    content: String,
+    encoding: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VariableResponse {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListVariablesResponse {
+    total_count: usize,
+    variables: Vec<EnvironmentVariable>,
+}
+
+impl PageBody for ListVariablesResponse {
+    type Item = EnvironmentVariable;
+
+    fn into_items(self) -> (Vec<EnvironmentVariable>, Option<usize>) {
+        (self.variables, Some(self.total_count))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListSecretsResponse {
+    total_count: usize,
+    secrets: Vec<Secret>,
+}
+
+impl PageBody for ListSecretsResponse {
+    type Item = Secret;
+
+    fn into_items(self) -> (Vec<Secret>, Option<usize>) {
+        (self.secrets, Some(self.total_count))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListProtectionRulesResponse {
+    custom_deployment_protection_rules: Vec<ProtectionRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListProtectionRuleAppsResponse {
+    total_count: usize,
+    available_custom_deployment_protection_rule_integrations: Vec<ProtectionRuleApp>,
+}
+
+impl PageBody for ListProtectionRuleAppsResponse {
+    type Item = ProtectionRuleApp;
+
+    fn into_items(self) -> (Vec<ProtectionRuleApp>, Option<usize>) {
+        (
+            self.available_custom_deployment_protection_rule_integrations,
+            Some(self.total_count),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ViewerData {
+    viewer: User,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryEnvironmentsData {
+    repository: RepositoryEnvironments,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryEnvironments {
+    environments: EnvironmentConnection,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvironmentConnection {
+    nodes: Vec<Environment>,
+    page_info: PageInfo,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+/// Settings for the underlying HTTP client.  The defaults fail requests on
+/// unresponsive networks after a bounded amount of time rather than hanging.
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Total time allowed for a single request, from connecting until the
+    /// response body has been read.
+    pub timeout: Duration,
+    /// Time allowed to establish a connection.
+    pub connect_timeout: Duration,
+    /// How long idle pooled connections are kept alive.
+    pub pool_idle_timeout: Duration,
+    /// Maximum number of idle pooled connections per host.
+    pub pool_max_idle_per_host: usize,
+    /// The product token the User-Agent header starts with, e.g.
+    /// `gh-env-sync/0.1.0`.  Defaults to this crate's name and version.
+    pub user_agent: String,
+    /// Sent with every request as `X-Correlation-Id`, so that the requests of
+    /// a single run can be attributed to it by Github support or proxies.
+    pub correlation_id: Option<String>,
+    /// Paces requests to stay under Github's secondary rate limits.
+    pub throttle: Option<Throttle>,
+    /// Secrets public keys to share between clients, or to keep on disk.
+    /// Each client caches keys in memory without one.
+    pub public_key_cache: Option<PublicKeyCache>,
+    /// Cache of read responses to refresh, or with
+    /// [`ResponseCache::offline`] to answer reads from instead of the
+    /// network.
+    pub response_cache: Option<ResponseCache>,
+    /// Called with every request before it is sent, to add headers or
+    /// otherwise change it.  See [`RequestHook`].
+    pub request_hooks: Vec<Arc<dyn RequestHook>>,
+    /// Refuse every request but reads, failing with [`ReadOnlyError`], so
+    /// that a token able to write can be used without risk of writing.
+    /// The client's own GraphQL queries are reads; any other GraphQL request
+    /// isn't.
+    pub read_only: bool,
+    /// Cassette to record requests to or replay responses from.
+    #[cfg(feature = "cassette")]
+    pub cassette: Option<Cassette>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 8,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+            correlation_id: None,
+            throttle: None,
+            public_key_cache: None,
+            response_cache: None,
+            request_hooks: Vec::new(),
+            read_only: false,
+            #[cfg(feature = "cassette")]
+            cassette: None,
+        }
+    }
+}
+
+impl HttpOptions {
+    fn build_transport(&self) -> Result<Transport> {
+        let client = Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .build()?;
+
+        Ok(Transport {
+            client,
+            user_agent: self.user_agent.clone(),
+            correlation_id: self.correlation_id.clone(),
+            throttle: self.throttle.clone(),
+            token_kind: TokenKind::Unknown,
+            counter: RequestCounter::default(),
+            response_cache: self.response_cache.clone(),
+            request_hooks: self.request_hooks.clone(),
+            read_only: self.read_only,
+            #[cfg(feature = "cassette")]
+            cassette: self.cassette.clone(),
+        })
+    }
+
+    /// Builds a transport for requests made with `token`, adding `username`
+    /// to the User-Agent.
+    fn build_authenticated_transport(
+        &self,
+        username: Option<String>,
+        token: &str,
+    ) -> Result<Transport> {
+        let mut transport = self.build_transport()?;
+        transport.token_kind = TokenKind::of(token);
+        if let Some(username) = username {
+            transport.user_agent = format!("{} ({})", transport.user_agent, username);
+        }
+
+        Ok(transport)
+    }
+}
+
+/// Simple client over Github's environment and actions APIs.
+#[derive(Debug)]
+pub struct GithubEnvClient {
+    token: String,
+    repository: Repository,
+    transport: Transport,
+    reviewers: ReviewerCache,
+    public_keys: PublicKeyCache,
+}
+
+impl GithubEnvClient {
+    /// Intializes a new GithubEnvClient from the provided arguments.  Gets
+    /// repository information from Github at creation time so that it has a
+    /// repository_id to use in future API calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - Appended to the User-Agent header (see
+    ///   [`HttpOptions::user_agent`]) in requests to the Github API, to
+    ///   identify the user making them.
+    ///
+    /// * `token` - The Github personal access token to use for authentication.f
+    ///
+    /// * `repository_owner` - The owner of the repository
+    ///
+    /// * `repository_name` - The name of the repository
+    ///
+    /// * `http_options` - Timeout and connection pool settings for the
+    ///   underlying HTTP client
+    pub async fn init(
+        username: Option<String>,
+        token: String,
+        repository_owner: &str,
+        repository_name: &str,
+        http_options: &HttpOptions,
+    ) -> Result<Self> {
+        debug!(
+            "Initializing GithubEnvClient with arguments username = {:?}, token = {}, repository_owner = {}, repository_name = {}, http_options = {:?}",
+            &username, "<token>", repository_owner, repository_name, http_options
+        );
+
+        let transport = http_options.build_authenticated_transport(username, &token)?;
+        let repository =
+            get_repository_details(&transport, &token, repository_owner, repository_name).await?;
+
+        Ok(Self {
+            token,
+            repository,
+            transport,
+            reviewers: ReviewerCache::default(),
+            public_keys: http_options.public_key_cache.clone().unwrap_or_default(),
+        })
+    }
+
+    /// The repository the client was initialized for.
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    /// The login of the repository owner.
+    pub fn repository_owner(&self) -> &str {
+        &self.repository.owner.login
+    }
+
+    /// The name of the repository.
+    pub fn repository_name(&self) -> &str {
+        &self.repository.name
+    }
+
+    /// The repository's default branch.
+    pub fn default_branch(&self) -> &str {
+        &self.repository.default_branch
+    }
+
+    /// The id sent with every request as `X-Correlation-Id`, if any.
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.transport.correlation_id.as_deref()
+    }
+
+    /// The number of requests made so far, and the rate limit they used.
+    pub fn request_stats(&self) -> RequestStats {
+        self.transport.counter.stats()
+    }
+
+    /// The secrets public keys the client has fetched.
+    pub fn public_key_cache(&self) -> &PublicKeyCache {
+        &self.public_keys
+    }
+
+    /// Lists all environments for the repository.  See
+    /// https://docs.github.com/en/rest/deployments/environments?apiVersion=2022-11-28#list-environments
+    pub async fn list_environments(&self) -> Result<Vec<String>> {
+        debug!("Listing environments for {}", self.repository.name);
+
+        let environments = self.paginate_environments().collect_all().await?;
+        debug!("Got environments: {:?}", environments);
+
+        Ok(environments)
+    }
+
+    /// The names of the repository's environments, a page at a time.
+    pub fn paginate_environments(&self) -> Paginator<'_, String> {
+        Paginator::new::<ListEnvironmentsResponse>(
+            &self.transport,
+            &self.token,
+            format!(
+                "https://api.github.com/repos/{}/{}/environments",
+                self.repository.owner.login, self.repository.name
+            ),
+            ENVIRONMENTS_PER_PAGE,
+            "environments".to_string(),
+        )
+    }
+
+    /// Lists all environments for the repository using Github's GraphQL API,
+    /// which returns up to 100 environments per request.  Only their names:
+    /// GraphQL's `Environment` has no field for its variables.  See:
+    /// https://docs.github.com/en/graphql/reference/objects#repository
+    pub async fn list_environments_graphql(&self) -> Result<Vec<String>> {
+        debug!(
+            "Listing environments for {} via GraphQL",
+            self.repository.name
+        );
+
+        let mut environments = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let request = self
+                .transport
+                .client
+                .post("https://api.github.com/graphql")
+                .with_env_client(self)
+                .json(&serde_json::json!({
+                    "query": LIST_ENVIRONMENTS_QUERY,
+                    "variables": {
+                        "owner": self.repository.owner.login,
+                        "name": self.repository.name,
+                        "cursor": cursor,
+                    }
+                }));
+            let response = self.transport.send(request).await?;
+
+            let body: GraphQLResponse<RepositoryEnvironmentsData> =
+                match error_for_status(response).await {
+                    Ok(res) => res.json().await?,
+                    Err(e) => return Err(eyre!("Error getting environments via GraphQL: {}", e)),
+                };
+
+            if let Some(errors) = body.errors {
+                let messages = errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>();
+                return Err(eyre!(
+                    "Error getting environments via GraphQL: {}",
+                    messages.join("; ")
+                ));
+            }
+
+            let connection = body
+                .data
+                .ok_or_else(|| eyre!("GraphQL response for environments contained no data"))?
+                .repository
+                .environments;
+            debug!("Got environments: {:?}", connection.nodes);
+
+            environments.extend(connection.nodes.into_iter().map(|env| env.name));
+
+            match (
+                connection.page_info.has_next_page,
+                connection.page_info.end_cursor,
+            ) {
+                (true, Some(end_cursor)) => cursor = Some(end_cursor),
+                _ => break,
+            }
+        }
+
+        Ok(environments)
+    }
+
+    /// Creates or updates a given environment.  See:
+    /// https://docs.github.com/en/rest/deployments/environments?apiVersion=2022-11-28#create-or-update-an-environment
+    pub async fn upsert_environment(&self, environment_name: &str) -> Result<()> {
+        debug!(
+            "Upserting environment {} for {}",
+            environment_name, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/environments/{}",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(environment_name)
+        );
+
+        let request = self.transport.client.put(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully upserted environment {}", environment_name);
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error upserting environment {} for repo {}: {}",
+                environment_name,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Deletes an environment.  See:
+    /// https://docs.github.com/en/rest/deployments/environments?apiVersion=2022-11-28#delete-an-environment
+    pub async fn delete_environment(&self, environment_name: &str) -> Result<()> {
+        debug!(
+            "Deleting environment {} for {}",
+            environment_name, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/environments/{}",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(environment_name)
+        );
+
+        let request = self.transport.client.delete(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully deleted environment {}", environment_name);
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error deleting environment {} for repo {}: {}",
+                environment_name,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Lists the custom deployment protection rules enabled for an
+    /// environment.  See:
+    /// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#get-all-deployment-protection-rules-for-an-environment
+    pub async fn list_protection_rules(
+        &self,
+        environment_name: &str,
+    ) -> Result<Vec<ProtectionRule>> {
+        debug!(
+            "Listing deployment protection rules for environment {}",
+            environment_name
+        );
+
+        let request = self
+            .transport
+            .client
+            .get(self.protection_rules_url(environment_name))
+            .with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(response) => {
+                let body: ListProtectionRulesResponse = response.json().await?;
+                debug!(
+                    "Got deployment protection rules for environment {}: {:?}",
+                    environment_name, body.custom_deployment_protection_rules
+                );
+                Ok(body.custom_deployment_protection_rules)
+            }
+            Err(e) => Err(eyre!(
+                "Error listing deployment protection rules for environment {}: {}",
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Lists the Github Apps installed on the repository that can be enabled
+    /// as custom deployment protection rules of an environment.  See:
+    /// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#list-custom-deployment-rule-integrations-available-for-an-environment
+    pub async fn list_protection_rule_apps(
+        &self,
+        environment_name: &str,
+    ) -> Result<Vec<ProtectionRuleApp>> {
+        debug!(
+            "Listing deployment protection rule apps for environment {}",
+            environment_name
+        );
+
+        Paginator::new::<ListProtectionRuleAppsResponse>(
+            &self.transport,
+            &self.token,
+            format!("{}/apps", self.protection_rules_url(environment_name)),
+            PROTECTION_RULE_APPS_PER_PAGE,
+            format!(
+                "deployment protection rule apps for environment {}",
+                environment_name
+            ),
+        )
+        .collect_all()
+        .await
+    }
+
+    /// Enables a Github App, by its id, as a custom deployment protection
+    /// rule of an environment.  See:
+    /// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#create-a-custom-deployment-protection-rule-on-an-environment
+    pub async fn enable_protection_rule(
+        &self,
+        environment_name: &str,
+        integration_id: usize,
+    ) -> Result<ProtectionRule> {
+        debug!(
+            "Enabling deployment protection rule app {} for environment {}",
+            integration_id, environment_name
+        );
+
+        let request = self
+            .transport
+            .client
+            .post(self.protection_rules_url(environment_name))
+            .with_env_client(self)
+            .json(&serde_json::json!({ "integration_id": integration_id }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(response) => {
+                let rule: ProtectionRule = response.json().await?;
+                debug!(
+                    "Successfully enabled deployment protection rule {} for environment {}",
+                    rule.app.slug, environment_name
+                );
+                Ok(rule)
+            }
+            Err(e) => Err(eyre!(
+                "Error enabling deployment protection rule app {} for environment {}: {}",
+                integration_id,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Disables a custom deployment protection rule of an environment, by
+    /// the rule's id rather than its app's.  See:
+    /// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#disable-a-custom-protection-rule-for-an-environment
+    pub async fn disable_protection_rule(
+        &self,
+        environment_name: &str,
+        rule_id: usize,
+    ) -> Result<()> {
+        debug!(
+            "Disabling deployment protection rule {} for environment {}",
+            rule_id, environment_name
+        );
+
+        let url = format!(
+            "{}/{}",
+            self.protection_rules_url(environment_name),
+            rule_id
+        );
+        let request = self.transport.client.delete(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully disabled deployment protection rule {} for environment {}",
+                    rule_id, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error disabling deployment protection rule {} for environment {}: {}",
+                rule_id,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    fn protection_rules_url(&self, environment_name: &str) -> String {
+        format!(
+            "https://api.github.com/repos/{}/{}/environments/{}/deployment_protection_rules",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(environment_name)
+        )
+    }
+
+    /// Creates an environment variable for the given environment.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#create-an-environment-variable
+    pub async fn create_environment_variable(
+        &self,
+        environment_name: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        debug!(
+            "Creating environment variable (key: {}, value: {}) for environment {}",
+            key, value, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/variables",
+            self.repository.id,
+            encode_path_segment(environment_name)
+        );
+
+        let request = self
+            .transport
+            .client
+            .post(url)
+            .with_env_client(self)
+            .json(&serde_json::json!({ "name": key, "value": value }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully created environment variable (key: {}, value: {}) for environment {}",
+                    key, value, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error creating environment variable (key: {}, value: {}) for environment {}: {}",
+                key,
+                value,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Gets the value of an environment variable for the given environment.
+    /// See [`GithubEnvClient::get_environment_variable_details`].
+    pub async fn get_environment_variable(
+        &self,
+        environment_name: &str,
+        key: &str,
+    ) -> Result<Option<String>> {
+        Ok(self
+            .get_environment_variable_details(environment_name, key)
+            .await?
+            .map(|variable| variable.value))
+    }
+
+    /// Gets an environment variable for the given environment, with its
+    /// timestamps.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#get-an-environment-variable
+    pub async fn get_environment_variable_details(
+        &self,
+        environment_name: &str,
+        key: &str,
+    ) -> Result<Option<EnvironmentVariable>> {
+        debug!(
+            "Getting environment variable (key: {}) for environment {}",
+            key, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/variables/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(key)
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let variable: EnvironmentVariable = res.json().await?;
+                debug!(
+                    "Successfully got environment variable (key: {}) for environment {}: {:?}",
+                    key, environment_name, &variable.value
+                );
+                Ok(Some(variable))
+            }
+            Err(e) => {
+                if e.status == StatusCode::NOT_FOUND {
+                    debug!(
+                        "Environment variable (key: {}) for environment {} not found",
+                        key, environment_name
+                    );
+                    Ok(None)
+                } else {
+                    Err(eyre!(
+                        "Error getting environment variable (key: {}) for environment {}: {}",
+                        key,
+                        environment_name,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Lists the values of all variables for the given environment, keyed by
+    /// variable name.  Github returns names uppercased.  See
+    /// [`GithubEnvClient::list_environment_variable_details`].
+    pub async fn list_environment_variables(
+        &self,
+        environment_name: &str,
+    ) -> Result<HashMap<String, String>> {
+        Ok(self
+            .list_environment_variable_details(environment_name)
+            .await?
+            .into_iter()
+            .map(|variable| (variable.name, variable.value))
+            .collect())
+    }
+
+    /// Lists all variables for the given environment, with their timestamps.
+    /// See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#list-environment-variables
+    pub async fn list_environment_variable_details(
+        &self,
+        environment_name: &str,
+    ) -> Result<Vec<EnvironmentVariable>> {
+        debug!(
+            "Listing environment variables for environment {}",
+            environment_name
+        );
+
+        let variables = self
+            .paginate_environment_variables(environment_name)
+            .collect_all()
+            .await?;
+        debug!(
+            "Got {} environment variables for environment {}",
+            variables.len(),
+            environment_name
+        );
+
+        Ok(variables)
+    }
+
+    /// The variables of the given environment, a page at a time.
+    pub fn paginate_environment_variables(
+        &self,
+        environment_name: &str,
+    ) -> Paginator<'_, EnvironmentVariable> {
+        Paginator::new::<ListVariablesResponse>(
+            &self.transport,
+            &self.token,
+            format!(
+                "https://api.github.com/repositories/{}/environments/{}/variables",
+                self.repository.id,
+                encode_path_segment(environment_name)
+            ),
+            VARIABLES_PER_PAGE,
+            format!("environment variables for environment {}", environment_name),
+        )
+    }
+
+    /// Updates an environment variable for the given environment.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#update-an-environment-variable
+    pub async fn update_environment_variable(
+        &self,
+        environment_name: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        debug!(
+            "Updating environment variable (key: {}, value: {}) for environment {}",
+            key, value, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/variables/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(key)
+        );
+
+        let request = self
+            .transport
+            .client
+            .patch(url)
+            .with_env_client(self)
+            .json(&serde_json::json!({ "value": value }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully updated environment variable (key: {}, value: {}) for environment {}",
+                    key, value, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error updating environment variable (key: {}, value: {}) for environment {}: {}",
+                key,
+                value,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Utility function that either creates or updates an environment variable,
+    /// depending on the result from get_environment_variable.
+    pub async fn upsert_environment_variable(
+        &self,
+        environment_name: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        match self.get_environment_variable(environment_name, key).await? {
+            Some(_) => {
+                self.update_environment_variable(environment_name, key, value)
+                    .await
+            }
+            None => {
+                self.create_environment_variable(environment_name, key, value)
+                    .await
+            }
+        }
+    }
+
+    /// Deletes an environment variable for the given environment.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#delete-an-environment-variable
+    pub async fn delete_environment_variable(
+        &self,
+        environment_name: &str,
+        key: &str,
+    ) -> Result<()> {
+        debug!(
+            "Deleting environment variable (key: {}) for environment {}",
+            key, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/variables/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(key)
+        );
+
+        let request = self.transport.client.delete(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully deleted environment variable (key: {}) for environment {}",
+                    key, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error deleting environment variable (key: {}) for environment {}: {}",
+                key,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Gets the public key used to encrypt secrets for the given environment,
+    /// from the [`PublicKeyCache`] if it was fetched before.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#get-an-environment-public-key
+    pub async fn get_environment_public_key(&self, environment_name: &str) -> Result<PublicKey> {
+        let scope = self.environment_key_scope(environment_name);
+        if let Some(public_key) = self.public_keys.get(&scope) {
+            return Ok(public_key);
+        }
+        debug!("Getting public key for environment {}", environment_name);
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/secrets/public-key",
+            self.repository.id,
+            encode_path_segment(environment_name)
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let public_key: PublicKey = res.json().await?;
+                debug!(
+                    "Got public key {} for environment {}",
+                    public_key.key_id, environment_name
+                );
+                self.public_keys.insert(&scope, public_key.clone());
+                Ok(public_key)
+            }
+            Err(e) => Err(eyre!(
+                "Error getting public key for environment {}: {}",
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    /// Lists the secrets of the given environment.  Only their names and
+    /// timestamps can be read, never their values.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#list-environment-secrets
+    pub async fn list_environment_secrets(&self, environment_name: &str) -> Result<Vec<Secret>> {
+        debug!(
+            "Listing environment secrets for environment {}",
+            environment_name
+        );
+
+        let secrets = self
+            .paginate_environment_secrets(environment_name)
+            .collect_all()
+            .await?;
+        debug!(
+            "Got {} environment secrets for environment {}",
+            secrets.len(),
+            environment_name
+        );
+
+        Ok(secrets)
+    }
+
+    /// The secrets of the given environment, a page at a time.
+    pub fn paginate_environment_secrets(&self, environment_name: &str) -> Paginator<'_, Secret> {
+        Paginator::new::<ListSecretsResponse>(
+            &self.transport,
+            &self.token,
+            format!(
+                "https://api.github.com/repositories/{}/environments/{}/secrets",
+                self.repository.id,
+                encode_path_segment(environment_name)
+            ),
+            SECRETS_PER_PAGE,
+            format!("environment secrets for environment {}", environment_name),
+        )
+    }
+
+    /// Creates or updates a secret for the given environment.  The value is
+    /// encrypted client-side with the environment's public key, so it never
+    /// leaves this process in plaintext.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#create-or-update-an-environment-secret
+    pub async fn put_environment_secret(
+        &self,
+        environment_name: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<()> {
+        debug!(
+            "Putting environment secret (name: {}) for environment {}",
+            name, environment_name
+        );
+
+        let public_key = self.get_environment_public_key(environment_name).await?;
+        let encrypted_value = public_key.encrypt(value)?;
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/secrets/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(name)
+        );
+
+        let request =
+            self.transport
+                .client
+                .put(url)
+                .with_env_client(self)
+                .json(&serde_json::json!({
+                    "encrypted_value": encrypted_value,
+                    "key_id": public_key.key_id,
+                }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully put environment secret (name: {}) for environment {}",
+                    name, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => {
+                // The key may have been rotated since it was cached.
+                self.public_keys
+                    .remove(&self.environment_key_scope(environment_name));
+                Err(eyre!(
+                    "Error putting environment secret (name: {}) for environment {}: {}",
+                    name,
+                    environment_name,
+                    e
+                ))
+            }
+        }
+    }
+
+    /// Gets a secret of the given environment, without its value, which
+    /// can't be read.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#get-an-environment-secret
+    pub async fn get_environment_secret(
+        &self,
+        environment_name: &str,
+        name: &str,
+    ) -> Result<Option<Secret>> {
+        debug!(
+            "Getting environment secret (name: {}) for environment {}",
+            name, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/secrets/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(name)
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let secret: Secret = res.json().await?;
+                debug!(
+                    "Successfully got environment secret (name: {}) for environment {}",
+                    name, environment_name
+                );
+                Ok(Some(secret))
+            }
+            Err(e) => {
+                if e.status == StatusCode::NOT_FOUND {
+                    debug!(
+                        "Environment secret (name: {}) for environment {} not found",
+                        name, environment_name
+                    );
+                    Ok(None)
+                } else {
+                    Err(eyre!(
+                        "Error getting environment secret (name: {}) for environment {}: {}",
+                        name,
+                        environment_name,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Whether the given environment has a secret.  See
+    /// [`GithubEnvClient::get_environment_secret`].
+    pub async fn environment_secret_exists(
+        &self,
+        environment_name: &str,
+        name: &str,
+    ) -> Result<bool> {
+        Ok(self
+            .get_environment_secret(environment_name, name)
+            .await?
+            .is_some())
+    }
+
+    /// Deletes a secret of the given environment.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#delete-an-environment-secret
+    pub async fn delete_environment_secret(
+        &self,
+        environment_name: &str,
+        name: &str,
+    ) -> Result<()> {
+        debug!(
+            "Deleting environment secret (name: {}) for environment {}",
+            name, environment_name
+        );
+
+        let url = format!(
+            "https://api.github.com/repositories/{}/environments/{}/secrets/{}",
+            self.repository.id,
+            encode_path_segment(environment_name),
+            encode_path_segment(name)
+        );
+
+        let request = self.transport.client.delete(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully deleted environment secret (name: {}) for environment {}",
+                    name, environment_name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error deleting environment secret (name: {}) for environment {}: {}",
+                name,
+                environment_name,
+                e
+            )),
+        }
+    }
+
+    fn environment_key_scope(&self, environment_name: &str) -> PublicKeyScope {
+        PublicKeyScope::Environment {
+            repository_id: self.repository.id,
+            environment: environment_name.to_string(),
+        }
+    }
+
+    /// Gets the id of a repository, e.g. for selecting the repositories that
+    /// can access an organization secret.  See:
+    /// https://docs.github.com/en/rest/repos/repos?apiVersion=2022-11-28#get-a-repository
+    pub async fn get_repository_id(&self, owner: &str, name: &str) -> Result<usize> {
+        if owner == self.repository.owner.login && name == self.repository.name {
+            return Ok(self.repository.id);
+        }
+
+        let repository = get_repository_details(&self.transport, &self.token, owner, name).await?;
+        Ok(repository.id)
+    }
+
+    /// Gets the contents of a file in any repository at the given ref (a
+    /// branch, tag or commit sha), or `None` if the file doesn't exist.  See:
+    /// https://docs.github.com/en/rest/repos/contents?apiVersion=2022-11-28#get-repository-content
+    pub async fn get_repository_file(
+        &self,
+        owner: &str,
+        name: &str,
+        path: &str,
+        reference: &str,
+    ) -> Result<Option<String>> {
+        debug!(
+            "Getting file {} at {} from {}/{}",
+            path, reference, owner, name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/contents/{}",
+            owner,
+            name,
+            path.trim_start_matches('/')
+                .split('/')
+                .map(encode_path_segment)
+                .collect::<Vec<_>>()
+                .join("/")
+        );
+
+        let request = self
+            .transport
+            .client
+            .get(url)
+            .query(&[("ref", reference)])
+            .with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let response: FileContentsResponse = res.json().await?;
+                if response.encoding != "base64" {
+                    return Err(eyre!(
+                        "Unsupported encoding '{}' for file {} in {}/{}",
+                        response.encoding,
+                        path,
+                        owner,
+                        name
+                    ));
+                }
+
+                // The content is wrapped at 60 characters.
+                let content = response.content.replace('\n', "");
+                let bytes = STANDARD.decode(content)?;
+                Ok(Some(String::from_utf8(bytes).map_err(|e| {
+                    eyre!("File {} in {}/{} is not UTF-8: {}", path, owner, name, e)
+                })?))
+            }
+            Err(e) => {
+                if e.status == StatusCode::NOT_FOUND {
+                    debug!("File {} not found in {}/{}", path, owner, name);
+                    Ok(None)
+                } else {
+                    Err(eyre!(
+                        "Error getting file {} from {}/{}: {}",
+                        path,
+                        owner,
+                        name,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Gets the public key used to encrypt secrets for the organization that
+    /// owns the repository, from the [`PublicKeyCache`] if it was fetched
+    /// before.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#get-an-organization-public-key
+    pub async fn get_organization_public_key(&self) -> Result<PublicKey> {
+        let scope = PublicKeyScope::Organization(self.repository.owner.login.clone());
+        if let Some(public_key) = self.public_keys.get(&scope) {
+            return Ok(public_key);
+        }
+        debug!(
+            "Getting public key for organization {}",
+            self.repository.owner.login
+        );
+
+        let url = format!(
+            "https://api.github.com/orgs/{}/actions/secrets/public-key",
+            self.repository.owner.login
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let public_key: PublicKey = res.json().await?;
+                self.public_keys.insert(&scope, public_key.clone());
+                Ok(public_key)
+            }
+            Err(e) => Err(eyre!(
+                "Error getting public key for organization {}: {}",
+                self.repository.owner.login,
+                e
+            )),
+        }
+    }
+
+    /// Gets a secret of the organization that owns the repository, without
+    /// its value, which can't be read.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#get-an-organization-secret
+    pub async fn get_organization_secret(&self, name: &str) -> Result<Option<Secret>> {
+        debug!(
+            "Getting organization secret (name: {}) for {}",
+            name, self.repository.owner.login
+        );
+
+        let url = format!(
+            "https://api.github.com/orgs/{}/actions/secrets/{}",
+            self.repository.owner.login,
+            encode_path_segment(name)
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let secret: Secret = res.json().await?;
+                debug!("Successfully got organization secret (name: {})", name);
+                Ok(Some(secret))
+            }
+            Err(e) => {
+                if e.status == StatusCode::NOT_FOUND {
+                    debug!("Organization secret (name: {}) not found", name);
+                    Ok(None)
+                } else {
+                    Err(eyre!(
+                        "Error getting organization secret (name: {}) for {}: {}",
+                        name,
+                        self.repository.owner.login,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Creates or updates a secret for the organization that owns the
+    /// repository, encrypting the value client-side.  When `visibility` is
+    /// [`OrganizationSecretVisibility::Selected`], set the selected
+    /// repositories with [`Self::set_organization_secret_repositories`].  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#create-or-update-an-organization-secret
+    pub async fn put_organization_secret(
+        &self,
+        name: &str,
+        value: &str,
+        visibility: OrganizationSecretVisibility,
+    ) -> Result<()> {
+        debug!(
+            "Putting organization secret (name: {}, visibility: {:?}) for {}",
+            name, visibility, self.repository.owner.login
+        );
+
+        let public_key = self.get_organization_public_key().await?;
+        let encrypted_value = public_key.encrypt(value)?;
+
+        let url = format!(
+            "https://api.github.com/orgs/{}/actions/secrets/{}",
+            self.repository.owner.login,
+            encode_path_segment(name)
+        );
+
+        let request =
+            self.transport
+                .client
+                .put(url)
+                .with_env_client(self)
+                .json(&serde_json::json!({
+                    "encrypted_value": encrypted_value,
+                    "key_id": public_key.key_id,
+                    "visibility": visibility,
+                }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully put organization secret (name: {})", name);
+                Ok(())
+            }
+            Err(e) => {
+                // The key may have been rotated since it was cached.
+                self.public_keys.remove(&PublicKeyScope::Organization(
+                    self.repository.owner.login.clone(),
+                ));
+                Err(eyre!(
+                    "Error putting organization secret (name: {}) for {}: {}",
+                    name,
+                    self.repository.owner.login,
+                    e
+                ))
+            }
+        }
+    }
+
+    /// Replaces the repositories that can access an organization secret with
+    /// `selected` visibility.  See:
+    /// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#set-selected-repositories-for-an-organization-secret
+    pub async fn set_organization_secret_repositories(
+        &self,
+        name: &str,
+        repository_ids: &[usize],
+    ) -> Result<()> {
+        debug!(
+            "Setting selected repositories {:?} for organization secret {}",
+            repository_ids, name
+        );
+
+        let url = format!(
+            "https://api.github.com/orgs/{}/actions/secrets/{}/repositories",
+            self.repository.owner.login,
+            encode_path_segment(name)
+        );
+
+        let request = self
+            .transport
+            .client
+            .put(url)
+            .with_env_client(self)
+            .json(&serde_json::json!({ "selected_repository_ids": repository_ids }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully set selected repositories for organization secret {}",
+                    name
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error setting selected repositories for organization secret {}: {}",
+                name,
+                e
+            )),
+        }
+    }
+
+    /// Triggers a `workflow_dispatch` event for a workflow, identified by its
+    /// file name (e.g. `deploy.yml`) or id, on the given branch or tag.  The
+    /// workflow must declare every input it is given.  See:
+    /// https://docs.github.com/en/rest/actions/workflows?apiVersion=2022-11-28#create-a-workflow-dispatch-event
+    pub async fn dispatch_workflow(
+        &self,
+        workflow: &str,
+        reference: &str,
+        inputs: &HashMap<String, String>,
+    ) -> Result<()> {
+        debug!(
+            "Dispatching workflow {} on {} for {}",
+            workflow, reference, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/workflows/{}/dispatches",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(workflow)
+        );
+
+        let request = self
+            .transport
+            .client
+            .post(url)
+            .with_env_client(self)
+            .json(&serde_json::json!({ "ref": reference, "inputs": inputs }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully dispatched workflow {}", workflow);
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error dispatching workflow {} on {} for repo {}: {}",
+                workflow,
+                reference,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Triggers a `repository_dispatch` event, which runs every workflow
+    /// listening for `event_type`.  See:
+    /// https://docs.github.com/en/rest/repos/repos?apiVersion=2022-11-28#create-a-repository-dispatch-event
+    pub async fn create_repository_dispatch(
+        &self,
+        event_type: &str,
+        client_payload: &serde_json::Value,
+    ) -> Result<()> {
+        debug!(
+            "Creating repository dispatch event {} for {}",
+            event_type, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/dispatches",
+            self.repository.owner.login, self.repository.name
+        );
+
+        let request =
+            self.transport
+                .client
+                .post(url)
+                .with_env_client(self)
+                .json(&serde_json::json!({
+                    "event_type": event_type,
+                    "client_payload": client_payload,
+                }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!(
+                    "Successfully created repository dispatch event {}",
+                    event_type
+                );
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error creating repository dispatch event {} for repo {}: {}",
+                event_type,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Gets a repository-level Actions variable.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#get-a-repository-variable
+    pub async fn get_repository_variable(&self, key: &str) -> Result<Option<String>> {
+        debug!(
+            "Getting repository variable (key: {}) for {}",
+            key, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/variables/{}",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(key)
+        );
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => {
+                let response: VariableResponse = res.json().await?;
+                Ok(Some(response.value))
+            }
+            Err(e) => {
+                if e.status == StatusCode::NOT_FOUND {
+                    debug!("Repository variable (key: {}) not found", key);
+                    Ok(None)
+                } else {
+                    Err(eyre!(
+                        "Error getting repository variable (key: {}) for repo {}: {}",
+                        key,
+                        self.repository.name,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Creates a repository-level Actions variable.  Fails if the variable
+    /// already exists.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#create-a-repository-variable
+    pub async fn create_repository_variable(&self, key: &str, value: &str) -> Result<()> {
+        debug!(
+            "Creating repository variable (key: {}) for {}",
+            key, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/variables",
+            self.repository.owner.login, self.repository.name
+        );
+
+        let request = self
+            .transport
+            .client
+            .post(url)
+            .with_env_client(self)
+            .json(&serde_json::json!({ "name": key, "value": value }));
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully created repository variable (key: {})", key);
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error creating repository variable (key: {}) for repo {}: {}",
+                key,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Deletes a repository-level Actions variable.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#delete-a-repository-variable
+    pub async fn delete_repository_variable(&self, key: &str) -> Result<()> {
+        debug!(
+            "Deleting repository variable (key: {}) for {}",
+            key, self.repository.name
+        );
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/variables/{}",
+            self.repository.owner.login,
+            self.repository.name,
+            encode_path_segment(key)
+        );
+
+        let request = self.transport.client.delete(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(_) => {
+                debug!("Successfully deleted repository variable (key: {})", key);
+                Ok(())
+            }
+            Err(e) => Err(eyre!(
+                "Error deleting repository variable (key: {}) for repo {}: {}",
+                key,
+                self.repository.name,
+                e
+            )),
+        }
+    }
+
+    /// Lists the repository-level Actions variables, keyed by name, which
+    /// workflows see unless an environment variable of the same name
+    /// shadows them.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#list-repository-variables
+    pub async fn list_repository_variables(&self) -> Result<HashMap<String, String>> {
+        debug!("Listing repository variables for {}", self.repository.name);
+
+        Ok(self
+            .paginate_repository_variables()
+            .collect_all()
+            .await?
+            .into_iter()
+            .map(|variable| (variable.name, variable.value))
+            .collect())
+    }
+
+    /// The repository-level variables, a page at a time.
+    pub fn paginate_repository_variables(&self) -> Paginator<'_, EnvironmentVariable> {
+        Paginator::new::<ListVariablesResponse>(
+            &self.transport,
+            &self.token,
+            format!(
+                "https://api.github.com/repos/{}/{}/actions/variables",
+                self.repository.owner.login, self.repository.name
+            ),
+            VARIABLES_PER_PAGE,
+            format!("repository variables for repo {}", self.repository.name),
+        )
+    }
+
+    /// Lists the organization variables shared with the repository, keyed
+    /// by name, which workflows see unless a repository or environment
+    /// variable of the same name shadows them.  See:
+    /// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#list-repository-organization-variables
+    pub async fn list_organization_variables(&self) -> Result<HashMap<String, String>> {
+        debug!(
+            "Listing organization variables shared with {}",
+            self.repository.name
+        );
+
+        Ok(self
+            .paginate_organization_variables()
+            .collect_all()
+            .await?
+            .into_iter()
+            .map(|variable| (variable.name, variable.value))
+            .collect())
+    }
+
+    /// The organization variables shared with the repository, a page at a
+    /// time.
+    pub fn paginate_organization_variables(&self) -> Paginator<'_, EnvironmentVariable> {
+        Paginator::new::<ListVariablesResponse>(
+            &self.transport,
+            &self.token,
+            format!(
+                "https://api.github.com/repos/{}/{}/actions/organization-variables",
+                self.repository.owner.login, self.repository.name
+            ),
+            VARIABLES_PER_PAGE,
+            format!(
+                "organization variables shared with repo {}",
+                self.repository.name
+            ),
+        )
+    }
+
+    /// Gets the token's current rate limits.  Checking them doesn't count
+    /// against them.  See:
+    /// https://docs.github.com/en/rest/rate-limit/rate-limit?apiVersion=2022-11-28#get-rate-limit-status-for-the-authenticated-user
+    pub async fn get_rate_limit(&self) -> Result<RateLimitStatus> {
+        debug!("Getting rate limit status");
+
+        let url = "https://api.github.com/rate_limit";
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(response) => {
+                let rate_limit: RateLimitResponse = response.json().await?;
+                debug!("Got rate limit status: {:?}", rate_limit.resources);
+
+                Ok(rate_limit.resources)
+            }
+            Err(e) => Err(eyre!("Error getting rate limit status: {}", e)),
+        }
+    }
+
+    /// Resolves a reviewer given by name to the id Github's environments API
+    /// expects: `org/team-slug` names a team, and anything else a user login,
+    /// optionally prefixed with `@`.  Names are matched case-insensitively
+    /// and cached, so each is only looked up once per client.  See:
+    /// https://docs.github.com/en/rest/users/users?apiVersion=2022-11-28#get-a-user
+    /// https://docs.github.com/en/rest/teams/teams?apiVersion=2022-11-28#get-a-team-by-name
+    pub async fn resolve_reviewer(&self, name: &str) -> Result<Reviewer> {
+        let name = name.trim_start_matches('@').to_lowercase();
+        if let Some(reviewer) = self.reviewers.get(&name) {
+            return Ok(reviewer);
+        }
+
+        debug!("Resolving reviewer {}", name);
+
+        let (url, reviewer_type) = match name.split_once('/') {
+            Some((organization, slug)) => (
+                format!(
+                    "https://api.github.com/orgs/{}/teams/{}",
+                    encode_path_segment(organization),
+                    encode_path_segment(slug)
+                ),
+                ReviewerType::Team,
+            ),
+            None => (
+                format!(
+                    "https://api.github.com/users/{}",
+                    encode_path_segment(&name)
+                ),
+                ReviewerType::User,
+            ),
+        };
+
+        let request = self.transport.client.get(url).with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(response) => {
+                let account: AccountResponse = response.json().await?;
+                let reviewer = Reviewer {
+                    reviewer_type,
+                    id: account.id,
+                };
+                debug!("Resolved reviewer {} to {:?}", name, reviewer);
+
+                self.reviewers.insert(name, reviewer);
+                Ok(reviewer)
+            }
+            Err(e) => Err(eyre!("Error resolving reviewer {}: {}", name, e)),
+        }
+    }
+
+    /// The login of the account the token belongs to, e.g. `octocat`, or
+    /// `my-app[bot]` for a Github App installation token.  Installation
+    /// tokens can't read `/user`, so they are looked up through GraphQL's
+    /// `viewer`.  See:
+    /// https://docs.github.com/en/rest/users/users?apiVersion=2022-11-28#get-the-authenticated-user
+    pub async fn get_authenticated_login(&self) -> Result<String> {
+        debug!("Getting the account of the token");
+
+        if self.transport.token_kind == TokenKind::App {
+            let request = self
+                .transport
+                .client
+                .post("https://api.github.com/graphql")
+                .with_env_client(self)
+                .json(&serde_json::json!({ "query": VIEWER_QUERY }));
+            let response = self.transport.send(request).await?;
+
+            let body: GraphQLResponse<ViewerData> = match error_for_status(response).await {
+                Ok(res) => res.json().await?,
+                Err(e) => return Err(eyre!("Error getting the token's account: {}", e)),
+            };
+            if let Some(errors) = body.errors {
+                let messages = errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>();
+                return Err(eyre!(
+                    "Error getting the token's account via GraphQL: {}",
+                    messages.join("; ")
+                ));
+            }
+
+            return body.data.map(|data| data.viewer.login).ok_or_else(|| {
+                eyre!("GraphQL response for the token's account contained no data")
+            });
+        }
+
+        let request = self
+            .transport
+            .client
+            .get("https://api.github.com/user")
+            .with_env_client(self);
+        let response = self.transport.send(request).await?;
+
+        match error_for_status(response).await {
+            Ok(res) => Ok(res.json::<User>().await?.login),
+            Err(e) => Err(eyre!("Error getting the token's account: {}", e)),
+        }
+    }
+}
+
+/// Lists every repository of an organization that the token can see,
+/// including archived ones.  Unlike the methods of [`GithubEnvClient`], this
+/// doesn't need a repository to start from.  See:
+/// https://docs.github.com/en/rest/repos/repos?apiVersion=2022-11-28#list-organization-repositories
+pub async fn list_organization_repositories(
+    username: Option<String>,
+    token: &str,
+    organization: &str,
+    http_options: &HttpOptions,
+) -> Result<Vec<OrganizationRepository>> {
+    debug!("Listing repositories of organization {}", organization);
+
+    let transport = http_options.build_authenticated_transport(username, token)?;
+    let repositories = Paginator::new::<Vec<OrganizationRepository>>(
+        &transport,
+        token,
+        format!(
+            "https://api.github.com/orgs/{}/repos",
+            encode_path_segment(organization)
+        ),
+        REPOSITORIES_PER_PAGE,
+        format!("repositories of organization {}", organization),
+    )
+    .collect_all()
+    .await?;
+    debug!("Got repositories: {:?}", repositories);
+
+    Ok(repositories)
+}
+
+/// Gets the repository details for the given repository name.
+async fn get_repository_details(
+    transport: &Transport,
+    token: &str,
+    repository_owner: &str,
+    repository_name: &str,
+) -> Result<Repository> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}",
+        repository_owner, repository_name
+    );
+
+    debug!("Getting repository details from {}", url);
+
+    let request = transport
+        .client
+        .get(url)
+        .bearer_auth(token)
+        .header("X-Github-Api-Version", "2022-11-28");
+    let response = transport.send(request).await?;
+
+    match error_for_status(response).await {
+        Ok(res) => {
+            let repository: Repository = res.json().await?;
+            debug!("Got repository details: {:?}", repository);
+
+            Ok(repository)
+        }
+        Err(e) => Err(eyre!("Error getting repository details: {}", e)),
+    }
+}
+
+/// The HTTP layer every request to Github goes through.  Reads can be cached
+/// to, or answered offline from, a response cache.  When built with the
+/// `cassette` feature, requests can be recorded to or replayed from a
+/// cassette file instead of (only) going over the network.
+#[derive(Debug)]
+struct Transport {
+    client: Client,
+    /// Sent as the User-Agent of every request.
+    user_agent: String,
+    correlation_id: Option<String>,
+    throttle: Option<Throttle>,
+    /// Used to explain 403 responses.
+    token_kind: TokenKind,
+    counter: RequestCounter,
+    response_cache: Option<ResponseCache>,
+    request_hooks: Vec<Arc<dyn RequestHook>>,
+    read_only: bool,
+    #[cfg(feature = "cassette")]
+    cassette: Option<Cassette>,
+}
+
+impl Transport {
+    /// Sends a request once the throttle allows it, with the User-Agent and
+    /// correlation id headers added and any changes the request hooks make.
+    /// 403 responses are turned into an error explaining which permission
+    /// the token is missing.  Offline, the response cache answers instead,
+    /// without pacing or counting.  In read-only mode, anything but a read
+    /// fails before it is sent.
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let mut request = request.header("User-Agent", &self.user_agent);
+        if let Some(correlation_id) = &self.correlation_id {
+            request = request.header("X-Correlation-Id", correlation_id);
+        }
+        let mut request = request.build()?;
+        for hook in &self.request_hooks {
+            hook.on_request(&mut request)?;
+        }
+        let (method, url) = (request.method().clone(), request.url().clone());
+        if self.read_only && !is_read(&request) {
+            return Err(ReadOnlyError {
+                method,
+                path: url.path().to_string(),
+            }
+            .into());
+        }
+
+        if let Some(cache) = self.response_cache.as_ref().filter(|c| c.is_offline()) {
+            return cache.replay(&request);
+        }
+
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire().await;
+        }
+        self.counter.record_request();
+
+        #[cfg(feature = "cassette")]
+        let response = match &self.cassette {
+            Some(cassette) => cassette.send(&self.client, request).await?,
+            None => self.client.execute(request).await?,
+        };
+        #[cfg(not(feature = "cassette"))]
+        let response = self.client.execute(request).await?;
+
+        self.counter.record_response(&response);
+        if response.status() == StatusCode::FORBIDDEN {
+            return Err(
+                diagnostics::forbidden_error(&method, &url, self.token_kind, response).await,
+            );
+        }
+
+        match &self.response_cache {
+            Some(cache) => cache.store(&method, &url, response).await,
+            None => Ok(response),
+        }
+    }
+}
+
+/// Whether a request only reads: a GET or HEAD, or one of the client's own
+/// GraphQL queries, which are POSTed like mutations.  Any other GraphQL
+/// document, e.g. one a [`RequestHook`] rewrote, counts as a write, since
+/// telling queries from mutations means parsing it.
+fn is_read(request: &reqwest::Request) -> bool {
+    match *request.method() {
+        Method::GET | Method::HEAD => true,
+        Method::POST if request.url().path().ends_with("/graphql") => request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .and_then(|body| body.get("query")?.as_str().map(str::to_string))
+            .is_some_and(|query| READ_QUERIES.contains(&query.as_str())),
+        _ => false,
+    }
+}
+
+trait AuthenticatedGhRequestBuilder {
+    fn with_env_client(self, client: &GithubEnvClient) -> Self;
+}
+
+impl AuthenticatedGhRequestBuilder for RequestBuilder {
+    fn with_env_client(self, client: &GithubEnvClient) -> Self {
+        self.bearer_auth(&client.token)
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("X-Github-Api-Version", "2022-11-28")
+    }
+}
@@ -0,0 +1,172 @@
+use color_eyre::{eyre::eyre, Result};
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::header::LINK;
+use serde::de::DeserializeOwned;
+use tracing::debug;
+
+use crate::{error::error_for_status, Transport};
+
+/// A single page of a list endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The number of the page, starting at 1.
+    pub number: usize,
+    /// Whether another page follows this one.
+    pub has_next: bool,
+}
+
+/// The body of a list endpoint's page, which is either a bare array or an
+/// object holding the items and their total count.
+pub(crate) trait PageBody: DeserializeOwned {
+    type Item;
+
+    /// The page's items, and the total number of items across all pages if
+    /// the endpoint reports it.
+    fn into_items(self) -> (Vec<Self::Item>, Option<usize>);
+}
+
+impl<T: DeserializeOwned> PageBody for Vec<T> {
+    type Item = T;
+
+    fn into_items(self) -> (Vec<T>, Option<usize>) {
+        (self, None)
+    }
+}
+
+type ParsePage<T> = fn(serde_json::Value) -> serde_json::Result<(Vec<T>, Option<usize>)>;
+
+fn parse_page<B: PageBody>(
+    body: serde_json::Value,
+) -> serde_json::Result<(Vec<B::Item>, Option<usize>)> {
+    serde_json::from_value::<B>(body).map(PageBody::into_items)
+}
+
+/// Requests the pages of a list endpoint one at a time, so that large lists
+/// can be processed without holding every item in memory.  Whether another
+/// page follows is read from the response's `Link` header, or, for cached
+/// and replayed responses that don't have one, from whether the page was
+/// full.
+///
+/// Pages are always requested by number rather than by following the
+/// `Link` URL, so that requests match their cached and recorded responses.
+pub struct Paginator<'a, T> {
+    transport: &'a Transport,
+    token: &'a str,
+    url: String,
+    per_page: usize,
+    /// What is being listed, for errors, e.g. `environments`.
+    description: String,
+    parse: ParsePage<T>,
+    next_page: Option<usize>,
+    fetched: usize,
+}
+
+impl<'a, T> Paginator<'a, T> {
+    pub(crate) fn new<B: PageBody<Item = T>>(
+        transport: &'a Transport,
+        token: &'a str,
+        url: String,
+        per_page: usize,
+        description: String,
+    ) -> Self {
+        Self {
+            transport,
+            token,
+            url,
+            per_page,
+            description,
+            parse: parse_page::<B>,
+            next_page: Some(1),
+            fetched: 0,
+        }
+    }
+
+    /// Requests the next page, or returns `None` after the last one.
+    pub async fn next_page(&mut self) -> Result<Option<Page<T>>> {
+        let Some(number) = self.next_page else {
+            return Ok(None);
+        };
+
+        let request = self
+            .transport
+            .client
+            .get(&self.url)
+            .bearer_auth(self.token)
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("X-Github-Api-Version", "2022-11-28")
+            .query(&[("per_page", self.per_page), ("page", number)]);
+        let response = self.transport.send(request).await?;
+
+        let response = error_for_status(response)
+            .await
+            .map_err(|e| eyre!("Error listing {}: {}", self.description, e))?;
+        let links_to_next = response
+            .headers()
+            .get(LINK)
+            .and_then(|link| link.to_str().ok())
+            .map(links_to_next);
+        let (items, total_count) = (self.parse)(response.json().await?)
+            .map_err(|e| eyre!("Error parsing {}: {}", self.description, e))?;
+        self.fetched += items.len();
+        debug!(
+            "Got {} {} on page {}",
+            items.len(),
+            self.description,
+            number
+        );
+
+        let has_next = links_to_next.unwrap_or_else(|| {
+            items.len() >= self.per_page && total_count.is_none_or(|total| self.fetched < total)
+        });
+        self.next_page = has_next.then_some(number + 1);
+
+        Ok(Some(Page {
+            items,
+            number,
+            has_next,
+        }))
+    }
+
+    /// Requests every remaining page, returning all of their items.
+    pub async fn collect_all(mut self) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            items.extend(page.items);
+        }
+
+        Ok(items)
+    }
+
+    /// The remaining items as a stream, which requests each page once the
+    /// items of the previous one have been consumed.
+    pub fn into_stream(self) -> impl Stream<Item = Result<T>> + 'a
+    where
+        T: 'a,
+    {
+        stream::try_unfold(self, |mut paginator| async move {
+            let page = paginator.next_page().await?;
+            Ok::<_, color_eyre::Report>(page.map(|page| (page.items, paginator)))
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+/// Whether a `Link` header has a `rel="next"` link, per RFC 8288, e.g.
+/// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn links_to_next(header: &str) -> bool {
+    header.split(',').any(|link| {
+        link.split(';').skip(1).any(|parameter| {
+            let Some((name, value)) = parameter.split_once('=') else {
+                return false;
+            };
+            name.trim().eq_ignore_ascii_case("rel")
+                && value
+                    .trim()
+                    .trim_matches('"')
+                    .split_ascii_whitespace()
+                    .any(|relation| relation.eq_ignore_ascii_case("next"))
+        })
+    })
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use tracing::{debug, warn};
+
+use crate::PublicKey;
+
+/// What a secrets public key encrypts secrets for.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PublicKeyScope {
+    /// An environment, of the repository with this id.
+    Environment {
+        repository_id: usize,
+        environment: String,
+    },
+    /// An organization, by login.
+    Organization(String),
+}
+
+/// Used as the key of the on-disk cache, e.g. `environment/123/production`
+/// or `organization/octo-org`.
+impl fmt::Display for PublicKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Environment {
+                repository_id,
+                environment,
+            } => write!(f, "environment/{}/{}", repository_id, environment),
+            Self::Organization(organization) => write!(f, "organization/{}", organization),
+        }
+    }
+}
+
+/// Secrets public keys already fetched, so that writing many secrets to the
+/// same environment or organization fetches its key once.  Github rotates
+/// keys rarely, and a key is dropped from the cache when a secret encrypted
+/// with it is rejected, so that the next write fetches it again.
+///
+/// Cloning a cache shares its keys, e.g. between the clients of each
+/// environment's token.  A cache can also be kept in a JSON file to be reused
+/// across runs.
+#[derive(Debug, Clone, Default)]
+pub struct PublicKeyCache {
+    path: Option<PathBuf>,
+    keys: Arc<Mutex<BTreeMap<String, PublicKey>>>,
+}
+
+impl PublicKeyCache {
+    /// An empty cache, kept in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache kept in the JSON file at `path`, starting with the keys it
+    /// holds if it exists.
+    pub fn persistent(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let keys = match path.exists() {
+            true => {
+                let contents = std::fs::read_to_string(&path).map_err(|e| {
+                    eyre!("Error reading public key cache {}: {}", path.display(), e)
+                })?;
+                serde_json::from_str(&contents).map_err(|e| {
+                    eyre!("Error parsing public key cache {}: {}", path.display(), e)
+                })?
+            }
+            false => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            keys: Arc::new(Mutex::new(keys)),
+        })
+    }
+
+    /// The cached key of a scope, if any.
+    pub fn get(&self, scope: &PublicKeyScope) -> Option<PublicKey> {
+        self.lock().get(&scope.to_string()).cloned()
+    }
+
+    /// Caches the key of a scope.
+    pub fn insert(&self, scope: &PublicKeyScope, public_key: PublicKey) {
+        let mut keys = self.lock();
+        keys.insert(scope.to_string(), public_key);
+        self.save(&keys);
+    }
+
+    /// Drops the cached key of a scope, so that it is fetched again.
+    pub fn remove(&self, scope: &PublicKeyScope) {
+        let mut keys = self.lock();
+        if keys.remove(&scope.to_string()).is_some() {
+            debug!("Dropped cached public key for {}", scope);
+            self.save(&keys);
+        }
+    }
+
+    /// The number of cached keys.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, PublicKey>> {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Writes the keys to the cache file, if there is one.  A cache that
+    /// can't be written only costs extra requests later, so failures are
+    /// logged rather than returned.
+    fn save(&self, keys: &BTreeMap<String, PublicKey>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(keys)
+            .map_err(|e| eyre!("{}", e))
+            .and_then(|contents| std::fs::write(path, contents).map_err(|e| eyre!("{}", e)));
+        if let Err(e) = result {
+            warn!("Error writing public key cache {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{encode_path_segment, error::error_for_status, HttpOptions, Transport};
+
+/// A published release of a repository.  See:
+/// https://docs.github.com/en/rest/releases/releases?apiVersion=2022-11-28#get-the-latest-release
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    /// The asset with the given file name, if the release has one.
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// A file attached to a release.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    /// The API URL of the asset, which downloads it when requested as
+    /// `application/octet-stream`.
+    pub url: String,
+    pub size: u64,
+}
+
+/// Gets a release of a repository by tag, or its latest release.  Releases
+/// of public repositories can be read without a token.
+pub async fn get_release(
+    owner: &str,
+    repository: &str,
+    tag: Option<&str>,
+    token: Option<&str>,
+    http_options: &HttpOptions,
+) -> Result<Release> {
+    let url = match tag {
+        Some(tag) => format!(
+            "https://api.github.com/repos/{}/{}/releases/tags/{}",
+            encode_path_segment(owner),
+            encode_path_segment(repository),
+            encode_path_segment(tag)
+        ),
+        None => format!(
+            "https://api.github.com/repos/{}/{}/releases/latest",
+            encode_path_segment(owner),
+            encode_path_segment(repository)
+        ),
+    };
+    debug!("Getting release from {}", url);
+
+    let transport = transport(token, http_options)?;
+    let mut request = transport
+        .client
+        .get(url)
+        .header("Accept", "application/vnd.github.v3+json")
+        .header("X-Github-Api-Version", "2022-11-28");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = transport.send(request).await?;
+
+    match error_for_status(response).await {
+        Ok(res) => Ok(res.json().await?),
+        Err(e) => Err(eyre!(
+            "Error getting release {} of {}/{}: {}",
+            tag.unwrap_or("latest"),
+            owner,
+            repository,
+            e
+        )),
+    }
+}
+
+/// Downloads a release asset.  Github redirects the download to its storage,
+/// which is sent the request without the token.
+pub async fn download_release_asset(
+    asset: &ReleaseAsset,
+    token: Option<&str>,
+    http_options: &HttpOptions,
+) -> Result<Vec<u8>> {
+    debug!(
+        "Downloading release asset {} from {}",
+        asset.name, asset.url
+    );
+
+    let transport = transport(token, http_options)?;
+    let mut request = transport
+        .client
+        .get(&asset.url)
+        .header("Accept", "application/octet-stream")
+        .header("X-Github-Api-Version", "2022-11-28");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = transport.send(request).await?;
+
+    match error_for_status(response).await {
+        Ok(res) => Ok(res.bytes().await?.to_vec()),
+        Err(e) => Err(eyre!("Error downloading {}: {}", asset.name, e)),
+    }
+}
+
+fn transport(token: Option<&str>, http_options: &HttpOptions) -> Result<Transport> {
+    match token {
+        Some(token) => http_options.build_authenticated_transport(None, token),
+        None => http_options.build_transport(),
+    }
+}
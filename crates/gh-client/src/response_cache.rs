@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use reqwest::{Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    body: String,
+}
+
+impl CachedResponse {
+    fn to_response(&self) -> Result<Response> {
+        let response = http::Response::builder()
+            .status(self.status)
+            .header("Content-Type", "application/json")
+            .body(self.body.clone())?;
+
+        Ok(Response::from(response))
+    }
+}
+
+/// A JSON file of the Github API's latest response to each read, keyed by
+/// URL.  Online, every successful read refreshes its entry; offline, reads
+/// are answered from the file and anything else fails, so the network is
+/// never touched.  Cloning a cache shares its entries.
+///
+/// Unlike a cassette, a cache keeps one response per URL however often it is
+/// requested, and never stores writes, so it can be kept up to date by
+/// ordinary runs.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    path: PathBuf,
+    offline: bool,
+    entries: Arc<Mutex<BTreeMap<String, CachedResponse>>>,
+}
+
+impl ResponseCache {
+    /// Opens the cache file at `path` to be refreshed by reads, creating it
+    /// on the first read if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() {
+            Self::load(&path)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            offline: false,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Opens the cache file at `path` to answer reads from without network
+    /// access.
+    pub fn offline(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = Self::load(&path)?;
+        debug!(
+            "Loaded {} cached responses from {}",
+            entries.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            path,
+            offline: true,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Whether requests are answered from the cache file alone.
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    fn load(path: &Path) -> Result<BTreeMap<String, CachedResponse>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading response cache {}: {}", path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| eyre!("Error parsing response cache {}: {}", path.display(), e))
+    }
+
+    /// Answers a request from the cache.  Only reads can be answered; writes
+    /// fail, as do reads that no online run has made yet.
+    pub(crate) fn replay(&self, request: &Request) -> Result<Response> {
+        if request.method() != Method::GET {
+            return Err(eyre!(
+                "Can't {} {} while offline, only reads are answered from the response cache",
+                request.method(),
+                request.url()
+            ));
+        }
+
+        let entries = self.entries.lock().expect("response cache lock poisoned");
+        let response = entries.get(request.url().as_str()).ok_or_else(|| {
+            eyre!(
+                "GET {} is not in the response cache {}; run once online with it to fill it",
+                request.url(),
+                self.path.display()
+            )
+        })?;
+        debug!("Answering GET {} from the response cache", request.url());
+
+        response.to_response()
+    }
+
+    /// Stores the response to a read, if it is one, and rewrites the cache
+    /// file.  404s are stored too, since a missing environment or variable is
+    /// part of the remote state.  Returns an equivalent response, as reading
+    /// the body consumes the original.
+    pub(crate) async fn store(
+        &self,
+        method: &Method,
+        url: &reqwest::Url,
+        response: Response,
+    ) -> Result<Response> {
+        let status = response.status();
+        if method != Method::GET || !(status.is_success() || status == StatusCode::NOT_FOUND) {
+            return Ok(response);
+        }
+
+        let cached = CachedResponse {
+            status: status.as_u16(),
+            body: response.text().await?,
+        };
+        let rebuilt = cached.to_response()?;
+
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        entries.insert(url.to_string(), cached);
+        let contents = serde_json::to_string_pretty(&*entries)?;
+        std::fs::write(&self.path, contents).map_err(|e| {
+            eyre!(
+                "Error writing response cache {}: {}",
+                self.path.display(),
+                e
+            )
+        })?;
+
+        Ok(rebuilt)
+    }
+}
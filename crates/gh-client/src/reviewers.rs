@@ -0,0 +1,44 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use serde::Serialize;
+
+/// Whether a reviewer is a user or a team.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ReviewerType {
+    User,
+    Team,
+}
+
+/// A required reviewer of an environment, identified the way Github's
+/// environments API expects it.  See:
+/// https://docs.github.com/en/rest/deployments/environments?apiVersion=2022-11-28#create-or-update-an-environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Reviewer {
+    #[serde(rename = "type")]
+    pub reviewer_type: ReviewerType,
+    pub id: usize,
+}
+
+/// Reviewers already resolved by a client, keyed by lowercased login or
+/// `org/team-slug`, so that each is only looked up once.
+#[derive(Debug, Default)]
+pub(crate) struct ReviewerCache {
+    reviewers: Mutex<HashMap<String, Reviewer>>,
+}
+
+impl ReviewerCache {
+    pub(crate) fn get(&self, name: &str) -> Option<Reviewer> {
+        self.reviewers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .copied()
+    }
+
+    pub(crate) fn insert(&self, name: String, reviewer: Reviewer) {
+        self.reviewers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, reviewer);
+    }
+}
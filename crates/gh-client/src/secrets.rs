@@ -0,0 +1,58 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{eyre::eyre, Result};
+use crypto_box::aead::OsRng;
+use serde::{Deserialize, Serialize};
+
+/// A public key used to encrypt secrets before they are sent to Github.  See:
+/// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#get-an-environment-public-key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKey {
+    /// Identifies the key when uploading a secret encrypted with it.
+    pub key_id: String,
+    /// The base64 encoded Curve25519 public key.
+    pub key: String,
+}
+
+impl PublicKey {
+    /// Encrypts a secret value with this key.  See [`encrypt_secret`].
+    pub fn encrypt(&self, value: &str) -> Result<String> {
+        encrypt_secret(&self.key, value)
+    }
+}
+
+/// Which repositories in an organization can use an organization secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrganizationSecretVisibility {
+    /// Every repository in the organization.
+    All,
+    /// Private and internal repositories.
+    #[default]
+    Private,
+    /// Only the repositories explicitly selected for the secret.
+    Selected,
+}
+
+/// Encrypts a secret value with a base64 encoded public key using libsodium's
+/// sealed box construction (`crypto_box_seal`), which is what Github requires
+/// for every secrets API.  Returns the base64 encoded ciphertext.  See:
+/// https://docs.github.com/en/rest/guides/encrypting-secrets-for-the-rest-api
+pub fn encrypt_secret(public_key: &str, value: &str) -> Result<String> {
+    let key_bytes: [u8; crypto_box::KEY_SIZE] = STANDARD
+        .decode(public_key)
+        .map_err(|e| eyre!("Error decoding secrets public key: {}", e))?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| {
+            eyre!(
+                "Expected a {} byte secrets public key, got {} bytes",
+                crypto_box::KEY_SIZE,
+                bytes.len()
+            )
+        })?;
+
+    let sealed = crypto_box::PublicKey::from(key_bytes)
+        .seal(&mut OsRng, value.as_bytes())
+        .map_err(|e| eyre!("Error encrypting secret: {}", e))?;
+
+    Ok(STANDARD.encode(sealed))
+}
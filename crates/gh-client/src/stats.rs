@@ -0,0 +1,110 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use reqwest::{header::HeaderMap, Response};
+use serde::Deserialize;
+
+/// Github's rate limit for the token, as of the most recent response.  See:
+/// https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api#checking-the-status-of-your-rate-limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    pub used: u64,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+        };
+
+        Some(Self {
+            limit: header("x-ratelimit-limit")?,
+            remaining: header("x-ratelimit-remaining")?,
+            used: header("x-ratelimit-used")?,
+        })
+    }
+}
+
+/// A single bucket of the token's rate limit, from the `/rate_limit`
+/// endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct RateLimitResource {
+    pub limit: u64,
+    pub remaining: u64,
+    pub used: u64,
+    /// Unix time at which the limit resets.
+    pub reset: u64,
+}
+
+/// The rate limits of the token for the APIs the client uses.  See:
+/// https://docs.github.com/en/rest/rate-limit/rate-limit?apiVersion=2022-11-28#get-rate-limit-status-for-the-authenticated-user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct RateLimitStatus {
+    /// The REST API.
+    pub core: RateLimitResource,
+    pub graphql: Option<RateLimitResource>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RateLimitResponse {
+    pub(crate) resources: RateLimitStatus,
+}
+
+/// Accounting of the requests a client has made.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestStats {
+    /// Requests sent, including failed ones.
+    pub requests: usize,
+    /// The rate limit reported by the most recent response, if Github sent
+    /// one.  Replayed cassette responses don't carry rate limit headers.
+    pub rate_limit: Option<RateLimit>,
+    /// How much of the rate limit was used since the client's first
+    /// response.  Other clients sharing the token also count towards this.
+    pub rate_limit_consumed: Option<u64>,
+}
+
+/// Counts requests as they go through the transport.
+#[derive(Debug, Default)]
+pub(crate) struct RequestCounter {
+    requests: AtomicUsize,
+    /// The remaining limit before the first response, and the latest limit.
+    rate_limit: Mutex<Option<(u64, RateLimit)>>,
+}
+
+impl RequestCounter {
+    pub(crate) fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_response(&self, response: &Response) {
+        let Some(latest) = RateLimit::from_headers(response.headers()) else {
+            return;
+        };
+
+        let mut rate_limit = self.rate_limit.lock().unwrap_or_else(|e| e.into_inner());
+        // The first response already counts against the limit, so start from
+        // just before it.
+        let initial = rate_limit.map_or(latest.remaining + 1, |(initial, _)| initial);
+        *rate_limit = Some((initial, latest));
+    }
+
+    pub(crate) fn stats(&self) -> RequestStats {
+        let rate_limit = *self.rate_limit.lock().unwrap_or_else(|e| e.into_inner());
+
+        RequestStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            rate_limit: rate_limit.map(|(_, latest)| latest),
+            // The limit resets hourly, so this saturates rather than
+            // underflowing across a reset.
+            rate_limit_consumed: rate_limit
+                .map(|(initial, latest)| initial.saturating_sub(latest.remaining)),
+        }
+    }
+}
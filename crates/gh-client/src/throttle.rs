@@ -0,0 +1,68 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+/// Paces requests with a token bucket, so that bursts of writes don't trip
+/// Github's secondary rate limits.  See:
+/// https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api#about-secondary-rate-limits
+///
+/// Clones share the same bucket, so a single throttle can pace every client
+/// of a process, e.g. one per repository of a multi-repository sync.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    requests_per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// May be negative, when requests have reserved tokens that haven't been
+    /// refilled yet.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Throttle {
+    /// Allows `requests_per_second` on average, in bursts of up to a second's
+    /// worth of requests.
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            requests_per_second,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: Self::capacity(requests_per_second),
+                refilled_at: Instant::now(),
+            })),
+        }
+    }
+
+    fn capacity(requests_per_second: f64) -> f64 {
+        requests_per_second.max(1.0)
+    }
+
+    /// Waits until a request may be sent.  Waiting callers reserve their
+    /// token up front, so concurrent requests are spaced out rather than all
+    /// sent as soon as one token is available.
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let refilled =
+                now.duration_since(bucket.refilled_at).as_secs_f64() * self.requests_per_second;
+            bucket.tokens =
+                (bucket.tokens + refilled).min(Self::capacity(self.requests_per_second));
+            bucket.refilled_at = now;
+
+            bucket.tokens -= 1.0;
+            (bucket.tokens < 0.0)
+                .then(|| Duration::from_secs_f64(-bucket.tokens / self.requests_per_second))
+        };
+
+        if let Some(wait) = wait {
+            debug!("Throttling request for {:?}", wait);
+            crate::timer::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Waiting for a duration without assuming an async runtime, so that the
+//! client's own pacing works under any executor.  With the `tokio` feature,
+//! Tokio's timer is used instead.
+
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::time::sleep;
+
+#[cfg(not(feature = "tokio"))]
+pub(crate) use thread::sleep;
+
+#[cfg(not(feature = "tokio"))]
+mod thread {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll, Waker},
+        time::Duration,
+    };
+
+    /// Completes after `duration`.  The wait happens on a short-lived thread
+    /// which wakes the task when it is done, so no runtime timer is needed.
+    pub(crate) fn sleep(duration: Duration) -> Sleep {
+        Sleep {
+            duration,
+            shared: None,
+        }
+    }
+
+    pub(crate) struct Sleep {
+        duration: Duration,
+        /// Set once the thread has been started, on first poll.
+        shared: Option<Arc<Mutex<SleepState>>>,
+    }
+
+    #[derive(Default)]
+    struct SleepState {
+        done: bool,
+        waker: Option<Waker>,
+    }
+
+    impl Future for Sleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let duration = self.duration;
+            let shared = self.shared.get_or_insert_with(|| {
+                let shared = Arc::new(Mutex::new(SleepState::default()));
+                let thread_shared = shared.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(duration);
+                    let mut state = thread_shared.lock().unwrap_or_else(|e| e.into_inner());
+                    state.done = true;
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                });
+                shared
+            });
+
+            let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+            if state.done {
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+/// A repository, as returned by Github's get repository endpoint.  See:
+/// https://docs.github.com/en/rest/repos/repos?apiVersion=2022-11-28#get-a-repository
+///
+/// Timestamps are ISO 8601 strings, e.g. `2023-09-01T12:00:00Z`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Repository {
+    pub id: usize,
+    pub name: String,
+    pub owner: User,
+    pub default_branch: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// The account that owns a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+/// An Actions variable of an environment.  Github returns names uppercased.
+/// See:
+/// https://docs.github.com/en/rest/actions/variables?apiVersion=2022-11-28#get-an-environment-variable
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EnvironmentVariable {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// An Actions secret of an environment or organization.  Only its metadata
+/// can be read back, never its value.  See:
+/// https://docs.github.com/en/rest/actions/secrets?apiVersion=2022-11-28#list-environment-secrets
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Secret {
+    pub name: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// A Github App that can gate deployments to an environment as a custom
+/// deployment protection rule.  See:
+/// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#list-custom-deployment-rule-integrations-available-for-an-environment
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProtectionRuleApp {
+    pub id: usize,
+    pub slug: String,
+    #[serde(default)]
+    pub integration_url: Option<String>,
+}
+
+/// A custom deployment protection rule enabled for an environment.  See:
+/// https://docs.github.com/en/rest/deployments/protection-rules?apiVersion=2022-11-28#get-all-deployment-protection-rules-for-an-environment
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProtectionRule {
+    pub id: usize,
+    pub enabled: bool,
+    pub app: ProtectionRuleApp,
+}
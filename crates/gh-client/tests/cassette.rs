@@ -0,0 +1,177 @@
+#![cfg(feature = "cassette")]
+
+use std::collections::HashMap;
+
+use common::{replay_client, replay_options, LIST_VARIABLES};
+
+mod common;
+
+#[tokio::test]
+async fn replays_environments_and_variables() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let environments = client.list_environments().await.unwrap();
+    assert_eq!(environments, vec!["development", "production"]);
+
+    let variables = client
+        .list_environment_variables("production")
+        .await
+        .unwrap();
+    assert_eq!(variables.get("FOO").map(String::as_str), Some("baz"));
+}
+
+#[tokio::test]
+async fn replays_variable_timestamps() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let variables = client
+        .list_environment_variable_details("production")
+        .await
+        .unwrap();
+    assert_eq!(variables.len(), 1);
+    assert_eq!(variables[0].name, "FOO");
+    assert_eq!(
+        variables[0].created_at.as_deref(),
+        Some("2023-09-01T12:00:00Z")
+    );
+    assert_eq!(
+        variables[0].updated_at.as_deref(),
+        Some("2023-09-02T12:00:00Z")
+    );
+}
+
+#[tokio::test]
+async fn replays_not_found_as_missing_variable() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let value = client
+        .get_environment_variable("production", "MISSING")
+        .await
+        .unwrap();
+    assert_eq!(value, None);
+}
+
+#[tokio::test]
+async fn encodes_environment_names_in_paths() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let variables = client
+        .list_environment_variables("QA / Staging")
+        .await
+        .unwrap();
+    assert_eq!(
+        variables.get("API_URL").map(String::as_str),
+        Some("https://qa.example.com")
+    );
+}
+
+#[tokio::test]
+async fn fails_on_unrecorded_request() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let error = client.list_environments_graphql().await.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("No unplayed interaction in cassette"));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn replays_through_blocking_client() {
+    let client = gh_client::blocking::GithubEnvClient::init(
+        Some("octo-org".to_string()),
+        "not-a-real-token".to_string(),
+        "octo-org",
+        "octo-repo",
+        &replay_options(LIST_VARIABLES),
+    )
+    .unwrap();
+
+    let environments = client.list_environments().unwrap();
+    assert_eq!(environments, vec!["development", "production"]);
+}
+
+#[cfg(feature = "blocking")]
+#[tokio::test]
+async fn blocking_client_fails_inside_a_runtime() {
+    let error = gh_client::blocking::GithubEnvClient::init(
+        Some("octo-org".to_string()),
+        "not-a-real-token".to_string(),
+        "octo-org",
+        "octo-repo",
+        &replay_options(LIST_VARIABLES),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("within an async runtime"));
+}
+
+#[tokio::test]
+async fn caches_environment_public_keys() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    // The cassette holds a single public key response, so the second call
+    // fails unless it is answered from the cache.
+    let first = client
+        .get_environment_public_key("production")
+        .await
+        .unwrap();
+    let second = client
+        .get_environment_public_key("production")
+        .await
+        .unwrap();
+    assert_eq!(first, second);
+    assert_eq!(client.public_key_cache().len(), 1);
+}
+
+#[tokio::test]
+async fn replays_the_token_account() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let login = client.get_authenticated_login().await.unwrap();
+    assert_eq!(login, "octo-deployer");
+}
+
+#[tokio::test]
+async fn replays_repository_and_organization_variables() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let repository = client.list_repository_variables().await.unwrap();
+    assert_eq!(
+        repository,
+        HashMap::from([("FOO".to_string(), "repository-foo".to_string())])
+    );
+
+    let organization = client.list_organization_variables().await.unwrap();
+    assert_eq!(organization.len(), 2);
+    assert_eq!(organization["REGION"], "eu-west-1");
+}
+
+#[tokio::test]
+async fn replays_environment_secrets() {
+    let client = replay_client(LIST_VARIABLES).await;
+
+    let secrets = client.list_environment_secrets("production").await.unwrap();
+    assert_eq!(
+        secrets
+            .iter()
+            .map(|secret| secret.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["API_TOKEN", "DB_PASSWORD"]
+    );
+
+    let secret = client
+        .get_environment_secret("production", "API_TOKEN")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(secret.updated_at.as_deref(), Some("2023-09-03T12:00:00Z"));
+    assert!(!client
+        .environment_secret_exists("production", "MISSING")
+        .await
+        .unwrap());
+
+    client
+        .delete_environment_secret("production", "DB_PASSWORD")
+        .await
+        .unwrap();
+}
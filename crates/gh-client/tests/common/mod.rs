@@ -0,0 +1,45 @@
+//! Clients for the integration tests that replay recorded fixtures rather
+//! than calling Github.
+
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use gh_client::{Cassette, GithubEnvClient, HttpOptions};
+
+/// Environments of octo-org/octo-repo and the variables of `production`.
+pub const LIST_VARIABLES: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/list_variables.json"
+);
+
+/// The variables of `production`, across two pages.
+pub const PAGINATED_VARIABLES: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/paginated_variables.json"
+);
+
+/// Options that replay `fixture` instead of sending requests.
+pub fn replay_options(fixture: &str) -> HttpOptions {
+    HttpOptions {
+        cassette: Some(Cassette::replay(fixture).unwrap()),
+        ..HttpOptions::default()
+    }
+}
+
+/// A client for octo-org/octo-repo.
+pub async fn client(http_options: &HttpOptions) -> GithubEnvClient {
+    GithubEnvClient::init(
+        Some("octo-org".to_string()),
+        "not-a-real-token".to_string(),
+        "octo-org",
+        "octo-repo",
+        http_options,
+    )
+    .await
+    .unwrap()
+}
+
+/// A client for octo-org/octo-repo which replays `fixture`.
+pub async fn replay_client(fixture: &str) -> GithubEnvClient {
+    client(&replay_options(fixture)).await
+}
@@ -0,0 +1,100 @@
+use gh_client::{ApiHost, RequestHook, StaticHeaders};
+use reqwest::{Method, Request, Url};
+
+fn request() -> Request {
+    let mut request = Request::new(
+        Method::GET,
+        Url::parse("https://api.github.com/repos/octo-org/octo-repo").unwrap(),
+    );
+    request
+        .headers_mut()
+        .insert("X-Audit-Id", "from-the-client".parse().unwrap());
+    request
+}
+
+#[test]
+fn static_headers_are_added_to_requests() {
+    let hook = StaticHeaders::new([
+        ("Proxy-Authorization", "Bearer proxy-token"),
+        ("X-Audit-Id", "run-42"),
+    ])
+    .unwrap();
+
+    let mut request = request();
+    hook.on_request(&mut request).unwrap();
+
+    assert_eq!(
+        request.headers()["Proxy-Authorization"],
+        "Bearer proxy-token"
+    );
+    let audit_ids = request.headers().get_all("X-Audit-Id").iter();
+    assert_eq!(
+        audit_ids.map(|id| id.to_str().unwrap()).collect::<Vec<_>>(),
+        ["run-42"]
+    );
+}
+
+#[test]
+fn static_headers_hide_their_values() {
+    let hook = StaticHeaders::new([("Proxy-Authorization", "Bearer proxy-token")]).unwrap();
+
+    let debug = format!("{:?}", hook);
+    assert!(debug.contains("proxy-authorization"));
+    assert!(!debug.contains("proxy-token"));
+}
+
+#[test]
+fn invalid_headers_are_rejected() {
+    assert!(StaticHeaders::new([("Not A Header", "value")]).is_err());
+    assert!(StaticHeaders::new([("X-Audit-Id", "line\nbreak")]).is_err());
+}
+
+#[test]
+fn api_host_sends_requests_to_an_enterprise_server() {
+    let hook = ApiHost::new("https://github.example.com/").unwrap();
+    assert_eq!(hook.host(), "github.example.com");
+
+    let mut rest = Request::new(
+        Method::GET,
+        Url::parse("https://api.github.com/repos/octo-org/octo-repo/environments?per_page=100")
+            .unwrap(),
+    );
+    hook.on_request(&mut rest).unwrap();
+    assert_eq!(
+        rest.url().as_str(),
+        "https://github.example.com/api/v3/repos/octo-org/octo-repo/environments?per_page=100"
+    );
+
+    let mut graphql = Request::new(
+        Method::POST,
+        Url::parse("https://api.github.com/graphql").unwrap(),
+    );
+    hook.on_request(&mut graphql).unwrap();
+    assert_eq!(
+        graphql.url().as_str(),
+        "https://github.example.com/api/graphql"
+    );
+
+    let mut next_page = Request::new(
+        Method::GET,
+        Url::parse("https://github.example.com/api/v3/repositories/1/environments?page=2").unwrap(),
+    );
+    hook.on_request(&mut next_page).unwrap();
+    assert_eq!(
+        next_page.url().as_str(),
+        "https://github.example.com/api/v3/repositories/1/environments?page=2"
+    );
+}
+
+#[test]
+fn api_host_accepts_an_api_url() {
+    let hook = ApiHost::new("https://github.example.com/api/v3/").unwrap();
+    assert_eq!(hook.host(), "github.example.com");
+}
+
+#[test]
+fn api_host_is_not_needed_for_github_com() {
+    assert_eq!(ApiHost::new("github.com"), None);
+    assert_eq!(ApiHost::new("api.github.com"), None);
+    assert_eq!(ApiHost::new(""), None);
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "cassette")]
+
+use common::{replay_client, PAGINATED_VARIABLES};
+use futures_util::TryStreamExt;
+
+mod common;
+
+#[tokio::test]
+async fn follows_link_headers_to_the_last_page() {
+    let client = replay_client(PAGINATED_VARIABLES).await;
+
+    let mut pages = client.paginate_environment_variables("production");
+    let first = pages.next_page().await.unwrap().unwrap();
+    assert_eq!(
+        (first.number, first.items.len(), first.has_next),
+        (1, 2, true)
+    );
+    let second = pages.next_page().await.unwrap().unwrap();
+    assert_eq!((second.number, second.has_next), (2, false));
+    assert_eq!(second.items[0].name, "REGION");
+    assert!(pages.next_page().await.unwrap().is_none());
+
+    // The cassette has both pages a second time, for the stream.
+    let names = client
+        .paginate_environment_variables("production")
+        .into_stream()
+        .map_ok(|variable| variable.name)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(names, vec!["API_URL", "LOG_LEVEL", "REGION"]);
+}
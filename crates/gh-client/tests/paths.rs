@@ -0,0 +1,23 @@
+use gh_client::encode_path_segment;
+
+#[test]
+fn leaves_plain_names_alone() {
+    assert_eq!(encode_path_segment("production"), "production");
+    assert_eq!(encode_path_segment("API_URL"), "API_URL");
+    assert_eq!(encode_path_segment("deploy.yml"), "deploy.yml");
+}
+
+#[test]
+fn encodes_spaces_and_slashes() {
+    assert_eq!(encode_path_segment("QA / Staging"), "QA%20%2F%20Staging");
+}
+
+#[test]
+fn encodes_unicode_as_utf8() {
+    assert_eq!(encode_path_segment("préprod"), "pr%C3%A9prod");
+}
+
+#[test]
+fn encodes_characters_that_would_change_the_url() {
+    assert_eq!(encode_path_segment("a?b#c%d"), "a%3Fb%23c%25d");
+}
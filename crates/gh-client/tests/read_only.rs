@@ -0,0 +1,86 @@
+#![cfg(feature = "cassette")]
+
+use std::sync::Arc;
+
+use color_eyre::Result;
+use common::{client, replay_options, LIST_VARIABLES};
+use gh_client::{GithubEnvClient, HttpOptions, ReadOnlyError, RequestHook};
+use reqwest::{Method, Request};
+
+mod common;
+
+/// Replaces the GraphQL document of every GraphQL request.
+#[derive(Debug)]
+struct RewriteQuery(&'static str);
+
+impl RequestHook for RewriteQuery {
+    fn on_request(&self, request: &mut Request) -> Result<()> {
+        if request.url().path().ends_with("/graphql") {
+            let body = serde_json::json!({ "query": self.0 }).to_string();
+            *request.body_mut() = Some(body.into());
+        }
+        Ok(())
+    }
+}
+
+async fn read_only_client() -> GithubEnvClient {
+    read_only_client_with_hooks(Vec::new()).await
+}
+
+async fn read_only_client_with_hooks(request_hooks: Vec<Arc<dyn RequestHook>>) -> GithubEnvClient {
+    client(&HttpOptions {
+        read_only: true,
+        request_hooks,
+        ..replay_options(LIST_VARIABLES)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn reads_are_allowed_in_read_only_mode() {
+    let client = read_only_client().await;
+
+    let environments = client.list_environments().await.unwrap();
+    assert_eq!(environments, vec!["development", "production"]);
+}
+
+#[tokio::test]
+async fn writes_are_refused_in_read_only_mode() {
+    let client = read_only_client().await;
+
+    let error = client
+        .delete_environment_variable("production", "FOO")
+        .await
+        .unwrap_err();
+    let error = error.downcast_ref::<ReadOnlyError>().unwrap();
+    assert_eq!(error.method, reqwest::Method::DELETE);
+    assert_eq!(
+        error.path,
+        "/repositories/1296269/environments/production/variables/FOO"
+    );
+}
+
+#[tokio::test]
+async fn own_graphql_queries_are_allowed_in_read_only_mode() {
+    let client = read_only_client().await;
+
+    // Refused only by the cassette, which has no GraphQL interaction.
+    let error = client.list_environments_graphql().await.unwrap_err();
+    assert!(error.downcast_ref::<ReadOnlyError>().is_none(), "{}", error);
+}
+
+#[tokio::test]
+async fn other_graphql_documents_are_refused_in_read_only_mode() {
+    for query in [
+        "# query\nmutation { deleteEnvironment(input: {}) { clientMutationId } }",
+        "query Q { viewer { login } } mutation M { deleteEnvironment(input: {}) { clientMutationId } }",
+        "{ viewer { login } }",
+    ] {
+        let client = read_only_client_with_hooks(vec![Arc::new(RewriteQuery(query))]).await;
+
+        let error = client.list_environments_graphql().await.unwrap_err();
+        let error = error.downcast_ref::<ReadOnlyError>().unwrap();
+        assert_eq!(error.method, Method::POST);
+        assert_eq!(error.path, "/graphql");
+    }
+}
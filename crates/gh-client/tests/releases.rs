@@ -0,0 +1,29 @@
+#![cfg(feature = "cassette")]
+
+use gh_client::{download_release_asset, get_release, Cassette, HttpOptions};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/releases.json");
+
+#[tokio::test]
+async fn downloads_release_assets_without_a_token() {
+    let http_options = HttpOptions {
+        cassette: Some(Cassette::replay(FIXTURE).unwrap()),
+        ..HttpOptions::default()
+    };
+
+    let release = get_release("michaelhelvey", "gh-env-sync", None, None, &http_options)
+        .await
+        .unwrap();
+    assert_eq!(release.tag_name, "v0.2.0");
+    assert!(release
+        .asset("gh-env-sync-x86_64-unknown-linux-musl")
+        .is_none());
+
+    let checksums = release.asset("SHA256SUMS").unwrap();
+    let contents = download_release_asset(checksums, None, &http_options)
+        .await
+        .unwrap();
+    assert!(String::from_utf8(contents)
+        .unwrap()
+        .ends_with("  gh-env-sync-x86_64-unknown-linux-musl\n"));
+}
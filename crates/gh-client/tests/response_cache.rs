@@ -0,0 +1,75 @@
+#![cfg(feature = "cassette")]
+
+use std::path::{Path, PathBuf};
+
+use common::{client, replay_options, LIST_VARIABLES};
+use gh_client::{HttpOptions, ResponseCache};
+
+mod common;
+
+/// Fills a cache file by reading through a client that replays the fixture.
+async fn fill_cache(path: &Path) {
+    let client = client(&HttpOptions {
+        response_cache: Some(ResponseCache::open(path).unwrap()),
+        ..replay_options(LIST_VARIABLES)
+    })
+    .await;
+
+    client.list_environments().await.unwrap();
+    client
+        .list_environment_variables("production")
+        .await
+        .unwrap();
+}
+
+fn cache_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("gh-client-{}-{}.json", name, std::process::id()))
+}
+
+#[tokio::test]
+async fn answers_cached_reads_offline() {
+    let path = cache_path("offline-reads");
+    fill_cache(&path).await;
+
+    let client = client(&HttpOptions {
+        response_cache: Some(ResponseCache::offline(&path).unwrap()),
+        ..HttpOptions::default()
+    })
+    .await;
+
+    let environments = client.list_environments().await.unwrap();
+    assert_eq!(environments, vec!["development", "production"]);
+    let variables = client
+        .list_environment_variables("production")
+        .await
+        .unwrap();
+    assert_eq!(variables.get("FOO").map(String::as_str), Some("baz"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn refuses_writes_and_uncached_reads_offline() {
+    let path = cache_path("offline-writes");
+    fill_cache(&path).await;
+
+    let client = client(&HttpOptions {
+        response_cache: Some(ResponseCache::offline(&path).unwrap()),
+        ..HttpOptions::default()
+    })
+    .await;
+
+    let error = client
+        .delete_environment_variable("production", "FOO")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("while offline"));
+
+    let error = client
+        .list_environment_variables("development")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("is not in the response cache"));
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,35 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crypto_box::{aead::OsRng, SecretKey};
+use gh_client::{encrypt_secret, PublicKey};
+
+#[test]
+fn sealed_secret_opens_with_matching_secret_key() {
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let public_key = PublicKey {
+        key_id: "012345678912345678".to_string(),
+        key: STANDARD.encode(secret_key.public_key().as_bytes()),
+    };
+
+    let encrypted = public_key.encrypt("hunter2").unwrap();
+    let sealed = STANDARD.decode(encrypted).unwrap();
+    let opened = secret_key.unseal(&sealed).unwrap();
+
+    assert_eq!(opened, b"hunter2");
+}
+
+#[test]
+fn sealing_is_randomized() {
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let key = STANDARD.encode(secret_key.public_key().as_bytes());
+
+    assert_ne!(
+        encrypt_secret(&key, "value").unwrap(),
+        encrypt_secret(&key, "value").unwrap()
+    );
+}
+
+#[test]
+fn rejects_malformed_public_keys() {
+    assert!(encrypt_secret("not base64!", "value").is_err());
+    assert!(encrypt_secret(&STANDARD.encode([0u8; 16]), "value").is_err());
+}
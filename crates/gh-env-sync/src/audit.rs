@@ -0,0 +1,136 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::expiry::civil_from_days;
+
+/// A write made to Github.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    CreateEnvironment,
+    Create,
+    Update,
+    Delete,
+    DeleteEnvironment,
+    /// An environment secret was written.  Its value isn't recorded.
+    PutSecret,
+}
+
+/// A single line of the audit log.  Variable values are not secret, so both
+/// the previous and new values are recorded, which is what makes rollback
+/// possible.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub run_id: String,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    /// `owner/repo`.
+    pub repository: String,
+    pub environment: String,
+    /// Empty for environment level operations.
+    #[serde(default)]
+    pub key: String,
+    pub operation: AuditOperation,
+    #[serde(default)]
+    pub previous_value: Option<String>,
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Which token made the write, when it wasn't the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// The person the run was attributed to with `--actor`, for tokens
+    /// shared by several people.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+/// An append-only JSON lines file recording every write made to Github.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Appends a record to the log.
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        debug!("Appending audit record {:?}", record);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| eyre!("Error opening audit log {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .map_err(|e| eyre!("Error writing audit log {}: {}", self.path.display(), e))
+    }
+
+    /// Reads every record in the log, oldest first.
+    pub fn read(&self) -> Result<Vec<AuditRecord>> {
+        let file = std::fs::File::open(&self.path)
+            .map_err(|e| eyre!("Error opening audit log {}: {}", self.path.display(), e))?;
+
+        BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|(index, line)| {
+                serde_json::from_str(&line?).map_err(|e| {
+                    eyre!(
+                        "Error parsing line {} of audit log {}: {}",
+                        index + 1,
+                        self.path.display(),
+                        e
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Seconds since the unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats a unix timestamp as a `YYYY-MM-DD HH:MM` UTC date and time.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    let seconds = timestamp % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60
+    )
+}
+
+/// A reasonably unique identifier for a single invocation, used to group
+/// audit records.
+pub fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    format!("{:x}-{:x}", nanos, std::process::id())
+}
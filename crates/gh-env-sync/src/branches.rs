@@ -0,0 +1,67 @@
+use std::{collections::BTreeMap, process::Command};
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::discover::matches_glob;
+
+/// The environment a branch deploys to, per the config document's
+/// `[branches]` rules, which map branch names or globs to environments:
+///
+/// ```toml
+/// [branches]
+/// main = "production"
+/// develop = "staging"
+/// "feature/*" = "preview"
+/// ```
+///
+/// A rule naming the branch exactly wins over globs.  When several globs
+/// match, the longest, i.e. most specific, one wins.
+pub fn environment_for_branch<'a>(
+    rules: &'a BTreeMap<String, String>,
+    branch: &str,
+) -> Option<&'a str> {
+    if let Some(environment) = rules.get(branch) {
+        return Some(environment);
+    }
+
+    rules
+        .iter()
+        .filter(|(pattern, _)| matches_glob(pattern, branch))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, environment)| environment.as_str())
+}
+
+/// The branch being deployed.  In Github Actions this is the pull request's
+/// head branch (`GITHUB_HEAD_REF`) or the pushed branch (from `GITHUB_REF`),
+/// and elsewhere the branch checked out in the working directory.
+pub fn current_branch() -> Result<String> {
+    if let Some(branch) = std::env::var("GITHUB_HEAD_REF")
+        .ok()
+        .filter(|branch| !branch.is_empty())
+    {
+        return Ok(branch);
+    }
+    if let Ok(reference) = std::env::var("GITHUB_REF") {
+        if let Some(branch) = reference.strip_prefix("refs/heads/") {
+            return Ok(branch.to_string());
+        }
+    }
+
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .map_err(|e| eyre!("Error running git: {}", e))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Error reading the current git branch: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "HEAD" => Err(eyre!(
+            "HEAD is detached, so there is no branch to pick an environment for"
+        )),
+        branch => Ok(branch.to_string()),
+    }
+}
@@ -0,0 +1,61 @@
+//! Stopping a sync cleanly on Ctrl-C or SIGTERM: the request in flight is
+//! finished and the writes made so far are recorded in the --checkpoint-file
+//! for --resume, rather than leaving an environment half written with no
+//! record of which half.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, OnceLock,
+};
+
+use tracing::warn;
+
+/// The exit code of a process killed by SIGINT, which a second interrupt
+/// exits with straight away.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Set once the process is interrupted.  The signals are only listened for
+/// from the first call on, so commands that never sync keep the default
+/// behavior of exiting immediately.
+pub fn cancellation() -> Arc<AtomicBool> {
+    static CANCELLED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    CANCELLED
+        .get_or_init(|| {
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let flag = cancelled.clone();
+            tokio::spawn(async move {
+                loop {
+                    interrupted().await;
+                    if flag.swap(true, Ordering::Relaxed) {
+                        std::process::exit(INTERRUPTED_EXIT_CODE);
+                    }
+                    warn!("Stopping once the request in flight finishes; interrupt again to exit immediately");
+                }
+            });
+            cancelled
+        })
+        .clone()
+}
+
+/// Whether the process was interrupted.
+pub fn is_cancelled() -> bool {
+    cancellation().load(Ordering::Relaxed)
+}
+
+/// Waits for Ctrl-C or, on unix, SIGTERM.
+async fn interrupted() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Error listening for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    audit::{unix_timestamp, AuditOperation},
+    state::{hash_value, State},
+};
+
+/// A write that an interrupted sync completed before it stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedWrite {
+    /// Empty for organization secrets.
+    pub environment: String,
+    /// Empty for environment level operations.
+    #[serde(default)]
+    pub key: String,
+    pub operation: AuditOperation,
+    /// SHA-256 hash of the written value, as in the state file, so that a
+    /// value changed in the config since is written again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_hash: Option<String>,
+}
+
+impl CompletedWrite {
+    pub fn new(
+        environment: &str,
+        key: &str,
+        operation: AuditOperation,
+        value: Option<&str>,
+    ) -> Self {
+        Self {
+            environment: environment.to_string(),
+            key: key.to_uppercase(),
+            operation,
+            value_hash: value.map(hash_value),
+        }
+    }
+}
+
+/// What an interrupted sync of a single repository had written.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryCheckpoint {
+    /// The run id of the interrupted sync, which a resumed sync keeps so
+    /// that the audit log records both as one run.
+    pub run_id: String,
+    pub completed: Vec<CompletedWrite>,
+}
+
+impl RepositoryCheckpoint {
+    /// Whether the given write was completed with the same value.
+    pub fn contains(&self, write: &CompletedWrite) -> bool {
+        self.completed.contains(write)
+    }
+
+    /// Records the variables the interrupted sync wrote in the state, which
+    /// it was cancelled before recording, so that the resumed sync doesn't
+    /// report them as drift.
+    pub fn record_in(&self, state: &mut State, repository: &str) {
+        for write in &self.completed {
+            match (write.operation, &write.value_hash) {
+                (AuditOperation::Create | AuditOperation::Update, Some(hash)) => {
+                    state
+                        .repositories
+                        .entry(repository.to_string())
+                        .or_default()
+                        .environments
+                        .entry(write.environment.clone())
+                        .or_default()
+                        .insert(write.key.clone(), hash.clone());
+                }
+                (AuditOperation::Delete, _) => state.record_deleted(
+                    repository,
+                    &write.environment,
+                    &write.key,
+                    unix_timestamp(),
+                ),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A record of interrupted syncs, written when a sync is cancelled so that
+/// `--resume` can pick up where it stopped rather than writing, and auditing,
+/// everything again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Keyed by `owner/repo`.
+    pub repositories: BTreeMap<String, RepositoryCheckpoint>,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint file at the given path.  A missing file is
+    /// treated as an empty checkpoint, since no sync was interrupted.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            debug!("No checkpoint file at {}", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading checkpoint file {}: {}", path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| eyre!("Error parsing checkpoint file {}: {}", path.display(), e))
+    }
+
+    /// Writes the checkpoint to the given path, removing the file instead
+    /// once no interrupted syncs are left.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if self.repositories.is_empty() {
+            if path.exists() {
+                debug!("Removing checkpoint file {}", path.display());
+                std::fs::remove_file(path).map_err(|e| {
+                    eyre!("Error removing checkpoint file {}: {}", path.display(), e)
+                })?;
+            }
+            return Ok(());
+        }
+
+        debug!("Writing checkpoint file {}", path.display());
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .map_err(|e| eyre!("Error writing checkpoint file {}: {}", path.display(), e))
+    }
+
+    /// Records what an interrupted sync of the repository had written,
+    /// replacing any earlier record.
+    pub fn record(
+        path: impl AsRef<Path>,
+        repository: &str,
+        checkpoint: RepositoryCheckpoint,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let mut document = Self::load(path)?;
+        document
+            .repositories
+            .insert(repository.to_string(), checkpoint);
+        document.save(path)
+    }
+
+    /// Forgets the interrupted sync of the repository, once it has finished.
+    pub fn clear(path: impl AsRef<Path>, repository: &str) -> Result<()> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let mut document = Self::load(path)?;
+        if document.repositories.remove(repository).is_some() {
+            document.save(path)?;
+        }
+        Ok(())
+    }
+}
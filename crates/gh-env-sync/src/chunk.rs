@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    config::{Environment, EnvironmentConfig},
+    quota::MAX_VALUE_BYTES,
+    sync::{VariableChange, VariableReport},
+};
+
+/// Separates a chunked key from the number of each of its parts, e.g.
+/// `SERVICE_MAP__PART2`.
+pub const PART_SEPARATOR: &str = "__PART";
+
+/// The name of a chunked key's part, numbered from 1.
+pub fn part_key(key: &str, part: usize) -> String {
+    format!("{}{}{}", key, PART_SEPARATOR, part)
+}
+
+/// Splits a part's name into the key it belongs to and its number, or
+/// `None` if it isn't one.
+pub fn parse_part_key(key: &str) -> Option<(&str, usize)> {
+    let (base, part) = key.rsplit_once(PART_SEPARATOR)?;
+    let number = part
+        .parse::<usize>()
+        .ok()
+        .filter(|number| *number > 0 && !part.starts_with('0'))?;
+    (!base.is_empty()).then_some((base, number))
+}
+
+/// Splits a value into parts of at most `max_bytes` each, without splitting
+/// a character.
+pub fn split_value(value: &str, max_bytes: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = value;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max_bytes);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (part, tail) = rest.split_at(end);
+        parts.push(part.to_string());
+        rest = tail;
+    }
+    parts
+}
+
+/// Replaces each variable marked with `chunk = true` whose value is larger
+/// than Github allows with `KEY__PART1` to `KEY__PARTn` variables holding
+/// consecutive pieces of it, which [`join_chunks`] puts back together.
+/// Values that fit are left alone.  Parts keep the variable's other
+/// options, so that [`stale_chunks`] can tell which keys were chunked.
+pub fn split_chunked(environment: &mut EnvironmentConfig) {
+    let oversized = environment
+        .iter()
+        .filter(|(_, variable)| variable.chunk && variable.value.len() > MAX_VALUE_BYTES)
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+
+    for key in oversized {
+        let variable = environment.remove(&key).expect("oversized keys exist");
+        for (i, part) in split_value(&variable.value, MAX_VALUE_BYTES)
+            .into_iter()
+            .enumerate()
+        {
+            let mut part_variable = variable.clone();
+            part_variable.value = part;
+            environment.insert(part_key(&key, i + 1), part_variable);
+        }
+    }
+}
+
+/// Whether any variable of the environment may be chunked.
+pub fn has_chunked(environment: &EnvironmentConfig) -> bool {
+    environment.values().any(|variable| variable.chunk)
+}
+
+/// Splits the values of `keys` that are larger than Github allows the same
+/// way as [`split_chunked`], for plain values that were already resolved.
+pub fn split_values<'a>(variables: &mut Environment, keys: impl IntoIterator<Item = &'a String>) {
+    for key in keys {
+        let Some(value) = variables
+            .get(key)
+            .filter(|value| value.len() > MAX_VALUE_BYTES)
+        else {
+            continue;
+        };
+        let parts = split_value(value, MAX_VALUE_BYTES);
+        variables.remove(key);
+        for (i, part) in parts.into_iter().enumerate() {
+            variables.insert(part_key(key, i + 1), part);
+        }
+    }
+}
+
+/// Puts the parts of chunked values read from Github back together: every
+/// run of `KEY__PART1`, `KEY__PART2` and so on, without gaps, is replaced
+/// by `KEY` holding their concatenated values, unless `KEY` itself exists.
+/// Returns the keys that were joined.
+pub fn join_chunks(variables: &mut Environment) -> Vec<String> {
+    let mut bases = variables
+        .keys()
+        .filter_map(|key| parse_part_key(key))
+        .filter(|(_, number)| *number == 1)
+        .map(|(base, _)| base.to_string())
+        .filter(|base| !variables.contains_key(base))
+        .collect::<Vec<_>>();
+    bases.sort();
+
+    for base in &bases {
+        let mut value = String::new();
+        let mut part = 1;
+        while let Some(piece) = variables.remove(&part_key(base, part)) {
+            value.push_str(&piece);
+            part += 1;
+        }
+        variables.insert(base.clone(), value);
+    }
+    bases
+}
+
+/// The deletions that clean up after chunked keys changed shape: parts left
+/// over from a longer value, and the whole key once it is split into parts,
+/// or its parts once it fits again.  `environment` is the resolved
+/// environment, after [`split_chunked`], and `remote` the environment's
+/// variables on Github, keyed by uppercased name.
+pub fn stale_chunks(environment: &EnvironmentConfig, remote: &Environment) -> Vec<VariableReport> {
+    let configured = environment
+        .keys()
+        .map(|key| key.to_uppercase())
+        .collect::<HashSet<_>>();
+    let chunked = environment
+        .iter()
+        .filter(|(_, variable)| variable.chunk)
+        .map(|(key, _)| match parse_part_key(key) {
+            Some((base, _)) => base.to_uppercase(),
+            None => key.to_uppercase(),
+        })
+        .collect::<HashSet<_>>();
+
+    let mut stale = remote
+        .keys()
+        .filter(|key| !configured.contains(*key))
+        .filter(|key| {
+            chunked.contains(*key)
+                || parse_part_key(key).is_some_and(|(base, _)| chunked.contains(base))
+        })
+        .map(|key| VariableReport {
+            key: key.clone(),
+            change: VariableChange::Delete,
+            drifted: false,
+        })
+        .collect::<Vec<_>>();
+    stale.sort_by(|a, b| a.key.cmp(&b.key));
+    stale
+}
+
+/// The keys marked with `chunk = true` in each environment, keyed by
+/// environment name.
+pub fn chunked_keys<'a>(
+    environments: impl IntoIterator<Item = (&'a String, &'a EnvironmentConfig)>,
+) -> HashMap<String, Vec<String>> {
+    environments
+        .into_iter()
+        .map(|(name, environment)| {
+            let keys = environment
+                .iter()
+                .filter(|(_, variable)| variable.chunk)
+                .map(|(key, _)| key.clone())
+                .collect();
+            (name.clone(), keys)
+        })
+        .collect()
+}
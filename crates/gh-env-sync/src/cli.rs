@@ -1,12 +1,512 @@
-use clap::Parser;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgAction, CommandFactory, FromArgMatches, Parser,
+    Subcommand, ValueEnum,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::{
+    list_organization_repositories, ApiHost, GithubEnvClient, HttpOptions, OrganizationRepository,
+    PublicKeyCache, RequestHook, ResponseCache, StaticHeaders, Throttle,
+};
+use gh_env_sync::{
+    audit::new_run_id,
+    defaults::{read_cli_defaults, CliDefaults, OutputFormat},
+    discover::{
+        locate_config, matches_glob, user_config_dir, DEFAULT_CONFIG_FILE, REQUEST_HEADERS_FILE,
+    },
+    encryption,
+    env_prefix::EnvPrefix,
+    exclusions::{Exclusions, IGNORE_FILE_NAME},
+    files::config_dir,
+    plan::{SignatureKind, SigstoreIdentity},
+    preview::detect_pull_request,
+    providers::{BuiltinProvider, ProviderRegistry},
+    source::{ConfigSource, SourceAuth},
+    state::hash_value,
+    ApplyStrategy, AuthConfig, ConfigDocument, ConflictStrategy, LockMode, MissingEnvironments,
+    SyncOptions, Syncer,
+};
+use tracing::{debug, info, warn};
+
+/// What `export` renders the config file as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Resources of the Github Terraform provider.
+    Terraform,
+}
+
+/// Formats of the --report written after a dry run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A standalone HTML page with a section per environment and search.
+    Html,
+}
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Syncing is the default when no subcommand is given.
+    #[command(flatten)]
+    pub sync: Option<SyncArgs>,
+
     #[arg(
-        help = "The repository to sync environment variables for, specified as an owner/repo pair, e.g. rust-lang/rust-lang."
+        long,
+        global = true,
+        help = "Print tables without color.  Color is also off when NO_COLOR is set or stdout isn't a terminal."
     )]
-    pub repository: String,
+    pub no_color: bool,
+}
+
+impl Args {
+    /// Parses the command line, exiting with a usage error if it's invalid.
+    /// clap's `args_conflicts_with_subcommands` would also reject the global
+    /// options given before a subcommand, so a sync's other arguments are
+    /// rejected there here instead.
+    pub fn parse_command_line(args: Vec<String>) -> Self {
+        let mut command = Self::command();
+        let matches = command.clone().get_matches_from(args);
+        if matches.subcommand().is_some() {
+            let given = command
+                .get_arguments()
+                .find(|arg| {
+                    !arg.is_global_set()
+                        && matches.value_source(arg.get_id().as_str())
+                            == Some(ValueSource::CommandLine)
+                })
+                .map(ToString::to_string);
+            if let Some(arg) = given {
+                command
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("{} can't be given with a subcommand", arg),
+                    )
+                    .exit();
+            }
+        }
+
+        Self::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut command).exit())
+    }
+
+    /// The arguments of a sync, whether given with the `sync`, `diff` or
+    /// `plan` subcommand or without one.
+    pub fn sync_args(&self) -> Option<&SyncArgs> {
+        match &self.command {
+            Some(Command::Sync(options))
+            | Some(Command::Diff(options))
+            | Some(Command::Plan(options)) => Some(options.as_ref()),
+            _ => self.sync.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Sync the config file to Github.  Giving a sync's arguments without a
+    /// subcommand does the same.
+    Sync(Box<SyncArgs>),
+    /// Print the changes a sync would make without writing anything to
+    /// Github, like `sync --dry-run`.
+    Diff(Box<SyncArgs>),
+    /// Write the variable changes a sync would make to a plan file for
+    /// review, to be made later with `apply`, like `sync --dry-run
+    /// --plan-file`.
+    Plan(Box<SyncArgs>),
+    /// Make the writes of a plan file written by `plan`, refusing if any of
+    /// its variables changed on Github since.
+    Apply(Box<ApplyArgs>),
+    /// Undo every write recorded in the audit log after the given run.
+    Rollback(Box<RollbackArgs>),
+    /// Recreate a variable deleted by a sync, e.g. with --prune, with its
+    /// value from before the deletion as recorded in the audit log.
+    Undelete(Box<UndeleteArgs>),
+    /// Browse environments in a terminal UI, comparing them against the
+    /// config file and applying selected changes.
+    Tui(Box<TuiArgs>),
+    /// Keep running, re-reading the config file and syncing it on an
+    /// interval, with health and metrics served over HTTP.
+    Serve(Box<ServeArgs>),
+    /// Print a JSON Schema for the config file, for editor validation and
+    /// autocompletion (e.g. with taplo's `#:schema` directive).
+    Schema,
+    /// Show the token's remaining rate limit, and whether a sync of the
+    /// config file fits in it.
+    RateLimit(Box<RateLimitArgs>),
+    /// Add remote variables that are missing from the config file to it,
+    /// marking keys whose remote value differs with a comment.
+    #[command(visible_alias = "pull")]
+    Import(Box<ImportArgs>),
+    /// Print the config file's environments and variables in another tool's
+    /// format, e.g. as Terraform resources.
+    Export(Box<ExportArgs>),
+    /// Report keys with the same value in every environment, which could be
+    /// shared, and keys whose values differ suspiciously little between
+    /// environments, which may be copy-paste mistakes.
+    Duplicates(Box<DuplicatesArgs>),
+    /// Flag environments configured but missing on Github and the other way
+    /// around, empty environments, references and placeholders that don't
+    /// resolve, and plaintext values that look like credentials.
+    Lint(Box<LintArgs>),
+    /// List the repository's environments with how many variables and
+    /// secrets each holds, or the keys of one environment.
+    List(Box<ListArgs>),
+    /// Check that the config file parses and that Github would accept every
+    /// environment name, key and value in it, without contacting Github.
+    Validate(Box<ValidateArgs>),
+    /// Rename a variable in place: create the new key with the old value,
+    /// verify it, then delete the old key.
+    RenameKey(Box<RenameKeyArgs>),
+    /// Check that the repository's variables hold the configured values by
+    /// comparing SHA-256 hashes, without ever printing a value.
+    Verify(Box<VerifyArgs>),
+    /// Show where a key's value comes from in the config file, and what it
+    /// holds on Github in the environment, the repository and the
+    /// organization, to tell which value a workflow sees.
+    Explain(Box<ExplainArgs>),
+    /// Show a timeline of the variable changes recorded in the audit log.
+    History(Box<HistoryArgs>),
+    /// Delete a pull request's preview environment, e.g. when it is closed,
+    /// or the environments and variables of the config file that expired.
+    Cleanup(Box<CleanupArgs>),
+    /// Run the syncs of several profiles from the profiles file in the user
+    /// config directory concurrently, e.g. one per organization, and print a
+    /// combined summary.
+    All(Box<AllArgs>),
+    /// Replace this binary with the latest release of gh-env-sync for this
+    /// platform, after verifying its checksum.
+    SelfUpdate(Box<SelfUpdateArgs>),
+}
+
+/// Options for connecting to the Github API, shared by every command.  They
+/// are global, so that they can be given before the subcommand too, e.g.
+/// `gh-env-sync --token $TOKEN list owner/repo`.  Global arguments can't be
+/// required, so a missing --token is only reported once a command needs it.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ConnectionArgs {
+    #[arg(
+        help = "The repository to sync environment variables for, specified as an owner/repo pair, e.g. rust-lang/rust-lang.  Syncing can use --repos-file or --org instead."
+    )]
+    pub repository: Option<String>,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        env = "GH_TOKEN",
+        hide_env_values = true,
+        help = "A 'repo' scoped Github access token to use for requests to the Github API.  Defaults to GH_TOKEN, then GITHUB_TOKEN, then the token `gh auth login` stored, as when run as `gh env-sync`."
+    )]
+    pub token: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        visible_alias = "api-url",
+        env = "GH_HOST",
+        value_name = "HOST",
+        help = "The Github Enterprise Server to use instead of github.com, e.g. github.example.com, or its API URL, e.g. https://github.example.com/api/v3.  Requests go to its API at https://HOST/api/v3."
+    )]
+    pub host: Option<String>,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        help = "A username to add to the User-Agent header of requests to the Github API, which otherwise only names gh-env-sync and its version."
+    )]
+    pub username: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "NAME",
+        help = "Insert the arguments of this profile from profiles.toml in the user config directory (see `all`) in place of --profile, e.g. to take --token and --config-path from it."
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        help = "How sync, diff, list and validate report their results: 'text', or 'json' to write JSON to stdout, for a sync one object per sync event.  Defaults to output in the config file's [cli] section, or 'text'."
+    )]
+    pub output: Option<OutputFormat>,
+
+    /// Sent with every request and recorded in the audit log, to correlate a
+    /// run's requests and writes.
+    #[arg(skip = new_run_id())]
+    pub run_id: String,
+
+    #[arg(
+        global = true,
+        long,
+        default_value_t = 30,
+        value_name = "SECONDS",
+        help = "Total time allowed for a single request to the Github API before it is aborted."
+    )]
+    pub http_timeout: u64,
+
+    #[arg(
+        global = true,
+        long,
+        default_value_t = 10,
+        value_name = "SECONDS",
+        help = "Time allowed to establish a connection to the Github API."
+    )]
+    pub connect_timeout: u64,
+
+    #[arg(
+        global = true,
+        long,
+        default_value_t = 90,
+        value_name = "SECONDS",
+        help = "How long idle connections to the Github API are kept open for reuse."
+    )]
+    pub pool_idle_timeout: u64,
+
+    #[arg(
+        global = true,
+        long,
+        default_value_t = 8,
+        help = "Maximum number of idle connections to keep open to the Github API."
+    )]
+    pub pool_max_idle: usize,
+
+    #[arg(
+        global = true,
+        long,
+        default_value_t = 3.0,
+        value_parser = parse_requests_per_second,
+        help = "Pace requests to the Github API to at most this many per second on average, across every repository synced, to stay clear of its secondary rate limits.  The default keeps writes under 900 points a minute.  0 disables pacing."
+    )]
+    pub max_requests_per_second: f64,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "An age identity file used to decrypt encrypted values in the config.  Defaults to $SOPS_AGE_KEY_FILE."
+    )]
+    pub age_identity: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Reject config documents with unknown __ sections or keys, unknown variable options, non-string values, or nested tables, rather than syncing or ignoring them."
+    )]
+    pub strict_config: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Never read, write or prune the keys and environments matching the gitignore-style patterns in this file, in addition to those of a .ghenvignore file next to a local config file.  Patterns are KEY, ENVIRONMENT/KEY or ENVIRONMENT/, with * and ? wildcards and ! to re-include."
+    )]
+    pub exclude_keys_from_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "NAME",
+        help = "Attribute this run's writes to a person, for when several people share a token.  The name is recorded in the audit log and in each changed environment's GH_ENV_SYNC_LAST_ACTOR variable."
+    )]
+    pub actor: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "LOGIN",
+        help = "Abort unless --token belongs to this Github account, e.g. a bot user, so that a personal token pasted into automation is caught before anything is written.  For Github App installation tokens the login is the app's bot, e.g. my-app[bot]."
+    )]
+    pub require_actor: Option<String>,
+
+    #[arg(
+        global = true,
+        long = "value-provider",
+        value_name = "NAME",
+        help = "Resolve ${NAME:...} placeholders in values with a built-in provider: 'env' reads ${env:VARIABLE} from the environment, and 'exec' runs ${exec:COMMAND} and takes its output.  Repeat to enable several.  Providers run with this process's privileges, so only enable them for trusted config files."
+    )]
+    pub value_providers: Vec<BuiltinProvider>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Keep the public keys secrets are encrypted with in this file, so that later runs don't fetch them again.  Keys are always shared within a run."
+    )]
+    pub public_key_cache: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Keep the Github API's latest response to every read in this file, so that later --offline runs can work from it.  It holds variable values in plain text."
+    )]
+    pub cache_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long = "request-header",
+        value_name = "NAME: VALUE",
+        value_parser = parse_request_header,
+        help = "Add this header to every request to the Github API, e.g. the authentication header of an egress proxy or an audit id.  Repeat for more headers.  Headers listed one per line in the request_headers file of the user config directory, e.g. ~/.config/gh-env-sync/request_headers, are added too, before these."
+    )]
+    pub request_headers: Vec<(String, String)>,
+
+    #[arg(
+        global = true,
+        long,
+        requires = "cache_file",
+        help = "Never touch the network: answer reads from --cache-file and refuse writes.  A sync becomes a dry run, so config changes can be checked and planned against the last cached remote state."
+    )]
+    pub offline: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Refuse to send anything but reads to the Github API, so that a token able to write can be used to explore, e.g. with `diff` or `pull`, without risk of writing.  A sync becomes a dry run, and any write fails with a read-only error."
+    )]
+    pub read_only: bool,
+
+    #[cfg(feature = "cassette")]
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        conflicts_with = "replay_cassette",
+        help = "Record every Github API interaction to a cassette file."
+    )]
+    pub record_cassette: Option<PathBuf>,
+
+    #[cfg(feature = "cassette")]
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Answer every Github API request from a previously recorded cassette file instead of the network."
+    )]
+    pub replay_cassette: Option<PathBuf>,
+}
+
+/// Options of every command that writes to Github.
+#[derive(Debug, Clone, clap::Args)]
+pub struct WriteArgs {
+    #[arg(
+        long,
+        default_value_t = LockMode::Local,
+        help = "How to prevent concurrent syncs of the same repository: 'local' (a lock file on this machine), 'remote' (a marker repository variable), or 'none'."
+    )]
+    pub lock: LockMode,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append a record of every write made to Github to this JSON lines file, which `rollback` can later undo."
+    )]
+    pub audit_log: Option<PathBuf>,
+}
+
+/// clap leaves the group of a struct with flattened fields empty, which would
+/// make [`Args::sync`] always `None`, so the group names --token, which every
+/// sync is given, explicitly.
+#[derive(Debug, clap::Args)]
+#[group(args = ["token"])]
+pub struct SyncArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "repository",
+        help = "Sync the config file to every owner/repo listed in this file, one per line, instead of a single <REPOSITORY>.  Blank lines and lines starting with # are skipped.  A failure in one repository doesn't stop the others."
+    )]
+    pub repos_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "ORG",
+        conflicts_with_all = ["repository", "repos_file"],
+        help = "Sync the config file to every repository of this organization that matches --repo-filter and --topic, instead of a single <REPOSITORY>.  Archived repositories are skipped."
+    )]
+    pub org: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        conflicts_with_all = ["repository", "repos_file", "org"],
+        help = "Sync every config file matching this glob, e.g. 'services/*/github_environments.toml', to the owner/repo named by the `repository` key inside it, instead of --config-path.  * and ? match within a directory, and ** matches any number of directories.  Every file is read before anything is synced."
+    )]
+    pub config_glob: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PREFIX=ENVIRONMENT",
+        conflicts_with_all = ["config_glob", "config_path"],
+        help = "Sync this process's environment variables that start with PREFIX to ENVIRONMENT, without the prefix, instead of reading --config-path, e.g. GHES_PROD_=production syncs $GHES_PROD_API_URL as API_URL.  Repeat for more environments."
+    )]
+    pub from_env_prefix: Vec<EnvPrefix>,
+
+    #[arg(
+        long,
+        value_name = "OWNER/REPO",
+        conflicts_with_all = ["config_glob", "config_path", "from_env_prefix"],
+        help = "Copy the environments and variables of this repository, e.g. a template repository, instead of reading --config-path.  Secrets can't be read back from Github, so they are left out with a warning."
+    )]
+    pub from_remote: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        requires = "from_remote",
+        help = "With --from-remote, only copy the environments matching this pattern, where * matches any run of characters and ? any single one.  Repeat for more patterns."
+    )]
+    pub mirror_environment: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        requires = "from_remote",
+        help = "With --from-remote, only copy the variables whose key matches this pattern, e.g. 'DATABASE_*'.  Repeat for more patterns."
+    )]
+    pub mirror_key: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "REF",
+        requires = "config_glob",
+        help = "Only sync the files found by --config-glob that git reports as changed since this commit or ref, e.g. origin/main, so that CI doesn't reconcile every unchanged service on each push."
+    )]
+    pub changed_since: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        requires = "org",
+        help = "With --org, only sync repositories whose name matches this pattern, where * matches any run of characters and ? any single one, e.g. 'service-*'."
+    )]
+    pub repo_filter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOPIC",
+        requires = "org",
+        help = "With --org, only sync repositories with this topic.  Can be given more than once, in which case repositories need every topic."
+    )]
+    pub topic: Vec<String>,
+
     #[arg(
         short,
         long,
@@ -14,21 +514,1691 @@ pub struct Args {
     )]
     pub environment: Option<String>,
 
-    #[arg(short, long, default_value = "github_environments.toml")]
-    pub config_path: String,
+    #[arg(
+        long,
+        help = "Only sync the variables owned by this team, per their `owner` or their environment's entry in [owners], so that teams can share a config file.  Organization secrets are left out."
+    )]
+    pub owner: Option<String>,
 
     #[arg(
-        short,
         long,
-        required = true,
-        help = "A 'repo' scoped Github access token to use for requests to the Github API."
+        value_name = "NUMBER",
+        value_parser = parse_pull_request,
+        conflicts_with = "environment",
+        help = "Sync only the pull request's preview environment, preview-pr-<NUMBER>, expanded from the [preview] environment of the config file with ${pr} replaced by the number in keys and values.  'auto' reads the number from GITHUB_REF in a pull_request workflow."
+    )]
+    pub pr: Option<PullRequest>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["environment", "pr"],
+        help = "Sync only the environment the current git branch deploys to, per the [branches] section of the config file, e.g. main = \"production\" or \"feature/*\" = \"preview\".  In Github Actions the branch is read from GITHUB_HEAD_REF or GITHUB_REF."
     )]
-    pub token: String,
+    pub environment_from_branch: bool,
 
     #[arg(
         short,
         long,
-        help = "The username to apply to User-Agent headers to requests to the Github API.  Defaults to the repository owner."
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path.  Remote sources authenticate with $GH_ENV_SYNC_CONFIG_TOKEN, the standard AWS_* variables, and --token respectively."
     )]
-    pub username: Option<String>,
+    pub config_path: String,
+
+    #[arg(
+        long,
+        help = "List the repository's environments through Github's GraphQL API, 100 per request.  GraphQL has no field for environment variables, so they are still read, and everything written, through the REST API."
+    )]
+    pub graphql: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Record hashes of applied values to this file after each sync, and report values changed outside of gh-env-sync (drift) on the next run.  Generated values are also kept here."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Delete remote variables that were applied by gh-env-sync, per the --state-file, but are no longer in the config.  Variables it never applied, such as ones created by hand or by other teams, are left alone.  Defaults to prune in the config file's [cli] section; --prune=false overrides it."
+    )]
+    pub prune: Option<bool>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Abort before writing anything if the sync would update or delete more than N existing variables, e.g. because it was pointed at the wrong config or repository.  Overrides max_changes in the config document."
+    )]
+    pub max_changes: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Resolve the value providers of environment secrets while they are written, keeping at most N resolved values in memory, so that uploads start while slow lookups (Vault, AWS, ...) are still running.  Secrets that are generated, chunked or referenced by other keys are still resolved up front, as is everything with --apply-strategy transactional."
+    )]
+    pub pipeline_depth: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = ".gh-env-sync-checkpoint.json",
+        help = "Where to record the writes made so far when a sync is interrupted with Ctrl-C or SIGTERM, for --resume.  The repository is cleared from it once a sync finishes."
+    )]
+    pub checkpoint_file: PathBuf,
+
+    #[arg(
+        long,
+        help = "Resume an interrupted sync from the --checkpoint-file, skipping the writes it already made rather than making and auditing them again.  Repositories without a checkpoint are synced as usual."
+    )]
+    pub resume: bool,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Also read the repository's variables and the organization variables shared with it, and warn about configured variables of the same name.  Environment variables take precedence in jobs that use the environment, so such jobs and others see different values.  Defaults to check_shadowing in the config file's [cli] section."
+    )]
+    pub check_shadowing: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Sync even though an environment is inside one of the config file's [[freeze]] windows, e.g. for an urgent fix during a release freeze.  The override is logged."
+    )]
+    pub override_freeze: bool,
+
+    #[arg(
+        long,
+        help = "What to do when a value changed remotely since the last sync (see --state-file) and also differs from the config: 'overwrite', 'keep' the remote value, 'fail', or 'prompt' for each conflict.  Keys can override this with `on_conflict`.  Defaults to on_conflict in the config file's [cli] section, or 'overwrite'."
+    )]
+    pub on_conflict: Option<ConflictStrategy>,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations (descriptions, owners, ticket links) and are never synced.  Set to an empty string to sync every key."
+    )]
+    pub ignore_prefix: String,
+
+    #[arg(
+        long,
+        default_value_t = ApplyStrategy::Interleaved,
+        help = "When environments are validated relative to writing them: 'interleaved' reads, validates and writes each environment in turn, while 'transactional' reads and validates every environment before writing any of them."
+    )]
+    pub apply_strategy: ApplyStrategy,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "With --apply-strategy transactional, stop at the first environment that fails to apply instead of applying the rest.  Defaults to fail_fast in the config file's [cli] section."
+    )]
+    pub fail_fast: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Fail instead of warning when a variable's key looks like a credential (*_TOKEN, *_PASSWORD, *_SECRET, ...).  Variables are visible in plaintext to anyone with read access to the repository, so credentials belong under [org_secrets].  Defaults to strict_secrets in the config file's [cli] section."
+    )]
+    pub strict_secrets: Option<bool>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Warn instead of failing when the sync would exceed Github's limits of 200 variables per environment, 48KB per value or 256KB in total across the synced environments.  Defaults to warn_on_quota in the config file's [cli] section."
+    )]
+    pub warn_on_quota: Option<bool>,
+
+    #[arg(
+        long,
+        default_value_t = true,
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Create configured environments that don't exist on Github.  With false, fail before writing anything instead, for when environments and their protection rules are managed elsewhere."
+    )]
+    pub create_missing_environments: bool,
+
+    #[arg(
+        long,
+        help = "Leave configured environments that don't exist on Github out of the sync, instead of creating them or failing."
+    )]
+    pub skip_missing_environments: bool,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Print the changes that would be made, with multi-line values as unified diffs, without writing anything to Github.  Defaults to dry_run in the config file's [cli] section; --dry-run=false overrides it."
+    )]
+    pub dry_run: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Print variable values in full when prompting or with --dry-run, rather than masking them.  Without it, --dry-run masks keys that look like credentials, such as *_TOKEN or *_PASSWORD."
+    )]
+    pub show_values: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        requires = "report_file",
+        conflicts_with_all = ["repos_file", "org", "config_glob"],
+        help = "Also write the --dry-run's planned changes to --report-file in this format, for sharing drift reviews.  Values are masked as in the terminal, per --show-values."
+    )]
+    pub report: Option<ReportFormat>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "report",
+        help = "Where to write the --report, e.g. drift.html."
+    )]
+    pub report_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Also write the --dry-run's planned variable writes to this plan file, to be reviewed and then made as they are with `apply`.  Secrets aren't planned."
+    )]
+    pub plan_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "gpg",
+        value_name = "KIND",
+        help = "Sign the plan file, with gpg (the default) into PLAN.asc or with sigstore's cosign into PLAN.sigstore.json, so that `apply --verify` can prove it is the plan that was reviewed."
+    )]
+    pub sign: Option<SignatureKind>,
+
+    #[arg(
+        long,
+        help = "Print nothing but a final line such as 'result=changed repositories=1 failed=0 created=2 updated=1 unchanged=9 kept=0 drifted=0'.  The exit code is 0 when everything was in sync, 2 when changes were applied, 3 when --dry-run found differences, 4 when some environments or repositories failed and 1 on other errors."
+    )]
+    pub summary_only: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "After syncing, post the result to this URL as JSON: a Slack-compatible 'text' message along with the totals printed by --summary-only, or the error if the sync failed."
+    )]
+    pub notify_url: Option<String>,
+
+    #[arg(
+        long,
+        help = "Print how long each phase of the sync took, and how many API requests it made, when it finishes."
+    )]
+    pub timings: bool,
+
+    #[arg(
+        long,
+        value_name = "WORKFLOW",
+        help = "After a sync that changed anything, run this workflow (a file name such as deploy.yml, or an id) through workflow_dispatch so deployments pick up the changes."
+    )]
+    pub trigger_workflow: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "REF",
+        requires = "trigger_workflow",
+        help = "The branch or tag to run --trigger-workflow on.  Defaults to the repository's default branch."
+    )]
+    pub trigger_ref: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "EVENT_TYPE",
+        help = "After a sync that changed anything, send a repository_dispatch event of this type, with the run id and changed environments as the client payload."
+    )]
+    pub dispatch_event: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(mut_arg("audit_log", |arg| arg.required(true).help("The audit log written by previous syncs.  The rollback itself is appended to it.")))]
+pub struct RollbackArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(
+        long,
+        value_name = "RUN_ID",
+        help = "Restore every variable to its value at the end of this run, undoing every later run recorded in the audit log."
+    )]
+    pub to: String,
+
+    #[arg(
+        long,
+        help = "Print the operations that would be applied without applying them."
+    )]
+    pub dry_run: bool,
+}
+
+/// Without `allow_missing_positional`, a required <PLAN> couldn't follow the
+/// optional <REPOSITORY>; with it, a single positional is the plan, whose
+/// repository is used.
+#[derive(Debug, clap::Args)]
+#[command(allow_missing_positional = true)]
+pub struct ApplyArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(help = "The plan file written by `plan`.")]
+    pub plan: PathBuf,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "gpg",
+        value_name = "KIND",
+        help = "Refuse to apply the plan unless it has a valid signature of this kind next to it, gpg (the default) or sigstore, as written by `plan --sign`, and was made from the config at --config-path as it is now."
+    )]
+    pub verify: Option<SignatureKind>,
+
+    #[arg(
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file the plan was made from, which --verify checks it against.  Its .ghenvignore also applies."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        value_name = "IDENTITY",
+        requires = "certificate_oidc_issuer",
+        help = "With --verify=sigstore, who must have signed the plan, e.g. an email address or a Github Actions workflow URL."
+    )]
+    pub certificate_identity: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        requires = "certificate_identity",
+        help = "With --verify=sigstore, the OIDC issuer of --certificate-identity, e.g. https://token.actions.githubusercontent.com."
+    )]
+    pub certificate_oidc_issuer: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Only apply the operations on environments matching this pattern, where * matches any run of characters and ? any single one.  Repeat for more patterns.  The rest are written to a residual plan to apply later."
+    )]
+    pub only_env: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Only apply the operations on variables whose key matches this pattern, e.g. 'FEATURE_*'.  Repeat for more patterns.  The rest are written to a residual plan to apply later."
+    )]
+    pub only_keys: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Where --only-env and --only-keys write the operations they leave out, as a plan of their own.  Defaults to the plan file with a .residual.json extension, e.g. gh-env-sync.plan.residual.json."
+    )]
+    pub residual: Option<PathBuf>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "gpg",
+        value_name = "KIND",
+        help = "Sign the residual plan like `plan --sign`, with gpg (the default) or sigstore, so that it can be applied with --verify."
+    )]
+    pub sign: Option<SignatureKind>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, in which the applied values are recorded as a sync would."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Check the plan against Github and print what would be applied without applying it."
+    )]
+    pub dry_run: bool,
+}
+
+/// Without `allow_missing_positional`, a required <KEY> couldn't follow the
+/// optional <REPOSITORY>; with it, a single positional is the key.
+#[derive(Debug, clap::Args)]
+#[command(mut_arg("audit_log", |arg| arg.required(true).help("The audit log written by the sync that deleted the variable, which holds its value.  The restore is appended to it.")))]
+#[command(allow_missing_positional = true)]
+pub struct UndeleteArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(help = "The deleted variable to restore.")]
+    pub key: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Only restore the variable in this environment.  By default it is restored in every environment with a tombstone of it in --state-file, or else every environment the audit log records deleting it from."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, whose tombstones tell where the variable was deleted.  Restored variables' tombstones are removed from it."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Print the environments the variable would be restored in without restoring it."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HistoryArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The audit log written by previous syncs."
+    )]
+    pub audit_log: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "OWNER/REPO",
+        help = "Only show changes to this repository.  By default changes to every repository in the audit log are shown."
+    )]
+    pub repository: Option<String>,
+
+    #[arg(short, long, help = "Only show changes to this environment.")]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_interval,
+        help = "Only show changes made within this long, such as 7d or 12h.  By default the whole audit log is shown."
+    )]
+    pub since: Option<Duration>,
+
+    #[arg(
+        long,
+        help = "Print values in full, rather than masking keys that look like credentials, such as *_TOKEN or *_PASSWORD."
+    )]
+    pub show_values: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RenameKeyArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(long = "from", value_name = "KEY", help = "The key to rename.")]
+    pub old_key: String,
+
+    #[arg(
+        long = "to",
+        value_name = "KEY",
+        help = "The new name of the key.  Rename it in the config file too, or the next sync recreates the old key."
+    )]
+    pub new_key: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Only rename the key in this environment.  By default it is renamed in every environment of the repository."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, in which what was applied to the old key is moved to the new one."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Print the environments the key would be renamed in without renaming it."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file to verify against: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Only verify this environment.  By default every environment in the config file is verified."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, which holds the generated values to verify.  It is never written to."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and aren't verified.  Set to an empty string to verify every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+/// <REPOSITORY> comes before <KEY> but is optional for other commands, which
+/// clap only allows with `allow_missing_positional`.
+#[derive(Debug, clap::Args)]
+#[command(allow_missing_positional = true)]
+pub struct ExplainArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(help = "The key to explain, matched case-insensitively like Github does.")]
+    pub key: String,
+
+    #[arg(short, long, help = "The environment to explain the key in.")]
+    pub environment: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, which holds generated values.  It is never written to."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and are never synced.  Set to an empty string to sync every key."
+    )]
+    pub ignore_prefix: String,
+
+    #[arg(
+        long,
+        help = "Print values in full, rather than masking secrets and keys that look like credentials, such as *_TOKEN or *_PASSWORD."
+    )]
+    pub show_values: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CleanupArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(
+        long,
+        value_name = "NUMBER",
+        value_parser = parse_pull_request,
+        required_unless_present = "expired",
+        help = "The pull request whose preview environment, preview-pr-<NUMBER>, is deleted along with its variables.  'auto' reads the number from GITHUB_REF in a pull_request workflow."
+    )]
+    pub pr: Option<PullRequest>,
+
+    #[arg(
+        long,
+        conflicts_with = "pr",
+        help = "Delete the environments and variables of the config file that expired, per their __expires key or expires option."
+    )]
+    pub expired: bool,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file declaring expiries for --expired: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, which records when each environment was first synced for TTL expiries.  Deleted entries are removed from it."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Print the environments that would be deleted without deleting them."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path.  Remote sources authenticate with $GH_ENV_SYNC_CONFIG_TOKEN, the standard AWS_* variables, and --token respectively."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The state file used by `sync`, which keeps generated values and records hashes of the values applied from the UI."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations (descriptions, owners, ticket links) and are never synced.  Set to an empty string to sync every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ImportArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The local config file to import into.  It is created if it doesn't exist."
+    )]
+    pub config_path: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "Only import this environment.  By default every environment in the repository is imported."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Import the repository's github_actions_environment_variable resources from this Terraform state file (e.g. from `terraform state pull`) instead of from Github."
+    )]
+    pub from_terraform_state: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Decode the remote values of keys read from a `file` and write them to those files, e.g. to restore certificates.  By default they are only compared."
+    )]
+    pub write_files: bool,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "The --ignore-prefix of the syncs the config is used with, so that remote keys starting with it, which those syncs would skip as annotations, are reported."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct AllArgs {
+    #[arg(
+        long,
+        required = true,
+        value_delimiter = ',',
+        help = "The profiles to sync, e.g. --profiles work,oss.  Each profile of profiles.toml in the user config directory lists the arguments of a sync, where $NAME arguments are read from the environment."
+    )]
+    pub profiles: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Only plan every profile's sync, as if each had --dry-run."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SelfUpdateArgs {
+    #[arg(
+        short,
+        long,
+        help = "A Github token to read releases with, to avoid the lower rate limit of anonymous requests.  Defaults to $GITHUB_TOKEN."
+    )]
+    pub token: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        help = "Install this release, e.g. 'v0.2.0', instead of the latest one, even if it is older than this binary."
+    )]
+    pub version: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "version",
+        help = "Only report whether a newer release is available."
+    )]
+    pub check: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+    #[arg(
+        help = "The repository the config file is synced to, as an owner/repo pair, e.g. rust-lang/rust-lang."
+    )]
+    pub repository: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The local config file to export."
+    )]
+    pub config_path: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "Only export this environment.  By default every environment in the config file is exported."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = ExportFormat::Terraform, help = "The format to export to.")]
+    pub format: ExportFormat,
+
+    #[arg(
+        long,
+        help = "Follow each resource with a Terraform import block, so that `terraform apply` adopts the environments and variables that already exist on Github."
+    )]
+    pub import_blocks: bool,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and are never exported.  Set to an empty string to export every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DuplicatesArgs {
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The local config file to analyze."
+    )]
+    pub config_path: PathBuf,
+
+    #[arg(
+        long,
+        help = "Print the values of keys that look like credentials, such as *_TOKEN, which are masked by default."
+    )]
+    pub show_values: bool,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and are never analyzed.  Set to an empty string to analyze every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct LintArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file to lint: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path.  Without <REPOSITORY>, its environments aren't compared against Github's."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and aren't linted.  Set to an empty string to lint every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        help = "List the variables and secrets of this environment instead of the repository's environments."
+    )]
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file to validate: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations and aren't synced, so they aren't validated.  Set to an empty string to validate every key."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RateLimitArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file to estimate the cost of syncing: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Estimate the cost of syncing only this environment."
+    )]
+    pub environment: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = LockMode::Local,
+        help = "The lock mode the sync would use, since 'remote' costs extra requests."
+    )]
+    pub lock: LockMode,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "The --ignore-prefix the sync would use."
+    )]
+    pub ignore_prefix: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = DEFAULT_CONFIG_FILE,
+        help = "The config file: a local path, an https:// URL, s3://bucket/key, or git::owner/repo@ref:path.  Remote sources authenticate with $GH_ENV_SYNC_CONFIG_TOKEN, the standard AWS_* variables, and --token respectively."
+    )]
+    pub config_path: String,
+
+    #[arg(
+        long,
+        default_value = "15m",
+        value_parser = parse_interval,
+        help = "How often to re-read the config file and sync it, e.g. '30s', '15m', or '1h'."
+    )]
+    pub interval: Duration,
+
+    #[arg(
+        long,
+        default_value = "127.0.0.1:8080",
+        help = "The address to serve /healthz and /metrics on."
+    )]
+    pub listen: SocketAddr,
+
+    #[arg(
+        long,
+        help = "List the repository's environments through Github's GraphQL API, 100 per request.  GraphQL has no field for environment variables, so they are still read, and everything written, through the REST API."
+    )]
+    pub graphql: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Record hashes of applied values to this file after each sync, and report values changed outside of gh-env-sync (drift) on the next run.  Generated values are also kept here."
+    )]
+    pub state_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "state_file",
+        help = "Delete remote variables that were applied by gh-env-sync, per the --state-file, but are no longer in the config.  Variables it never applied, such as ones created by hand or by other teams, are left alone."
+    )]
+    pub prune: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Abort before writing anything if the sync would update or delete more than N existing variables, e.g. because it was pointed at the wrong config or repository.  Overrides max_changes in the config document."
+    )]
+    pub max_changes: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = ConflictStrategy::Overwrite,
+        help = "What to do when a value changed remotely since the last sync (see --state-file) and also differs from the config: 'overwrite', 'keep' the remote value, or 'fail'.  Keys can override this with `on_conflict`."
+    )]
+    pub on_conflict: ConflictStrategy,
+
+    #[arg(
+        long,
+        default_value = "_",
+        help = "Keys starting with this prefix are local-only annotations (descriptions, owners, ticket links) and are never synced.  Set to an empty string to sync every key."
+    )]
+    pub ignore_prefix: String,
+
+    #[arg(
+        long,
+        default_value_t = ApplyStrategy::Interleaved,
+        help = "When environments are validated relative to writing them: 'interleaved' reads, validates and writes each environment in turn, while 'transactional' reads and validates every environment before writing any of them."
+    )]
+    pub apply_strategy: ApplyStrategy,
+
+    #[arg(
+        long,
+        help = "With --apply-strategy transactional, stop at the first environment that fails to apply instead of applying the rest."
+    )]
+    pub fail_fast: bool,
+
+    #[arg(
+        long,
+        help = "Fail instead of warning when a variable's key looks like a credential (*_TOKEN, *_PASSWORD, *_SECRET, ...).  Variables are visible in plaintext to anyone with read access to the repository, so credentials belong under [org_secrets]."
+    )]
+    pub strict_secrets: bool,
+
+    #[arg(
+        long,
+        help = "Warn instead of failing when the sync would exceed Github's limits of 200 variables per environment, 48KB per value or 256KB in total across the synced environments."
+    )]
+    pub warn_on_quota: bool,
+
+    #[arg(
+        long,
+        default_value_t = true,
+        action = ArgAction::Set,
+        value_name = "BOOL",
+        help = "Create configured environments that don't exist on Github.  With false, fail before writing anything instead, for when environments and their protection rules are managed elsewhere."
+    )]
+    pub create_missing_environments: bool,
+
+    #[arg(
+        long,
+        help = "Leave configured environments that don't exist on Github out of the sync, instead of creating them or failing."
+    )]
+    pub skip_missing_environments: bool,
+
+    #[arg(
+        long,
+        value_name = "SECRET",
+        help = "Accept Github push webhooks signed with this secret on POST /webhook, and sync as soon as a push to the default branch changes the config file."
+    )]
+    pub webhook_secret: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "Serve each environment's resolved variables as JSON on GET /repos/OWNER/REPO/environments/ENV to requests with an `Authorization: Bearer TOKEN` header, so other tools can read them without Github access.  Secrets are never served."
+    )]
+    pub read_token: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "OWNER/REPO",
+        requires = "webhook_secret",
+        help = "The repository holding the config file, if not the synced repository."
+    )]
+    pub config_repository: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "webhook_secret",
+        help = "The path of the config file within --config-repository.  Defaults to the path in --config-path."
+    )]
+    pub config_repository_path: Option<String>,
+}
+
+impl SyncArgs {
+    pub fn missing_environments(&self) -> MissingEnvironments {
+        missing_environments(
+            self.create_missing_environments,
+            self.skip_missing_environments,
+        )
+    }
+
+    /// How to report progress, per --output.
+    pub fn output(&self) -> OutputFormat {
+        self.connection.output.unwrap_or(OutputFormat::Text)
+    }
+
+    /// Whether to print tables and diffs on stdout.
+    pub fn prints_tables(&self) -> bool {
+        self.output() == OutputFormat::Text && !self.summary_only
+    }
+
+    /// Whether to print the final summary line on stdout, which would get in
+    /// the way of JSON output.
+    pub fn prints_summary(&self) -> bool {
+        self.output() == OutputFormat::Text || self.summary_only
+    }
+
+    /// Fills in the flags that weren't given from the `[cli]` section of the
+    /// local config file at --config-path, if it has one.  Config files read
+    /// from elsewhere, or with --config-glob, aren't consulted.
+    pub fn apply_config_defaults(&mut self) -> Result<()> {
+        let located = locate_config(&self.config_path);
+        let path = Path::new(&located);
+        let defaults = match self.config_glob.is_none()
+            && self.from_remote.is_none()
+            && self.from_env_prefix.is_empty()
+            && path.is_file()
+        {
+            true => read_cli_defaults(path)?,
+            false => CliDefaults::default(),
+        };
+        if defaults != CliDefaults::default() {
+            debug!("Using flag defaults from the [cli] section of {}", located);
+        }
+
+        self.on_conflict = self.on_conflict.or(defaults.on_conflict);
+        self.connection.output = self.connection.output.or(defaults.output);
+        if let (None, Some(state_file)) = (&self.state_file, &defaults.state_file) {
+            self.state_file = Some(config_dir(path).join(state_file));
+        }
+        self.prune = self.prune.or(defaults.prune);
+        self.check_shadowing = self.check_shadowing.or(defaults.check_shadowing);
+        self.strict_secrets = self.strict_secrets.or(defaults.strict_secrets);
+        self.warn_on_quota = self.warn_on_quota.or(defaults.warn_on_quota);
+        self.fail_fast = self.fail_fast.or(defaults.fail_fast);
+        self.dry_run = self.dry_run.or(defaults.dry_run);
+        self.max_changes = self.max_changes.or(defaults.max_changes);
+
+        if self.prune == Some(true) && self.state_file.is_none() {
+            return Err(match defaults.prune {
+                Some(true) => eyre!(
+                    "prune in the [cli] section of {} requires --state-file",
+                    located
+                ),
+                _ => eyre!("--prune requires --state-file"),
+            });
+        }
+        let dry_run = self.dry_run.unwrap_or_default();
+        if dry_run && self.resume {
+            return Err(match defaults.dry_run {
+                Some(true) => eyre!(
+                    "dry_run in the [cli] section of {} can't be used with --resume; pass --dry-run=false",
+                    located
+                ),
+                _ => eyre!("--resume can't be used with --dry-run"),
+            });
+        }
+        if !dry_run && self.report.is_some() {
+            return Err(eyre!("--report requires --dry-run"));
+        }
+        if self.plan_file.is_some() {
+            if !dry_run {
+                return Err(eyre!("--plan-file requires --dry-run"));
+            }
+            if self.repos_file.is_some()
+                || self.org.is_some()
+                || self.config_glob.is_some()
+                || self.from_remote.is_some()
+                || !self.from_env_prefix.is_empty()
+            {
+                return Err(eyre!(
+                    "Plans are made of a single repository and config document, so --plan-file can't be used with --repos-file, --org, --config-glob, --from-remote or --from-env-prefix"
+                ));
+            }
+        }
+        if self.sign.is_some() && self.plan_file.is_none() {
+            return Err(eyre!("--sign requires --plan-file"));
+        }
+        Ok(())
+    }
+
+    /// Whether a repository discovered with --org matches --repo-filter and
+    /// every --topic.
+    pub fn selects(&self, repository: &OrganizationRepository) -> bool {
+        self.repo_filter
+            .as_deref()
+            .is_none_or(|pattern| matches_pattern(pattern, &repository.name))
+            && self.topic.iter().all(|topic| {
+                repository
+                    .topics
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(topic))
+            })
+    }
+}
+
+/// Matches a repository name against a --repo-filter, ignoring case as
+/// Github does for names.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    matches_glob(&pattern.to_lowercase(), &name.to_lowercase())
+}
+
+impl ServeArgs {
+    pub fn missing_environments(&self) -> MissingEnvironments {
+        missing_environments(
+            self.create_missing_environments,
+            self.skip_missing_environments,
+        )
+    }
+
+    /// The `owner/repo` holding the config file: --config-repository, the
+    /// repository of a `git::` --config-path, or the synced repository.
+    pub fn config_repository(&self) -> Result<String> {
+        match (&self.config_repository, self.config_path.parse()) {
+            (Some(repository), _) => Ok(repository.clone()),
+            (
+                None,
+                Ok(ConfigSource::Git {
+                    owner, repository, ..
+                }),
+            ) => Ok(format!("{}/{}", owner, repository)),
+            (None, _) => self.connection.repository().map(str::to_string),
+        }
+    }
+
+    /// The path of the config file within its repository.
+    pub fn config_repository_path(&self) -> String {
+        match (&self.config_repository_path, self.config_path.parse()) {
+            (Some(path), _) => path.clone(),
+            (None, Ok(ConfigSource::Git { path, .. })) => path,
+            (None, _) => self.config_path.clone(),
+        }
+    }
+}
+
+impl ApplyArgs {
+    /// Who must have signed the plan for --verify=sigstore.
+    pub fn sigstore_signer(&self) -> Option<SigstoreIdentity> {
+        Some(SigstoreIdentity {
+            identity: self.certificate_identity.clone()?,
+            issuer: self.certificate_oidc_issuer.clone()?,
+        })
+    }
+
+    /// Whether --only-env or --only-keys leave part of the plan for later.
+    pub fn is_partial(&self) -> bool {
+        !self.only_env.is_empty() || !self.only_keys.is_empty()
+    }
+
+    /// Where a partial apply writes the operations it leaves out.
+    pub fn residual_path(&self) -> PathBuf {
+        self.residual
+            .clone()
+            .unwrap_or_else(|| self.plan.with_extension("residual.json"))
+    }
+}
+
+/// What to do with missing environments per --create-missing-environments
+/// and --skip-missing-environments.  Skipping wins, since it never writes.
+fn missing_environments(create: bool, skip: bool) -> MissingEnvironments {
+    match (create, skip) {
+        (_, true) => MissingEnvironments::Skip,
+        (true, false) => MissingEnvironments::Create,
+        (false, false) => MissingEnvironments::Fail,
+    }
+}
+
+/// Parses a duration such as `90s`, `15m`, `1h` or `1d`.  A bare number is
+/// taken as seconds.
+/// A pull request given by number, or detected from the Github Actions
+/// environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullRequest {
+    Number(u64),
+    Detect,
+}
+
+impl PullRequest {
+    pub fn number(self) -> Result<u64> {
+        match self {
+            Self::Number(number) => Ok(number),
+            Self::Detect => detect_pull_request(),
+        }
+    }
+}
+
+fn parse_pull_request(value: &str) -> Result<PullRequest, String> {
+    match value.trim() {
+        "auto" => Ok(PullRequest::Detect),
+        number => number
+            .parse()
+            .map(PullRequest::Number)
+            .map_err(|_| format!("expected a pull request number or 'auto', got '{}'", value)),
+    }
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected a duration such as '15m', got '{}'", value))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{}', expected s, m, h or d",
+                unit
+            ))
+        }
+    };
+
+    if amount == 0 {
+        return Err("the interval must be greater than zero".to_string());
+    }
+
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+impl ConnectionArgs {
+    /// The <REPOSITORY> argument, which every command but a --repos-file
+    /// sync requires.
+    pub fn repository(&self) -> Result<&str> {
+        self.repository
+            .as_deref()
+            .ok_or_else(|| eyre!("The <REPOSITORY> argument is required"))
+    }
+
+    /// The same connection options for another repository.
+    pub fn with_repository(&self, repository: impl Into<String>) -> Self {
+        Self {
+            repository: Some(repository.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Splits the <REPOSITORY> argument into its owner and name.
+    pub fn repository_parts(&self) -> Result<(&str, &str)> {
+        self.repository()?.split_once('/').ok_or_else(|| {
+            eyre!("Expected <REPOSITORY> argument to be a owner/repo_name pair, e.g. rust-lang/rust-lang")
+        })
+    }
+
+    /// The --token argument, which every command but `validate` requires.
+    pub fn token(&self) -> Result<&str> {
+        self.token
+            .as_deref()
+            .ok_or_else(|| eyre!("The --token argument, or GH_TOKEN, is required"))
+    }
+
+    /// Lists the repositories of an organization with --token.
+    pub async fn organization_repositories(
+        &self,
+        organization: &str,
+    ) -> Result<Vec<OrganizationRepository>> {
+        list_organization_repositories(
+            self.username.clone(),
+            self.token()?,
+            organization,
+            &self.http_options()?,
+        )
+        .await
+    }
+
+    /// Initializes a client for the requested repository.
+    pub async fn client(&self) -> Result<GithubEnvClient> {
+        let client = self.client_with_token(self.token()?.to_string()).await?;
+        self.check_account(&client).await?;
+        Ok(client)
+    }
+
+    /// Logs which account --token belongs to, looking it up once per run,
+    /// and fails if it isn't --require-actor.  Without --require-actor, a
+    /// failed lookup only warns, as it isn't needed to sync.
+    async fn check_account(&self, client: &GithubEnvClient) -> Result<()> {
+        static ACCOUNT: OnceLock<String> = OnceLock::new();
+
+        let login = match ACCOUNT.get() {
+            Some(login) => login,
+            None => match client.get_authenticated_login().await {
+                Ok(login) => {
+                    info!(
+                        "Using the token of {} for {}",
+                        login,
+                        self.repository().unwrap_or_default()
+                    );
+                    ACCOUNT.get_or_init(|| login)
+                }
+                Err(e) if self.require_actor.is_some() => {
+                    return Err(eyre!(
+                        "Error checking that --token belongs to {}: {}",
+                        self.require_actor.as_deref().unwrap_or_default(),
+                        e
+                    ))
+                }
+                Err(e) => {
+                    match self.offline {
+                        true => debug!("Couldn't tell which account --token belongs to: {}", e),
+                        false => warn!("Couldn't tell which account --token belongs to: {}", e),
+                    }
+                    return Ok(());
+                }
+            },
+        };
+
+        match &self.require_actor {
+            Some(required) if !login.eq_ignore_ascii_case(required) => Err(eyre!(
+                "--token belongs to {}, not {} as --require-actor requires; not continuing",
+                login,
+                required
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Initializes a client for the requested repository, authenticated with
+    /// the given token rather than --token.
+    pub async fn client_with_token(&self, token: String) -> Result<GithubEnvClient> {
+        let (repository_owner, repository_name) = self.repository_parts()?;
+
+        GithubEnvClient::init(
+            self.username.clone(),
+            token,
+            repository_owner,
+            repository_name,
+            &self.http_options()?,
+        )
+        .await
+    }
+
+    /// Initializes a syncer for the requested repository, authenticating with
+    /// the tokens from the config document's `auth` section where given.  If
+    /// `environment` is given, only that environment's client is created.
+    pub async fn syncer(
+        &self,
+        auth: &AuthConfig,
+        options: SyncOptions,
+        environment: Option<&str>,
+    ) -> Result<Syncer> {
+        let mut syncer = match auth.repository(self.repository()?) {
+            Some(source) => {
+                let client = self.client_with_token(source.token()?).await?;
+                Syncer::new(client, options).with_identity(source.identity())
+            }
+            None => Syncer::new(self.client().await?, options),
+        };
+        syncer = self.attribute(syncer);
+        if !self.value_providers.is_empty() {
+            syncer = syncer.with_value_providers(self.value_provider_registry()?);
+        }
+
+        for (name, source) in &auth.environments {
+            if environment.is_some_and(|environment| environment != name) {
+                continue;
+            }
+
+            let client = self.client_with_token(source.token()?).await?;
+            syncer = syncer.with_environment_client(name, client, source.identity());
+        }
+
+        Ok(syncer)
+    }
+
+    /// The providers enabled with --value-provider.
+    pub fn value_provider_registry(&self) -> Result<ProviderRegistry> {
+        let mut providers = ProviderRegistry::new();
+        for builtin in &self.value_providers {
+            providers.register_builtin(*builtin)?;
+        }
+
+        Ok(providers)
+    }
+
+    /// Attributes a syncer's writes to --actor, if one was given.
+    pub fn attribute(&self, syncer: Syncer) -> Syncer {
+        match &self.actor {
+            Some(actor) => syncer.with_actor(actor),
+            None => syncer,
+        }
+    }
+
+    /// Reads the config document from a --config-path, which may be a local
+    /// file or a remote source (see [`ConfigSource`]), and decrypts it.
+    pub async fn load_config(&self, location: &str) -> Result<ConfigDocument> {
+        let mut config = self.read_config(location).await?;
+        self.decrypt_config(&mut config)?;
+        Ok(config)
+    }
+
+    /// Reads the config document from a --config-path without decrypting
+    /// it, for when only its shape matters.
+    pub async fn read_config(&self, location: &str) -> Result<ConfigDocument> {
+        let located = locate_config(location);
+        if located != location {
+            info!("Reading {} since {} doesn't exist here", located, location);
+        }
+        let source: ConfigSource = located.parse()?;
+        let client = match source {
+            ConfigSource::Git { .. } => Some(self.client().await?),
+            _ => None,
+        };
+
+        let auth = SourceAuth::from_env(client.as_ref());
+        let mut config = match self.strict_config {
+            true => source.load_strict(&auth).await,
+            false => source.load(&auth).await,
+        }?;
+
+        config.exclude(self.exclusions_for(location)?);
+        Ok(config)
+    }
+
+    /// The keys and environments excluded for the config document at a
+    /// --config-path, also when the synced config is built from somewhere
+    /// else, such as --from-remote.
+    pub fn exclusions_for(&self, location: &str) -> Result<Exclusions> {
+        match locate_config(location).parse()? {
+            ConfigSource::File(path) => self.exclusions(Some(&path)),
+            _ => self.exclusions(None),
+        }
+    }
+
+    /// The keys and environments excluded by the .ghenvignore file next to
+    /// a local config file, if there is one, and by --exclude-keys-from-file.
+    pub fn exclusions(&self, config_path: Option<&Path>) -> Result<Exclusions> {
+        let mut exclusions = Exclusions::default();
+        let ignore_file = config_path
+            .map(|path| {
+                path.parent()
+                    .unwrap_or(Path::new(""))
+                    .join(IGNORE_FILE_NAME)
+            })
+            .filter(|path| path.is_file());
+        if let Some(path) = ignore_file {
+            info!(
+                "Excluding the keys and environments listed in {}",
+                path.display()
+            );
+            exclusions.extend(Exclusions::load(path)?);
+        }
+        if let Some(path) = &self.exclude_keys_from_file {
+            exclusions.extend(Exclusions::load(path)?);
+        }
+
+        Ok(exclusions)
+    }
+
+    /// The SHA-256 hash of the config document at a --config-path as read
+    /// from its source, which plans record to tell whether they were made
+    /// from it.
+    pub async fn config_hash(&self, location: &str) -> Result<String> {
+        let source: ConfigSource = locate_config(location).parse()?;
+        let client = match source {
+            ConfigSource::Git { .. } => Some(self.client().await?),
+            _ => None,
+        };
+
+        let contents = source.fetch(&SourceAuth::from_env(client.as_ref())).await?;
+        Ok(hash_value(&contents))
+    }
+
+    /// Decrypts any age encrypted values in the config document with the
+    /// --age-identity (or $SOPS_AGE_KEY_FILE) identity file.  Documents
+    /// without encrypted values don't need an identity.
+    pub fn decrypt_config(&self, config: &mut ConfigDocument) -> Result<()> {
+        if !encryption::has_encrypted_values(config) {
+            return Ok(());
+        }
+
+        let path = self
+            .age_identity
+            .clone()
+            .or_else(|| std::env::var_os("SOPS_AGE_KEY_FILE").map(PathBuf::from))
+            .ok_or_else(|| {
+                eyre!("The config has age encrypted values, but neither --age-identity nor SOPS_AGE_KEY_FILE is set")
+            })?;
+        let identities = encryption::load_identities(&path)?;
+        let decrypted = encryption::decrypt_config(config, &identities)?;
+        debug!("Decrypted {} values with {}", decrypted, path.display());
+
+        Ok(())
+    }
+
+    /// The throttle pacing every client of this process, so that syncing
+    /// many repositories doesn't multiply the request rate.
+    fn throttle(&self) -> Option<Throttle> {
+        static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+        (self.max_requests_per_second > 0.0).then(|| {
+            THROTTLE
+                .get_or_init(|| Throttle::new(self.max_requests_per_second))
+                .clone()
+        })
+    }
+
+    /// The public key cache shared by every client of this process, so that
+    /// each environment's or organization's key is fetched once per run.
+    fn public_key_cache(&self) -> Result<PublicKeyCache> {
+        static PUBLIC_KEYS: OnceLock<PublicKeyCache> = OnceLock::new();
+
+        if let Some(cache) = PUBLIC_KEYS.get() {
+            return Ok(cache.clone());
+        }
+        let cache = match &self.public_key_cache {
+            Some(path) => PublicKeyCache::persistent(path)?,
+            None => PublicKeyCache::new(),
+        };
+        Ok(PUBLIC_KEYS.get_or_init(|| cache).clone())
+    }
+
+    /// The headers to add to every request: those of the user's
+    /// request_headers file, then those given on the command line.
+    fn request_headers(&self) -> Result<StaticHeaders> {
+        let mut headers = Vec::new();
+        if let Some(path) = user_config_dir().map(|directory| directory.join(REQUEST_HEADERS_FILE))
+        {
+            if path.is_file() {
+                let contents = std::fs::read_to_string(&path)?;
+                for line in contents.lines().map(str::trim) {
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    headers.push(
+                        parse_request_header(line)
+                            .map_err(|e| eyre!("{}: {}", path.display(), e))?,
+                    );
+                }
+            }
+        }
+        headers.extend(self.request_headers.iter().cloned());
+
+        StaticHeaders::new(headers)
+    }
+
+    /// Whether every write would fail, per --offline or --read-only, so a
+    /// sync can only be planned.
+    pub fn refuses_writes(&self) -> bool {
+        self.offline || self.read_only
+    }
+
+    /// The HTTP client settings requested on the command line.
+    pub fn http_options(&self) -> Result<HttpOptions> {
+        let response_cache = match (&self.cache_file, self.offline) {
+            (Some(path), true) => Some(ResponseCache::offline(path)?),
+            (Some(path), false) => Some(ResponseCache::open(path)?),
+            (None, _) => None,
+        };
+
+        #[cfg(feature = "cassette")]
+        let cassette = match (&self.record_cassette, &self.replay_cassette) {
+            (Some(path), _) => Some(gh_client::Cassette::record(path)),
+            (None, Some(path)) => Some(gh_client::Cassette::replay(path)?),
+            (None, None) => None,
+        };
+
+        let request_headers = self.request_headers()?;
+        let mut request_hooks = match request_headers.is_empty() {
+            true => Vec::new(),
+            false => vec![Arc::new(request_headers) as Arc<dyn RequestHook>],
+        };
+        if let Some(host) = self.host.as_deref().and_then(ApiHost::new) {
+            debug!("Sending requests to {}", host.host());
+            request_hooks.push(Arc::new(host));
+        }
+
+        Ok(HttpOptions {
+            timeout: Duration::from_secs(self.http_timeout),
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            pool_idle_timeout: Duration::from_secs(self.pool_idle_timeout),
+            pool_max_idle_per_host: self.pool_max_idle,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+            correlation_id: Some(self.run_id.clone()),
+            throttle: self.throttle(),
+            public_key_cache: Some(self.public_key_cache()?),
+            response_cache,
+            request_hooks,
+            read_only: self.read_only,
+            #[cfg(feature = "cassette")]
+            cassette,
+        })
+    }
+}
+
+/// Sets GH_TOKEN, which --token defaults to, when neither it nor --token is
+/// given, finding a token the way `gh` does: in GITHUB_TOKEN, or else with
+/// `gh auth token`, which prints the token `gh auth login` stored.  `gh`
+/// doesn't pass that token to its extensions, so this lets `gh env-sync`
+/// and a standalone `gh-env-sync` run with the same arguments.
+pub fn default_token() {
+    if std::env::var_os("GH_TOKEN").is_some()
+        || std::env::args()
+            .any(|arg| arg == "-t" || arg == "--token" || arg.starts_with("--token="))
+    {
+        return;
+    }
+
+    let token = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .or_else(gh_auth_token);
+    if let Some(token) = token {
+        std::env::set_var("GH_TOKEN", token);
+    }
+}
+
+/// The token `gh` is logged in with for GH_HOST, or github.com, if `gh` is
+/// installed and logged in.
+fn gh_auth_token() -> Option<String> {
+    let mut command = std::process::Command::new("gh");
+    command.args(["auth", "token"]);
+    if let Ok(host) = std::env::var("GH_HOST") {
+        command.args(["--hostname", &host]);
+    }
+    let output = command
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let token = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!token.is_empty()).then_some(token)
+}
+
+/// Parses a `NAME: VALUE` header.
+fn parse_request_header(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((name, header)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), header.trim().to_string()))
+        }
+        _ => Err(format!(
+            "expected a header such as 'X-Audit-Id: 1234', got '{}'",
+            value
+        )),
+    }
+}
+
+/// Parses a request rate, which may be fractional, e.g. `0.5` for one
+/// request every two seconds.
+fn parse_requests_per_second(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(rate),
+        _ => Err(format!(
+            "expected a number of requests per second such as '3', got '{}'",
+            value
+        )),
+    }
 }
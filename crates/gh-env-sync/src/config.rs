@@ -0,0 +1,925 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::OrganizationSecretVisibility;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{
+    conflict::ConflictStrategy,
+    defaults::{CliDefaults, CLI_SECTION},
+    encryption::{is_encrypted, AGE_ARMOR_HEADER},
+    exclusions::Exclusions,
+    expiry::{Expiry, EXPIRES_KEY},
+    files::{config_dir, read_files, FileEncoding, FileSource},
+    freeze::FreezeWindow,
+    generate::GenerateSpec,
+    naming::NamingPolicy,
+    transform::Transform,
+};
+
+/// A dictionary of key/value environment variable pairs.
+pub type Environment = HashMap<String, String>;
+
+/// A single configured variable.  In the config document this is either a
+/// plain string value, or an inline table with a `value` (or age encrypted
+/// `age` value, `generate` spec or `file` to read) and per-key options:
+///
+/// ```toml
+/// [production]
+/// API_URL = "https://example.com"
+/// FEATURE_FLAGS = { value = "a,b", on_conflict = "keep", owner = "team-web", expires = "2024-12-31" }
+/// API_KEY = { age = "-----BEGIN AGE ENCRYPTED FILE-----\n..." }
+/// WEBHOOK_SECRET = { generate = { length = 32, charset = "alnum" } }
+/// KEYSTORE = { file = "certs/keystore.p12", encoding = "base64" }
+/// SETTINGS = { value = '{ "debug": false }', transform = ["json_minify"] }
+/// DATABASE_PASSWORD = { value = "hunter2", kind = "secret" }
+/// ```
+///
+/// Keys can also be grouped by kind into `[production.vars]` and
+/// `[production.secrets]` subsections (see [`parse_config`]).
+///
+/// Encrypted values stay encrypted until they are replaced by
+/// [`crate::encryption::decrypt_config`].  Generated values are empty until
+/// the syncer fills them in from its state file.  Values read from a file
+/// are empty until [`load_config`] reads it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawVariableConfig")]
+pub struct VariableConfig {
+    pub value: String,
+    /// Overrides the run's conflict strategy for this key.
+    pub on_conflict: Option<ConflictStrategy>,
+    /// Set when the value is generated rather than configured.
+    pub generate: Option<GenerateSpec>,
+    /// Set when the value is read from a local file (see [`FileSource`]).
+    pub file: Option<FileSource>,
+    /// The team responsible for the key, overriding its environment's owner
+    /// (see [`ConfigDocument::owners`]).
+    pub owner: Option<String>,
+    /// When the key stops being synced (see [`Expiry`]).
+    pub expires: Option<Expiry>,
+    /// Normalizations applied to the value before it is compared and
+    /// uploaded, in order.
+    pub transform: Vec<Transform>,
+    /// Whether the key is synced as a variable or an environment secret.
+    pub kind: VariableKind,
+    /// Whether a value too large for Github is split across `KEY__PART1` to
+    /// `KEY__PARTn` rather than failing the sync (see
+    /// [`crate::chunk::split_chunked`]).
+    pub chunk: bool,
+}
+
+/// How a configured key is stored on Github.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableKind {
+    /// An Actions variable, readable in plaintext and compared before it is
+    /// written.
+    #[default]
+    Variable,
+    /// An Actions environment secret, encrypted before upload.  Secrets
+    /// can't be read back, so unless a state file records what was written
+    /// they are written on every sync.
+    Secret,
+}
+
+impl FromStr for VariableKind {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "variable" => Ok(Self::Variable),
+            "secret" => Ok(Self::Secret),
+            _ => Err(eyre!(
+                "Unknown kind '{}', expected one of variable, secret",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VariableKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Variable => "variable",
+            Self::Secret => "secret",
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawVariableConfig {
+    Plain(String),
+    Detailed {
+        value: String,
+        on_conflict: Option<ConflictStrategy>,
+        owner: Option<String>,
+        expires: Option<Expiry>,
+        #[serde(default)]
+        transform: Vec<Transform>,
+        kind: Option<VariableKind>,
+        #[serde(default)]
+        chunk: bool,
+    },
+    Encrypted {
+        age: String,
+        on_conflict: Option<ConflictStrategy>,
+        owner: Option<String>,
+        expires: Option<Expiry>,
+        #[serde(default)]
+        transform: Vec<Transform>,
+        kind: Option<VariableKind>,
+        #[serde(default)]
+        chunk: bool,
+    },
+    Generated {
+        generate: GenerateSpec,
+        on_conflict: Option<ConflictStrategy>,
+        owner: Option<String>,
+        expires: Option<Expiry>,
+        #[serde(default)]
+        transform: Vec<Transform>,
+        kind: Option<VariableKind>,
+        #[serde(default)]
+        chunk: bool,
+    },
+    File {
+        file: PathBuf,
+        #[serde(default)]
+        encoding: FileEncoding,
+        on_conflict: Option<ConflictStrategy>,
+        owner: Option<String>,
+        expires: Option<Expiry>,
+        #[serde(default)]
+        transform: Vec<Transform>,
+        kind: Option<VariableKind>,
+        #[serde(default)]
+        chunk: bool,
+    },
+}
+
+impl TryFrom<RawVariableConfig> for VariableConfig {
+    type Error = String;
+
+    fn try_from(raw: RawVariableConfig) -> Result<Self, Self::Error> {
+        match raw {
+            RawVariableConfig::Plain(value) => Ok(Self {
+                value,
+                on_conflict: None,
+                generate: None,
+                file: None,
+                owner: None,
+                expires: None,
+                transform: Vec::new(),
+                kind: VariableKind::Variable,
+                chunk: false,
+            }),
+            RawVariableConfig::Detailed {
+                value,
+                on_conflict,
+                owner,
+                expires,
+                transform,
+                kind,
+                chunk,
+            } => Ok(Self {
+                value,
+                on_conflict,
+                generate: None,
+                file: None,
+                owner,
+                expires,
+                transform,
+                kind: kind.unwrap_or_default(),
+                chunk,
+            }),
+            RawVariableConfig::Encrypted {
+                age,
+                on_conflict,
+                owner,
+                expires,
+                transform,
+                kind,
+                chunk,
+            } => {
+                if !is_encrypted(&age) {
+                    return Err(format!(
+                        "`age` values must be ASCII armored, starting with {}",
+                        AGE_ARMOR_HEADER
+                    ));
+                }
+
+                Ok(Self {
+                    value: age,
+                    on_conflict,
+                    generate: None,
+                    file: None,
+                    owner,
+                    expires,
+                    transform,
+                    kind: kind.unwrap_or_default(),
+                    chunk,
+                })
+            }
+            RawVariableConfig::Generated {
+                generate,
+                on_conflict,
+                owner,
+                expires,
+                transform,
+                kind,
+                chunk,
+            } => {
+                if generate.length == 0 {
+                    return Err("`generate` values must have a length of at least 1".to_string());
+                }
+
+                Ok(Self {
+                    value: String::new(),
+                    on_conflict,
+                    generate: Some(generate),
+                    file: None,
+                    owner,
+                    expires,
+                    transform,
+                    kind: kind.unwrap_or_default(),
+                    chunk,
+                })
+            }
+            RawVariableConfig::File {
+                file,
+                encoding,
+                on_conflict,
+                owner,
+                expires,
+                transform,
+                kind,
+                chunk,
+            } => Ok(Self {
+                value: String::new(),
+                on_conflict,
+                generate: None,
+                file: Some(FileSource {
+                    path: file,
+                    encoding,
+                }),
+                owner,
+                expires,
+                transform,
+                kind: kind.unwrap_or_default(),
+                chunk,
+            }),
+        }
+    }
+}
+
+/// The configured variables of a single environment.
+pub type EnvironmentConfig = HashMap<String, VariableConfig>;
+
+/// A secret shared across repositories of the organization that owns the
+/// synced repository:
+///
+/// ```toml
+/// [org_secrets.NPM_TOKEN]
+/// value = "..."
+/// visibility = "selected"
+/// repositories = ["web", "other-org/api"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OrganizationSecretConfig {
+    pub value: String,
+    #[serde(default)]
+    pub visibility: OrganizationSecretVisibility,
+    /// The repositories that can access the secret when `visibility` is
+    /// `selected`, either as a bare name in the organization or as an
+    /// `owner/repo` pair.
+    #[serde(default)]
+    pub repositories: Vec<String>,
+}
+
+/// Where to read a Github token from.  Tokens are never stored in the config
+/// document itself, only the name of the environment variable holding them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TokenSource {
+    pub token_env: String,
+}
+
+impl TokenSource {
+    /// Reads the token from the environment.
+    pub fn token(&self) -> Result<String> {
+        std::env::var(&self.token_env)
+            .map_err(|e| eyre!("Error reading Github token from ${}: {}", self.token_env, e))
+    }
+
+    /// Identifies changes made with this token, e.g. in the audit log.
+    pub fn identity(&self) -> String {
+        format!("${}", self.token_env)
+    }
+}
+
+/// Tokens to use instead of `--token`, for fine-grained tokens scoped to a
+/// single repository or environment:
+///
+/// ```toml
+/// [auth.repositories."acme/web"]
+/// token_env = "ACME_WEB_TOKEN"
+///
+/// [auth.environments.production]
+/// token_env = "ACME_WEB_PRODUCTION_TOKEN"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct AuthConfig {
+    /// Keyed by `owner/repo`.
+    #[serde(default)]
+    pub repositories: HashMap<String, TokenSource>,
+    /// Keyed by environment name.
+    #[serde(default)]
+    pub environments: HashMap<String, TokenSource>,
+}
+
+impl AuthConfig {
+    /// The token override for a repository, matched case-insensitively as
+    /// Github does.
+    pub fn repository(&self, repository: &str) -> Option<&TokenSource> {
+        self.repositories
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(repository))
+            .map(|(_, source)| source)
+    }
+}
+
+/// Represents a TOML environment configuration document, where each key
+/// corresponds to an environment name, and contains a dictionary of key/value
+/// environment variable pairs.  Of course only string values are supported.
+///
+/// The `repository`, `org_secrets`, `auth`, `owners`, `branches`,
+/// `max_changes`, `naming`, `freeze`, `protection_rules` and `cli` keys are
+/// reserved, so they can't be used as environment names.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ConfigDocument {
+    /// The `owner/repo` the document is synced to when it is found with
+    /// `--config-glob`, e.g. one per service of a monorepo.
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub org_secrets: BTreeMap<String, OrganizationSecretConfig>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// The team responsible for each environment, keyed by environment name,
+    /// so that teams sharing a config file can sync only their own entries:
+    ///
+    /// ```toml
+    /// [owners]
+    /// production = "team-infra"
+    /// ```
+    #[serde(default)]
+    pub owners: HashMap<String, String>,
+    /// The environment each branch deploys to, keyed by branch name or glob,
+    /// for `--environment-from-branch` (see
+    /// [`crate::branches::environment_for_branch`]).
+    #[serde(default)]
+    pub branches: BTreeMap<String, String>,
+    /// The most existing variables a sync may update or delete, unless
+    /// overridden by `--max-changes` (see [`crate::SyncOptions::max_changes`]).
+    #[serde(default)]
+    pub max_changes: Option<usize>,
+    /// How variable names must be written, checked before every sync.
+    #[serde(default)]
+    pub naming: NamingPolicy,
+    /// When syncs to some environments are refused, from `[[freeze]]`
+    /// entries (see [`FreezeWindow`]).
+    #[serde(default)]
+    pub freeze: Vec<FreezeWindow>,
+    /// The Github Apps gating deployments to each environment as custom
+    /// deployment protection rules, by app slug, keyed by environment name.
+    /// Environments left out keep whatever rules they have:
+    ///
+    /// ```toml
+    /// [protection_rules]
+    /// production = ["datadog-deployment-gate"]
+    /// ```
+    #[serde(default)]
+    pub protection_rules: BTreeMap<String, Vec<String>>,
+    /// Defaults for sync flags (see [`CliDefaults`]).
+    #[serde(default)]
+    pub cli: CliDefaults,
+    /// When each environment stops being synced, keyed by environment name,
+    /// from its `__expires` key (see [`EXPIRES_KEY`]).
+    #[serde(skip)]
+    pub expires: HashMap<String, Expiry>,
+    /// The keys each environment took from the `[__shared]` section rather
+    /// than defining them itself, keyed by environment name.
+    #[serde(skip)]
+    pub shared_keys: HashMap<String, BTreeSet<String>>,
+    /// Keys and environments that are never read, written or pruned, from
+    /// an ignore file (see [`ConfigDocument::exclude`]).
+    #[serde(skip)]
+    pub exclusions: Exclusions,
+    #[serde(flatten)]
+    pub environments: HashMap<String, EnvironmentConfig>,
+}
+
+impl ConfigDocument {
+    /// Drops the environments and variables excluded by an ignore file, and
+    /// keeps the exclusions so that syncs don't read or prune the remote
+    /// ones either.
+    pub fn exclude(&mut self, exclusions: Exclusions) {
+        self.environments.retain(|name, _| {
+            let excluded = exclusions.excludes_environment(name);
+            if excluded {
+                debug!("Excluding environment '{}' per the ignore file", name);
+            }
+            !excluded
+        });
+        for (name, environment) in self.environments.iter_mut() {
+            environment.retain(|key, _| {
+                let excluded = exclusions.excludes_key(name, key);
+                if excluded {
+                    debug!(
+                        "Excluding {} of environment '{}' per the ignore file",
+                        key, name
+                    );
+                }
+                !excluded
+            });
+        }
+        self.exclusions.extend(exclusions);
+    }
+
+    /// Drops every variable not owned by `owner`, and the environments left
+    /// empty.  A variable's owner is its own `owner`, or else its
+    /// environment's.  Organization secrets have no owner, so they are
+    /// dropped too.
+    pub fn retain_owner(&mut self, owner: &str) {
+        for (environment, variables) in self.environments.iter_mut() {
+            let environment_owner = self.owners.get(environment).map(String::as_str);
+            variables.retain(|_, variable| {
+                variable.owner.as_deref().or(environment_owner) == Some(owner)
+            });
+        }
+        self.environments
+            .retain(|_, variables| !variables.is_empty());
+        self.org_secrets.clear();
+    }
+}
+
+/// The plain values of an environment's configured variables, leaving out
+/// its secrets.
+pub fn environment_values(environment: &EnvironmentConfig) -> Environment {
+    environment
+        .iter()
+        .filter(|(_, variable)| variable.kind == VariableKind::Variable)
+        .map(|(key, variable)| (key.clone(), variable.value.clone()))
+        .collect()
+}
+
+/// The plain values of an environment's configured secrets, in key order.
+pub fn environment_secrets(environment: &EnvironmentConfig) -> BTreeMap<String, String> {
+    environment
+        .iter()
+        .filter(|(_, variable)| variable.kind == VariableKind::Secret)
+        .map(|(key, variable)| (key.clone(), variable.value.clone()))
+        .collect()
+}
+
+/// The subsections an environment's keys can be grouped into by kind.
+const KIND_SECTIONS: [(&str, VariableKind); 2] = [
+    ("vars", VariableKind::Variable),
+    ("secrets", VariableKind::Secret),
+];
+
+/// Parses a configuration document from a TOML string.  An environment's
+/// keys can be listed directly in it, or grouped by kind:
+///
+/// ```toml
+/// [production.vars]
+/// API_URL = "https://example.com"
+///
+/// [production.secrets]
+/// DATABASE_PASSWORD = { age = "-----BEGIN AGE ENCRYPTED FILE-----\n..." }
+/// ```
+///
+/// Keys of the `[__shared]` section are added to every environment that
+/// doesn't define them itself, or only to some with `only` or `except`:
+///
+/// ```toml
+/// [__shared]
+/// LOG_FORMAT = "json"
+/// SENTRY_ENABLED = { value = "true", only = ["production", "staging"] }
+/// DEBUG = { value = "true", except = ["production"] }
+/// ```
+pub fn parse_config(contents: &str) -> Result<ConfigDocument> {
+    let mut document: toml::Table = toml::from_str(contents)?;
+    let flattened = flatten_kind_sections(&mut document)?;
+    let shared_keys = apply_shared_section(&mut document)?;
+    let mut config: ConfigDocument = match flattened || shared_keys.is_some() {
+        true => toml::Value::Table(document).try_into()?,
+        // Deserializing from the text keeps line numbers in errors.
+        false => toml::from_str(contents)?,
+    };
+    config.shared_keys = shared_keys.unwrap_or_default();
+    for (name, environment) in config.environments.iter_mut() {
+        let Some(expires) = environment.remove(EXPIRES_KEY) else {
+            continue;
+        };
+        let expiry = expires.value.parse().map_err(|e| {
+            eyre!(
+                "{} in environment '{}' must be a plain date or TTL: {}",
+                EXPIRES_KEY,
+                name,
+                e
+            )
+        })?;
+        config.expires.insert(name.clone(), expiry);
+    }
+    debug!("Read config document: {:?}", config.environments);
+
+    for (name, secret) in &config.org_secrets {
+        if secret.visibility != OrganizationSecretVisibility::Selected
+            && !secret.repositories.is_empty()
+        {
+            return Err(eyre!(
+                "Organization secret '{}' lists repositories, but only secrets with visibility = \"selected\" can be limited to repositories",
+                name
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Moves the keys of `vars` and `secrets` subsections up into their
+/// environment with their kind set, so that both layouts deserialize alike.
+/// Returns whether there were any subsections.
+fn flatten_kind_sections(document: &mut toml::Table) -> Result<bool> {
+    let mut found = false;
+    for (name, section) in document.iter_mut() {
+        if RESERVED_SECTIONS.contains(&name.as_str()) {
+            continue;
+        }
+        let Some(environment) = section.as_table_mut() else {
+            continue;
+        };
+
+        for (subsection, kind) in KIND_SECTIONS {
+            if !environment
+                .get(subsection)
+                .is_some_and(toml::Value::is_table)
+            {
+                continue;
+            }
+            let Some(toml::Value::Table(entries)) = environment.remove(subsection) else {
+                continue;
+            };
+            found = true;
+
+            for (key, value) in entries {
+                let mut options = match value {
+                    toml::Value::String(value) => {
+                        toml::Table::from_iter([("value".to_string(), toml::Value::String(value))])
+                    }
+                    toml::Value::Table(options) => options,
+                    other => {
+                        return Err(eyre!(
+                            "'{}.{}.{}' must be a string, found {}",
+                            name,
+                            subsection,
+                            key,
+                            other.type_str()
+                        ))
+                    }
+                };
+                match options.get("kind").and_then(toml::Value::as_str) {
+                    Some(declared) if declared != kind.to_string() => {
+                        return Err(eyre!(
+                            "'{}.{}.{}' has kind = \"{}\", but is listed under {}",
+                            name,
+                            subsection,
+                            key,
+                            declared,
+                            subsection
+                        ))
+                    }
+                    _ => {
+                        options.insert("kind".to_string(), toml::Value::String(kind.to_string()));
+                    }
+                }
+                if environment
+                    .insert(key.clone(), toml::Value::Table(options))
+                    .is_some()
+                {
+                    return Err(eyre!(
+                        "{} is defined more than once in environment '{}'",
+                        key,
+                        name
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// The section of keys shared by every environment, see [`parse_config`].
+const SHARED_SECTION: &str = "__shared";
+
+/// Options of a shared key's inline table that choose its environments.
+const SHARED_OPTIONS: [&str; 2] = ["only", "except"];
+
+/// Copies the keys of the [`SHARED_SECTION`] into the environments they
+/// apply to, unless an environment defines the key itself, and removes the
+/// section.  Returns the keys copied into each environment, if there was
+/// one.
+fn apply_shared_section(
+    document: &mut toml::Table,
+) -> Result<Option<HashMap<String, BTreeSet<String>>>> {
+    let Some(shared) = document.remove(SHARED_SECTION) else {
+        return Ok(None);
+    };
+    let toml::Value::Table(shared) = shared else {
+        return Err(eyre!(
+            "'{}' must be a table, found {}",
+            SHARED_SECTION,
+            shared.type_str()
+        ));
+    };
+
+    let environment_names = document
+        .iter()
+        .filter(|(name, section)| !RESERVED_SECTIONS.contains(&name.as_str()) && section.is_table())
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    let mut copied = HashMap::<String, BTreeSet<String>>::new();
+    for (key, mut variable) in shared {
+        if key.starts_with("__") {
+            return Err(eyre!("{} can't be shared between environments", key));
+        }
+        let targets = match &mut variable {
+            toml::Value::Table(options) => shared_targets(&key, options, &environment_names)?,
+            _ => environment_names.clone(),
+        };
+
+        for name in targets {
+            let Some(environment) = document.get_mut(&name).and_then(toml::Value::as_table_mut)
+            else {
+                continue;
+            };
+            // Github variable names are case-insensitive.
+            if !environment
+                .keys()
+                .any(|existing| existing.eq_ignore_ascii_case(&key))
+            {
+                environment.insert(key.clone(), variable.clone());
+                copied.entry(name).or_default().insert(key.clone());
+            }
+        }
+    }
+
+    Ok(Some(copied))
+}
+
+/// The environments a shared key applies to, per its `only` or `except`
+/// list, which are removed from its options.
+fn shared_targets(
+    key: &str,
+    options: &mut toml::Table,
+    environment_names: &[String],
+) -> Result<Vec<String>> {
+    let only = take_environment_list(key, options, "only", environment_names)?;
+    let except = take_environment_list(key, options, "except", environment_names)?;
+
+    match (only, except) {
+        (Some(_), Some(_)) => Err(eyre!(
+            "'{}.{}' can't have both only and except",
+            SHARED_SECTION,
+            key
+        )),
+        (Some(only), None) => Ok(only),
+        (None, Some(except)) => Ok(environment_names
+            .iter()
+            .filter(|name| !except.contains(name))
+            .cloned()
+            .collect()),
+        (None, None) => Ok(environment_names.to_vec()),
+    }
+}
+
+/// Removes a list of environment names from a shared key's options.
+fn take_environment_list(
+    key: &str,
+    options: &mut toml::Table,
+    option: &str,
+    environment_names: &[String],
+) -> Result<Option<Vec<String>>> {
+    let Some(list) = options.remove(option) else {
+        return Ok(None);
+    };
+    let names = list
+        .as_array()
+        .and_then(|names| {
+            names
+                .iter()
+                .map(|name| name.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| {
+            eyre!(
+                "'{}.{}.{}' must be a list of environment names",
+                SHARED_SECTION,
+                key,
+                option
+            )
+        })?;
+
+    // A misspelt name would otherwise silently apply the key to the wrong
+    // environments.
+    if let Some(unknown) = names.iter().find(|name| !environment_names.contains(name)) {
+        return Err(eyre!(
+            "'{}.{}.{}' lists environment '{}', which isn't defined in the config document",
+            SHARED_SECTION,
+            key,
+            option,
+            unknown
+        ));
+    }
+
+    Ok(Some(names))
+}
+
+/// Top-level keys that aren't environments.
+const RESERVED_SECTIONS: [&str; 10] = [
+    "repository",
+    "org_secrets",
+    "auth",
+    "owners",
+    "branches",
+    "max_changes",
+    "naming",
+    "freeze",
+    "protection_rules",
+    CLI_SECTION,
+];
+
+/// Whether a top-level name in a config document is one of its own sections
+/// rather than an environment, including `__` sections such as `__shared`.
+pub fn is_reserved_section(name: &str) -> bool {
+    RESERVED_SECTIONS.contains(&name) || name.starts_with("__")
+}
+
+/// Keys of a variable's inline table.
+const VARIABLE_OPTIONS: [&str; 11] = [
+    "value",
+    "age",
+    "generate",
+    "file",
+    "encoding",
+    "on_conflict",
+    "owner",
+    "expires",
+    "transform",
+    "kind",
+    "chunk",
+];
+
+/// Checks a configuration document for mistakes that parsing would accept,
+/// or reject without saying where, for `--strict-config`:
+///
+/// - unknown `__` sections and environment keys, such as `__expirs`, which
+///   would otherwise be synced as environments or ignored as annotations;
+/// - values that aren't strings, such as `PORT = 8080`;
+/// - unknown variable options, such as `on_confict`, which are otherwise
+///   dropped;
+/// - nested tables where a value is expected, such as
+///   `[production.database]`.
+///
+/// Every problem is reported at once.
+pub fn check_strict(contents: &str) -> Result<()> {
+    let document: toml::Table = toml::from_str(contents)?;
+
+    let mut problems = Vec::new();
+    for (name, section) in &document {
+        if RESERVED_SECTIONS.contains(&name.as_str()) {
+            continue;
+        }
+        let shared = name == SHARED_SECTION;
+        if name.starts_with("__") && !shared {
+            problems.push(format!("unknown reserved section '{}'", name));
+            continue;
+        }
+        let Some(environment) = section.as_table() else {
+            problems.push(format!(
+                "'{}' must be an environment table, found {}",
+                name,
+                section.type_str()
+            ));
+            continue;
+        };
+
+        for (key, variable) in environment {
+            if key.starts_with("__") && key != EXPIRES_KEY {
+                problems.push(format!(
+                    "unknown reserved key '{}' in environment '{}'",
+                    key, name
+                ));
+                continue;
+            }
+
+            match (
+                KIND_SECTIONS.iter().any(|(section, _)| section == key),
+                variable,
+            ) {
+                (true, toml::Value::Table(entries)) => {
+                    for (entry, variable) in entries {
+                        check_variable(
+                            &format!("{}.{}.{}", name, key, entry),
+                            variable,
+                            shared,
+                            &mut problems,
+                        );
+                    }
+                }
+                _ => check_variable(
+                    &format!("{}.{}", name, key),
+                    variable,
+                    shared,
+                    &mut problems,
+                ),
+            }
+        }
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(eyre!(
+            "The config document failed --strict-config checks:\n  {}",
+            problems.join("\n  ")
+        )),
+    }
+}
+
+/// Checks a single variable for `--strict-config`, where `path` names it in
+/// problems, e.g. `production.API_URL`.  Shared variables may also have the
+/// [`SHARED_OPTIONS`].
+fn check_variable(path: &str, variable: &toml::Value, shared: bool, problems: &mut Vec<String>) {
+    match variable {
+        toml::Value::String(_) => {}
+        toml::Value::Table(options) => {
+            if !["value", "age", "generate", "file"]
+                .iter()
+                .any(|option| options.contains_key(*option))
+            {
+                problems.push(format!(
+                    "'{}' is a table without value, age, generate or file; nested tables aren't supported",
+                    path
+                ));
+                return;
+            }
+            for option in options.keys() {
+                let known = VARIABLE_OPTIONS.contains(&option.as_str())
+                    || shared && SHARED_OPTIONS.contains(&option.as_str());
+                if !known {
+                    problems.push(format!("'{}' has unknown option '{}'", path, option));
+                }
+            }
+            if let Some(kind) = options.get("kind") {
+                if let Err(e) = kind.as_str().unwrap_or_default().parse::<VariableKind>() {
+                    problems.push(format!("'{}' has an invalid kind: {}", path, e));
+                }
+            }
+            if let Some(encoding) = options.get("encoding") {
+                if let Err(e) = encoding
+                    .as_str()
+                    .unwrap_or_default()
+                    .parse::<FileEncoding>()
+                {
+                    problems.push(format!("'{}' has an invalid encoding: {}", path, e));
+                }
+            }
+        }
+        other => problems.push(format!(
+            "'{}' must be a string, found {}",
+            path,
+            other.type_str()
+        )),
+    }
+}
+
+/// Reads and parses the configuration document at the given path, and
+/// reads its `file` values relative to it.
+pub fn load_config(path: impl AsRef<Path>) -> Result<ConfigDocument> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Error reading config file {}: {}", path.display(), e))?;
+
+    let mut config = parse_config(&contents)?;
+    read_files(&mut config, config_dir(path))?;
+    Ok(config)
+}
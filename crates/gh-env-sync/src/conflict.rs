@@ -0,0 +1,80 @@
+use std::{fmt, str::FromStr};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+/// What to do when a remote value was changed outside the tool since the last
+/// sync and also differs from the config, i.e. someone edited it in the
+/// Github UI.  Conflicts can only be detected when a state file is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Replace the remote value with the configured one.
+    #[default]
+    Overwrite,
+    /// Leave the remote value in place.
+    Keep,
+    /// Abort the sync before writing anything to the environment.
+    Fail,
+    /// Ask a [`ConflictResolver`] what to do with each conflict.
+    Prompt,
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "overwrite" => Ok(Self::Overwrite),
+            "keep" => Ok(Self::Keep),
+            "fail" => Ok(Self::Fail),
+            "prompt" => Ok(Self::Prompt),
+            _ => Err(eyre!(
+                "Unknown conflict strategy '{}', expected one of overwrite, keep, fail, prompt",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ConflictStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Overwrite => "overwrite",
+            Self::Keep => "keep",
+            Self::Fail => "fail",
+            Self::Prompt => "prompt",
+        })
+    }
+}
+
+/// A value which changed remotely since the last sync and also differs from
+/// the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict<'a> {
+    pub environment: &'a str,
+    pub key: &'a str,
+    pub local_value: &'a str,
+    /// `None` if the variable was deleted remotely.
+    pub remote_value: Option<&'a str>,
+}
+
+/// A decision about a single conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Write the configured value.
+    Apply,
+    /// Leave the remote value in place.
+    Skip,
+    /// Write the configured value for this and every remaining conflict in
+    /// the run without asking again.
+    ApplyAll,
+    /// Stop the sync.
+    Abort,
+}
+
+/// Decides conflicts for [`ConflictStrategy::Prompt`], e.g. by asking the
+/// user.
+pub trait ConflictResolver: Send + Sync {
+    fn resolve(&self, conflict: &Conflict<'_>) -> Result<Resolution>;
+}
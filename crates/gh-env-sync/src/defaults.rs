@@ -0,0 +1,114 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+use crate::conflict::ConflictStrategy;
+
+/// The top-level section holding [`CliDefaults`].
+pub const CLI_SECTION: &str = "cli";
+
+/// Defaults for sync flags from the `[cli]` section of a config document,
+/// so that a team's preferred safety settings apply to whoever syncs it:
+///
+/// ```toml
+/// [cli]
+/// on_conflict = "fail"
+/// prune = true
+/// state_file = "state.json"
+/// check_shadowing = true
+/// ```
+///
+/// Flags given on the command line win, e.g. `--prune=false` over `prune =
+/// true`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CliDefaults {
+    /// `--on-conflict`.
+    #[serde(default)]
+    pub on_conflict: Option<ConflictStrategy>,
+    /// `--output`.
+    #[serde(default)]
+    pub output: Option<OutputFormat>,
+    /// `--state-file`, relative to the config file.
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+    /// `--prune`, which needs a state file.
+    #[serde(default)]
+    pub prune: Option<bool>,
+    /// `--check-shadowing`.
+    #[serde(default)]
+    pub check_shadowing: Option<bool>,
+    /// `--strict-secrets`.
+    #[serde(default)]
+    pub strict_secrets: Option<bool>,
+    /// `--warn-on-quota`.
+    #[serde(default)]
+    pub warn_on_quota: Option<bool>,
+    /// `--fail-fast`.
+    #[serde(default)]
+    pub fail_fast: Option<bool>,
+    /// `--dry-run`, so that syncing only checks for changes unless
+    /// `--dry-run=false` is given.
+    #[serde(default)]
+    pub dry_run: Option<bool>,
+    /// `--max-changes`, over the document's own `max_changes`.
+    #[serde(default)]
+    pub max_changes: Option<usize>,
+}
+
+/// How progress and results are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human readable log lines.
+    Text,
+    /// One JSON object per sync event on stdout.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(eyre!("Unknown output '{}', expected text or json", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// Reads only the `[cli]` section of the config file at `path`, before the
+/// flags it sets defaults for are used to load the rest.
+pub fn read_cli_defaults(path: &Path) -> Result<CliDefaults> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Error reading {}: {}", path.display(), e))?;
+    let mut document: toml::Table =
+        toml::from_str(&contents).map_err(|e| eyre!("Error parsing {}: {}", path.display(), e))?;
+
+    match document.remove(CLI_SECTION) {
+        Some(section) => section.try_into().map_err(|e| {
+            eyre!(
+                "Invalid [{}] section in {}: {}",
+                CLI_SECTION,
+                path.display(),
+                e
+            )
+        }),
+        None => Ok(CliDefaults::default()),
+    }
+}
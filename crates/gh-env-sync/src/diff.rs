@@ -0,0 +1,139 @@
+//! Renders the values a dry run would write, with multi-line values shown as
+//! unified diffs rather than in full.
+
+use crossterm::style::{Color, Stylize};
+use gh_env_sync::{sensitive::is_sensitive_key, sync::VariableChange, PlannedValue};
+use similar::{ChangeTag, TextDiff};
+
+use crate::{
+    output::{short_timestamp, Terminal},
+    prompt::mask_value,
+};
+
+/// Lines of unchanged context around each hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// Renders every planned value, grouped by environment.  Values of keys that
+/// look like credentials are masked unless `show_values` is set.
+pub fn render_planned(planned: &[PlannedValue], show_values: bool, terminal: Terminal) -> String {
+    let paint = |text: String, color: Color| match terminal.color {
+        true => text.with(color).to_string(),
+        false => text,
+    };
+
+    let mut output = String::new();
+    let mut environment = None;
+    for value in planned {
+        if environment != Some(&value.environment) {
+            environment = Some(&value.environment);
+            output.push_str(&format!("{}\n", value.environment));
+        }
+
+        let masked = !show_values && is_sensitive_key(&value.key);
+        let remote_value = value.remote_value.as_deref();
+        let (marker, color) = match value.change {
+            VariableChange::Create => ("+", Color::Green),
+            VariableChange::Delete => ("-", Color::Red),
+            _ => ("~", Color::Yellow),
+        };
+        let key = paint(format!("  {} {}", marker, value.key), color);
+        let note = match remote_note(value) {
+            Some(note) => paint(format!("  ({})", note), Color::DarkGrey),
+            None => String::new(),
+        };
+
+        if value.change == VariableChange::Delete {
+            let remote = remote_value.unwrap_or_default();
+            let remote = match masked {
+                true => mask_value(remote),
+                false => format!("{:?}", remote),
+            };
+            output.push_str(&format!("{} = {}{}\n", key, remote, note));
+        } else if masked {
+            let local = mask_value(&value.local_value);
+            match remote_value {
+                Some(remote) => output.push_str(&format!(
+                    "{}: {} -> {}{}\n",
+                    key,
+                    mask_value(remote),
+                    local,
+                    note
+                )),
+                None => output.push_str(&format!("{} = {}\n", key, local)),
+            }
+        } else if is_multiline(&value.local_value) || remote_value.is_some_and(is_multiline) {
+            output.push_str(&format!("{}{}\n", key, note));
+            output.push_str(&unified_diff(
+                remote_value.unwrap_or_default(),
+                &value.local_value,
+                &paint,
+            ));
+        } else {
+            match remote_value {
+                Some(remote) => output.push_str(&format!(
+                    "{}: {:?} -> {:?}{}\n",
+                    key, remote, value.local_value, note
+                )),
+                None => output.push_str(&format!("{} = {:?}\n", key, value.local_value)),
+            }
+        }
+
+        if let Some(rejection) = &value.rejection {
+            output.push_str(&paint(
+                format!("    ! Github would reject this: it {}", rejection),
+                Color::Red,
+            ));
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+/// When the remote value was last updated, and whether that was outside
+/// the tool, so that stale values and drift stand out.
+fn remote_note(value: &PlannedValue) -> Option<String> {
+    let updated_at = value.remote_updated_at.as_deref().map(short_timestamp);
+    match (updated_at, value.drifted) {
+        (Some(updated_at), true) => Some(format!(
+            "remote updated {}, outside gh-env-sync",
+            updated_at
+        )),
+        (Some(updated_at), false) => Some(format!("remote updated {}", updated_at)),
+        (None, true) => Some("remote changed outside gh-env-sync".to_string()),
+        (None, false) => None,
+    }
+}
+
+fn is_multiline(value: &str) -> bool {
+    value.contains('\n')
+}
+
+/// A unified diff from the remote value to the local one, indented under
+/// the key.
+fn unified_diff(remote: &str, local: &str, paint: &impl Fn(String, Color) -> String) -> String {
+    let diff = TextDiff::from_lines(remote, local);
+
+    let mut output = String::new();
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .iter_hunks()
+    {
+        output.push_str(&paint(format!("    {}", hunk.header()), Color::Cyan));
+        output.push('\n');
+
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            output.push_str(&match change.tag() {
+                ChangeTag::Delete => paint(format!("    -{}", line), Color::Red),
+                ChangeTag::Insert => paint(format!("    +{}", line), Color::Green),
+                ChangeTag::Equal => format!("     {}", line),
+            });
+            output.push('\n');
+        }
+    }
+
+    output
+}
@@ -0,0 +1,206 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+/// The config file read from the working directory when none is given.
+pub const DEFAULT_CONFIG_FILE: &str = "github_environments.toml";
+
+/// The file in [`user_config_dir`] listing headers to add to every request
+/// to the Github API, one `NAME: VALUE` per line.
+pub const REQUEST_HEADERS_FILE: &str = "request_headers";
+
+/// The file in [`user_config_dir`] holding the named sets of sync arguments
+/// that `all --profiles` runs.
+pub const PROFILES_FILE: &str = "profiles.toml";
+
+/// The directory holding a user's own config file:
+/// `%APPDATA%\gh-env-sync` on Windows (under `%USERPROFILE%` if `APPDATA`
+/// isn't set), `~/Library/Application Support/gh-env-sync` on macOS, and
+/// `$XDG_CONFIG_HOME/gh-env-sync` or `~/.config/gh-env-sync` elsewhere.
+pub fn user_config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        var("APPDATA")
+            .map(PathBuf::from)
+            .or_else(|| var("USERPROFILE").map(|home| Path::new(&home).join("AppData/Roaming")))?
+    } else if cfg!(target_os = "macos") {
+        Path::new(&var("HOME")?).join("Library/Application Support")
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".config")))?
+    };
+
+    Some(base.join("gh-env-sync"))
+}
+
+/// Where to read the config file given as `location`.  The default
+/// [`DEFAULT_CONFIG_FILE`] falls back to the one in [`user_config_dir`] when
+/// the working directory has none, so that a personal config works from
+/// anywhere.  Other locations are used as they are.
+pub fn locate_config(location: &str) -> String {
+    if location != DEFAULT_CONFIG_FILE || Path::new(location).exists() {
+        return location.to_string();
+    }
+
+    match user_config_dir().map(|directory| directory.join(DEFAULT_CONFIG_FILE)) {
+        Some(path) if path.is_file() => path.to_string_lossy().into_owned(),
+        _ => location.to_string(),
+    }
+}
+
+/// Matches `name` against a pattern where `*` matches any run of characters
+/// and `?` any single character.
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // matched[j] is whether the pattern so far matches the first j
+    // characters of the name.
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+
+    matched[name.len()]
+}
+
+/// Finds the files matching a glob such as
+/// `services/*/github_environments.toml`, in path order.  Each `/`-separated
+/// part of the pattern is matched with [`matches_glob`], and `**` matches any
+/// number of directories.  Hidden files and directories are only matched by
+/// parts that start with a `.`.  On Windows, parts may also be separated by
+/// `\`, and the pattern may start with a drive such as `C:`.
+pub fn discover_config_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = match cfg!(windows) {
+        true => pattern.replace('\\', "/"),
+        false => pattern.to_string(),
+    };
+    let mut parts = pattern
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>();
+    let root = match parts.first() {
+        Some(drive) if cfg!(windows) && is_drive(drive) => {
+            let root = PathBuf::from(format!("{}\\", drive));
+            parts.remove(0);
+            root
+        }
+        _ if pattern.starts_with('/') => PathBuf::from("/"),
+        _ => PathBuf::from("."),
+    };
+
+    let mut files = Vec::new();
+    walk(&root, &parts, &mut files)?;
+    files.sort();
+    files.dedup();
+
+    // Paths relative to the working directory read better without `./`.
+    Ok(files
+        .into_iter()
+        .map(|file| match file.strip_prefix(".") {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => file,
+        })
+        .collect())
+}
+
+/// The files that git reports as changed between `reference` and the
+/// working tree, e.g. `origin/main` or the commit a CI run last synced.
+/// Paths are canonicalized, and deleted files are left out.
+pub fn changed_since(reference: &str) -> Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args(["diff", "--name-only", "--relative", "-z", reference, "--"])
+        .output()
+        .map_err(|e| eyre!("Error running git: {}", e))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "git diff against {} failed: {}",
+            reference,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| std::fs::canonicalize(path).ok())
+        .collect())
+}
+
+fn walk(directory: &Path, parts: &[&str], files: &mut Vec<PathBuf>) -> Result<()> {
+    let Some((part, rest)) = parts.split_first() else {
+        if directory.is_file() {
+            files.push(directory.to_path_buf());
+        }
+        return Ok(());
+    };
+
+    if *part == "**" {
+        walk(directory, rest, files)?;
+        for entry in read_directory(directory)? {
+            if entry.is_dir() && !is_hidden(&entry) {
+                walk(&entry, parts, files)?;
+            }
+        }
+        return Ok(());
+    }
+
+    if !part.contains(['*', '?']) {
+        let path = directory.join(part);
+        if path.exists() {
+            walk(&path, rest, files)?;
+        }
+        return Ok(());
+    }
+
+    for entry in read_directory(directory)? {
+        let name = entry
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if matches_glob(part, &name) && (part.starts_with('.') || !is_hidden(&entry)) {
+            walk(&entry, rest, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_directory(directory: &Path) -> Result<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    std::fs::read_dir(directory)
+        .map_err(|e| eyre!("Error reading directory {}: {}", directory.display(), e))?
+        .map(|entry| {
+            entry
+                .map(|entry| entry.path())
+                .map_err(|e| eyre!("Error reading directory {}: {}", directory.display(), e))
+        })
+        .collect()
+}
+
+/// Whether a path part is a Windows drive, such as `C:`.
+fn is_drive(part: &str) -> bool {
+    let bytes = part.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
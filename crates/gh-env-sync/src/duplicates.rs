@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use crate::config::Environment;
+
+/// Values shorter than this are never reported as similar, since short
+/// values such as `true` and `1` differ by a character or two by design.
+const MIN_SIMILAR_LENGTH: usize = 8;
+
+/// The most characters two values may differ by to be reported as similar.
+const MAX_SIMILAR_DISTANCE: usize = 2;
+
+/// A key with the same value in every environment, which could be moved to
+/// the `[__shared]` section or a repository variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdenticalKey {
+    pub key: String,
+    pub value: String,
+}
+
+/// Values of the same key that differ by only a few characters between two
+/// environments, which is often a copy-paste mistake such as a typo in a
+/// host name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimilarValues {
+    pub key: String,
+    pub environments: [String; 2],
+    pub values: [String; 2],
+    /// How many characters have to be inserted, deleted or replaced to turn
+    /// one value into the other.
+    pub distance: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateReport {
+    pub identical: Vec<IdenticalKey>,
+    pub similar: Vec<SimilarValues>,
+}
+
+impl DuplicateReport {
+    pub fn is_empty(&self) -> bool {
+        self.identical.is_empty() && self.similar.is_empty()
+    }
+}
+
+/// Finds keys with the same value in every environment, and keys with
+/// suspiciously similar values in two environments.  Keys are compared
+/// case-insensitively, like Github does, and reported uppercased.  With
+/// fewer than two environments there's nothing to compare.
+pub fn find_duplicates(environments: &BTreeMap<String, Environment>) -> DuplicateReport {
+    let mut report = DuplicateReport::default();
+    if environments.len() < 2 {
+        return report;
+    }
+
+    // The value of each key in each environment that has it.
+    let mut keys = BTreeMap::<String, Vec<(&str, &str)>>::new();
+    for (environment_name, variables) in environments {
+        for (key, value) in variables {
+            keys.entry(key.to_uppercase())
+                .or_default()
+                .push((environment_name.as_str(), value.as_str()));
+        }
+    }
+
+    for (key, values) in keys {
+        if values.len() == environments.len() && values.iter().all(|(_, v)| *v == values[0].1) {
+            report.identical.push(IdenticalKey {
+                key,
+                value: values[0].1.to_string(),
+            });
+            continue;
+        }
+
+        for (i, (first_environment, first)) in values.iter().enumerate() {
+            for (second_environment, second) in &values[i + 1..] {
+                if first == second
+                    || first.chars().count() < MIN_SIMILAR_LENGTH
+                    || second.chars().count() < MIN_SIMILAR_LENGTH
+                {
+                    continue;
+                }
+
+                let distance = edit_distance(first, second);
+                if distance <= MAX_SIMILAR_DISTANCE {
+                    report.similar.push(SimilarValues {
+                        key: key.clone(),
+                        environments: [
+                            first_environment.to_string(),
+                            second_environment.to_string(),
+                        ],
+                        values: [first.to_string(), second.to_string()],
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+
+    report
+}
+
+/// The Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
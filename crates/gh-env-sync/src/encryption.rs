@@ -0,0 +1,112 @@
+use std::{io::Read, path::Path};
+
+use age::{armor::ArmoredReader, Decryptor, Identity, IdentityFile, IdentityFileEntry};
+use color_eyre::{eyre::eyre, Result};
+use tracing::debug;
+
+use crate::config::ConfigDocument;
+
+/// The first line of an ASCII armored age file, which is how encrypted values
+/// are recognized in the config document:
+///
+/// ```toml
+/// [production]
+/// API_KEY = """
+/// -----BEGIN AGE ENCRYPTED FILE-----
+/// ...
+/// -----END AGE ENCRYPTED FILE-----
+/// """
+/// ```
+pub const AGE_ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Whether a config value is age ciphertext.
+pub fn is_encrypted(value: &str) -> bool {
+    value.trim_start().starts_with(AGE_ARMOR_HEADER)
+}
+
+/// Reads the identities (private keys) in an age identity file, such as one
+/// generated by `age-keygen`.
+pub fn load_identities(path: impl AsRef<Path>) -> Result<Vec<Box<dyn Identity>>> {
+    let path = path.as_ref();
+    let identities = IdentityFile::from_file(path.to_string_lossy().into_owned())
+        .map_err(|e| eyre!("Error reading age identity file {}: {}", path.display(), e))?
+        .into_identities()
+        .into_iter()
+        .map(|entry| match entry {
+            IdentityFileEntry::Native(identity) => Box::new(identity) as Box<dyn Identity>,
+        })
+        .collect::<Vec<_>>();
+
+    if identities.is_empty() {
+        return Err(eyre!("No age identities found in {}", path.display()));
+    }
+
+    Ok(identities)
+}
+
+/// Decrypts a single ASCII armored value.
+pub fn decrypt_value(ciphertext: &str, identities: &[Box<dyn Identity>]) -> Result<String> {
+    let decryptor = match Decryptor::new(ArmoredReader::new(ciphertext.trim().as_bytes()))? {
+        Decryptor::Recipients(decryptor) => decryptor,
+        Decryptor::Passphrase(_) => {
+            return Err(eyre!(
+                "Passphrase encrypted values are not supported, encrypt to a recipient instead"
+            ))
+        }
+    };
+
+    let mut plaintext = String::new();
+    decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))?
+        .read_to_string(&mut plaintext)?;
+
+    Ok(plaintext)
+}
+
+/// Whether any value in the config document is age ciphertext.
+pub fn has_encrypted_values(config: &ConfigDocument) -> bool {
+    config
+        .environments
+        .values()
+        .flat_map(|environment| environment.values())
+        .map(|variable| &variable.value)
+        .chain(config.org_secrets.values().map(|secret| &secret.value))
+        .any(|value| is_encrypted(value))
+}
+
+/// Replaces every encrypted value in the config document with its
+/// plaintext, returning how many were decrypted.
+pub fn decrypt_config(
+    config: &mut ConfigDocument,
+    identities: &[Box<dyn Identity>],
+) -> Result<usize> {
+    let mut decrypted = 0;
+
+    for (environment_name, environment) in &mut config.environments {
+        for (key, variable) in environment.iter_mut() {
+            if is_encrypted(&variable.value) {
+                debug!("Decrypting {} in environment '{}'", key, environment_name);
+                variable.value = decrypt_value(&variable.value, identities).map_err(|e| {
+                    eyre!(
+                        "Error decrypting {} in environment '{}': {}",
+                        key,
+                        environment_name,
+                        e
+                    )
+                })?;
+                decrypted += 1;
+            }
+        }
+    }
+
+    for (name, secret) in &mut config.org_secrets {
+        if is_encrypted(&secret.value) {
+            debug!("Decrypting organization secret {}", name);
+            secret.value = decrypt_value(&secret.value, identities)
+                .map_err(|e| eyre!("Error decrypting organization secret {}: {}", name, e))?;
+            decrypted += 1;
+        }
+    }
+
+    Ok(decrypted)
+}
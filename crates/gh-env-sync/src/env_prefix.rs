@@ -0,0 +1,113 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use tracing::warn;
+
+use crate::config::{ConfigDocument, EnvironmentConfig, VariableConfig, VariableKind};
+
+/// Maps process environment variables starting with a prefix to a Github
+/// environment, for CI systems that provide values as environment variables.
+/// Parsed from `PREFIX=ENVIRONMENT`, e.g. `GHES_PROD_=production` syncs
+/// `GHES_PROD_API_URL` as `API_URL` of `production`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvPrefix {
+    pub prefix: String,
+    pub environment: String,
+}
+
+impl FromStr for EnvPrefix {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((prefix, environment)) if !prefix.is_empty() && !environment.is_empty() => {
+                Ok(Self {
+                    prefix: prefix.to_string(),
+                    environment: environment.to_string(),
+                })
+            }
+            _ => Err(eyre!(
+                "Expected an environment variable prefix like GHES_PROD_=production, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EnvPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.prefix, self.environment)
+    }
+}
+
+/// Builds a config document from the given process environment variables,
+/// with each variable that starts with a prefix added to the prefix's
+/// environment without it.  A variable matching several prefixes belongs to
+/// the longest, so `APP_` and `APP_PROD_` can be used together.  Values are
+/// synced as plain variables.
+pub fn config_from_env(
+    prefixes: &[EnvPrefix],
+    variables: impl IntoIterator<Item = (String, String)>,
+) -> Result<ConfigDocument> {
+    let mut environments = prefixes
+        .iter()
+        .map(|prefix| (prefix.environment.clone(), EnvironmentConfig::new()))
+        .collect::<HashMap<_, _>>();
+
+    for (name, value) in variables {
+        let Some(prefix) = prefixes
+            .iter()
+            .filter(|prefix| name.starts_with(&prefix.prefix))
+            .max_by_key(|prefix| prefix.prefix.len())
+        else {
+            continue;
+        };
+        let key = &name[prefix.prefix.len()..];
+        if key.is_empty() {
+            continue;
+        }
+
+        let environment = environments
+            .get_mut(&prefix.environment)
+            .expect("every prefix's environment was added");
+        if environment
+            .keys()
+            .any(|existing| existing.eq_ignore_ascii_case(key))
+        {
+            return Err(eyre!(
+                "{} of environment '{}' is set by more than one environment variable, including {}",
+                key,
+                prefix.environment,
+                name
+            ));
+        }
+        environment.insert(
+            key.to_string(),
+            VariableConfig {
+                value,
+                on_conflict: None,
+                generate: None,
+                file: None,
+                owner: None,
+                expires: None,
+                transform: Vec::new(),
+                kind: VariableKind::Variable,
+                chunk: false,
+            },
+        );
+    }
+
+    for (name, environment) in &environments {
+        if environment.is_empty() {
+            warn!(
+                "No environment variables start with the prefixes of environment '{}'",
+                name
+            );
+        }
+    }
+
+    Ok(ConfigDocument {
+        environments,
+        ..ConfigDocument::default()
+    })
+}
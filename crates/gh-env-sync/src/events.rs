@@ -0,0 +1,108 @@
+use std::{fmt, sync::mpsc::Sender};
+
+use serde::Serialize;
+
+/// Progress reported by a [`crate::Syncer`] while it runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SyncEvent {
+    EnvironmentStarted {
+        environment: String,
+        variable_count: usize,
+        /// Which token the environment is synced with, when it isn't the
+        /// default one.
+        identity: Option<String>,
+    },
+    EnvironmentCreated {
+        environment: String,
+    },
+    VariableCreated {
+        environment: String,
+        key: String,
+    },
+    VariableUpdated {
+        environment: String,
+        key: String,
+    },
+    /// A variable the tool applied was removed from the config and deleted
+    /// with `prune`.
+    VariableDeleted {
+        environment: String,
+        key: String,
+    },
+    /// An environment secret was written.  Secret values can't be read
+    /// back, so without a state file they are written on every sync.
+    SecretUpdated {
+        environment: String,
+        key: String,
+    },
+    VariableSkipped {
+        environment: String,
+        key: String,
+    },
+    /// The remote value no longer matches what the previous sync applied.
+    DriftDetected {
+        environment: String,
+        key: String,
+    },
+    /// An organization secret was written.  Secret values can't be read
+    /// back, so without a state file they are written on every sync.
+    OrganizationSecretUpdated {
+        name: String,
+    },
+    EnvironmentFinished {
+        environment: String,
+    },
+    Error {
+        environment: Option<String>,
+        key: Option<String>,
+        message: String,
+    },
+}
+
+/// Receives [`SyncEvent`]s as a sync progresses.  Implemented for closures and
+/// for `std::sync::mpsc::Sender`, so events can be handled inline or consumed
+/// from another thread.
+pub trait SyncEventHandler: Send + Sync {
+    fn on_event(&self, event: &SyncEvent);
+}
+
+impl<F> SyncEventHandler for F
+where
+    F: Fn(&SyncEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &SyncEvent) {
+        self(event)
+    }
+}
+
+impl SyncEventHandler for Sender<SyncEvent> {
+    fn on_event(&self, event: &SyncEvent) {
+        // A dropped receiver just means nobody is listening anymore.
+        let _ = self.send(event.clone());
+    }
+}
+
+/// The optional handler owned by a [`crate::Syncer`].
+#[derive(Default)]
+pub(crate) struct EventSink(Option<Box<dyn SyncEventHandler>>);
+
+impl EventSink {
+    pub(crate) fn new(handler: impl SyncEventHandler + 'static) -> Self {
+        Self(Some(Box::new(handler)))
+    }
+
+    pub(crate) fn emit(&self, event: SyncEvent) {
+        if let Some(handler) = &self.0 {
+            handler.on_event(&event);
+        }
+    }
+}
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EventSink")
+            .field(&self.0.as_ref().map(|_| "<handler>"))
+            .finish()
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use color_eyre::{eyre::eyre, Result};
+use tracing::debug;
+
+use crate::discover::matches_glob;
+
+/// The ignore file looked for next to a local config file.
+pub const IGNORE_FILE_NAME: &str = ".ghenvignore";
+
+/// A single line of an ignore file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Lowercased, `None` for rules applying to every environment.
+    environment: Option<String>,
+    /// Uppercased, `None` for rules excluding a whole environment.
+    key: Option<String>,
+    /// Whether the rule starts with `!`, re-including what earlier rules
+    /// excluded.
+    negated: bool,
+}
+
+/// Keys and environments that are never read, written or pruned, from a
+/// `.ghenvignore` file with gitignore-style patterns, one per line:
+///
+/// ```text
+/// # Keys managed by the deploy pipeline, in every environment.
+/// DEPLOY_*
+/// # A key only in matching environments.
+/// preview-*/DATABASE_URL
+/// # A whole environment.
+/// sandbox/
+/// # Later rules win, so ! re-includes a key.
+/// !DEPLOY_REGION
+/// ```
+///
+/// `*` matches any run of characters and `?` a single one.  Keys and
+/// environment names are matched case-insensitively, as Github treats them.
+/// As with gitignore, keys of an excluded environment can't be re-included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exclusions {
+    rules: Vec<Rule>,
+}
+
+impl Exclusions {
+    /// Parses the contents of an ignore file.  Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern.trim_start()),
+                None => (false, line),
+            };
+            // Keys can't contain slashes, but environment names can, so the
+            // key is whatever follows the last one.
+            let (environment, key) = match pattern.rsplit_once('/') {
+                Some((environment, key)) => (Some(environment.trim()), key.trim()),
+                None => (None, pattern),
+            };
+            if environment.is_some_and(str::is_empty) || (environment.is_none() && key.is_empty()) {
+                return Err(eyre!(
+                    "Expected a KEY, ENVIRONMENT/KEY or ENVIRONMENT/ pattern on line {}, got '{}'",
+                    number + 1,
+                    line
+                ));
+            }
+
+            rules.push(Rule {
+                environment: environment.map(str::to_lowercase),
+                key: (!key.is_empty()).then(|| key.to_uppercase()),
+                negated,
+            });
+        }
+
+        Ok(Self { rules })
+    }
+
+    /// Reads the ignore file at the given path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading ignore file {}: {}", path.display(), e))?;
+        let exclusions = Self::parse(&contents)
+            .map_err(|e| eyre!("Error parsing ignore file {}: {}", path.display(), e))?;
+        debug!(
+            "Read {} exclusion rules from {}",
+            exclusions.rules.len(),
+            path.display()
+        );
+
+        Ok(exclusions)
+    }
+
+    /// Whether there are no rules, so nothing is excluded.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Adds the rules of another ignore file after these, so that they take
+    /// precedence.
+    pub fn extend(&mut self, other: Exclusions) {
+        self.rules.extend(other.rules);
+    }
+
+    /// Whether the whole environment is excluded.
+    pub fn excludes_environment(&self, environment: &str) -> bool {
+        let environment = environment.to_lowercase();
+        self.rules
+            .iter()
+            .filter(|rule| rule.key.is_none())
+            .rfind(|rule| {
+                rule.environment
+                    .as_deref()
+                    .is_some_and(|pattern| matches_glob(pattern, &environment))
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    /// Whether the key is excluded in the environment, including when the
+    /// whole environment is.
+    pub fn excludes_key(&self, environment: &str, key: &str) -> bool {
+        if self.excludes_environment(environment) {
+            return true;
+        }
+
+        let environment = environment.to_lowercase();
+        let key = key.to_uppercase();
+        self.rules
+            .iter()
+            .rfind(|rule| {
+                rule.key
+                    .as_deref()
+                    .is_some_and(|pattern| matches_glob(pattern, &key))
+                    && rule
+                        .environment
+                        .as_deref()
+                        .is_none_or(|pattern| matches_glob(pattern, &environment))
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+}
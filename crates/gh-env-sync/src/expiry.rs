@@ -0,0 +1,369 @@
+use std::{fmt, str::FromStr};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    audit::{unix_timestamp, AuditOperation},
+    config::ConfigDocument,
+    lock::SyncLock,
+    state::State,
+    sync::Syncer,
+};
+
+/// The environment key declaring when an environment expires.  It is taken
+/// out of the environment when the config document is parsed, so it is
+/// never synced as a variable.
+pub const EXPIRES_KEY: &str = "__expires";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// When an environment or variable stops being synced, and is deleted by
+/// `cleanup --expired`:
+///
+/// ```toml
+/// [preview]
+/// __expires = "7d"
+/// TEMPORARY_FLAG = { value = "on", expires = "2024-12-31" }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Expiry {
+    /// Expires at the end of this day, UTC, as days since the unix epoch.
+    Date(i64),
+    /// Expires this many seconds after the tool first applied the entry, as
+    /// recorded in the state file.
+    Ttl(u64),
+}
+
+impl Expiry {
+    /// When the entry expires, in seconds since the unix epoch, or `None`
+    /// for a TTL when the entry's creation wasn't recorded.
+    pub fn expires_at(&self, created_at: Option<u64>) -> Option<u64> {
+        match self {
+            Self::Date(days) => Some((days + 1).max(0) as u64 * SECONDS_PER_DAY),
+            Self::Ttl(seconds) => created_at.map(|created_at| created_at + seconds),
+        }
+    }
+
+    /// Whether the entry has expired by `now`.
+    pub fn is_expired(&self, created_at: Option<u64>, now: u64) -> bool {
+        self.expires_at(created_at)
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl FromStr for Expiry {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(days) = parse_date(s) {
+            return Ok(Self::Date(days));
+        }
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (amount, unit) = s.split_at(split);
+        let seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => SECONDS_PER_DAY,
+            "w" => 7 * SECONDS_PER_DAY,
+            _ => 0,
+        };
+        match amount.parse::<u64>() {
+            Ok(amount) if seconds > 0 && amount > 0 => Ok(Self::Ttl(amount * seconds)),
+            _ => Err(eyre!(
+                "Invalid expiry '{}', expected a date such as 2024-12-31 or a TTL such as 7d",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Expiry {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse().map_err(|e: Report| e.to_string())
+    }
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Date(days) => {
+                let (year, month, day) = civil_from_days(*days);
+                write!(f, "{:04}-{:02}-{:02}", year, month, day)
+            }
+            Self::Ttl(seconds) => match seconds {
+                s if s % (7 * SECONDS_PER_DAY) == 0 => write!(f, "{}w", s / (7 * SECONDS_PER_DAY)),
+                s if s % SECONDS_PER_DAY == 0 => write!(f, "{}d", s / SECONDS_PER_DAY),
+                s if s % (60 * 60) == 0 => write!(f, "{}h", s / (60 * 60)),
+                s if s % 60 == 0 => write!(f, "{}m", s / 60),
+                s => write!(f, "{}s", s),
+            },
+        }
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date into days since the unix epoch.
+pub(crate) fn parse_date(s: &str) -> Option<i64> {
+    let mut parts = s.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day) = (
+        year.parse::<i64>().ok()?,
+        month.parse::<i64>().ok()?,
+        day.parse::<i64>().ok()?,
+    );
+    if !(1..=12).contains(&month) || day < 1 {
+        return None;
+    }
+
+    // Howard Hinnant's days-from-civil algorithm.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    // Rejects days past the end of the month, e.g. 2024-02-30.
+    (civil_from_days(days).2 == day).then_some(days)
+}
+
+/// The `(year, month, day)` of a number of days since the unix epoch.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// The environments and variables of a config document that have expired.
+/// Variables of expired environments aren't listed separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expired {
+    pub environments: Vec<String>,
+    /// `(environment, key)` pairs.
+    pub variables: Vec<(String, String)>,
+}
+
+impl Expired {
+    pub fn is_empty(&self) -> bool {
+        self.environments.is_empty() && self.variables.is_empty()
+    }
+
+    /// A copy of the config document without the expired entries.
+    pub fn remove_from(&self, config: &ConfigDocument) -> ConfigDocument {
+        let mut config = config.clone();
+        for environment in &self.environments {
+            config.environments.remove(environment);
+        }
+        for (environment, key) in &self.variables {
+            if let Some(variables) = config.environments.get_mut(environment) {
+                variables.remove(key);
+            }
+        }
+
+        config
+    }
+}
+
+/// Finds the entries of a config document that have expired by `now`.  TTLs
+/// count from when the tool first applied the entry to `repository`, per the
+/// state file, so entries it hasn't applied yet never expire.
+pub fn find_expired(
+    config: &ConfigDocument,
+    state: Option<&State>,
+    repository: &str,
+    now: u64,
+) -> Expired {
+    let created_at = |environment: &str, key: Option<&str>| {
+        state.and_then(|state| state.created(repository, environment, key))
+    };
+
+    let mut expired = Expired::default();
+    let mut environment_names = config.environments.keys().collect::<Vec<_>>();
+    environment_names.sort();
+    for environment in environment_names {
+        if config
+            .expires
+            .get(environment)
+            .is_some_and(|expiry| expiry.is_expired(created_at(environment, None), now))
+        {
+            expired.environments.push(environment.clone());
+            continue;
+        }
+
+        let mut keys = config.environments[environment]
+            .iter()
+            .filter(|(key, variable)| {
+                variable.expires.is_some_and(|expiry| {
+                    expiry.is_expired(created_at(environment, Some(key)), now)
+                })
+            })
+            .map(|(key, _)| (environment.clone(), key.clone()))
+            .collect::<Vec<_>>();
+        keys.sort();
+        expired.variables.extend(keys);
+    }
+
+    expired
+}
+
+impl Syncer {
+    /// The expired entries of a config document, per the state file if one
+    /// is in use, leaving out those excluded by [`SyncOptions::exclusions`].
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub fn expired(&self, config: &ConfigDocument) -> Result<Expired> {
+        let state = match &self.options().state_file {
+            Some(path) => Some(State::load(path)?),
+            None => None,
+        };
+
+        let mut expired =
+            find_expired(config, state.as_ref(), &self.repository(), unix_timestamp());
+        let exclusions = &self.options().exclusions;
+        expired
+            .environments
+            .retain(|environment| !exclusions.excludes_environment(environment));
+        expired
+            .variables
+            .retain(|(environment, key)| !exclusions.excludes_key(environment, key));
+        Ok(expired)
+    }
+
+    /// Deletes the expired environments and variables of a config document
+    /// that exist in the repository.  Returns those deleted, or with
+    /// `dry_run` those that would be.
+    pub async fn delete_expired(&self, config: &ConfigDocument, dry_run: bool) -> Result<Expired> {
+        let expired = self.expired(config)?;
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+
+        let remote_environments = self.client().list_environments().await?;
+        let mut existing = Expired {
+            environments: expired
+                .environments
+                .iter()
+                .filter(|environment| remote_environments.contains(environment))
+                .cloned()
+                .collect(),
+            variables: Vec::new(),
+        };
+        let mut previous_values = Vec::new();
+        for (environment, key) in &expired.variables {
+            if !remote_environments.contains(environment) {
+                continue;
+            }
+            let remote_variables = self
+                .client_for(environment)
+                .list_environment_variables(environment)
+                .await?;
+            if let Some(value) = remote_variables.get(&key.to_uppercase()) {
+                existing.variables.push((environment.clone(), key.clone()));
+                previous_values.push(value.clone());
+            }
+        }
+        if dry_run || existing.is_empty() {
+            return Ok(existing);
+        }
+
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let result = self
+            .delete_expired_variables(&existing.variables, previous_values)
+            .await;
+        let result = match result {
+            Ok(()) => self.apply_deletions(&existing.environments).await,
+            Err(e) => Err(e),
+        };
+        let result = lock.release_after(self.client(), result).await;
+
+        result.map(|_| existing)
+    }
+
+    async fn delete_expired_variables(
+        &self,
+        variables: &[(String, String)],
+        previous_values: Vec<String>,
+    ) -> Result<()> {
+        for ((environment, key), previous_value) in variables.iter().zip(previous_values) {
+            info!(
+                "Deleting expired {} from environment '{}'",
+                key, environment
+            );
+            self.client_for(environment)
+                .delete_environment_variable(environment, key)
+                .await?;
+            self.audit(
+                environment,
+                key,
+                AuditOperation::Delete,
+                Some(previous_value),
+                None,
+            )?;
+            self.forget_created(environment, Some(key))?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops an environment's, or a single variable's, records from the
+    /// state file, so that a TTL counts from scratch if it is created again.
+    pub(crate) fn forget_created(&self, environment: &str, key: Option<&str>) -> Result<()> {
+        let Some(path) = &self.options().state_file else {
+            return Ok(());
+        };
+
+        let mut state = State::load(path)?;
+        state.forget(&self.repository(), environment, key);
+        state.save(path)
+    }
+
+    /// Leaves expired entries out of a config document before syncing it.
+    pub(crate) fn without_expired(
+        &self,
+        config: &ConfigDocument,
+    ) -> Result<Option<ConfigDocument>> {
+        let expired = self.expired(config)?;
+        if expired.is_empty() {
+            return Ok(None);
+        }
+
+        for environment in &expired.environments {
+            warn!(
+                "Environment '{}' expired ({}), not syncing it; `cleanup --expired` deletes it",
+                environment, config.expires[environment]
+            );
+        }
+        for (environment, key) in &expired.variables {
+            warn!(
+                "{} in environment '{}' expired, not syncing it; `cleanup --expired` deletes it",
+                key, environment
+            );
+        }
+
+        Ok(Some(expired.remove_from(config)))
+    }
+}
@@ -0,0 +1,265 @@
+use std::{collections::HashSet, fmt};
+
+use color_eyre::Result;
+use tracing::warn;
+
+use crate::{
+    chunk::join_chunks,
+    config::{ConfigDocument, Environment, EnvironmentConfig, VariableConfig, VariableKind},
+    encryption::is_encrypted,
+    providers::find_placeholders,
+    references::referenced_keys,
+    shadowing::VariableLevel,
+    sync::Syncer,
+    transform::Transform,
+};
+
+/// A step in how a configured key's value comes about, in the order the
+/// steps apply when syncing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueSource {
+    /// Defined in the environment's own section.
+    Environment,
+    /// Copied from the `[__shared]` section, as the environment doesn't
+    /// define the key itself.
+    Shared,
+    /// Decrypted from an age encrypted value.
+    Encrypted,
+    /// Generated on the first sync and kept in the state file.
+    Generated,
+    /// Read from a value provider through a `${scheme:argument}`
+    /// placeholder.
+    Provider { scheme: String, argument: String },
+    /// Filled in with another key's value through `${ref:KEY}`.  `via` names
+    /// the key whose value holds the reference, when it isn't the explained
+    /// key itself, and `shared` whether the referenced key came from the
+    /// `[__shared]` section.
+    Reference {
+        key: String,
+        via: Option<String>,
+        shared: bool,
+    },
+    /// Normalized by a transform.
+    Transform(Transform),
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Environment => f.write_str("defined in the environment's section"),
+            Self::Shared => f.write_str("copied from [__shared]"),
+            Self::Encrypted => f.write_str("decrypted from an age encrypted value"),
+            Self::Generated => {
+                f.write_str("generated on the first sync and kept in the state file")
+            }
+            Self::Provider { scheme, argument } => {
+                write!(
+                    f,
+                    "read from the '{}' provider: ${{{}:{}}}",
+                    scheme, scheme, argument
+                )
+            }
+            Self::Reference { key, via, shared } => {
+                write!(f, "references {}", key)?;
+                if let Some(via) = via {
+                    write!(f, " through {}", via)?;
+                }
+                if *shared {
+                    f.write_str(", copied from [__shared]")?;
+                }
+                Ok(())
+            }
+            Self::Transform(transform) => write!(f, "transformed with {}", transform),
+        }
+    }
+}
+
+/// Where a configured key's value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyProvenance {
+    pub environment: String,
+    /// The key as it is written in the config document.
+    pub key: String,
+    pub kind: VariableKind,
+    pub sources: Vec<ValueSource>,
+}
+
+/// Explains where a key's value in an environment comes from, or `None` if
+/// the environment doesn't configure it.  Keys are matched
+/// case-insensitively, like Github does.  The config document should not
+/// be decrypted yet, so that encrypted values can be told apart.
+pub fn explain_key(config: &ConfigDocument, environment: &str, key: &str) -> Option<KeyProvenance> {
+    let variables = config.environments.get(environment)?;
+    let (key, variable) = find_key(variables, key)?;
+    let is_shared = |key: &String| {
+        config
+            .shared_keys
+            .get(environment)
+            .is_some_and(|keys| keys.contains(key))
+    };
+
+    let mut sources = vec![match is_shared(key) {
+        true => ValueSource::Shared,
+        false => ValueSource::Environment,
+    }];
+    if is_encrypted(&variable.value) {
+        sources.push(ValueSource::Encrypted);
+    }
+    if variable.generate.is_some() {
+        sources.push(ValueSource::Generated);
+    }
+    for (scheme, argument) in find_placeholders(&variable.value) {
+        sources.push(ValueSource::Provider {
+            scheme: scheme.to_string(),
+            argument: argument.to_string(),
+        });
+    }
+
+    // Every key reached through references, each once, so that cycles end.
+    let mut seen = HashSet::from([key.to_uppercase()]);
+    let mut pending = vec![(key, variable.value.as_str())];
+    while let Some((referencing, value)) = pending.pop() {
+        for referenced in referenced_keys(value) {
+            if !seen.insert(referenced.clone()) {
+                continue;
+            }
+            let found = find_key(variables, &referenced);
+            sources.push(ValueSource::Reference {
+                key: found.map_or(referenced.clone(), |(key, _)| key.clone()),
+                via: (referencing != key).then(|| referencing.clone()),
+                shared: found.is_some_and(|(key, _)| is_shared(key)),
+            });
+            if let Some((key, variable)) = found {
+                pending.push((key, variable.value.as_str()));
+            }
+        }
+    }
+
+    sources.extend(
+        variable
+            .transform
+            .iter()
+            .copied()
+            .map(ValueSource::Transform),
+    );
+
+    Some(KeyProvenance {
+        environment: environment.to_string(),
+        key: key.clone(),
+        kind: variable.kind,
+        sources,
+    })
+}
+
+/// Finds a key of an environment, ignoring case.
+fn find_key<'a>(
+    variables: &'a EnvironmentConfig,
+    key: &str,
+) -> Option<(&'a String, &'a VariableConfig)> {
+    variables
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+}
+
+/// What a key holds on Github at each level a workflow job can read it
+/// from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteKey {
+    /// Every level defining the key as a variable with its value, most
+    /// specific first.
+    pub levels: Vec<(VariableLevel, String)>,
+    /// Whether the environment has a secret of the same name, whose value
+    /// can't be read back.
+    pub environment_secret: bool,
+}
+
+impl RemoteKey {
+    /// The level whose value a job sees, depending on whether the job uses
+    /// the environment.
+    pub fn effective(&self, uses_environment: bool) -> Option<VariableLevel> {
+        self.levels
+            .iter()
+            .map(|(level, _)| *level)
+            .find(|level| uses_environment || *level != VariableLevel::Environment)
+    }
+}
+
+impl Syncer {
+    /// The key's value in the environment as a sync would upload it, with
+    /// generated values, placeholders, references and transforms resolved,
+    /// or `None` if the environment doesn't configure it.  Chunked values
+    /// are whole.  The config document must already be decrypted.
+    pub fn resolved_value(
+        &self,
+        config: &ConfigDocument,
+        environment: &str,
+        key: &str,
+    ) -> Result<Option<String>> {
+        let mut config = config.clone();
+        config.environments.retain(|name, _| name == environment);
+        self.resolve_values(&mut config)?;
+
+        // Chunked values are split into parts by now, so put them back
+        // together.
+        let mut values = config
+            .environments
+            .remove(environment)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, variable)| (key, variable.value))
+            .collect::<Environment>();
+        join_chunks(&mut values);
+
+        Ok(values
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value))
+    }
+
+    /// Reads the key from the environment, the repository and the
+    /// organization.  The repository's and organization's variables can
+    /// fail to be read, e.g. without permission to read organization
+    /// variables, in which case they are left out with a warning.
+    pub async fn remote_key(&self, environment: &str, key: &str) -> Result<RemoteKey> {
+        let name = key.to_uppercase();
+        let mut remote = RemoteKey::default();
+
+        let environments = self.client().list_environments().await?;
+        if environments.iter().any(|name| name == environment) {
+            let client = self.client_for(environment);
+            let mut variables = client.list_environment_variables(environment).await?;
+            join_chunks(&mut variables);
+            if let Some(value) = variables.remove(&name) {
+                remote.levels.push((VariableLevel::Environment, value));
+            }
+            remote.environment_secret = client
+                .list_environment_secrets(environment)
+                .await?
+                .iter()
+                .any(|secret| secret.name.eq_ignore_ascii_case(&name));
+        }
+
+        let inherited = [
+            (
+                VariableLevel::Repository,
+                self.client().list_repository_variables().await,
+            ),
+            (
+                VariableLevel::Organization,
+                self.client().list_organization_variables().await,
+            ),
+        ];
+        for (level, variables) in inherited {
+            match variables {
+                Ok(mut variables) => {
+                    if let Some(value) = variables.remove(&name) {
+                        remote.levels.push((level, value));
+                    }
+                }
+                Err(e) => warn!("Couldn't read the {} variables: {}", level, e),
+            }
+        }
+
+        Ok(remote)
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::config::ConfigDocument;
+
+/// How a file's contents are turned into a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileEncoding {
+    /// The file is text, synced as it is.
+    #[default]
+    Utf8,
+    /// The file is synced as standard base64, for binary files such as
+    /// keystores.
+    Base64,
+}
+
+impl FromStr for FileEncoding {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "utf8" => Ok(Self::Utf8),
+            "base64" => Ok(Self::Base64),
+            _ => Err(eyre!(
+                "Unknown encoding '{}', expected one of utf8, base64",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FileEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "utf8",
+            Self::Base64 => "base64",
+        })
+    }
+}
+
+impl FileEncoding {
+    /// The value a file with these contents is synced as.
+    pub fn encode(&self, contents: Vec<u8>) -> Result<String> {
+        match self {
+            Self::Utf8 => String::from_utf8(contents)
+                .map_err(|_| eyre!("the file isn't valid UTF-8; use encoding = \"base64\"")),
+            Self::Base64 => Ok(STANDARD.encode(contents)),
+        }
+    }
+
+    /// The file contents a synced value was encoded from.
+    pub fn decode(&self, value: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Utf8 => Ok(value.as_bytes().to_vec()),
+            Self::Base64 => STANDARD
+                .decode(value.trim())
+                .map_err(|e| eyre!("the value isn't valid base64: {}", e)),
+        }
+    }
+}
+
+/// A variable whose value is read from a local file, e.g. a certificate or
+/// a keystore:
+///
+/// ```toml
+/// [production.secrets]
+/// CA_CERT = { file = "certs/ca.pem" }
+/// KEYSTORE = { file = "certs/keystore.p12", encoding = "base64" }
+/// ```
+///
+/// Paths are relative to the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSource {
+    pub path: PathBuf,
+    pub encoding: FileEncoding,
+}
+
+impl FileSource {
+    /// Where the file is, relative to the directory of the config file.
+    pub fn resolve(&self, config_dir: &Path) -> PathBuf {
+        config_dir.join(&self.path)
+    }
+
+    /// Reads and encodes the file.
+    pub fn read(&self, config_dir: &Path) -> Result<String> {
+        let path = self.resolve(config_dir);
+        let contents =
+            std::fs::read(&path).map_err(|e| eyre!("Error reading {}: {}", path.display(), e))?;
+        self.encoding
+            .encode(contents)
+            .map_err(|e| eyre!("Error reading {}: {}", path.display(), e))
+    }
+
+    /// Decodes a synced value and writes it back to the file.  Returns
+    /// whether the file changed.
+    pub fn write(&self, config_dir: &Path, value: &str) -> Result<bool> {
+        let path = self.resolve(config_dir);
+        let contents = self
+            .encoding
+            .decode(value)
+            .map_err(|e| eyre!("Error decoding {}: {}", path.display(), e))?;
+        if std::fs::read(&path).is_ok_and(|existing| existing == contents) {
+            return Ok(false);
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| eyre!("Error creating {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, contents)
+            .map_err(|e| eyre!("Error writing {}: {}", path.display(), e))?;
+        Ok(true)
+    }
+}
+
+/// The directory that the `file` values of the config file at `path` are
+/// relative to.
+pub fn config_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Fills in the values of every variable read from a file, relative to
+/// `config_dir`.
+pub fn read_files(config: &mut ConfigDocument, config_dir: &Path) -> Result<()> {
+    for (name, environment) in config.environments.iter_mut() {
+        for (key, variable) in environment.iter_mut() {
+            let Some(file) = &variable.file else {
+                continue;
+            };
+            debug!(
+                "Reading {} of environment '{}' from {}",
+                key,
+                name,
+                file.path.display()
+            );
+            variable.value = file
+                .read(config_dir)
+                .map_err(|e| eyre!("{} of environment '{}': {}", key, name, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Rejects a config document read from somewhere other than a local file
+/// if it has `file` values, since those would read files of whoever syncs
+/// it.
+pub fn reject_files(config: &ConfigDocument, source: &impl fmt::Display) -> Result<()> {
+    let mut keys = config
+        .environments
+        .iter()
+        .flat_map(|(name, environment)| {
+            environment
+                .iter()
+                .filter(|(_, variable)| variable.file.is_some())
+                .map(move |(key, _)| format!("{}.{}", name, key))
+        })
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    keys.sort();
+    Err(eyre!(
+        "{} isn't a local file, so its `file` values can't be read: {}",
+        source,
+        keys.join(", ")
+    ))
+}
@@ -0,0 +1,292 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use serde::Deserialize;
+
+use crate::{
+    discover::matches_glob,
+    expiry::{civil_from_days, parse_date},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A time during which syncs to some environments are refused without
+/// `--override-freeze`, for change freezes around releases.  A window is
+/// either a range of dates, UTC and inclusive, where either end may be left
+/// open, or a cron-like schedule matched against the current UTC minute:
+///
+/// ```toml
+/// [[freeze]]
+/// environments = ["production"]
+/// from = "2024-12-20"
+/// until = "2025-01-02"
+/// reason = "Holiday change freeze"
+///
+/// [[freeze]]
+/// environments = ["production", "staging-*"]
+/// schedule = "* 16-23 * * 5"
+/// reason = "No changes on Friday evenings"
+/// ```
+///
+/// Without `environments`, every environment is frozen.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawFreezeWindow")]
+pub struct FreezeWindow {
+    /// Names or globs of the frozen environments, or empty for every
+    /// environment.
+    pub environments: Vec<String>,
+    pub period: FreezePeriod,
+    pub reason: Option<String>,
+}
+
+/// When a [`FreezeWindow`] is in effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreezePeriod {
+    /// From the start of `from` to the end of `until`, as days since the
+    /// unix epoch.
+    Dates {
+        from: Option<i64>,
+        until: Option<i64>,
+    },
+    /// Every minute matching the schedule.
+    Schedule(Schedule),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFreezeWindow {
+    #[serde(default)]
+    environments: Vec<String>,
+    from: Option<String>,
+    until: Option<String>,
+    schedule: Option<String>,
+    reason: Option<String>,
+}
+
+impl TryFrom<RawFreezeWindow> for FreezeWindow {
+    type Error = String;
+
+    fn try_from(raw: RawFreezeWindow) -> Result<Self, Self::Error> {
+        let date = |date: Option<String>| {
+            date.map(|date| {
+                parse_date(date.trim()).ok_or_else(|| {
+                    format!(
+                        "Invalid freeze date '{}', expected a date such as 2024-12-31",
+                        date
+                    )
+                })
+            })
+            .transpose()
+        };
+
+        let period = match (raw.schedule, raw.from, raw.until) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                return Err(
+                    "A freeze window has either a schedule or from/until dates, not both"
+                        .to_string(),
+                )
+            }
+            (Some(schedule), None, None) => {
+                FreezePeriod::Schedule(schedule.parse().map_err(|e: Report| e.to_string())?)
+            }
+            (None, None, None) => {
+                return Err(
+                    "A freeze window needs a schedule, or from and/or until dates".to_string(),
+                )
+            }
+            (None, from, until) => {
+                let (from, until) = (date(from)?, date(until)?);
+                if let (Some(from), Some(until)) = (from, until) {
+                    if from > until {
+                        return Err(
+                            "A freeze window's from date must not be after its until date"
+                                .to_string(),
+                        );
+                    }
+                }
+                FreezePeriod::Dates { from, until }
+            }
+        };
+
+        Ok(Self {
+            environments: raw.environments,
+            period,
+            reason: raw.reason,
+        })
+    }
+}
+
+impl FreezeWindow {
+    /// Whether the window freezes the environment at `now`, in seconds
+    /// since the unix epoch.
+    pub fn freezes(&self, environment: &str, now: u64) -> bool {
+        let applies = self.environments.is_empty()
+            || self
+                .environments
+                .iter()
+                .any(|pattern| matches_glob(pattern, environment));
+
+        applies
+            && match &self.period {
+                FreezePeriod::Dates { from, until } => {
+                    let today = (now / SECONDS_PER_DAY) as i64;
+                    from.is_none_or(|from| from <= today)
+                        && until.is_none_or(|until| today <= until)
+                }
+                FreezePeriod::Schedule(schedule) => schedule.matches(now),
+            }
+    }
+}
+
+impl fmt::Display for FreezeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = |days: i64| {
+            let (year, month, day) = civil_from_days(days);
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        };
+
+        match &self.period {
+            FreezePeriod::Dates {
+                from: Some(from),
+                until: Some(until),
+            } => write!(f, "frozen from {} until {}", date(*from), date(*until))?,
+            FreezePeriod::Dates {
+                from: Some(from),
+                until: None,
+            } => write!(f, "frozen from {}", date(*from))?,
+            FreezePeriod::Dates { from: None, until } => {
+                write!(f, "frozen until {}", date(until.unwrap_or_default()))?
+            }
+            FreezePeriod::Schedule(schedule) => write!(f, "frozen on schedule '{}'", schedule)?,
+        }
+        match &self.reason {
+            Some(reason) => write!(f, ": {}", reason),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A cron-like schedule of minute, hour, day of month, month and day of
+/// week, where each field is `*`, a number, a range such as `1-5`, any of
+/// these with a step such as `*/15`, or a comma separated list of them.
+/// Days of the week count from Sunday as 0, and 7 is Sunday too.  As in
+/// cron, when both days of the month and of the week are restricted, a day
+/// matching either matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// Whether the day of month and day of week fields are `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// Whether the minute of `now`, in seconds since the unix epoch, UTC,
+    /// matches the schedule.
+    pub fn matches(&self, now: u64) -> bool {
+        let days = (now / SECONDS_PER_DAY) as i64;
+        let (_, month, day) = civil_from_days(days);
+        let seconds = now % SECONDS_PER_DAY;
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as usize;
+
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => self.days[day as usize] || self.weekdays[weekday],
+            _ => self.days[day as usize] && self.weekdays[weekday],
+        };
+        self.minutes[(seconds % 3_600 / 60) as usize]
+            && self.hours[(seconds / 3_600) as usize]
+            && self.months[month as usize]
+            && day_matches
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(eyre!(
+                "Invalid freeze schedule '{}', expected five fields: minute, hour, day of month, month and day of week",
+                s
+            ));
+        };
+
+        let mut weekday_field = parse_field(weekdays, 0..=7, "day of week")?;
+        // Both 0 and 7 are Sunday.
+        weekday_field[0] |= weekday_field[7];
+        weekday_field.truncate(7);
+
+        Ok(Self {
+            expression: s.trim().to_string(),
+            minutes: parse_field(minutes, 0..=59, "minute")?,
+            hours: parse_field(hours, 0..=23, "hour")?,
+            days: parse_field(days, 1..=31, "day of month")?,
+            months: parse_field(months, 1..=12, "month")?,
+            weekdays: weekday_field,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// Parses a schedule field into a table of which values it matches,
+/// indexed by value.
+fn parse_field(field: &str, range: RangeInclusive<usize>, name: &str) -> Result<Vec<bool>> {
+    let invalid = || {
+        eyre!(
+            "Invalid {} '{}' in freeze schedule, expected values from {} to {}",
+            name,
+            field,
+            range.start(),
+            range.end()
+        )
+    };
+    let value = |value: &str| {
+        value
+            .parse::<usize>()
+            .ok()
+            .filter(|value| range.contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut matches = vec![false; range.end() + 1];
+    for part in field.split(',') {
+        let (values, step) = match part.split_once('/') {
+            Some((values, step)) => (
+                values,
+                step.parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(invalid)?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match values.split_once('-') {
+            _ if values == "*" => (*range.start(), *range.end()),
+            Some((start, end)) => (value(start)?, value(end)?),
+            None if step > 1 => (value(values)?, *range.end()),
+            None => (value(values)?, value(values)?),
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step) {
+            matches[value] = true;
+        }
+    }
+
+    Ok(matches)
+}
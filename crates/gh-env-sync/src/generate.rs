@@ -0,0 +1,68 @@
+use rand::{rngs::OsRng, seq::SliceRandom};
+use serde::Deserialize;
+
+/// The characters a generated value is drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    /// Letters and digits.
+    #[default]
+    Alnum,
+    /// Letters only.
+    Alpha,
+    /// Digits only.
+    Numeric,
+    /// Lowercase hexadecimal digits.
+    Hex,
+    /// Every printable ASCII character except space.
+    Printable,
+}
+
+impl Charset {
+    fn alphabet(&self) -> Vec<u8> {
+        const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        const DIGITS: &[u8] = b"0123456789";
+
+        match self {
+            Charset::Alnum => [LETTERS, DIGITS].concat(),
+            Charset::Alpha => LETTERS.to_vec(),
+            Charset::Numeric => DIGITS.to_vec(),
+            Charset::Hex => b"0123456789abcdef".to_vec(),
+            Charset::Printable => (b'!'..=b'~').collect(),
+        }
+    }
+}
+
+/// A variable whose value is generated randomly on the first sync and reused
+/// afterwards, e.g. a webhook signing secret:
+///
+/// ```toml
+/// [production]
+/// WEBHOOK_SECRET = { generate = { length = 32, charset = "alnum" } }
+/// ```
+///
+/// Generated values are remembered in the state file, so syncing one
+/// requires `--state-file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct GenerateSpec {
+    #[serde(default = "default_length")]
+    pub length: usize,
+    #[serde(default)]
+    pub charset: Charset,
+}
+
+fn default_length() -> usize {
+    32
+}
+
+impl GenerateSpec {
+    /// Generates a new value from the operating system's random number
+    /// generator.
+    pub fn generate(&self) -> String {
+        let alphabet = self.charset.alphabet();
+
+        (0..self.length)
+            .map(|_| *alphabet.choose(&mut OsRng).expect("charsets are not empty") as char)
+            .collect()
+    }
+}
@@ -0,0 +1,155 @@
+//! Renders a dry run as a standalone HTML page, for sharing drift reviews
+//! with people who don't use a terminal.  The page has no external
+//! resources, so it can be attached to a ticket or opened offline.
+
+use gh_env_sync::{
+    audit::{format_timestamp, unix_timestamp},
+    sensitive::is_sensitive_key,
+    sync::VariableChange,
+    SyncReport,
+};
+
+use crate::{output::short_timestamp, prompt::mask_value};
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2328; }
+header { display: flex; gap: 1rem; align-items: center; flex-wrap: wrap; margin-bottom: 1rem; }
+input[type=search] { padding: 0.3rem 0.5rem; min-width: 20rem; }
+details { border: 1px solid #d0d7de; border-radius: 6px; margin-bottom: 0.75rem; }
+summary { cursor: pointer; padding: 0.5rem 0.75rem; font-weight: 600; }
+summary .counts { font-weight: normal; color: #656d76; margin-left: 0.5rem; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3rem 0.75rem; border-top: 1px solid #d0d7de; vertical-align: top; }
+td.value { font-family: ui-monospace, monospace; white-space: pre-wrap; word-break: break-all; }
+tr.create td.change { color: #1a7f37; }
+tr.update td.change { color: #9a6700; }
+tr.delete td.change { color: #cf222e; }
+.note { color: #656d76; }
+body.hide-values td.value { filter: blur(5px); }
+.empty { padding: 0.5rem 0.75rem; color: #656d76; }
+"#;
+
+const SCRIPT: &str = r#"
+const search = document.getElementById("search");
+search.addEventListener("input", () => {
+  const query = search.value.toLowerCase();
+  for (const section of document.querySelectorAll("details")) {
+    let matches = 0;
+    for (const row of section.querySelectorAll("tr[data-search]")) {
+      const match = row.dataset.search.includes(query);
+      row.hidden = !match;
+      matches += match;
+    }
+    section.hidden = query !== "" && matches === 0;
+    if (query !== "" && matches > 0) section.open = true;
+  }
+});
+document.getElementById("hide-values").addEventListener("change", (event) => {
+  document.body.classList.toggle("hide-values", event.target.checked);
+});
+"#;
+
+/// Renders the planned changes of a dry run of `repository`, with a section
+/// per environment.  Values of keys that look like credentials are masked
+/// unless `show_values` is set, so they are never written to the page; its
+/// toggle only blurs the values that are, e.g. while screen sharing.
+pub fn render_html(repository: &str, report: &SyncReport, show_values: bool) -> String {
+    let mut sections = String::new();
+    for environment in &report.environments {
+        let planned = report
+            .planned
+            .iter()
+            .filter(|value| value.environment == environment.name)
+            .collect::<Vec<_>>();
+        let counts = format!(
+            "{} created, {} updated, {} deleted, {} unchanged",
+            environment.count(VariableChange::Create),
+            environment.count(VariableChange::Update),
+            environment.count(VariableChange::Delete),
+            environment.count(VariableChange::Unchanged)
+        );
+
+        let mut rows = String::new();
+        for value in &planned {
+            let masked = !show_values && is_sensitive_key(&value.key);
+            let show = |text: &str| match masked {
+                true => mask_value(text),
+                false => text.to_string(),
+            };
+            let (class, change) = match value.change {
+                VariableChange::Create => ("create", "create"),
+                VariableChange::Delete => ("delete", "delete"),
+                _ => ("update", "update"),
+            };
+            let remote = value.remote_value.as_deref().map(show).unwrap_or_default();
+            let local = match value.change {
+                VariableChange::Delete => String::new(),
+                _ => show(&value.local_value),
+            };
+            let mut notes = Vec::new();
+            if let Some(updated_at) = &value.remote_updated_at {
+                notes.push(format!("remote updated {}", short_timestamp(updated_at)));
+            }
+            if value.drifted {
+                notes.push("changed outside gh-env-sync".to_string());
+            }
+            if let Some(rejection) = &value.rejection {
+                notes.push(format!("Github would reject this: it {}", rejection));
+            }
+
+            rows.push_str(&format!(
+                "<tr class=\"{}\" data-search=\"{}\"><td>{}</td><td class=\"change\">{}</td><td class=\"value\">{}</td><td class=\"value\">{}</td><td class=\"note\">{}</td></tr>\n",
+                class,
+                escape(&format!("{} {}", environment.name, value.key).to_lowercase()),
+                escape(&value.key),
+                change,
+                escape(&remote),
+                escape(&local),
+                escape(&notes.join(", "))
+            ));
+        }
+
+        let body = match rows.is_empty() {
+            true => "<p class=\"empty\">In sync.</p>".to_string(),
+            false => format!(
+                "<table>\n<tr><th>Key</th><th>Change</th><th>Remote value</th><th>Config value</th><th>Notes</th></tr>\n{}</table>",
+                rows
+            ),
+        };
+        sections.push_str(&format!(
+            "<details{}>\n<summary>{}<span class=\"counts\">{}</span></summary>\n{}\n</details>\n",
+            if planned.is_empty() { "" } else { " open" },
+            escape(&environment.name),
+            counts,
+            body
+        ));
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>gh-env-sync dry run of {repository}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>Dry run of {repository}</h1>\n<p>Generated by gh-env-sync {version} at {generated}: {created} to create, {updated} to update and {deleted} to delete, {drifted} drifted remotely.</p>\n<header>\n<input type=\"search\" id=\"search\" placeholder=\"Search keys and environments\">\n<label><input type=\"checkbox\" id=\"hide-values\"> Hide values</label>\n</header>\n{sections}<script>{SCRIPT}</script>\n</body>\n</html>\n",
+        repository = escape(repository),
+        version = env!("CARGO_PKG_VERSION"),
+        generated = format_timestamp(unix_timestamp()),
+        created = report.count(VariableChange::Create),
+        updated = report.count(VariableChange::Update),
+        deleted = report.count(VariableChange::Delete),
+        drifted = report.drift_count(),
+    )
+}
+
+/// Escapes text for HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,424 @@
+use std::{collections::BTreeMap, fmt};
+
+use color_eyre::{eyre::eyre, Result};
+use toml_edit::{Decor, Document, InlineTable, Item};
+
+use crate::{
+    config::{is_reserved_section, Environment},
+    files::{FileEncoding, FileSource},
+    naming::NamingPolicy,
+    quota::MAX_VALUE_BYTES,
+    sync::LAST_ACTOR_VARIABLE,
+};
+
+/// Starts the comment that marks a key whose remote value differs from the
+/// config.
+pub const CONFLICT_MARKER: &str = "# gh-env-sync import:";
+
+/// What importing did with a single remote variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportOutcome {
+    /// The config didn't have the key, so it was added.
+    Added,
+    /// The config has a different value, which was kept and marked with a
+    /// comment showing the remote value.
+    Conflict,
+    /// The config already has the same value, or an encrypted or generated
+    /// one, or a secret, that can't be compared.
+    Unchanged,
+    /// The config reads the value from a file, which the document doesn't
+    /// hold, so it was left alone (see [`ImportedVariable::file`]).
+    FromFile,
+}
+
+/// A remote variable considered by [`import_variables`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedVariable {
+    pub environment: String,
+    pub key: String,
+    pub outcome: ImportOutcome,
+    /// The file the config reads the value from, for
+    /// [`ImportOutcome::FromFile`].
+    pub file: Option<FileSource>,
+}
+
+/// Why a remote name wouldn't survive a round trip through the config
+/// unchanged, so that a sync right after importing it wouldn't be a no-op.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Normalization {
+    /// The environment is named like one of the config document's own
+    /// sections, such as `[naming]`, so it isn't imported.
+    ReservedEnvironment,
+    /// The config has the environment in different case, e.g.
+    /// `[Production]`, which syncs treat as another environment, so the
+    /// variables are imported into a table of the remote name next to it.
+    EnvironmentCase { local: String },
+    /// The key starts with `__`, which the config keeps for its own keys
+    /// such as `__expires`.
+    ReservedKey,
+    /// The key starts with the ignore prefix, so syncs treat it as a
+    /// local-only annotation.
+    IgnoredKey { prefix: String },
+    /// The key breaks the config's `[naming]` policy, so syncs refuse it.
+    NamingPolicy {
+        problem: String,
+        suggestion: Option<String>,
+    },
+}
+
+/// A remote environment or key whose name [`normalization_report`] found
+/// wouldn't round-trip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedName {
+    pub environment: String,
+    /// `None` for the environment itself.
+    pub key: Option<String>,
+    pub normalization: Normalization,
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReservedEnvironment => {
+                f.write_str("is a section of the config document, not imported")
+            }
+            Self::EnvironmentCase { local } => write!(
+                f,
+                "is [{}] in the config, which syncs as another environment, so it is imported as a new table",
+                local
+            ),
+            Self::ReservedKey => {
+                f.write_str("starts with __, which the config keeps for its own keys")
+            }
+            Self::IgnoredKey { prefix } => write!(
+                f,
+                "starts with the ignore prefix {}, so syncs skip it",
+                prefix
+            ),
+            Self::NamingPolicy {
+                problem,
+                suggestion: Some(suggestion),
+            } => write!(f, "{} (rename it to {})", problem, suggestion),
+            Self::NamingPolicy {
+                problem,
+                suggestion: None,
+            } => f.write_str(problem),
+        }
+    }
+}
+
+impl fmt::Display for NormalizedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(
+                f,
+                "{} in environment '{}' {}",
+                key, self.environment, self.normalization
+            ),
+            None => write!(
+                f,
+                "environment '{}' {}",
+                self.environment, self.normalization
+            ),
+        }
+    }
+}
+
+/// Finds the remote environments and keys whose names the config document
+/// in `contents` would alter or treat differently once imported, so that a
+/// `pull` followed by a sync is a no-op only when this is empty.  Keys the
+/// config already has are left out, as their names are the config's own.
+/// `ignore_prefix` is that of the syncs, see
+/// [`crate::SyncOptions::ignore_prefix`].
+pub fn normalization_report(
+    contents: &str,
+    remote: &BTreeMap<String, Environment>,
+    ignore_prefix: &str,
+) -> Result<Vec<NormalizedName>> {
+    let document = contents
+        .parse::<Document>()
+        .map_err(|e| eyre!("Error parsing config document: {}", e))?;
+    let naming = match toml::from_str::<toml::Table>(contents)
+        .map_err(|e| eyre!("Error parsing config document: {}", e))?
+        .remove("naming")
+    {
+        Some(section) => section
+            .try_into::<NamingPolicy>()
+            .map_err(|e| eyre!("Invalid [naming] section: {}", e))?,
+        None => NamingPolicy::default(),
+    };
+
+    let mut normalized = Vec::new();
+    for (environment_name, variables) in remote {
+        let name = |key: Option<&String>, normalization| NormalizedName {
+            environment: environment_name.clone(),
+            key: key.cloned(),
+            normalization,
+        };
+        if is_reserved_section(environment_name) {
+            normalized.push(name(None, Normalization::ReservedEnvironment));
+            continue;
+        }
+        if let Some(local) = differently_cased_environment(&document, environment_name) {
+            normalized.push(name(None, Normalization::EnvironmentCase { local }));
+        }
+        let table = document.get(environment_name).and_then(Item::as_table);
+
+        let mut keys = variables
+            .keys()
+            .filter(|key| key.as_str() != LAST_ACTOR_VARIABLE)
+            .filter(|key| table.is_none_or(|table| find_local_key(table, key).is_none()))
+            .collect::<Vec<_>>();
+        keys.sort();
+        for key in &keys {
+            if key.starts_with("__") {
+                normalized.push(name(Some(key), Normalization::ReservedKey));
+            } else if !ignore_prefix.is_empty() && key.starts_with(ignore_prefix) {
+                normalized.push(name(
+                    Some(key),
+                    Normalization::IgnoredKey {
+                        prefix: ignore_prefix.to_string(),
+                    },
+                ));
+            }
+        }
+        for violation in naming.check(environment_name, keys.iter().map(|key| key.as_str())) {
+            normalized.push(name(
+                Some(&violation.key),
+                Normalization::NamingPolicy {
+                    problem: violation.problem,
+                    suggestion: violation.suggestion,
+                },
+            ));
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Merges remote variables, keyed by environment name, into the config
+/// document in `contents`, returning the updated document.  Unlike
+/// overwriting the config, only keys missing from it are added, so that
+/// hand-managed variables can be adopted gradually:
+///
+/// ```toml
+/// [production]
+/// # gh-env-sync import: the remote value differs: "https://old.example.com"
+/// API_URL = "https://example.com"
+/// ```
+///
+/// Environments are matched to the config's tables by their exact name, as
+/// syncs match them, and those named like one of its own sections are
+/// skipped (see [`normalization_report`]).  Keys are looked up in an
+/// environment's `vars` and `secrets` subsections too, and new keys are added
+/// to its `vars` subsection if it has one, with `chunk = true` if their value
+/// is too large for a single variable.
+/// Formatting, comments and `\r\n` line endings in the document are
+/// preserved.  The [`LAST_ACTOR_VARIABLE`] marker is never imported.
+///
+/// Keys read from a `file` are returned with their [`FileSource`], so that
+/// the remote value can be decoded back into the file.
+pub fn import_variables(
+    contents: &str,
+    remote: &BTreeMap<String, Environment>,
+) -> Result<(String, Vec<ImportedVariable>)> {
+    let mut document = contents
+        .parse::<Document>()
+        .map_err(|e| eyre!("Error parsing config document: {}", e))?;
+    let mut imported = Vec::new();
+
+    for (environment_name, variables) in remote {
+        if is_reserved_section(environment_name) {
+            continue;
+        }
+        let table = document
+            .entry(environment_name)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| {
+                eyre!(
+                    "Environment '{}' must be a [{}] table in the config document to import into it",
+                    environment_name,
+                    environment_name
+                )
+            })?;
+
+        let mut keys = variables
+            .keys()
+            .filter(|key| key.as_str() != LAST_ACTOR_VARIABLE)
+            .collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let remote_value = &variables[key];
+            let mut file = None;
+            let outcome = match find_local_key(table, key) {
+                None => {
+                    let section = match table.get(VARS_SECTION).is_some_and(Item::is_table) {
+                        true => section_mut(table, Some(VARS_SECTION)),
+                        false => table,
+                    };
+                    section.insert(key, imported_value(remote_value));
+                    ImportOutcome::Added
+                }
+                Some((Some(SECRETS_SECTION), _)) => ImportOutcome::Unchanged,
+                Some((section_name, local_key)) => {
+                    let section = section_mut(table, section_name);
+                    file = file_source(&section[&local_key]).map_err(|e| {
+                        eyre!("{} of environment '{}': {}", local_key, environment_name, e)
+                    })?;
+                    match local_value(&section[&local_key]) {
+                        _ if file.is_some() => ImportOutcome::FromFile,
+                        Some(local_value) if local_value != remote_value => {
+                            if let Some(decor) = section.key_decor_mut(&local_key) {
+                                mark_conflict(decor, remote_value);
+                            }
+                            ImportOutcome::Conflict
+                        }
+                        _ => ImportOutcome::Unchanged,
+                    }
+                }
+            };
+
+            imported.push(ImportedVariable {
+                environment: environment_name.clone(),
+                key: key.clone(),
+                outcome,
+                file,
+            });
+        }
+    }
+
+    // The document is written back with `\n` line endings, so restore those
+    // of a document written on Windows.
+    let mut updated = document.to_string();
+    if contents.contains("\r\n") {
+        updated = updated.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+
+    Ok((updated, imported))
+}
+
+/// The config value for a remote variable.  Values too large for a single
+/// variable were chunked, and [`crate::chunk::join_chunks`] put them back
+/// together, so they are marked to be chunked again.
+fn imported_value(value: &str) -> Item {
+    if value.len() <= MAX_VALUE_BYTES {
+        return toml_edit::value(value);
+    }
+
+    let mut table = InlineTable::new();
+    table.insert("value", value.into());
+    table.insert("chunk", true.into());
+    toml_edit::value(table)
+}
+
+/// The name of a config table for a remote environment that only differs
+/// in case, unless the config has one of the same name.
+fn differently_cased_environment(document: &Document, environment_name: &str) -> Option<String> {
+    if document.contains_key(environment_name) {
+        return None;
+    }
+
+    document
+        .iter()
+        .map(|(name, _)| name)
+        .find(|name| {
+            !is_reserved_section(name) && name.to_lowercase() == environment_name.to_lowercase()
+        })
+        .map(str::to_string)
+}
+
+const VARS_SECTION: &str = "vars";
+const SECRETS_SECTION: &str = "secrets";
+
+/// Finds a remote variable's key in an environment table, directly or in its
+/// `vars` or `secrets` subsection, returning the subsection and the key as
+/// written in the config.  Github uppercases names, but the config may not.
+fn find_local_key(table: &toml_edit::Table, key: &str) -> Option<(Option<&'static str>, String)> {
+    let find = |section: &toml_edit::Table| {
+        section
+            .iter()
+            .map(|(local_key, _)| local_key.to_string())
+            .find(|local_key| local_key.to_uppercase() == key.to_uppercase())
+    };
+
+    [VARS_SECTION, SECRETS_SECTION]
+        .into_iter()
+        .find_map(|name| {
+            table
+                .get(name)
+                .and_then(Item::as_table)
+                .and_then(find)
+                .map(|local_key| (Some(name), local_key))
+        })
+        .or_else(|| {
+            find(table)
+                .filter(|local_key| local_key != VARS_SECTION && local_key != SECRETS_SECTION)
+                .map(|local_key| (None, local_key))
+        })
+}
+
+/// The environment table itself, or one of its subsections.
+fn section_mut<'a>(
+    table: &'a mut toml_edit::Table,
+    section: Option<&str>,
+) -> &'a mut toml_edit::Table {
+    match section {
+        Some(name) => table[name]
+            .as_table_mut()
+            .expect("subsection was found as a table"),
+        None => table,
+    }
+}
+
+/// The plaintext value of a config entry, either a plain string or the
+/// `value` of an inline table.  Entries declared with `kind = "secret"` have
+/// no comparable value.
+fn local_value(item: &Item) -> Option<&str> {
+    if item.get("kind").and_then(Item::as_str) == Some("secret") {
+        return None;
+    }
+
+    item.as_str()
+        .or_else(|| item.get("value").and_then(Item::as_str))
+}
+
+/// The file a config entry reads its value from, unless it is a secret.
+fn file_source(item: &Item) -> Result<Option<FileSource>> {
+    if item.get("kind").and_then(Item::as_str) == Some("secret") {
+        return Ok(None);
+    }
+    let Some(path) = item.get("file").and_then(Item::as_str) else {
+        return Ok(None);
+    };
+
+    let encoding = match item.get("encoding").and_then(Item::as_str) {
+        Some(encoding) => encoding.parse::<FileEncoding>()?,
+        None => FileEncoding::default(),
+    };
+    Ok(Some(FileSource {
+        path: path.into(),
+        encoding,
+    }))
+}
+
+/// Adds a comment above the key with the remote value, unless a previous
+/// import already did.
+fn mark_conflict(decor: &mut Decor, remote_value: &str) {
+    let prefix = decor
+        .prefix()
+        .and_then(|prefix| prefix.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if prefix.contains(CONFLICT_MARKER) {
+        return;
+    }
+
+    // JSON string escapes are valid in TOML basic strings, and keep
+    // multi-line values on a single comment line.
+    let quoted = serde_json::to_string(remote_value).unwrap_or_default();
+    decor.set_prefix(format!(
+        "{}{} the remote value differs: {}\n",
+        prefix, CONFLICT_MARKER, quoted
+    ));
+}
@@ -0,0 +1,63 @@
+//! The engine behind the `gh-env-sync` CLI: parsing configuration documents,
+//! diffing them against a repository's environments, and applying the
+//! differences through [`gh_client::GithubEnvClient`].
+
+pub mod audit;
+pub mod branches;
+pub mod checkpoint;
+pub mod chunk;
+pub mod config;
+pub mod conflict;
+pub mod defaults;
+pub mod discover;
+pub mod duplicates;
+pub mod encryption;
+pub mod env_prefix;
+pub mod events;
+pub mod exclusions;
+pub mod expiry;
+pub mod explain;
+pub mod files;
+pub mod freeze;
+pub mod generate;
+pub mod import;
+pub mod lint;
+pub mod lock;
+pub mod mirror;
+pub mod naming;
+pub mod plan;
+pub mod preview;
+pub mod protection;
+pub mod providers;
+pub mod quota;
+pub mod references;
+pub mod rename;
+pub mod rollback;
+pub mod schema;
+pub mod sensitive;
+pub mod shadowing;
+pub mod source;
+pub mod state;
+pub mod stream;
+pub mod sync;
+pub mod terraform;
+pub mod transform;
+pub mod undelete;
+pub mod validation;
+pub mod verify;
+
+pub use config::{
+    load_config, parse_config, AuthConfig, ConfigDocument, Environment, OrganizationSecretConfig,
+    TokenSource, VariableConfig, VariableKind,
+};
+pub use conflict::{Conflict, ConflictResolver, ConflictStrategy, Resolution};
+pub use events::{SyncEvent, SyncEventHandler};
+pub use lock::LockMode;
+pub use providers::{ProviderRegistry, ValueProvider};
+pub use schema::config_schema;
+pub use source::ConfigSource;
+pub use state::{AppliedSecret, State};
+pub use sync::{
+    ApplyStrategy, CancelledError, MissingEnvironments, PartialApplyError, PlannedValue,
+    SyncOptions, SyncReport, SyncTimings, Syncer,
+};
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    config::{ConfigDocument, VariableKind},
+    encryption::is_encrypted,
+    preview::PREVIEW_TEMPLATE,
+    providers::{find_placeholders, ProviderRegistry},
+    references::referenced_keys,
+};
+
+/// Values shorter than this are never reported as likely secrets, since
+/// short identifiers such as region names are common.
+const MIN_SECRET_LENGTH: usize = 20;
+
+/// The Shannon entropy, in bits per character, from which a value looks
+/// random enough to be a credential.  Hexadecimal values can't reach it, so
+/// they are held to [`MIN_HEX_SECRET_ENTROPY`] instead.
+const MIN_SECRET_ENTROPY: f64 = 4.0;
+
+/// The entropy from which a hexadecimal value of at least
+/// [`MIN_HEX_SECRET_LENGTH`] characters looks like a credential.
+const MIN_HEX_SECRET_ENTROPY: f64 = 3.0;
+
+/// Hexadecimal values shorter than this are commit hashes, colors and the
+/// like more often than credentials.
+const MIN_HEX_SECRET_LENGTH: usize = 32;
+
+/// Something `lint` found wrong with, or suspicious about, a config
+/// document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintProblem {
+    /// The environment is configured, but doesn't exist on Github yet.
+    MissingRemotely,
+    /// The environment exists on Github, but isn't configured.
+    MissingLocally,
+    /// The environment configures no keys to sync.
+    EmptyEnvironment,
+    /// A `${ref:KEY}` names a key the environment doesn't define.
+    UnresolvedReference { key: String },
+    /// A `${scheme:argument}` placeholder has no provider, or its provider
+    /// failed to resolve it.
+    UnresolvedPlaceholder { placeholder: String, reason: String },
+    /// A plaintext variable's value looks random enough to be a credential
+    /// committed by mistake.
+    LikelySecret,
+}
+
+impl fmt::Display for LintProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRemotely => f.write_str("configured, but doesn't exist on Github"),
+            Self::MissingLocally => f.write_str("exists on Github, but isn't configured"),
+            Self::EmptyEnvironment => f.write_str("has no keys to sync"),
+            Self::UnresolvedReference { key } => {
+                write!(f, "references {}, which isn't defined there", key)
+            }
+            Self::UnresolvedPlaceholder {
+                placeholder,
+                reason,
+            } => write!(f, "{} doesn't resolve: {}", placeholder, reason),
+            Self::LikelySecret => f.write_str(
+                "plaintext variable that looks like a credential; make it a secret or encrypt it",
+            ),
+        }
+    }
+}
+
+/// A problem with an environment, or with one of its keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub environment: String,
+    pub key: Option<String>,
+    pub problem: LintProblem,
+}
+
+/// Checks a config document for unused, orphaned and broken entries:
+///
+/// - with `remote_environments`, environments configured but missing on
+///   Github, and the other way around, leaving out preview environments;
+/// - environments without any keys to sync, besides annotations starting
+///   with `ignore_prefix`;
+/// - `${ref:KEY}` references to undefined keys, and value provider
+///   placeholders that `providers` can't resolve;
+/// - plaintext variables whose values look like credentials.
+///
+/// Findings are sorted by environment and key.  The config document should
+/// not be decrypted, as encrypted values are fine in plaintext variables.
+pub fn lint_config(
+    config: &ConfigDocument,
+    remote_environments: Option<&[String]>,
+    providers: &ProviderRegistry,
+    ignore_prefix: &str,
+) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let finding = |environment: &str, key: Option<&str>, problem| LintFinding {
+        environment: environment.to_string(),
+        key: key.map(str::to_string),
+        problem,
+    };
+
+    if let Some(remote) = remote_environments {
+        for name in config.environments.keys() {
+            if name != PREVIEW_TEMPLATE && !remote.contains(name) {
+                findings.push(finding(name, None, LintProblem::MissingRemotely));
+            }
+        }
+        let preview_prefix = format!("{}-pr-", PREVIEW_TEMPLATE);
+        for name in remote {
+            if !name.starts_with(&preview_prefix) && !config.environments.contains_key(name) {
+                findings.push(finding(name, None, LintProblem::MissingLocally));
+            }
+        }
+    }
+
+    // Each placeholder is resolved once, as providers may be slow or run
+    // commands.
+    let mut resolved = HashMap::<String, Result<(), String>>::new();
+    for (name, environment) in &config.environments {
+        let synced = environment
+            .iter()
+            .filter(|(key, _)| ignore_prefix.is_empty() || !key.starts_with(ignore_prefix))
+            .collect::<Vec<_>>();
+        if synced.is_empty() {
+            findings.push(finding(name, None, LintProblem::EmptyEnvironment));
+        }
+
+        for (key, variable) in synced {
+            for referenced in referenced_keys(&variable.value) {
+                if !environment
+                    .keys()
+                    .any(|defined| defined.eq_ignore_ascii_case(&referenced))
+                {
+                    findings.push(finding(
+                        name,
+                        Some(key.as_str()),
+                        LintProblem::UnresolvedReference { key: referenced },
+                    ));
+                }
+            }
+
+            let placeholders = find_placeholders(&variable.value);
+            for (scheme, argument) in &placeholders {
+                let placeholder = format!("${{{}:{}}}", scheme, argument);
+                let result = resolved
+                    .entry(placeholder.clone())
+                    .or_insert_with(|| match providers.provider(scheme) {
+                        Some(provider) => provider
+                            .resolve(argument)
+                            .map(|_| ())
+                            .map_err(|e| e.to_string()),
+                        None => Err(format!("--value-provider {} isn't enabled", scheme)),
+                    });
+                if let Err(reason) = result {
+                    findings.push(finding(
+                        name,
+                        Some(key.as_str()),
+                        LintProblem::UnresolvedPlaceholder {
+                            placeholder,
+                            reason: reason.clone(),
+                        },
+                    ));
+                }
+            }
+
+            let plaintext = variable.kind == VariableKind::Variable
+                && variable.generate.is_none()
+                && !is_encrypted(&variable.value)
+                && placeholders.is_empty()
+                && referenced_keys(&variable.value).is_empty();
+            if plaintext && looks_like_secret(&variable.value) {
+                findings.push(finding(name, Some(key.as_str()), LintProblem::LikelySecret));
+            }
+        }
+    }
+
+    findings.sort_by(|a, b| (&a.environment, &a.key).cmp(&(&b.environment, &b.key)));
+    findings
+}
+
+/// Whether a value looks like a random credential, such as an API key,
+/// rather than configuration: long, without spaces, mixing letters and
+/// digits, and with high Shannon entropy.  URLs are left out, as their
+/// paths can look random.
+pub fn looks_like_secret(value: &str) -> bool {
+    let value = value.trim();
+    let length = value.chars().count();
+    if length < MIN_SECRET_LENGTH
+        || value.contains(char::is_whitespace)
+        || value.contains("://")
+        || !value.contains(|c: char| c.is_ascii_alphabetic())
+        || !value.contains(|c: char| c.is_ascii_digit())
+    {
+        return false;
+    }
+
+    let entropy = shannon_entropy(value);
+    match value.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => length >= MIN_HEX_SECRET_LENGTH && entropy >= MIN_HEX_SECRET_ENTROPY,
+        false => entropy >= MIN_SECRET_ENTROPY,
+    }
+}
+
+/// The average information per character of a value, in bits.
+fn shannon_entropy(value: &str) -> f64 {
+    let mut counts = HashMap::<char, usize>::new();
+    for c in value.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+
+    let length = value.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,187 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::GithubEnvClient;
+use tracing::{debug, warn};
+
+use crate::audit::unix_timestamp;
+
+/// Name of the repository variable used as a marker by [`LockMode::Remote`].
+pub const REMOTE_LOCK_VARIABLE: &str = "GH_ENV_SYNC_LOCK";
+
+/// How a sync guards against other syncs of the same repository running at
+/// the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockMode {
+    /// No locking.
+    None,
+    /// A lock file in the system temp directory, which guards against other
+    /// syncs on the same machine.
+    #[default]
+    Local,
+    /// A marker repository variable, which guards against syncs from any
+    /// machine (e.g. parallel CI jobs).
+    Remote,
+}
+
+impl FromStr for LockMode {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "local" => Ok(Self::Local),
+            "remote" => Ok(Self::Remote),
+            _ => Err(eyre!(
+                "Unknown lock mode '{}', expected one of none, local, remote",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LockMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Local => "local",
+            Self::Remote => "remote",
+        })
+    }
+}
+
+/// A lock file which is removed when dropped.
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        debug!("Removing lock file {}", self.path.display());
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Error removing lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// A held lock on a repository.  Local locks are released when dropped, but
+/// remote locks must be released with [`SyncLock::release`].
+#[derive(Debug)]
+pub enum SyncLock {
+    Unlocked,
+    Local(LockFile),
+    Remote,
+}
+
+impl SyncLock {
+    /// Acquires a lock on the client's repository, failing immediately if
+    /// another sync already holds it.
+    pub async fn acquire(client: &GithubEnvClient, mode: LockMode) -> Result<Self> {
+        let repository = format!("{}/{}", client.repository_owner(), client.repository_name());
+        let holder = lock_holder();
+
+        match mode {
+            LockMode::None => Ok(Self::Unlocked),
+            LockMode::Local => {
+                let path = std::env::temp_dir().join(format!(
+                    "gh-env-sync-{}-{}.lock",
+                    client.repository_owner(),
+                    client.repository_name()
+                ));
+                debug!("Acquiring lock file {}", path.display());
+
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::AlreadyExists => {
+                            let existing = fs::read_to_string(&path).unwrap_or_default();
+                            eyre!(
+                                "Another sync of {} appears to be in progress (lock file {} is held by {}).  Remove the lock file if it is stale.",
+                                repository,
+                                path.display(),
+                                existing.trim()
+                            )
+                        }
+                        _ => eyre!("Error creating lock file {}: {}", path.display(), e),
+                    })?;
+                // From here on the lock file is ours, and is removed again
+                // if writing who holds it fails.  It is closed first, since
+                // open files can't be removed on Windows.
+                let written = file.write_all(holder.as_bytes());
+                drop(file);
+                let lock = LockFile { path };
+                written
+                    .map_err(|e| eyre!("Error writing lock file {}: {}", lock.path.display(), e))?;
+
+                Ok(Self::Local(lock))
+            }
+            LockMode::Remote => {
+                debug!("Acquiring remote lock variable {}", REMOTE_LOCK_VARIABLE);
+
+                if let Some(existing) = client.get_repository_variable(REMOTE_LOCK_VARIABLE).await?
+                {
+                    return Err(eyre!(
+                        "Another sync of {} appears to be in progress (repository variable {} is held by {}).  Delete the variable if it is stale.",
+                        repository,
+                        REMOTE_LOCK_VARIABLE,
+                        existing
+                    ));
+                }
+
+                // Creating a variable that already exists fails, so a sync
+                // which loses a race here errors rather than proceeding.
+                client
+                    .create_repository_variable(REMOTE_LOCK_VARIABLE, &holder)
+                    .await?;
+
+                Ok(Self::Remote)
+            }
+        }
+    }
+
+    /// Releases the lock.
+    pub async fn release(self, client: &GithubEnvClient) -> Result<()> {
+        match self {
+            Self::Unlocked | Self::Local(_) => Ok(()),
+            Self::Remote => {
+                debug!("Releasing remote lock variable {}", REMOTE_LOCK_VARIABLE);
+                client
+                    .delete_repository_variable(REMOTE_LOCK_VARIABLE)
+                    .await
+            }
+        }
+    }
+
+    /// Releases the lock once the work done while holding it finished with
+    /// `result`.  If both the work and releasing the lock fail, the work's
+    /// error is returned and the release error is logged, so that the cause
+    /// of a failed sync isn't hidden behind a stuck lock.
+    pub async fn release_after<T>(self, client: &GithubEnvClient, result: Result<T>) -> Result<T> {
+        match (self.release(client).await, result) {
+            (Ok(()), result) => result,
+            (Err(e), Ok(_)) => Err(e),
+            (Err(e), Err(original)) => {
+                warn!("Error releasing the lock: {}", e);
+                Err(original)
+            }
+        }
+    }
+}
+
+/// Describes this process, for display to whoever finds the lock held.
+fn lock_holder() -> String {
+    format!(
+        "pid {} since unix time {}",
+        std::process::id(),
+        unix_timestamp()
+    )
+}
@@ -1,105 +1,2056 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
-use cli::Args;
-use color_eyre::Result;
-use gh_client::GithubEnvClient;
-use tracing::{debug, info};
+use cancel::{cancellation, is_cancelled};
+use clap::{error::ErrorKind, CommandFactory};
+use cli::{
+    ApplyArgs, Args, CleanupArgs, Command, ConnectionArgs, DuplicatesArgs, ExplainArgs, ExportArgs,
+    ExportFormat, HistoryArgs, ImportArgs, LintArgs, ListArgs, RateLimitArgs, RenameKeyArgs,
+    ReportFormat, RollbackArgs, SyncArgs, UndeleteArgs, ValidateArgs, VerifyArgs,
+};
+use color_eyre::{eyre::eyre, Result};
+use crossterm::style::Color;
+use diff::render_planned;
+use gh_env_sync::{
+    audit::{format_timestamp, unix_timestamp, AuditLog, AuditOperation},
+    branches::{current_branch, environment_for_branch},
+    checkpoint::Checkpoint,
+    chunk::{chunked_keys, join_chunks, split_values},
+    config::environment_values,
+    config_schema,
+    defaults::OutputFormat,
+    discover::{changed_since, discover_config_files, locate_config, DEFAULT_CONFIG_FILE},
+    duplicates::find_duplicates,
+    encryption::is_encrypted,
+    env_prefix::config_from_env,
+    explain::{explain_key, ValueSource},
+    files::config_dir,
+    import::{import_variables, normalization_report, ImportOutcome},
+    lint::lint_config,
+    mirror::config_from_remote,
+    plan::{AppliedOperation, ApplyOutcome, Plan, DEFAULT_PLAN_FILE},
+    preview::{expand_preview, preview_environment},
+    references::resolve_references,
+    rename::RenameOutcome,
+    sensitive::is_sensitive_key,
+    shadowing::VariableLevel,
+    sync::{estimate_requests, VariableChange},
+    terraform::{export_terraform, variables_from_state},
+    transform::apply_transforms,
+    undelete::UndeleteOutcome,
+    validation::config_rejections,
+    verify::VerifyOutcome,
+    CancelledError, ConfigDocument, Environment, PartialApplyError, SyncEvent, SyncOptions,
+    SyncReport, SyncTimings, Syncer, VariableKind,
+};
+use html::render_html;
+use notify::Notification;
+use outcome::{Outcome, Summary};
+use output::{Cell, Table, Terminal};
+use prompt::{mask_value, PromptResolver};
+use tracing::{debug, error, info, warn};
 
+mod cancel;
 mod cli;
-mod gh_client;
+mod diff;
+mod html;
+mod notify;
+mod outcome;
+mod output;
+mod profiles;
+mod prompt;
+mod serve;
+mod tui;
+mod update;
+mod webhook;
 
-type Environment = HashMap<String, String>;
+/// Reports sync progress as log lines.
+fn log_event(event: &SyncEvent) {
+    match event {
+        SyncEvent::EnvironmentStarted {
+            environment,
+            identity: Some(identity),
+            ..
+        } => info!("Syncing environment '{}' as {}", environment, identity),
+        SyncEvent::EnvironmentCreated { environment } => {
+            info!("Created environment '{}'", environment)
+        }
+        SyncEvent::VariableCreated { environment, key } => {
+            info!("Created {} in environment '{}'", key, environment)
+        }
+        SyncEvent::VariableUpdated { environment, key } => {
+            info!("Updated {} in environment '{}'", key, environment)
+        }
+        SyncEvent::VariableDeleted { environment, key } => {
+            info!("Deleted {} from environment '{}'", key, environment)
+        }
+        SyncEvent::SecretUpdated { environment, key } => {
+            info!("Updated secret {} in environment '{}'", key, environment)
+        }
+        SyncEvent::OrganizationSecretUpdated { name } => {
+            info!("Updated organization secret {}", name)
+        }
+        SyncEvent::DriftDetected { environment, key } => {
+            warn!(
+                "{} in environment '{}' drifted from the last applied value",
+                key, environment
+            )
+        }
+        SyncEvent::Error {
+            environment,
+            key,
+            message,
+        } => error!(
+            "Sync failed (environment: {:?}, key: {:?}): {}",
+            environment, key, message
+        ),
+        _ => debug!("{:?}", event),
+    }
+}
+
+/// Reports sync progress as JSON lines on stdout.
+fn print_event_json(event: &SyncEvent) {
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{}", line),
+        Err(e) => error!("Error serializing sync event {:?}: {}", event, e),
+    }
+}
+
+/// Syncs the environments defined in the configuration document to Github
+/// based on the options given as CLI arguments, and reports the result.
+async fn sync_environments(options: &SyncArgs, terminal: Terminal) -> Result<Outcome> {
+    let summary = sync_and_notify(options, terminal).await?;
+    if options.prints_summary() {
+        println!("{}", summary);
+    }
+    Ok(summary.outcome)
+}
+
+/// Runs a sync given on the command line, exiting with its outcome.
+async fn run_sync(mut options: SyncArgs, terminal: Terminal) -> Result<ExitCode> {
+    options.apply_config_defaults()?;
+    // Offline or read-only, every write would fail, so only plan the sync.
+    if options.connection.refuses_writes() {
+        options.dry_run = Some(true);
+    }
+    sync_environments(&options, terminal)
+        .await
+        .map(|outcome| outcome.exit_code())
+}
+
+/// Syncs the config document, and posts the summary or error to
+/// --notify-url.
+async fn sync_and_notify(options: &SyncArgs, terminal: Terminal) -> Result<Summary> {
+    let result = sync_config(options, terminal).await;
+    if let Some(url) = &options.notify_url {
+        let notification = match &result {
+            Ok(summary) => Notification::summary(*summary),
+            Err(e) => Notification::error(e),
+        };
+        // A failed notification shouldn't fail a sync that succeeded.
+        if let Err(e) = notification.send(url).await {
+            warn!("{}", e);
+        }
+    }
+
+    result
+}
+
+/// Syncs the config document to the repositories selected by the CLI
+/// arguments.
+async fn sync_config(options: &SyncArgs, terminal: Terminal) -> Result<Summary> {
+    if let Some(pattern) = &options.config_glob {
+        return sync_config_files(options, pattern, terminal).await;
+    }
+
+    let started = Instant::now();
+    let config = match (&options.from_remote, options.from_env_prefix.is_empty()) {
+        (Some(repository), _) => {
+            info!("Reading environment variables from {}", repository);
+            let client = options
+                .connection
+                .with_repository(repository)
+                .client()
+                .await?;
+            config_from_remote(&client, &options.mirror_environment, &options.mirror_key).await?
+        }
+        (None, true) => {
+            info!("Reading environment variables from {}", options.config_path);
+            load_config(options, &options.config_path).await?
+        }
+        (None, false) => {
+            info!(
+                "Reading environment variables from the process environment ({})",
+                options
+                    .from_env_prefix
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            // Variables that aren't valid UTF-8 can't be synced.
+            let variables = std::env::vars_os().filter_map(|(name, value)| {
+                Some((name.into_string().ok()?, value.into_string().ok()?))
+            });
+            config_from_env(&options.from_env_prefix, variables)?
+        }
+    };
+    let config_load = started.elapsed();
+
+    let repositories = match (&options.repos_file, &options.org) {
+        (Some(path), _) => Some(read_repos_file(path)?),
+        (None, Some(organization)) => Some(discover_repositories(options, organization).await?),
+        (None, None) => None,
+    };
+    match repositories {
+        Some(repositories) => {
+            let targets = repositories
+                .into_iter()
+                .map(|repository| BatchTarget {
+                    repository,
+                    config: &config,
+                    config_path: None,
+                })
+                .collect::<Vec<_>>();
+            sync_repositories(options, &targets, config_load, terminal).await
+        }
+        None if options.connection.repository.is_none() => Err(eyre!(
+            "Either the <REPOSITORY> argument, --repos-file, --org or --config-glob is required"
+        )),
+        None => {
+            let result =
+                sync_repository(options, &options.connection, &config, config_load, terminal).await;
+            let (outcome, report, failed) = match result {
+                Ok(report) => (
+                    Outcome::of(&report, options.dry_run.unwrap_or_default()),
+                    report,
+                    0,
+                ),
+                Err(e) if e.is::<CancelledError>() => {
+                    warn!("{}", e);
+                    if !options.dry_run.unwrap_or_default() {
+                        info!("Run again with --resume to continue the sync");
+                    }
+                    return Ok(Summary::new(Outcome::Cancelled, 1, 0, &[]));
+                }
+                Err(e) => {
+                    let partial = e.downcast::<PartialApplyError>()?;
+                    error!("{}", partial);
+                    (Outcome::PartialFailure, partial.report, 1)
+                }
+            };
 
-/// Represents a TOML environment configuration document, where each key
-/// corresponds to an environment name, and contains a dictionary of key/value
-/// environment variable pairs.  Of course only string values are supported.
-type ConfigDocument = HashMap<String, Environment>;
+            Ok(Summary::new(outcome, 1, failed, &[&report]))
+        }
+    }
+}
 
-async fn sync_one_environment(
-    client: &GithubEnvClient,
-    environment_name: &str,
-    environment: &Environment,
-) -> Result<()> {
+/// Loads a config document, expanding the preview environment of --pr,
+/// picking the environment of --environment-from-branch, and keeping only
+/// the entries of --owner.
+async fn load_config(options: &SyncArgs, location: &str) -> Result<ConfigDocument> {
+    let mut config = options.connection.load_config(location).await?;
+    if options.environment_from_branch {
+        let branch = current_branch()?;
+        let environment = environment_for_branch(&config.branches, &branch)
+            .ok_or_else(|| {
+                eyre!(
+                    "Branch '{}' matches no rule in the [branches] section of {}",
+                    branch,
+                    location
+                )
+            })?
+            .to_string();
+        if !config.environments.contains_key(&environment) {
+            return Err(eyre!(
+                "Branch '{}' deploys to environment '{}', which is not defined in {}",
+                branch,
+                environment,
+                location
+            ));
+        }
+        config.environments.retain(|name, _| name == &environment);
+        config.org_secrets.clear();
+        info!(
+            "Syncing environment '{}' for branch '{}'",
+            environment, branch
+        );
+    }
+    if let Some(pull_request) = options.pr {
+        let pull_request = pull_request.number()?;
+        config = expand_preview(&config, pull_request)?;
+        info!(
+            "Syncing preview environment {} from {}",
+            preview_environment(pull_request),
+            location
+        );
+    }
+    if let Some(owner) = &options.owner {
+        config.retain_owner(owner);
+        info!(
+            "Syncing the {} environments of {} with variables owned by {}",
+            config.environments.len(),
+            location,
+            owner
+        );
+    }
+
+    Ok(config)
+}
+
+/// Syncs every config file matching --config-glob to the repository named
+/// inside it.  Every file is read first, so that a broken file stops the
+/// run before anything is written.
+async fn sync_config_files(
+    options: &SyncArgs,
+    pattern: &str,
+    terminal: Terminal,
+) -> Result<Summary> {
+    let started = Instant::now();
+    let mut paths = discover_config_files(pattern)?;
+    if paths.is_empty() {
+        return Err(eyre!("No config files match {}", pattern));
+    }
+    info!("Found {} config files matching {}", paths.len(), pattern);
+
+    if let Some(reference) = &options.changed_since {
+        let changed = changed_since(reference)?;
+        paths.retain(|path| std::fs::canonicalize(path).is_ok_and(|path| changed.contains(&path)));
+        info!(
+            "{} of them changed since {}: {:?}",
+            paths.len(),
+            reference,
+            paths
+        );
+        if paths.is_empty() {
+            return Ok(Summary::new(Outcome::InSync, 0, 0, &[]));
+        }
+    }
+
+    let mut configs = Vec::new();
+    for path in paths {
+        let path = path.display().to_string();
+        let config = load_config(options, &path).await?;
+        let repository = config.repository.clone().ok_or_else(|| {
+            eyre!(
+                "{} has no `repository` key naming the owner/repo to sync it to",
+                path
+            )
+        })?;
+        configs.push((path, repository, config));
+    }
+    let config_load = started.elapsed();
+
+    let targets = configs
+        .iter()
+        .map(|(path, repository, config)| BatchTarget {
+            repository: repository.clone(),
+            config,
+            config_path: Some(path.clone()),
+        })
+        .collect::<Vec<_>>();
+    sync_repositories(options, &targets, config_load, terminal).await
+}
+
+/// Reads the repositories listed in a --repos-file, one `owner/repo` per
+/// line, skipping blank lines and `#` comments.
+fn read_repos_file(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Error reading repositories file {}: {}", path.display(), e))?;
+
+    let repositories = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if let Some(repository) = repositories.iter().find(|line| !line.contains('/')) {
+        return Err(eyre!(
+            "Expected every line of {} to be an owner/repo_name pair, but found '{}'",
+            path.display(),
+            repository
+        ));
+    }
+
+    Ok(repositories)
+}
+
+/// Lists the repositories of --org that match --repo-filter and --topic,
+/// skipping archived ones since they can't be written to.
+async fn discover_repositories(options: &SyncArgs, organization: &str) -> Result<Vec<String>> {
+    let repositories = options
+        .connection
+        .organization_repositories(organization)
+        .await?;
+    let total = repositories.len();
+
+    let mut selected = repositories
+        .into_iter()
+        .filter(|repository| {
+            if repository.archived {
+                debug!("Skipping archived repository {}", repository.full_name);
+            }
+            !repository.archived && options.selects(repository)
+        })
+        .map(|repository| repository.full_name)
+        .collect::<Vec<_>>();
+    selected.sort();
+
+    if selected.is_empty() {
+        return Err(eyre!(
+            "None of the {} repositories of {} match --repo-filter and --topic",
+            total,
+            organization
+        ));
+    }
     info!(
-        "Syncing {} variables to environment '{}'",
-        environment.len(),
-        environment_name
+        "Found {} of {} repositories of {} to sync: {:?}",
+        selected.len(),
+        total,
+        organization,
+        selected
     );
 
-    client.upsert_environment(environment_name).await?;
+    Ok(selected)
+}
+
+/// A repository synced as part of a batch, and the config document synced
+/// to it.
+struct BatchTarget<'a> {
+    repository: String,
+    config: &'a ConfigDocument,
+    /// Where the config document was read from, when each repository has
+    /// its own.
+    config_path: Option<String>,
+}
+
+impl BatchTarget<'_> {
+    /// Names the target in output.
+    fn label(&self) -> String {
+        match &self.config_path {
+            Some(path) => format!("{} ({})", self.repository, path),
+            None => self.repository.clone(),
+        }
+    }
+}
+
+/// Syncs each target in turn, carrying on past failures and reporting the
+/// result for each one at the end.
+async fn sync_repositories(
+    options: &SyncArgs,
+    targets: &[BatchTarget<'_>],
+    config_load: Duration,
+    terminal: Terminal,
+) -> Result<Summary> {
+    let mut results = Vec::new();
+    for (i, target) in targets.iter().enumerate() {
+        if is_cancelled() {
+            warn!(
+                "Sync cancelled; skipping the remaining {} repositories",
+                targets.len() - i
+            );
+            break;
+        }
+        let label = target.label();
+        info!("Syncing repository {}", label);
+        if options.prints_tables() {
+            println!("{}", label);
+        }
+
+        let connection = options.connection.with_repository(&target.repository);
+        let result =
+            sync_repository(options, &connection, target.config, config_load, terminal).await;
+        if let Err(e) = &result {
+            error!("Error syncing repository {}: {:#}", label, e);
+        }
+        results.push((label, result));
+    }
+
+    if options.prints_tables() {
+        print!("{}", repositories_table(&results).render(terminal));
+    }
+
+    // Environments applied before a partial failure still count.
+    let reports = results
+        .iter()
+        .filter_map(|(_, result)| match result {
+            Ok(report) => Some(report),
+            Err(e) => e
+                .downcast_ref::<PartialApplyError>()
+                .map(|partial| &partial.report),
+        })
+        .collect::<Vec<_>>();
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if is_cancelled() {
+        if !options.dry_run.unwrap_or_default() {
+            info!("Run again with --resume to continue the sync");
+        }
+        return Ok(Summary::new(
+            Outcome::Cancelled,
+            results.len(),
+            failed,
+            &reports,
+        ));
+    }
+    if failed == results.len() && reports.is_empty() {
+        return Err(eyre!("Failed to sync all {} repositories", failed));
+    }
 
-    for (key, value) in environment {
+    let outcome = match failed {
+        0 => reports
+            .iter()
+            .map(|report| Outcome::of(report, options.dry_run.unwrap_or_default()))
+            .max()
+            .unwrap_or(Outcome::InSync),
+        _ => {
+            error!(
+                "Failed to sync {} of {} repositories",
+                failed,
+                results.len()
+            );
+            Outcome::PartialFailure
+        }
+    };
+    if failed == 0 {
+        info!("All {} repositories are synced successfully", results.len());
+    }
+
+    Ok(Summary::new(outcome, results.len(), failed, &reports))
+}
+
+/// A row per repository synced from --repos-file, --org or --config-glob,
+/// with its totals or error.
+fn repositories_table(results: &[(String, Result<SyncReport>)]) -> Table {
+    let mut table = Table::new(&["REPOSITORY", "RESULT", "CREATED", "UPDATED", "UNCHANGED"]);
+    for (repository, result) in results {
+        table.push(match result {
+            Ok(report) => vec![
+                Cell::new(repository),
+                Cell::colored("ok", Color::Green),
+                Cell::count(report.count(VariableChange::Create), Color::Green),
+                Cell::count(report.count(VariableChange::Update), Color::Yellow),
+                Cell::new(report.count(VariableChange::Unchanged)),
+            ],
+            Err(e) => vec![
+                Cell::new(repository),
+                Cell::colored(e, Color::Red),
+                Cell::new("-"),
+                Cell::new("-"),
+                Cell::new("-"),
+            ],
+        });
+    }
+
+    table
+}
+
+/// Syncs the config document to the repository of `connection`.
+async fn sync_repository(
+    options: &SyncArgs,
+    connection: &ConnectionArgs,
+    config: &ConfigDocument,
+    config_load: Duration,
+    terminal: Terminal,
+) -> Result<SyncReport> {
+    let started = Instant::now();
+    let syncer = connection
+        .syncer(
+            &config.auth,
+            SyncOptions {
+                graphql: options.graphql,
+                lock: options.write.lock,
+                state_file: options.state_file.clone(),
+                on_conflict: options.on_conflict.unwrap_or_default(),
+                audit_log: options.write.audit_log.clone(),
+                ignore_prefix: Some(options.ignore_prefix.clone()),
+                apply_strategy: options.apply_strategy,
+                fail_fast: options.fail_fast.unwrap_or_default(),
+                dry_run: options.dry_run.unwrap_or_default(),
+                strict_secrets: options.strict_secrets.unwrap_or_default(),
+                warn_on_quota: options.warn_on_quota.unwrap_or_default(),
+                missing_environments: options.missing_environments(),
+                prune: options.prune.unwrap_or_default(),
+                max_changes: options.max_changes,
+                checkpoint_file: Some(options.checkpoint_file.clone()),
+                check_shadowing: options.check_shadowing.unwrap_or_default(),
+                override_freeze: options.override_freeze,
+                pipeline_depth: options.pipeline_depth,
+                exclusions: connection.exclusions_for(&options.config_path)?,
+            },
+            options.environment.as_deref(),
+        )
+        .await?;
+    let repository_init = started.elapsed();
+
+    let mut syncer = syncer.with_cancellation(cancellation());
+    if options.resume {
+        let repository = connection.repository()?;
+        match Checkpoint::load(&options.checkpoint_file)?
+            .repositories
+            .remove(repository)
+        {
+            Some(checkpoint) => {
+                info!(
+                    "Resuming run {} of {}, which had made {} writes",
+                    checkpoint.run_id,
+                    repository,
+                    checkpoint.completed.len()
+                );
+                syncer = syncer.resuming(checkpoint);
+            }
+            None => info!(
+                "No interrupted sync of {} in {}, syncing from the start",
+                repository,
+                options.checkpoint_file.display()
+            ),
+        }
+    }
+    let syncer = syncer
+        .with_event_handler(match options.output() {
+            OutputFormat::Json if !options.summary_only => print_event_json,
+            _ => log_event,
+        })
+        .with_conflict_resolver(PromptResolver {
+            show_values: options.show_values,
+        });
+    let report = syncer.sync(config, options.environment.as_deref()).await?;
+
+    if options.dry_run.unwrap_or_default() {
+        info!(
+            "Dry run: {} variables would be created, {} updated and {} deleted ({} unchanged, {} drifted remotely)",
+            report.count(VariableChange::Create),
+            report.count(VariableChange::Update),
+            report.count(VariableChange::Delete),
+            report.count(VariableChange::Unchanged),
+            report.drift_count()
+        );
+        if !report.rejections.is_empty() {
+            warn!(
+                "Github would reject {} of the planned writes, so applying this plan would fail part way through",
+                report.rejections.len()
+            );
+        }
+        if options.prints_tables() {
+            print!(
+                "{}",
+                render_planned(&report.planned, options.show_values, terminal)
+            );
+            print!("{}", summary_table(&report).render(terminal));
+            if !report.shadowed.is_empty() {
+                print!(
+                    "{}",
+                    shadowed_table(&report, options.show_values).render(terminal)
+                );
+            }
+        }
+        if let (Some(format), Some(path)) = (options.report, &options.report_file) {
+            let contents = match format {
+                ReportFormat::Html => {
+                    render_html(connection.repository()?, &report, options.show_values)
+                }
+            };
+            std::fs::write(path, contents)
+                .map_err(|e| eyre!("Error writing report {}: {}", path.display(), e))?;
+            info!("Wrote the dry run report to {}", path.display());
+        }
+        if let Some(path) = &options.plan_file {
+            let plan = Plan::new(
+                connection.repository()?,
+                connection.config_hash(&options.config_path).await?,
+                &report.planned,
+            );
+            plan.save(path)?;
+            info!(
+                "Wrote the plan of {} variable writes to {}",
+                plan.operations.len(),
+                path.display()
+            );
+            if let Some(kind) = options.sign {
+                let signature = kind.sign(path)?;
+                info!("Signed the plan with {} in {}", kind, signature.display());
+            }
+        }
+        if options.timings {
+            log_timings(config_load, repository_init, &report.timings, &syncer);
+        }
+        return Ok(report);
+    }
+
+    info!(
+        "All specified environments are synced successfully ({} created, {} updated, {} deleted, {} unchanged, {} drifted remotely, {} remote values kept)",
+        report.count(VariableChange::Create),
+        report.count(VariableChange::Update),
+        report.count(VariableChange::Delete),
+        report.count(VariableChange::Unchanged),
+        report.drift_count(),
+        report.count(VariableChange::Kept)
+    );
+    let secret_count = report
+        .environments
+        .iter()
+        .map(|environment| environment.secrets.len())
+        .sum::<usize>();
+    if secret_count > 0 {
+        info!("Updated {} environment secrets", secret_count);
+    }
+    if !report.organization_secrets.is_empty() {
+        info!(
+            "Updated {} organization secrets",
+            report.organization_secrets.len()
+        );
+    }
+    if options.prints_tables() {
+        print!("{}", summary_table(&report).render(terminal));
+    }
+    if options.write.audit_log.is_some() {
+        info!(
+            "Recorded writes in the audit log as run {}",
+            syncer.run_id()
+        );
+    }
+    if report.has_changes() {
+        trigger_workflows(&syncer, &report, options).await?;
+    }
+    if options.timings {
+        log_timings(config_load, repository_init, &report.timings, &syncer);
+    }
+    Ok(report)
+}
+
+/// A row per synced environment, counting each kind of change.
+fn summary_table(report: &SyncReport) -> Table {
+    let mut table = Table::new(&[
+        "ENVIRONMENT",
+        "CREATED",
+        "UPDATED",
+        "DELETED",
+        "UNCHANGED",
+        "KEPT",
+        "DRIFTED",
+    ]);
+    for environment in &report.environments {
+        let drifted = environment
+            .variables
+            .iter()
+            .filter(|variable| variable.drifted)
+            .count();
+        let name = match environment.created {
+            true => Cell::colored(format!("{} (new)", environment.name), Color::Green),
+            false => Cell::new(&environment.name),
+        };
+
+        table.push(vec![
+            name,
+            Cell::count(environment.count(VariableChange::Create), Color::Green),
+            Cell::count(environment.count(VariableChange::Update), Color::Yellow),
+            Cell::count(environment.count(VariableChange::Delete), Color::Red),
+            Cell::new(environment.count(VariableChange::Unchanged)),
+            Cell::count(environment.count(VariableChange::Kept), Color::Magenta),
+            Cell::count(drifted, Color::Red),
+        ]);
+    }
+
+    table
+}
+
+/// A row per configured variable that shadows a repository or organization
+/// variable, found with --check-shadowing, with its value at each level.
+fn shadowed_table(report: &SyncReport, show_values: bool) -> Table {
+    let mut table = Table::new(&[
+        "ENVIRONMENT",
+        "KEY",
+        "ENVIRONMENT VALUE",
+        "REPOSITORY VALUE",
+        "ORGANIZATION VALUE",
+    ]);
+    for shadowed in &report.shadowed {
+        let masked = !show_values && is_sensitive_key(&shadowed.key);
+        let value = |level: VariableLevel| {
+            let value = shadowed
+                .levels
+                .iter()
+                .find(|(l, _)| *l == level)
+                .map(|(_, value)| value.as_str());
+            match (value, masked) {
+                (None, _) => Cell::new("-"),
+                (Some(value), true) => Cell::new(mask_value(value)),
+                (Some(value), false) => Cell::new(value),
+            }
+        };
+
+        table.push(vec![
+            Cell::new(&shadowed.environment),
+            match shadowed.differs() {
+                true => Cell::colored(&shadowed.key, Color::Yellow),
+                false => Cell::new(&shadowed.key),
+            },
+            value(VariableLevel::Environment),
+            value(VariableLevel::Repository),
+            value(VariableLevel::Organization),
+        ]);
+    }
+
+    table
+}
+
+/// Kicks off the workflows requested with --trigger-workflow and
+/// --dispatch-event, so that deployments pick up the synced changes.
+async fn trigger_workflows(syncer: &Syncer, report: &SyncReport, options: &SyncArgs) -> Result<()> {
+    let client = syncer.client();
+
+    if let Some(workflow) = &options.trigger_workflow {
+        let reference = options
+            .trigger_ref
+            .as_deref()
+            .unwrap_or(client.default_branch());
         client
-            .upsert_environment_variable(environment_name, key, value)
+            .dispatch_workflow(workflow, reference, &HashMap::new())
             .await?;
+        info!("Triggered workflow {} on {}", workflow, reference);
+    }
+
+    if let Some(event_type) = &options.dispatch_event {
+        let changed = report
+            .environments
+            .iter()
+            .filter(|environment| environment.has_changes())
+            .map(|environment| environment.name.as_str())
+            .collect::<Vec<_>>();
+        let payload = serde_json::json!({
+            "run_id": syncer.run_id(),
+            "environments": changed,
+        });
+        client
+            .create_repository_dispatch(event_type, &payload)
+            .await?;
+        info!("Sent repository_dispatch event {}", event_type);
     }
 
     Ok(())
 }
 
-/// Syncs the environments defined in the given configuration document to Github
-/// based on the options given as CLI arguments.
-async fn sync_environments(config: &ConfigDocument, options: &Args) -> Result<()> {
-    let (repository_owner, repository_name) = options.repository.split_once('/').expect(
-        "Expected <REPOSITORY> argument to be a owner/repo_name pair, e.g. rust-lang/rust-lang",
+/// Reports where a sync spent its time, and how many requests it made.
+fn log_timings(
+    config_load: Duration,
+    repository_init: Duration,
+    timings: &SyncTimings,
+    syncer: &Syncer,
+) {
+    info!("Config load:     {:>8.2?}", config_load);
+    info!("Repository init: {:>8.2?}", repository_init);
+    info!("Listing:         {:>8.2?}", timings.listing);
+    info!("Diff:            {:>8.2?}", timings.diff);
+    info!("Apply:           {:>8.2?}", timings.apply);
+
+    let stats = syncer.client().request_stats();
+    match (stats.rate_limit, stats.rate_limit_consumed) {
+        (Some(rate_limit), Some(consumed)) => info!(
+            "{} API requests, consuming {} of the rate limit ({} of {} remaining)",
+            stats.requests, consumed, rate_limit.remaining, rate_limit.limit
+        ),
+        _ => info!("{} API requests", stats.requests),
+    }
+}
+
+/// Makes the writes of a reviewed plan file.
+async fn apply(options: &ApplyArgs, terminal: Terminal) -> Result<()> {
+    if !options.is_partial() && (options.residual.is_some() || options.sign.is_some()) {
+        return Err(eyre!(
+            "--residual and --sign write a residual plan, so they require --only-env or --only-keys"
+        ));
+    }
+
+    let plan = Plan::load(&options.plan)?;
+    if let Some(kind) = options.verify {
+        kind.verify(&options.plan, options.sigstore_signer().as_ref())?;
+        let config_hash = options.connection.config_hash(&options.config_path).await?;
+        if config_hash != plan.config_hash {
+            return Err(eyre!(
+                "{} wasn't made from {} as it is now; make a new plan",
+                options.plan.display(),
+                options.config_path
+            ));
+        }
+        info!(
+            "Verified the {} signature of {} and its config",
+            kind,
+            options.plan.display()
+        );
+    }
+
+    let (plan, residual) = plan.partition(&options.only_env, &options.only_keys);
+    if plan.operations.is_empty() && options.is_partial() {
+        return Err(eyre!(
+            "None of the operations of {} match --only-env and --only-keys",
+            options.plan.display()
+        ));
+    }
+
+    let connection = match &options.connection.repository {
+        Some(_) => options.connection.clone(),
+        None => options.connection.with_repository(&plan.repository),
+    };
+    let syncer = connection.attribute(Syncer::new(
+        connection.client().await?,
+        SyncOptions {
+            lock: options.write.lock,
+            audit_log: options.write.audit_log.clone(),
+            state_file: options.state_file.clone(),
+            exclusions: connection.exclusions_for(&options.config_path)?,
+            ..SyncOptions::default()
+        },
+    ));
+    let applied = syncer.apply_plan(&plan, options.dry_run).await?;
+
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "RESULT"]);
+    for AppliedOperation { operation, outcome } in &applied {
+        let action = match (&operation.previous, &operation.value) {
+            (None, _) => "create",
+            (Some(_), Some(_)) => "update",
+            (Some(_), None) => "delete",
+        };
+        let result = match (outcome, options.dry_run) {
+            (ApplyOutcome::Applied, false) => Cell::colored(action, Color::Green),
+            (ApplyOutcome::Applied, true) => {
+                Cell::colored(format!("would {}", action), Color::Yellow)
+            }
+            (ApplyOutcome::AlreadyApplied, _) => Cell::new("already applied"),
+        };
+        table.push(vec![
+            Cell::new(&operation.environment),
+            Cell::new(&operation.key),
+            result,
+        ]);
+    }
+    if !table.is_empty() {
+        print!("{}", table.render(terminal));
+    }
+
+    let count = applied
+        .iter()
+        .filter(|applied| applied.outcome == ApplyOutcome::Applied)
+        .count();
+    match options.dry_run {
+        true => info!(
+            "Would make {} of the plan's {} writes",
+            count,
+            plan.operations.len()
+        ),
+        false => info!(
+            "Made {} of the plan's {} writes",
+            count,
+            plan.operations.len()
+        ),
+    }
+
+    if residual.operations.is_empty() {
+        return Ok(());
+    }
+    let path = options.residual_path();
+    match options.dry_run {
+        true => info!(
+            "Would leave {} writes to {}",
+            residual.operations.len(),
+            path.display()
+        ),
+        false => {
+            residual.save(&path)?;
+            if let Some(kind) = options.sign {
+                kind.sign(&path)?;
+            }
+            info!(
+                "Left {} writes to {}, to be applied later",
+                residual.operations.len(),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Undoes the writes recorded in the audit log after the requested run.
+async fn rollback(options: &RollbackArgs, terminal: Terminal) -> Result<()> {
+    let syncer = options.connection.attribute(Syncer::new(
+        options.connection.client().await?,
+        SyncOptions {
+            lock: options.write.lock,
+            audit_log: options.write.audit_log.clone(),
+            exclusions: options.connection.exclusions_for(DEFAULT_CONFIG_FILE)?,
+            ..SyncOptions::default()
+        },
+    ));
+
+    let operations = syncer.rollback(&options.to, options.dry_run).await?;
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "ACTION"]);
+    for operation in &operations {
+        let action = match &operation.target {
+            Some(_) => Cell::colored("restore", Color::Yellow),
+            None => Cell::colored("delete", Color::Red),
+        };
+        table.push(vec![
+            Cell::new(&operation.environment),
+            Cell::new(&operation.key),
+            action,
+        ]);
+    }
+    if !table.is_empty() {
+        if options.dry_run {
+            println!("Would roll back:");
+        }
+        print!("{}", table.render(terminal));
+    }
+
+    info!(
+        "Rolled back {} variables to their values after run {}",
+        operations.len(),
+        options.to
     );
+    Ok(())
+}
 
-    let username = match &options.username {
-        Some(username) => username.clone(),
-        None => repository_owner.to_string(),
+/// Recreates a deleted variable from its value in the audit log.
+async fn undelete(options: &UndeleteArgs, terminal: Terminal) -> Result<()> {
+    let syncer = options.connection.attribute(Syncer::new(
+        options.connection.client().await?,
+        SyncOptions {
+            lock: options.write.lock,
+            audit_log: options.write.audit_log.clone(),
+            state_file: options.state_file.clone(),
+            exclusions: options.connection.exclusions_for(DEFAULT_CONFIG_FILE)?,
+            ..SyncOptions::default()
+        },
+    ));
+
+    let environments = options
+        .environment
+        .clone()
+        .map(|environment| vec![environment]);
+    let undeleted = syncer
+        .undelete(&options.key, environments.as_deref(), options.dry_run)
+        .await?;
+
+    let mut table = Table::new(&["ENVIRONMENT", "DELETED", "RUN", "RESULT"]);
+    for key in &undeleted {
+        let result = match (key.outcome, options.dry_run) {
+            (UndeleteOutcome::Restored, false) => Cell::colored("restored", Color::Green),
+            (UndeleteOutcome::Restored, true) => Cell::colored("would restore", Color::Yellow),
+            (UndeleteOutcome::AlreadyExists, _) => Cell::new("already exists"),
+        };
+        table.push(vec![
+            Cell::new(&key.environment),
+            Cell::new(format_timestamp(key.deleted_at)),
+            Cell::new(&key.run_id),
+            result,
+        ]);
+    }
+    print!("{}", table.render(terminal));
+
+    let count = undeleted
+        .iter()
+        .filter(|key| key.outcome == UndeleteOutcome::Restored)
+        .count();
+    match options.dry_run {
+        true => info!(
+            "Would restore {} in {} environments",
+            options.key.to_uppercase(),
+            count
+        ),
+        false => info!(
+            "Restored {} in {} environments",
+            options.key.to_uppercase(),
+            count
+        ),
+    }
+    Ok(())
+}
+
+/// Prints the changes recorded in the audit log, oldest first.
+fn history(options: &HistoryArgs, terminal: Terminal) -> Result<()> {
+    let since = options
+        .since
+        .map(|since| unix_timestamp().saturating_sub(since.as_secs()))
+        .unwrap_or_default();
+    let records = AuditLog::new(&options.audit_log)
+        .read()?
+        .into_iter()
+        .filter(|record| record.timestamp >= since)
+        .filter(|record| {
+            options
+                .repository
+                .as_ref()
+                .is_none_or(|repository| record.repository.eq_ignore_ascii_case(repository))
+        })
+        .filter(|record| {
+            options
+                .environment
+                .as_ref()
+                .is_none_or(|environment| &record.environment == environment)
+        })
+        .collect::<Vec<_>>();
+
+    let value = |key: &str, value: &Option<String>| match value {
+        Some(value) if !options.show_values && is_sensitive_key(key) => mask_value(value),
+        Some(value) => format!("{:?}", value),
+        None => String::new(),
+    };
+    let mut table = Table::new(&[
+        "TIME",
+        "REPOSITORY",
+        "ENVIRONMENT",
+        "KEY",
+        "CHANGE",
+        "PREVIOUS",
+        "VALUE",
+        "RUN",
+        "ACTOR",
+    ]);
+    for record in &records {
+        let change = match record.operation {
+            AuditOperation::CreateEnvironment => Cell::colored("created environment", Color::Green),
+            AuditOperation::Create => Cell::colored("created", Color::Green),
+            AuditOperation::Update => Cell::colored("updated", Color::Yellow),
+            AuditOperation::Delete => Cell::colored("deleted", Color::Red),
+            AuditOperation::DeleteEnvironment => Cell::colored("deleted environment", Color::Red),
+            AuditOperation::PutSecret => Cell::colored("secret written", Color::Yellow),
+        };
+        table.push(vec![
+            Cell::new(format_timestamp(record.timestamp)),
+            Cell::new(&record.repository),
+            Cell::new(&record.environment),
+            Cell::new(&record.key),
+            change,
+            Cell::new(value(&record.key, &record.previous_value)),
+            Cell::new(value(&record.key, &record.value)),
+            Cell::new(&record.run_id),
+            Cell::new(record.actor.as_deref().unwrap_or_default()),
+        ]);
+    }
+    if !table.is_empty() {
+        print!("{}", table.render(terminal));
+    }
+
+    info!("{} changes in the audit log", records.len());
+    Ok(())
+}
+
+/// Renames a key in one or every environment of the repository.
+async fn rename_key(options: &RenameKeyArgs, terminal: Terminal) -> Result<()> {
+    let syncer = options.connection.attribute(Syncer::new(
+        options.connection.client().await?,
+        SyncOptions {
+            lock: options.write.lock,
+            audit_log: options.write.audit_log.clone(),
+            state_file: options.state_file.clone(),
+            exclusions: options.connection.exclusions_for(DEFAULT_CONFIG_FILE)?,
+            ..SyncOptions::default()
+        },
+    ));
+
+    let environments = match &options.environment {
+        Some(environment) => vec![environment.clone()],
+        None => syncer.client().list_environments().await?,
+    };
+    let renamed = syncer
+        .rename_key(
+            &environments,
+            &options.old_key,
+            &options.new_key,
+            options.dry_run,
+        )
+        .await?;
+
+    let mut table = Table::new(&["ENVIRONMENT", "RESULT"]);
+    for key in &renamed {
+        let result = match (key.outcome, options.dry_run) {
+            (RenameOutcome::Renamed, false) => Cell::colored("renamed", Color::Green),
+            (RenameOutcome::Renamed, true) => Cell::colored("would rename", Color::Yellow),
+            (RenameOutcome::AlreadyRenamed, _) => Cell::new("already renamed"),
+            (RenameOutcome::Missing, _) => Cell::colored("missing", Color::Red),
+        };
+        table.push(vec![Cell::new(&key.environment), result]);
+    }
+    print!("{}", table.render(terminal));
+
+    let count = renamed
+        .iter()
+        .filter(|key| key.outcome == RenameOutcome::Renamed)
+        .count();
+    match options.dry_run {
+        true => info!(
+            "Would rename {} to {} in {} environments",
+            options.old_key, options.new_key, count
+        ),
+        false => info!(
+            "Renamed {} to {} in {} environments",
+            options.old_key, options.new_key, count
+        ),
+    }
+    Ok(())
+}
+
+/// Deletes the preview environment of a pull request, or the expired
+/// entries of the config document.
+async fn cleanup(options: &CleanupArgs, terminal: Terminal) -> Result<()> {
+    let sync_options = SyncOptions {
+        lock: options.write.lock,
+        audit_log: options.write.audit_log.clone(),
+        state_file: options.state_file.clone(),
+        exclusions: options.connection.exclusions_for(&options.config_path)?,
+        ..SyncOptions::default()
+    };
+    let result = |deleted: bool| match (deleted, options.dry_run) {
+        (false, _) => Cell::new("missing"),
+        (true, false) => Cell::colored("deleted", Color::Green),
+        (true, true) => Cell::colored("would delete", Color::Yellow),
+    };
+
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "RESULT"]);
+    if let Some(pull_request) = options.pr {
+        let syncer = options.connection.attribute(Syncer::new(
+            options.connection.client().await?,
+            sync_options,
+        ));
+        let environment = preview_environment(pull_request.number()?);
+        let deleted = syncer
+            .delete_environments(std::slice::from_ref(&environment), options.dry_run)
+            .await?;
+        let excluded = syncer
+            .options()
+            .exclusions
+            .excludes_environment(&environment);
+        table.push(vec![
+            Cell::new(&environment),
+            Cell::new(""),
+            match excluded {
+                true => Cell::new("excluded"),
+                false => result(!deleted.is_empty()),
+            },
+        ]);
+    } else {
+        let config = options.connection.load_config(&options.config_path).await?;
+        let syncer = options
+            .connection
+            .syncer(&config.auth, sync_options, None)
+            .await?;
+        let expired = syncer.expired(&config)?;
+        let deleted = syncer.delete_expired(&config, options.dry_run).await?;
+        for environment in &expired.environments {
+            table.push(vec![
+                Cell::new(environment),
+                Cell::new(""),
+                result(deleted.environments.contains(environment)),
+            ]);
+        }
+        for (environment, key) in &expired.variables {
+            table.push(vec![
+                Cell::new(environment),
+                Cell::new(key),
+                result(
+                    deleted
+                        .variables
+                        .contains(&(environment.clone(), key.clone())),
+                ),
+            ]);
+        }
+        info!(
+            "{} {} expired environments and {} expired variables",
+            if options.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            },
+            deleted.environments.len(),
+            deleted.variables.len()
+        );
+    }
+    print!("{}", table.render(terminal));
+
+    Ok(())
+}
+
+/// Compares the hashes of the repository's variables against the config
+/// document, and fails if any differ.
+async fn verify(options: &VerifyArgs, terminal: Terminal) -> Result<()> {
+    let config = options.connection.load_config(&options.config_path).await?;
+    let syncer = options
+        .connection
+        .syncer(
+            &config.auth,
+            SyncOptions {
+                state_file: options.state_file.clone(),
+                ignore_prefix: Some(options.ignore_prefix.clone()),
+                // Keeps newly generated values out of the state file.
+                dry_run: true,
+                ..SyncOptions::default()
+            },
+            options.environment.as_deref(),
+        )
+        .await?;
+
+    let verified = syncer
+        .verify(&config, options.environment.as_deref())
+        .await?;
+
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "RESULT"]);
+    for key in &verified {
+        let result = match key.outcome {
+            VerifyOutcome::Match => Cell::colored("match", Color::Green),
+            VerifyOutcome::Mismatch => Cell::colored("mismatch", Color::Red),
+            VerifyOutcome::Missing => Cell::colored("missing", Color::Red),
+        };
+        table.push(vec![
+            Cell::new(&key.environment),
+            Cell::new(&key.key),
+            result,
+        ]);
+    }
+    if !table.is_empty() {
+        print!("{}", table.render(terminal));
+    }
+
+    let failed = verified
+        .iter()
+        .filter(|key| key.outcome != VerifyOutcome::Match)
+        .count();
+    if failed > 0 {
+        return Err(eyre!(
+            "{} of {} variables don't match the config",
+            failed,
+            verified.len()
+        ));
+    }
+
+    info!("All {} variables match the config", verified.len());
+    Ok(())
+}
+
+/// Prints where a key's value in an environment comes from in the config
+/// document, and what the key holds at each level on Github, to tell which
+/// value a workflow sees.
+async fn explain(options: &ExplainArgs, terminal: Terminal) -> Result<()> {
+    let environment = options.environment.as_str();
+    let mut config = options.connection.read_config(&options.config_path).await?;
+    // Encrypted values can only be told apart before they are decrypted.
+    let provenance = explain_key(&config, environment, &options.key);
+    options.connection.decrypt_config(&mut config)?;
+    let syncer = options
+        .connection
+        .syncer(
+            &config.auth,
+            SyncOptions {
+                state_file: options.state_file.clone(),
+                ignore_prefix: Some(options.ignore_prefix.clone()),
+                // Keeps newly generated values out of the state file.
+                dry_run: true,
+                ..SyncOptions::default()
+            },
+            Some(environment),
+        )
+        .await?;
+
+    let key = provenance
+        .as_ref()
+        .map_or(options.key.as_str(), |provenance| provenance.key.as_str());
+    let mut local = None;
+    match &provenance {
+        Some(provenance) => {
+            println!(
+                "{} in environment '{}' is a {}:",
+                provenance.key, environment, provenance.kind
+            );
+            for source in &provenance.sources {
+                println!("  {}", source);
+                if let ValueSource::Provider { scheme, .. } = source {
+                    if !options
+                        .connection
+                        .value_providers
+                        .iter()
+                        .any(|provider| provider.to_string() == *scheme)
+                    {
+                        println!(
+                            "    (synced as written, since --value-provider {} isn't given)",
+                            scheme
+                        );
+                    }
+                }
+            }
+            match syncer.is_ignored(key) {
+                true => println!(
+                    "  It is a local-only annotation per --ignore-prefix and is never synced."
+                ),
+                false => {
+                    local = syncer
+                        .resolved_value(&config, environment, key)
+                        .unwrap_or_else(|e| {
+                            warn!("Couldn't resolve the configured value: {:#}", e);
+                            None
+                        })
+                }
+            }
+        }
+        None if !config.environments.contains_key(environment) => println!(
+            "Environment '{}' is not defined in {}.",
+            environment, options.config_path
+        ),
+        None => println!(
+            "{} is not configured in environment '{}'.",
+            options.key, environment
+        ),
+    }
+    println!();
+
+    let remote = syncer.remote_key(environment, key).await?;
+    let secret = provenance
+        .as_ref()
+        .is_some_and(|provenance| provenance.kind == VariableKind::Secret);
+    let show = |value: &str| match !options.show_values && (secret || is_sensitive_key(key)) {
+        true => mask_value(value),
+        false => format!("{:?}", value),
     };
 
-    let gh_client = GithubEnvClient::init(
-        username,
-        options.token.clone(),
-        repository_owner,
-        repository_name,
-    )
-    .await?;
+    let mut table = Table::new(&["SOURCE", "VALUE", "NOTE"]);
+    let remote_value = |level: VariableLevel| {
+        remote
+            .levels
+            .iter()
+            .find(|(l, _)| *l == level)
+            .map(|(_, value)| value.as_str())
+    };
+    let note = match (&local, remote_value(VariableLevel::Environment)) {
+        (None, _) => Cell::new(""),
+        (Some(_), _) if secret => Cell::new("can't be compared"),
+        (Some(_), None) => Cell::colored("not synced yet", Color::Yellow),
+        (Some(local), Some(remote)) if local == remote => Cell::colored("in sync", Color::Green),
+        (Some(_), Some(_)) => Cell::colored("differs from the environment", Color::Yellow),
+    };
+    table.push(vec![
+        Cell::new("config"),
+        Cell::new(local.as_deref().map_or("-".to_string(), show)),
+        note,
+    ]);
+    for level in [
+        VariableLevel::Environment,
+        VariableLevel::Repository,
+        VariableLevel::Organization,
+    ] {
+        let seen_with_environment = remote.effective(true) == Some(level);
+        let seen_without_environment = remote.effective(false) == Some(level);
+        let (value, note) = match remote_value(level) {
+            None if level == VariableLevel::Environment && remote.environment_secret => (
+                "-".to_string(),
+                Cell::new("a secret of this name exists, but can't be read"),
+            ),
+            None => ("-".to_string(), Cell::new("")),
+            Some(value) => (
+                show(value),
+                match (seen_with_environment, seen_without_environment) {
+                    (true, true) => Cell::new("seen by every job"),
+                    (true, false) => Cell::new("seen by jobs using the environment"),
+                    (false, true) => Cell::new("seen by jobs without the environment"),
+                    (false, false) => Cell::colored("shadowed", Color::Yellow),
+                },
+            ),
+        };
+        table.push(vec![Cell::new(level), Cell::new(value), note]);
+    }
+    print!("{}", table.render(terminal));
+
+    Ok(())
+}
 
+/// Merges the repository's remote variables into the local config file.
+async fn import(options: &ImportArgs, terminal: Terminal) -> Result<()> {
+    let mut remote = match &options.from_terraform_state {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| eyre!("Error reading Terraform state {}: {}", path.display(), e))?;
+            let (_, repository_name) = options.connection.repository_parts()?;
+            variables_from_state(&contents, repository_name)?
+        }
+        None => remote_variables(options).await?,
+    };
+    for variables in remote.values_mut() {
+        join_chunks(variables);
+    }
+    let exclusions = options.connection.exclusions(Some(&options.config_path))?;
+    remote.retain(|environment, _| !exclusions.excludes_environment(environment));
+    for (environment, variables) in remote.iter_mut() {
+        variables.retain(|key, _| !exclusions.excludes_key(environment, key));
+    }
     if let Some(environment) = &options.environment {
+        remote.retain(|name, _| name == environment);
+        if !remote.contains_key(environment) {
+            return Err(eyre!(
+                "Environment '{}' does not exist in the repository",
+                environment
+            ));
+        }
+    }
+
+    let path = &options.config_path;
+    let contents = match path.exists() {
+        true => std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading config file {}: {}", path.display(), e))?,
+        false => String::new(),
+    };
+    let normalized = normalization_report(&contents, &remote, &options.ignore_prefix)?;
+    let (contents, imported) = import_variables(&contents, &remote)?;
+    std::fs::write(path, contents)
+        .map_err(|e| eyre!("Error writing config file {}: {}", path.display(), e))?;
+
+    let mut written = 0;
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "RESULT"]);
+    for variable in &imported {
+        let result = match variable.outcome {
+            ImportOutcome::Added => Cell::colored("added", Color::Green),
+            ImportOutcome::Conflict => Cell::colored("differs, kept local", Color::Yellow),
+            ImportOutcome::Unchanged => continue,
+            ImportOutcome::FromFile => {
+                let file = variable
+                    .file
+                    .as_ref()
+                    .expect("keys read from files have a file");
+                let remote_value = &remote[&variable.environment][&variable.key];
+                match options.write_files {
+                    true => {
+                        if !file.write(config_dir(path), remote_value)? {
+                            continue;
+                        }
+                        written += 1;
+                        Cell::colored(format!("written to {}", file.path.display()), Color::Green)
+                    }
+                    false => {
+                        if file.read(config_dir(path)).ok().as_ref() == Some(remote_value) {
+                            continue;
+                        }
+                        Cell::colored(
+                            format!("differs from {}, kept local", file.path.display()),
+                            Color::Yellow,
+                        )
+                    }
+                }
+            }
+        };
+        table.push(vec![
+            Cell::new(&variable.environment),
+            Cell::new(&variable.key),
+            result,
+        ]);
+    }
+    if !table.is_empty() {
+        print!("{}", table.render(terminal));
+    }
+
+    let count = |outcome| {
+        imported
+            .iter()
+            .filter(|variable| variable.outcome == outcome)
+            .count()
+    };
+    info!(
+        "Imported {} variables into {} ({} differ from the config and are marked with comments, {} already match)",
+        count(ImportOutcome::Added),
+        path.display(),
+        count(ImportOutcome::Conflict),
+        count(ImportOutcome::Unchanged)
+    );
+    if written > 0 {
         info!(
-            "Found single environment '{}' to sync based on --environment argument",
-            environment
+            "Wrote {} variables back to the files they're read from",
+            written
         );
+    }
 
-        let env_config_dict = config.get(environment).expect(
-            "Expected the --environment argument to be one of the environments defined in the config document",
+    // Names the config alters or treats differently mean that a sync right
+    // after importing wouldn't be a no-op.
+    if !normalized.is_empty() {
+        let mut table = Table::new(&["ENVIRONMENT", "KEY", "NORMALIZATION"]);
+        for name in &normalized {
+            table.push(vec![
+                Cell::new(&name.environment),
+                Cell::new(name.key.as_deref().unwrap_or("-")),
+                Cell::colored(&name.normalization, Color::Yellow),
+            ]);
+        }
+        print!("{}", table.render(terminal));
+        warn!(
+            "{} remote names don't round-trip through {} unchanged, so a sync right after importing won't be a no-op",
+            normalized.len(),
+            path.display()
         );
+    }
+    Ok(())
+}
 
-        sync_one_environment(&gh_client, environment.as_ref(), env_config_dict).await?
-    } else {
-        let all_envs = config.keys().collect::<Vec<_>>();
+/// Reads the variables of every environment in the repository, or only of
+/// --environment.
+async fn remote_variables(options: &ImportArgs) -> Result<BTreeMap<String, Environment>> {
+    let client = options.connection.client().await?;
 
-        info!(
-            "Syncing all environments ({:?}) because no --environment argument was given",
-            all_envs
+    let mut remote = BTreeMap::new();
+    for environment in client.list_environments().await? {
+        if options
+            .environment
+            .as_ref()
+            .is_some_and(|requested| requested != &environment)
+        {
+            continue;
+        }
+
+        let variables = client.list_environment_variables(&environment).await?;
+        remote.insert(environment, variables);
+    }
+
+    Ok(remote)
+}
+
+/// Prints the config file's environments and variables in another format.
+/// Only plaintext values are exported: secrets, and encrypted or generated
+/// values, which aren't in the config file, are left out.
+fn export(options: &ExportArgs) -> Result<()> {
+    let (_, repository_name) = options.repository.split_once('/').ok_or_else(|| {
+        eyre!(
+            "Expected <REPOSITORY> argument to be a owner/repo_name pair, e.g. rust-lang/rust-lang"
+        )
+    })?;
+    let mut config =
+        gh_env_sync::load_config(locate_config(&options.config_path.to_string_lossy()))?;
+    if let Some(environment) = &options.environment {
+        if !config.environments.contains_key(environment) {
+            return Err(eyre!(
+                "Environment '{}' is not defined in the config document",
+                environment
+            ));
+        }
+        config.environments.retain(|name, _| name == environment);
+    }
+
+    // Values are exported as a sync would upload them, chunks and all.
+    let chunked = chunked_keys(&config.environments);
+    let mut environments = plaintext_environments(config, &options.ignore_prefix, "exporting")?;
+    for (name, variables) in environments.iter_mut() {
+        split_values(variables, &chunked[name]);
+    }
+    match options.format {
+        ExportFormat::Terraform => print!(
+            "{}",
+            export_terraform(repository_name, &environments, options.import_blocks)
+        ),
+    }
+    Ok(())
+}
+
+/// The values of the config document's plaintext variables, with references
+/// resolved and transforms applied, keyed by environment name.  Secrets and
+/// generated or encrypted values are left out with a warning that names
+/// what was being done.
+fn plaintext_environments(
+    config: ConfigDocument,
+    ignore_prefix: &str,
+    doing: &str,
+) -> Result<BTreeMap<String, Environment>> {
+    let mut environments = BTreeMap::new();
+    for (name, mut environment) in config.environments {
+        environment.retain(|key, _| ignore_prefix.is_empty() || !key.starts_with(ignore_prefix));
+
+        let mut skipped = Vec::new();
+        environment.retain(|key, variable| {
+            let exportable = variable.kind == VariableKind::Variable
+                && variable.generate.is_none()
+                && !is_encrypted(&variable.value);
+            if !exportable {
+                skipped.push(key.clone());
+            }
+            exportable
+        });
+        if !skipped.is_empty() {
+            warn!(
+                "Not {} {} from environment '{}', which are secrets or aren't in plaintext in the config file",
+                doing,
+                skipped.join(", "),
+                name
+            );
+        }
+
+        resolve_references(&name, &mut environment)?;
+        apply_transforms(&name, &mut environment)?;
+        environments.insert(name, environment_values(&environment));
+    }
+
+    Ok(environments)
+}
+
+/// Prints the keys that have the same value in every environment, and those
+/// with suspiciously similar values.
+fn duplicates(options: &DuplicatesArgs, terminal: Terminal) -> Result<()> {
+    let config = gh_env_sync::load_config(locate_config(&options.config_path.to_string_lossy()))?;
+    let environment_count = config.environments.len();
+    let environments = plaintext_environments(config, &options.ignore_prefix, "analyzing")?;
+    let report = find_duplicates(&environments);
+    let show = |key: &str, value: &str| match !options.show_values && is_sensitive_key(key) {
+        true => mask_value(value),
+        false => value.to_string(),
+    };
+
+    if report.is_empty() {
+        println!(
+            "No duplicated or suspiciously similar values in {} environments.",
+            environment_count
         );
+        return Ok(());
+    }
+
+    if !report.identical.is_empty() {
+        println!(
+            "Same value in all {} environments, which could be moved to [__shared]:",
+            environment_count
+        );
+        let mut table = Table::new(&["KEY", "VALUE"]);
+        for identical in &report.identical {
+            table.push(vec![
+                Cell::new(&identical.key),
+                Cell::new(show(&identical.key, &identical.value)),
+            ]);
+        }
+        print!("{}", table.render(terminal));
+    }
+
+    if !report.similar.is_empty() {
+        if !report.identical.is_empty() {
+            println!();
+        }
+        println!("Suspiciously similar values, which may be copy-paste mistakes:");
+        let mut table = Table::new(&["KEY", "ENVIRONMENT", "VALUE", "ENVIRONMENT", "VALUE"]);
+        for similar in &report.similar {
+            table.push(vec![
+                Cell::new(&similar.key),
+                Cell::new(&similar.environments[0]),
+                Cell::colored(show(&similar.key, &similar.values[0]), Color::Yellow),
+                Cell::new(&similar.environments[1]),
+                Cell::colored(show(&similar.key, &similar.values[1]), Color::Yellow),
+            ]);
+        }
+        print!("{}", table.render(terminal));
+    }
+
+    Ok(())
+}
+
+/// Checks the config document for unused, orphaned and broken entries, and
+/// fails if there are any.
+async fn lint(options: &LintArgs, terminal: Terminal) -> Result<()> {
+    // Encrypted values are fine as they are, so they aren't decrypted.
+    let config = options.connection.read_config(&options.config_path).await?;
+    let remote_environments = match options.connection.repository.is_some() {
+        true => {
+            let mut environments = options
+                .connection
+                .client()
+                .await?
+                .list_environments()
+                .await?;
+            environments.retain(|name| !config.exclusions.excludes_environment(name));
+            Some(environments)
+        }
+        false => {
+            info!("Not comparing environments against Github, as <REPOSITORY> isn't given");
+            None
+        }
+    };
+    let findings = lint_config(
+        &config,
+        remote_environments.as_deref(),
+        &options.connection.value_provider_registry()?,
+        &options.ignore_prefix,
+    );
+
+    let mut table = Table::new(&["ENVIRONMENT", "KEY", "PROBLEM"]);
+    for finding in &findings {
+        table.push(vec![
+            Cell::new(&finding.environment),
+            Cell::new(finding.key.as_deref().unwrap_or_default()),
+            Cell::colored(&finding.problem, Color::Yellow),
+        ]);
+    }
+    if !table.is_empty() {
+        print!("{}", table.render(terminal));
+    }
+
+    if !findings.is_empty() {
+        return Err(eyre!(
+            "Found {} problems in {}",
+            findings.len(),
+            options.config_path
+        ));
+    }
+
+    info!("Found no problems in {}", options.config_path);
+    Ok(())
+}
+
+/// Lists the repository's environments with how many variables and secrets
+/// each holds, or with --environment the keys of one environment.
+async fn list(options: &ListArgs, terminal: Terminal) -> Result<()> {
+    let client = options.connection.client().await?;
+    let json = options.connection.output == Some(OutputFormat::Json);
+
+    let Some(environment_name) = &options.environment else {
+        let mut environments = Vec::new();
+        for name in client.list_environments().await? {
+            let variables = client.list_environment_variable_details(&name).await?;
+            let secrets = client.list_environment_secrets(&name).await?;
+            environments.push((name, variables.len(), secrets.len()));
+        }
+        environments.sort();
+
+        if json {
+            let environments = environments
+                .iter()
+                .map(|(name, variables, secrets)| {
+                    serde_json::json!({
+                        "name": name,
+                        "variables": variables,
+                        "secrets": secrets,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&environments)?);
+            return Ok(());
+        }
+        let mut table = Table::new(&["ENVIRONMENT", "VARIABLES", "SECRETS"]);
+        for (name, variables, secrets) in &environments {
+            table.push(vec![
+                Cell::new(name),
+                Cell::new(variables),
+                Cell::new(secrets),
+            ]);
+        }
+        print!("{}", table.render(terminal));
+        return Ok(());
+    };
+
+    // Values aren't listed, as secrets' can't be read and variables' are
+    // better compared with `diff` or `explain`.
+    let mut keys = client
+        .list_environment_variable_details(environment_name)
+        .await?
+        .into_iter()
+        .map(|variable| (variable.name, "variable", variable.updated_at))
+        .chain(
+            client
+                .list_environment_secrets(environment_name)
+                .await?
+                .into_iter()
+                .map(|secret| (secret.name, "secret", secret.updated_at)),
+        )
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    if json {
+        let keys = keys
+            .iter()
+            .map(|(key, kind, updated_at)| {
+                serde_json::json!({
+                    "key": key,
+                    "kind": kind,
+                    "updated_at": updated_at,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&keys)?);
+        return Ok(());
+    }
+    let mut table = Table::new(&["KEY", "KIND", "UPDATED AT"]);
+    for (key, kind, updated_at) in &keys {
+        table.push(vec![
+            Cell::new(key),
+            Cell::new(kind),
+            Cell::new(updated_at.as_deref().unwrap_or_default()),
+        ]);
+    }
+    print!("{}", table.render(terminal));
+    Ok(())
+}
+
+/// Checks the config document against Github's rules for names and values,
+/// without contacting Github, and fails if it would reject anything.
+async fn validate(options: &ValidateArgs, terminal: Terminal) -> Result<()> {
+    // Encrypted values are only checked by name, so they aren't decrypted.
+    let config = options.connection.read_config(&options.config_path).await?;
+    let rejections = config_rejections(&config, &options.ignore_prefix);
 
-        for (env_key, env_config_dict) in config {
-            sync_one_environment(&gh_client, env_key, env_config_dict).await?
+    if options.connection.output == Some(OutputFormat::Json) {
+        let rejections = rejections
+            .iter()
+            .map(|rejection| {
+                serde_json::json!({
+                    "environment": rejection.environment,
+                    "key": rejection.key,
+                    "problem": rejection.problem,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&rejections)?);
+    } else {
+        let mut table = Table::new(&["ENVIRONMENT", "KEY", "PROBLEM"]);
+        for rejection in &rejections {
+            table.push(vec![
+                Cell::new(rejection.environment.as_deref().unwrap_or_default()),
+                Cell::new(rejection.key.as_deref().unwrap_or_default()),
+                Cell::colored(&rejection.problem, Color::Red),
+            ]);
         }
+        if !table.is_empty() {
+            print!("{}", table.render(terminal));
+        }
+    }
+
+    if !rejections.is_empty() {
+        return Err(eyre!(
+            "Github would reject {} names or values in {}",
+            rejections.len(),
+            options.config_path
+        ));
+    }
+
+    info!("{} is valid", options.config_path);
+    Ok(())
+}
+
+/// Prints the token's remaining rate limits, and whether a sync of the
+/// config document would fit in them.
+async fn rate_limit(options: &RateLimitArgs, terminal: Terminal) -> Result<()> {
+    let client = options.connection.client().await?;
+    let config = options.connection.read_config(&options.config_path).await?;
+    let status = client.get_rate_limit().await?;
+
+    let now = unix_timestamp();
+    let mut table = Table::new(&["RESOURCE", "REMAINING", "LIMIT", "RESETS IN"]);
+    let resources = [
+        ("core", Some(&status.core)),
+        ("graphql", status.graphql.as_ref()),
+    ];
+    for (name, resource) in resources {
+        let Some(resource) = resource else {
+            continue;
+        };
+        let resets_in = resource.reset.saturating_sub(now);
+        let remaining = match resource.remaining {
+            0 => Cell::colored(0, Color::Red),
+            remaining => Cell::new(remaining),
+        };
+
+        table.push(vec![
+            Cell::new(name),
+            remaining,
+            Cell::new(resource.limit),
+            Cell::new(format!("{}m {}s", resets_in / 60, resets_in % 60)),
+        ]);
+    }
+    print!("{}", table.render(terminal));
+
+    let estimate = estimate_requests(
+        &config,
+        options.environment.as_deref(),
+        &SyncOptions {
+            lock: options.lock,
+            ignore_prefix: Some(options.ignore_prefix.clone()),
+            ..SyncOptions::default()
+        },
+    );
+    // The sync also initializes its own client.
+    let estimate = estimate + 1;
+    if estimate as u64 <= status.core.remaining {
+        println!(
+            "A sync of {} makes at most {} requests, which fits in the remaining budget.",
+            options.config_path, estimate
+        );
+    } else {
+        println!(
+            "A sync of {} makes up to {} requests, which may not fit in the remaining {}.  Wait for the limit to reset or sync one --environment at a time.",
+            options.config_path, estimate, status.core.remaining
+        );
     }
 
-    info!("All specified environments are synced successfully");
     Ok(())
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     color_eyre::install()?;
-    tracing_subscriber::fmt::init();
+    cli::default_token();
+    let args = Args::parse_command_line(profiles::insert_profile_args(std::env::args().collect())?);
 
-    let args = Args::parse();
+    if matches!(args.command, Some(Command::Tui(_))) {
+        // Log lines would draw over the terminal UI.
+        tracing_subscriber::fmt().with_writer(std::io::sink).init();
+    } else if args.sync_args().is_some_and(|sync| sync.summary_only) {
+        tracing_subscriber::fmt().with_writer(std::io::sink).init();
+    } else {
+        // Logs go to stderr so that stdout only carries --output data.
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    }
     debug!("Invoked with args: {:?}", args);
-    info!("Reading environment variables from {}", args.config_path);
-
-    let config_document_str = tokio::fs::read_to_string(&args.config_path).await?;
-    let config_document: ConfigDocument = toml::from_str(&config_document_str)?;
-    debug!("Read config document: {:?}", config_document);
 
-    sync_environments(&config_document, &args).await?;
+    let terminal = Terminal::detect(args.no_color);
+    let result = match (args.command, args.sync) {
+        (Some(Command::Sync(options)), _) => return run_sync(*options, terminal).await,
+        (Some(Command::Diff(mut options)), _) => {
+            options.dry_run = Some(true);
+            return run_sync(*options, terminal).await;
+        }
+        (Some(Command::Plan(mut options)), _) => {
+            options.dry_run = Some(true);
+            options
+                .plan_file
+                .get_or_insert_with(|| PathBuf::from(DEFAULT_PLAN_FILE));
+            return run_sync(*options, terminal).await;
+        }
+        (Some(Command::Apply(options)), _) => apply(&options, terminal).await,
+        (Some(Command::Rollback(options)), _) => rollback(&options, terminal).await,
+        (Some(Command::Undelete(options)), _) => undelete(&options, terminal).await,
+        (Some(Command::History(options)), _) => history(&options, terminal),
+        (Some(Command::Tui(options)), _) => {
+            let config_document = options.connection.load_config(&options.config_path).await?;
+            tui::run(&config_document, &options).await
+        }
+        (Some(Command::Serve(options)), _) => serve::run(&options).await,
+        (Some(Command::SelfUpdate(options)), _) => update::run(&options).await,
+        (Some(Command::Schema), _) => {
+            println!("{}", serde_json::to_string_pretty(&config_schema())?);
+            Ok(())
+        }
+        (Some(Command::RateLimit(options)), _) => rate_limit(&options, terminal).await,
+        (Some(Command::Import(options)), _) => import(&options, terminal).await,
+        (Some(Command::Export(options)), _) => export(&options),
+        (Some(Command::Duplicates(options)), _) => duplicates(&options, terminal),
+        (Some(Command::Lint(options)), _) => lint(&options, terminal).await,
+        (Some(Command::List(options)), _) => list(&options, terminal).await,
+        (Some(Command::Validate(options)), _) => validate(&options, terminal).await,
+        (Some(Command::RenameKey(options)), _) => rename_key(&options, terminal).await,
+        (Some(Command::Verify(options)), _) => verify(&options, terminal).await,
+        (Some(Command::Explain(options)), _) => explain(&options, terminal).await,
+        (Some(Command::Cleanup(options)), _) => cleanup(&options, terminal).await,
+        (Some(Command::All(options)), _) => {
+            return profiles::run(&options, terminal)
+                .await
+                .map(|outcome| outcome.exit_code());
+        }
+        (None, Some(options)) => return run_sync(options, terminal).await,
+        // Global options alone, e.g. --output, are neither.
+        (None, None) => Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "give a subcommand, or a sync's <REPOSITORY> and --token",
+            )
+            .exit(),
+    };
 
-    Ok(())
+    result.map(|_| ExitCode::SUCCESS)
 }
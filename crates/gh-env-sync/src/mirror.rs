@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use color_eyre::Result;
+use gh_client::GithubEnvClient;
+use tracing::{info, warn};
+
+use crate::{
+    chunk::join_chunks,
+    config::{ConfigDocument, EnvironmentConfig, VariableConfig, VariableKind},
+    discover::matches_glob,
+    quota::MAX_VALUE_BYTES,
+    sync::LAST_ACTOR_VARIABLE,
+};
+
+/// Builds a config document from the environments of another repository,
+/// e.g. a template repository that new services are stamped out from, so
+/// that syncing it copies them over.  Only environments matching one of
+/// `environments` and keys matching one of `keys` are copied, where `*`
+/// matches any run of characters and `?` any single one, and either may be
+/// empty to copy everything.  Names are matched ignoring case, like Github
+/// does.
+///
+/// Values that were chunked across `KEY__PART1` to `KEY__PARTn` are put
+/// back together and chunked again.  Only variables are copied: secrets
+/// can't be read back from Github, so they are left out with a warning
+/// naming them.  The [`LAST_ACTOR_VARIABLE`] marker isn't copied either.
+pub async fn config_from_remote(
+    client: &GithubEnvClient,
+    environments: &[String],
+    keys: &[String],
+) -> Result<ConfigDocument> {
+    let selects = |patterns: &[String], name: &str| {
+        patterns.is_empty()
+            || patterns
+                .iter()
+                .any(|pattern| matches_glob(&pattern.to_uppercase(), &name.to_uppercase()))
+    };
+
+    let source = format!("{}/{}", client.repository_owner(), client.repository_name());
+    let names = client.list_environments().await?;
+    let mut mirrored = HashMap::new();
+    for name in names.into_iter().filter(|name| selects(environments, name)) {
+        let mut remote = client.list_environment_variables(&name).await?;
+        join_chunks(&mut remote);
+        let variables = remote
+            .into_iter()
+            .filter(|(key, _)| key != LAST_ACTOR_VARIABLE)
+            .filter(|(key, _)| selects(keys, key))
+            .map(|(key, value)| {
+                let chunk = value.len() > MAX_VALUE_BYTES;
+                let variable = VariableConfig {
+                    value,
+                    on_conflict: None,
+                    generate: None,
+                    file: None,
+                    owner: None,
+                    expires: None,
+                    transform: Vec::new(),
+                    kind: VariableKind::Variable,
+                    chunk,
+                };
+                (key, variable)
+            })
+            .collect::<EnvironmentConfig>();
+
+        let mut secrets = client
+            .list_environment_secrets(&name)
+            .await?
+            .into_iter()
+            .map(|secret| secret.name)
+            .filter(|key| selects(keys, key))
+            .collect::<Vec<_>>();
+        if !secrets.is_empty() {
+            secrets.sort();
+            warn!(
+                "Secrets can't be read back from Github, so the secrets of environment '{}' aren't mirrored: {}",
+                name,
+                secrets.join(", ")
+            );
+        }
+
+        info!(
+            "Mirroring {} variables of environment '{}' from {}",
+            variables.len(),
+            name,
+            source
+        );
+        mirrored.insert(name, variables);
+    }
+
+    if mirrored.is_empty() {
+        warn!("{} has no environments to mirror", source);
+    }
+
+    Ok(ConfigDocument {
+        environments: mirrored,
+        ..ConfigDocument::default()
+    })
+}
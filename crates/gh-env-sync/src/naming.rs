@@ -0,0 +1,200 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+/// How variable names must be written, from the `[naming]` section of a
+/// config document:
+///
+/// ```toml
+/// [naming]
+/// style = "screaming_snake_case"
+/// max_length = 64
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamingPolicy {
+    #[serde(default)]
+    pub style: NamingStyle,
+    /// The longest a name may be, in characters.
+    #[serde(default)]
+    pub max_length: Option<usize>,
+}
+
+/// The casing required of variable names.  Github uppercases names, so
+/// `api_url` is synced as `API_URL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NamingStyle {
+    /// Any casing Github accepts.
+    #[default]
+    Any,
+    /// No lowercase letters, so that names read as Github stores them.
+    Uppercase,
+    /// Uppercase words of letters and digits separated by single
+    /// underscores, e.g. `API_URL`.
+    ScreamingSnakeCase,
+}
+
+impl FromStr for NamingStyle {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "any" => Ok(Self::Any),
+            "uppercase" => Ok(Self::Uppercase),
+            "screaming_snake_case" => Ok(Self::ScreamingSnakeCase),
+            _ => Err(eyre!(
+                "Unknown naming style '{}', expected one of any, uppercase, screaming_snake_case",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NamingStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Any => "any",
+            Self::Uppercase => "uppercase",
+            Self::ScreamingSnakeCase => "screaming_snake_case",
+        })
+    }
+}
+
+/// A variable name that breaks the naming policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingViolation {
+    pub environment: String,
+    pub key: String,
+    /// What is wrong with the name, e.g. `isn't SCREAMING_SNAKE_CASE`.
+    pub problem: String,
+    /// A name that would follow the policy, when one can be derived.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for NamingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in '{}' {}", self.key, self.environment, self.problem)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (rename it to {})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl NamingPolicy {
+    /// Checks the names of an environment's variables against the policy.
+    /// Names that only differ in case are always reported, whatever the
+    /// policy, since Github uppercases them into the same variable.
+    pub fn check<'a>(
+        &self,
+        environment: &str,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<NamingViolation> {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        keys.sort();
+
+        let mut violations = Vec::new();
+        let mut seen = HashMap::new();
+        for key in keys {
+            let violation = |problem: String, suggestion: Option<String>| NamingViolation {
+                environment: environment.to_string(),
+                key: key.to_string(),
+                problem,
+                suggestion,
+            };
+
+            match seen.entry(key.to_uppercase()) {
+                Entry::Occupied(first) => violations.push(violation(
+                    format!(
+                        "is the same variable as {} once Github uppercases it",
+                        first.get()
+                    ),
+                    None,
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert(key);
+                }
+            }
+            match self.style {
+                NamingStyle::Any => {}
+                NamingStyle::Uppercase if key.chars().any(char::is_lowercase) => {
+                    violations.push(violation(
+                        "has lowercase letters".to_string(),
+                        Some(key.to_uppercase()),
+                    ));
+                }
+                NamingStyle::Uppercase => {}
+                NamingStyle::ScreamingSnakeCase if !is_screaming_snake_case(key) => {
+                    violations.push(violation(
+                        "isn't SCREAMING_SNAKE_CASE".to_string(),
+                        screaming_snake_case(key),
+                    ));
+                }
+                NamingStyle::ScreamingSnakeCase => {}
+            }
+            if let Some(max_length) = self.max_length {
+                let length = key.chars().count();
+                if length > max_length {
+                    violations.push(violation(
+                        format!(
+                            "is {} characters long, more than the {} allowed",
+                            length, max_length
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// Whether a name is uppercase words of letters and digits, starting with a
+/// letter, separated by single underscores.
+fn is_screaming_snake_case(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_uppercase())
+        && key.split('_').all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        })
+}
+
+/// Converts a name such as `apiUrl`, `api-url` or `API__URL` to
+/// SCREAMING_SNAKE_CASE, splitting words at case changes and at anything
+/// other than letters and digits.  Names that don't start with a letter
+/// have no conversion.
+fn screaming_snake_case(key: &str) -> Option<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous: Option<char> = None;
+    for c in key.chars() {
+        if !c.is_ascii_alphanumeric() {
+            words.push(std::mem::take(&mut word));
+        } else if c.is_ascii_uppercase()
+            && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+        {
+            words.push(std::mem::take(&mut word));
+            word.push(c);
+        } else {
+            word.push(c.to_ascii_uppercase());
+        }
+        previous = Some(c);
+    }
+    words.push(word);
+
+    let name = words
+        .into_iter()
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    is_screaming_snake_case(&name).then_some(name)
+}
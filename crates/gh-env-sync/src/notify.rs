@@ -0,0 +1,75 @@
+//! Posting the result of a sync to --notify-url, for scheduled syncs that
+//! nobody watches.
+
+use std::time::Duration;
+
+use color_eyre::{eyre::eyre, Report, Result};
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde::Serialize;
+
+use crate::outcome::{Outcome, Summary};
+
+/// The JSON body posted to --notify-url.  `text` makes it a valid Slack
+/// incoming webhook message, and generic webhooks can read the totals from
+/// the other fields.
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    text: String,
+    #[serde(flatten)]
+    summary: Option<Summary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Notification {
+    pub fn summary(summary: Summary) -> Self {
+        let headline = match summary.outcome {
+            Outcome::InSync => "everything is in sync",
+            Outcome::Changed => "changes were applied",
+            Outcome::Drift => "drift was detected",
+            Outcome::PartialFailure => "some syncs failed",
+            Outcome::Cancelled => "the sync was cancelled",
+        };
+
+        Self {
+            text: format!(
+                "gh-env-sync: {} ({} repositories, {} failed; {} created, {} updated, {} deleted, {} unchanged, {} kept, {} drifted)",
+                headline,
+                summary.repositories,
+                summary.failed,
+                summary.created,
+                summary.updated,
+                summary.deleted,
+                summary.unchanged,
+                summary.kept,
+                summary.drifted
+            ),
+            summary: Some(summary),
+            error: None,
+        }
+    }
+
+    pub fn error(error: &Report) -> Self {
+        Self {
+            text: format!("gh-env-sync failed: {}", error),
+            summary: None,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+
+    /// Posts the notification as JSON to `url`.
+    pub async fn send(&self, url: &str) -> Result<()> {
+        let response = Client::new()
+            .post(url)
+            .timeout(Duration::from_secs(10))
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(self)?)
+            .send()
+            .await?;
+
+        match response.error_for_status() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(eyre!("Error posting notification to {}: {}", url, e)),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! How a sync ended, reported to wrapper scripts through the exit code and a
+//! final `key=value` summary line.
+
+use std::{fmt, process::ExitCode};
+
+use gh_env_sync::{sync::VariableChange, SyncReport};
+use serde::Serialize;
+
+/// The result of a sync that didn't fail outright.  Fatal errors exit with
+/// 1, as for every other command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Nothing needed changing.
+    InSync,
+    /// Changes were applied.
+    Changed,
+    /// A --dry-run found values that differ from the config.
+    Drift,
+    /// Some environments or repositories were synced and others failed.
+    PartialFailure,
+    /// The sync was interrupted with Ctrl-C or SIGTERM, and can be
+    /// continued with --resume.
+    Cancelled,
+}
+
+impl Outcome {
+    /// The outcome of a single successful sync.
+    pub fn of(report: &SyncReport, dry_run: bool) -> Self {
+        match (report.has_changes(), dry_run) {
+            (false, _) => Self::InSync,
+            (true, false) => Self::Changed,
+            (true, true) => Self::Drift,
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Self::InSync => 0,
+            Self::Changed => 2,
+            Self::Drift => 3,
+            Self::PartialFailure => 4,
+            Self::Cancelled => 130,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InSync => "in_sync",
+            Self::Changed => "changed",
+            Self::Drift => "drift",
+            Self::PartialFailure => "partial_failure",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// The totals of a sync, printed as its final line and posted to
+/// --notify-url.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Summary {
+    #[serde(rename = "result")]
+    pub outcome: Outcome,
+    pub repositories: usize,
+    pub failed: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    pub kept: usize,
+    pub drifted: usize,
+}
+
+impl Summary {
+    /// Counts cover every repository that was synced, including
+    /// environments applied before a partial failure.
+    pub fn new(
+        outcome: Outcome,
+        repositories: usize,
+        failed: usize,
+        reports: &[&SyncReport],
+    ) -> Self {
+        let total =
+            |count: &dyn Fn(&SyncReport) -> usize| reports.iter().map(|r| count(r)).sum::<usize>();
+
+        Self {
+            outcome,
+            repositories,
+            failed,
+            created: total(&|report| report.count(VariableChange::Create)),
+            updated: total(&|report| report.count(VariableChange::Update)),
+            deleted: total(&|report| report.count(VariableChange::Delete)),
+            unchanged: total(&|report| report.count(VariableChange::Unchanged)),
+            kept: total(&|report| report.count(VariableChange::Kept)),
+            drifted: total(&|report| report.drift_count()),
+        }
+    }
+}
+
+/// The final line of a sync, e.g.
+/// `result=changed repositories=1 failed=0 created=2 updated=1 deleted=0 unchanged=9 kept=0 drifted=0`.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "result={} repositories={} failed={} created={} updated={} deleted={} unchanged={} kept={} drifted={}",
+            self.outcome.name(),
+            self.repositories,
+            self.failed,
+            self.created,
+            self.updated,
+            self.deleted,
+            self.unchanged,
+            self.kept,
+            self.drifted,
+        )
+    }
+}
@@ -0,0 +1,213 @@
+//! Tables for human readable output on stdout, which lay out as a grid on
+//! wide terminals and as one block per row on narrow ones.
+
+use std::io::{self, IsTerminal};
+
+use crossterm::style::{Color, Stylize};
+
+/// Terminal width assumed when it can't be detected.
+const DEFAULT_WIDTH: usize = 80;
+
+/// How output is rendered for the current stdout.
+#[derive(Debug, Clone, Copy)]
+pub struct Terminal {
+    /// Columns available, or `None` when stdout isn't a terminal and lines
+    /// can be as long as they need to be.
+    pub width: Option<usize>,
+    pub color: bool,
+}
+
+impl Terminal {
+    /// Detects the width of stdout, and whether to use color.  Color is off
+    /// when stdout isn't a terminal, with --no-color, or when `NO_COLOR` is
+    /// set (https://no-color.org).
+    pub fn detect(no_color: bool) -> Self {
+        let is_terminal = io::stdout().is_terminal();
+        let width = is_terminal.then(|| {
+            crossterm::terminal::size()
+                .map(|(columns, _)| columns as usize)
+                .unwrap_or(DEFAULT_WIDTH)
+        });
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+        Self {
+            width,
+            color: is_terminal && !no_color && !no_color_env,
+        }
+    }
+}
+
+/// Shortens an ISO 8601 timestamp from the Github API, such as
+/// `2023-09-02T12:00:00Z`, to `2023-09-02 12:00`.  Anything else is returned
+/// as is.
+pub fn short_timestamp(timestamp: &str) -> String {
+    match (timestamp.get(..10), timestamp.get(11..16)) {
+        (Some(date), Some(time)) if timestamp.as_bytes()[10] == b'T' => {
+            format!("{} {}", date, time)
+        }
+        _ => timestamp.to_string(),
+    }
+}
+
+/// A single table cell, optionally colored.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn new(text: impl ToString) -> Self {
+        Self {
+            text: text.to_string(),
+            color: None,
+        }
+    }
+
+    pub fn colored(text: impl ToString, color: Color) -> Self {
+        Self {
+            text: text.to_string(),
+            color: Some(color),
+        }
+    }
+
+    /// A count, colored only when it is non-zero.
+    pub fn count(count: usize, color: Color) -> Self {
+        match count {
+            0 => Self::new(count),
+            _ => Self::colored(count, color),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// The cell's text padded to `width`.  Padding is added outside of the
+    /// color codes, which don't take up any columns.
+    fn render(&self, width: usize, color: bool) -> String {
+        let padding = " ".repeat(width.saturating_sub(self.width()));
+        match self.color {
+            Some(cell_color) if color => {
+                format!("{}{}", self.text.clone().with(cell_color), padding)
+            }
+            _ => format!("{}{}", self.text, padding),
+        }
+    }
+}
+
+/// A table with a header row.
+#[derive(Debug, Clone)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Renders the table as a grid if it fits in the terminal, and otherwise
+    /// as a `header: value` block per row.
+    pub fn render(&self, terminal: Terminal) -> String {
+        let widths = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(column, header)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[column].width())
+                    .chain([header.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        // "| " before each column, " |" after the last, and " | " between.
+        let grid_width = widths.iter().sum::<usize>() + 3 * widths.len() + 1;
+
+        match terminal.width {
+            Some(width) if grid_width > width => self.render_narrow(terminal.color),
+            _ => self.render_wide(&widths, terminal.color),
+        }
+    }
+
+    fn render_wide(&self, widths: &[usize], color: bool) -> String {
+        let border = widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<_>>()
+            .join("+");
+        let border = format!("+{}+\n", border);
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+        let mut output = border.clone();
+        output.push_str(&line(
+            self.headers
+                .iter()
+                .zip(widths)
+                .map(|(header, width)| {
+                    let header = format!("{:width$}", header, width = width);
+                    if color {
+                        header.bold().to_string()
+                    } else {
+                        header
+                    }
+                })
+                .collect(),
+        ));
+        output.push_str(&border);
+        for row in &self.rows {
+            output.push_str(&line(
+                row.iter()
+                    .zip(widths)
+                    .map(|(cell, width)| cell.render(*width, color))
+                    .collect(),
+            ));
+        }
+        output.push_str(&border);
+
+        output
+    }
+
+    fn render_narrow(&self, color: bool) -> String {
+        let header_width = self
+            .headers
+            .iter()
+            .map(|header| header.chars().count())
+            .max()
+            .unwrap_or_default();
+
+        self.rows
+            .iter()
+            .map(|row| {
+                self.headers
+                    .iter()
+                    .zip(row)
+                    .map(|(header, cell)| {
+                        format!(
+                            "{:width$}  {}\n",
+                            header,
+                            cell.render(0, color),
+                            width = header_width
+                        )
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    audit::{unix_timestamp, AuditOperation},
+    discover::matches_glob,
+    lock::SyncLock,
+    state::State,
+    sync::{PlannedValue, Syncer, VariableChange},
+};
+
+/// Where `plan` writes the plan file unless --plan-file says otherwise.
+pub const DEFAULT_PLAN_FILE: &str = "gh-env-sync.plan.json";
+
+/// The version of the plan file format written by this build.
+const PLAN_VERSION: u32 = 1;
+
+/// A variable write planned by a dry run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanOperation {
+    pub environment: String,
+    pub key: String,
+    /// The remote value when the plan was made, or `None` if the variable
+    /// didn't exist.  Applying the plan refuses to overwrite any other value.
+    pub previous: Option<String>,
+    /// The value to write, or `None` to delete the variable.
+    pub value: Option<String>,
+}
+
+/// The variable writes a dry run of a single repository would make, saved so
+/// that they can be reviewed and then made as they are with `apply`:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "repository": "owner/repo",
+///   "config_hash": "9f86d08…",
+///   "created_at": 1700000000,
+///   "operations": [
+///     { "environment": "production", "key": "API_URL", "previous": null, "value": "https://api.example.com" }
+///   ]
+/// }
+/// ```
+///
+/// Only variables are planned: secret values can't be compared in a dry run,
+/// so secrets are left to `sync`.  Values are kept in plain text, as in the
+/// audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    pub repository: String,
+    /// The SHA-256 hash of the config document the plan was made from, as
+    /// read from its source.
+    pub config_hash: String,
+    /// When the plan was made, in seconds since the unix epoch.
+    pub created_at: u64,
+    pub operations: Vec<PlanOperation>,
+}
+
+impl Plan {
+    /// The plan of a dry run's planned values.
+    pub fn new(repository: &str, config_hash: String, planned: &[PlannedValue]) -> Self {
+        let operations = planned
+            .iter()
+            .map(|planned| PlanOperation {
+                environment: planned.environment.clone(),
+                key: planned.key.to_uppercase(),
+                previous: planned.remote_value.clone(),
+                value: match planned.change {
+                    VariableChange::Delete => None,
+                    _ => Some(planned.local_value.clone()),
+                },
+            })
+            .collect();
+
+        Self {
+            version: PLAN_VERSION,
+            repository: repository.to_string(),
+            config_hash,
+            created_at: unix_timestamp(),
+            operations,
+        }
+    }
+
+    /// Reads the plan file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading plan file {}: {}", path.display(), e))?;
+        let plan: Self = serde_json::from_str(&contents)
+            .map_err(|e| eyre!("Error parsing plan file {}: {}", path.display(), e))?;
+        if plan.version != PLAN_VERSION {
+            return Err(eyre!(
+                "Plan file {} has version {}, but this gh-env-sync only applies version {}",
+                path.display(),
+                plan.version,
+                PLAN_VERSION
+            ));
+        }
+
+        Ok(plan)
+    }
+
+    /// Splits the plan into the operations on environments matching one of
+    /// `environments` and keys matching one of `keys`, and the rest, each a
+    /// plan of its own made from the same config.  Empty patterns match
+    /// everything, and patterns are case-insensitive globs where `*` matches
+    /// any run of characters and `?` any single character.
+    pub fn partition(&self, environments: &[String], keys: &[String]) -> (Self, Self) {
+        let matches = |patterns: &[String], name: &str| {
+            patterns.is_empty()
+                || patterns
+                    .iter()
+                    .any(|pattern| matches_glob(&pattern.to_uppercase(), &name.to_uppercase()))
+        };
+        let (selected, remaining) = self.operations.iter().cloned().partition(|operation| {
+            matches(environments, &operation.environment) && matches(keys, &operation.key)
+        });
+        let plan = |operations| Self {
+            version: self.version,
+            repository: self.repository.clone(),
+            config_hash: self.config_hash.clone(),
+            created_at: self.created_at,
+            operations,
+        };
+
+        (plan(selected), plan(remaining))
+    }
+
+    /// Writes the plan to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents + "\n")
+            .map_err(|e| eyre!("Error writing plan file {}: {}", path.display(), e))
+    }
+}
+
+/// How plan files are signed, so that whoever applies one can prove it is
+/// the plan that was reviewed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// A detached, ASCII armored signature by `gpg`'s default key, in
+    /// `<plan>.asc`.  Verifying accepts any key in the keyring.
+    Gpg,
+    /// A Sigstore bundle made with `cosign sign-blob`, keyless unless
+    /// cosign is configured with a key, in `<plan>.sigstore.json`.
+    Sigstore,
+}
+
+impl FromStr for SignatureKind {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gpg" => Ok(Self::Gpg),
+            "sigstore" => Ok(Self::Sigstore),
+            _ => Err(eyre!("Unknown signature '{}', expected gpg or sigstore", s)),
+        }
+    }
+}
+
+impl fmt::Display for SignatureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gpg => "gpg",
+            Self::Sigstore => "sigstore",
+        })
+    }
+}
+
+/// Who must have signed a plan for a Sigstore signature to verify, as the
+/// certificate Sigstore issued them records it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigstoreIdentity {
+    /// E.g. an email address, or a workflow URL for Github Actions.
+    pub identity: String,
+    /// E.g. `https://token.actions.githubusercontent.com`.
+    pub issuer: String,
+}
+
+impl SignatureKind {
+    /// Where the signature of the plan file at `plan` is kept.
+    pub fn signature_path(self, plan: &Path) -> PathBuf {
+        let extension = match self {
+            Self::Gpg => "asc",
+            Self::Sigstore => "sigstore.json",
+        };
+        let mut path = plan.as_os_str().to_owned();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    /// Signs the plan file at `plan`, and returns where the signature was
+    /// written.
+    pub fn sign(self, plan: &Path) -> Result<PathBuf> {
+        let signature = self.signature_path(plan);
+        match self {
+            Self::Gpg => run(
+                "gpg",
+                &[
+                    OsStr::new("--batch"),
+                    OsStr::new("--yes"),
+                    OsStr::new("--armor"),
+                    OsStr::new("--detach-sign"),
+                    OsStr::new("--output"),
+                    signature.as_os_str(),
+                    plan.as_os_str(),
+                ],
+            )?,
+            Self::Sigstore => run(
+                "cosign",
+                &[
+                    OsStr::new("sign-blob"),
+                    OsStr::new("--yes"),
+                    OsStr::new("--bundle"),
+                    signature.as_os_str(),
+                    plan.as_os_str(),
+                ],
+            )?,
+        }
+
+        Ok(signature)
+    }
+
+    /// Fails unless the plan file at `plan` has a valid signature of this
+    /// kind next to it.  Sigstore signatures must be by `signer`.
+    pub fn verify(self, plan: &Path, signer: Option<&SigstoreIdentity>) -> Result<()> {
+        let signature = self.signature_path(plan);
+        if !signature.is_file() {
+            return Err(eyre!(
+                "{} has no {} signature at {}",
+                plan.display(),
+                self,
+                signature.display()
+            ));
+        }
+
+        match self {
+            Self::Gpg => run(
+                "gpg",
+                &[
+                    OsStr::new("--batch"),
+                    OsStr::new("--verify"),
+                    signature.as_os_str(),
+                    plan.as_os_str(),
+                ],
+            ),
+            Self::Sigstore => {
+                let signer = signer.ok_or_else(|| {
+                    eyre!("Verifying a sigstore signature requires the identity and issuer of who signed it")
+                })?;
+                run(
+                    "cosign",
+                    &[
+                        OsStr::new("verify-blob"),
+                        OsStr::new("--bundle"),
+                        signature.as_os_str(),
+                        OsStr::new("--certificate-identity"),
+                        OsStr::new(&signer.identity),
+                        OsStr::new("--certificate-oidc-issuer"),
+                        OsStr::new(&signer.issuer),
+                        plan.as_os_str(),
+                    ],
+                )
+            }
+        }
+        .map_err(|e| eyre!("The signature of {} isn't valid: {}", plan.display(), e))
+    }
+}
+
+/// Runs a signing tool, failing with what it printed to stderr.
+fn run(program: &str, args: &[&OsStr]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| eyre!("Error running {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// What applying a plan did, or would do, to a single variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    /// The planned write was made.
+    Applied,
+    /// The variable already holds its planned value, e.g. because an earlier
+    /// apply of the plan was interrupted after writing it.
+    AlreadyApplied,
+}
+
+/// The outcome of a single operation of a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedOperation {
+    pub operation: PlanOperation,
+    pub outcome: ApplyOutcome,
+}
+
+impl Syncer {
+    /// Makes the writes of a plan, after checking that none of its variables
+    /// changed on Github since the plan was made, so that applying a
+    /// reviewed plan never overwrites a value its reviewers didn't see.
+    /// Variables already holding their planned value are skipped, so an
+    /// interrupted apply can be rerun, and excluded keys (see
+    /// [`SyncOptions::exclusions`]) are left alone.  With `dry_run`, nothing
+    /// is written.
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub async fn apply_plan(&self, plan: &Plan, dry_run: bool) -> Result<Vec<AppliedOperation>> {
+        let repository = self.repository();
+        if !plan.repository.eq_ignore_ascii_case(&repository) {
+            return Err(eyre!(
+                "The plan is for {}, not {}",
+                plan.repository,
+                repository
+            ));
+        }
+
+        let remote_environments = self.client().list_environments().await?;
+        let mut remote: BTreeMap<&str, HashMap<String, String>> = BTreeMap::new();
+        for operation in &plan.operations {
+            let environment = operation.environment.as_str();
+            if remote.contains_key(environment) {
+                continue;
+            }
+            let variables = match remote_environments.iter().any(|name| name == environment) {
+                true => {
+                    self.client_for(environment)
+                        .list_environment_variables(environment)
+                        .await?
+                }
+                false => HashMap::new(),
+            };
+            remote.insert(environment, variables);
+        }
+
+        let exclusions = &self.options().exclusions;
+        let mut applied = Vec::new();
+        let mut changed = Vec::new();
+        for operation in &plan.operations {
+            if exclusions.excludes_key(&operation.environment, &operation.key) {
+                info!(
+                    "Not applying {} in environment '{}', where it is excluded",
+                    operation.key, operation.environment
+                );
+                continue;
+            }
+
+            let current = remote[operation.environment.as_str()].get(&operation.key);
+            let outcome = match current {
+                current if current == operation.value.as_ref() => ApplyOutcome::AlreadyApplied,
+                current if current == operation.previous.as_ref() => ApplyOutcome::Applied,
+                _ => {
+                    changed.push(format!(
+                        "{} in environment '{}'",
+                        operation.key, operation.environment
+                    ));
+                    continue;
+                }
+            };
+            applied.push(AppliedOperation {
+                operation: operation.clone(),
+                outcome,
+            });
+        }
+        if !changed.is_empty() {
+            return Err(eyre!(
+                "Not applying the plan, since these variables changed on Github after it was made: {}; make a new plan",
+                changed.join(", ")
+            ));
+        }
+        if dry_run {
+            return Ok(applied);
+        }
+
+        let mut state = match &self.options().state_file {
+            Some(path) => Some(State::load(path)?),
+            None => None,
+        };
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let result = self
+            .apply_operations(&applied, &remote_environments, state.as_mut())
+            .await;
+        let result = lock.release_after(self.client(), result).await;
+
+        if let (Some(state), Some(path)) = (&state, &self.options().state_file) {
+            state.save(path)?;
+        }
+        result.map(|_| applied)
+    }
+
+    async fn apply_operations(
+        &self,
+        applied: &[AppliedOperation],
+        remote_environments: &[String],
+        mut state: Option<&mut State>,
+    ) -> Result<()> {
+        let repository = self.repository();
+        let mut created = Vec::new();
+        for AppliedOperation { operation, outcome } in applied {
+            if *outcome == ApplyOutcome::AlreadyApplied {
+                continue;
+            }
+
+            let environment = operation.environment.as_str();
+            let client = self.client_for(environment);
+            if !remote_environments.iter().any(|name| name == environment)
+                && !created.contains(&environment)
+            {
+                info!("Creating environment '{}'", environment);
+                client.upsert_environment(environment).await?;
+                self.audit(
+                    environment,
+                    "",
+                    AuditOperation::CreateEnvironment,
+                    None,
+                    None,
+                )?;
+                created.push(environment);
+            }
+
+            let key = operation.key.as_str();
+            match (&operation.previous, &operation.value) {
+                (None, Some(value)) => {
+                    info!("Creating {} in environment '{}'", key, environment);
+                    client
+                        .create_environment_variable(environment, key, value)
+                        .await?;
+                    self.audit(
+                        environment,
+                        key,
+                        AuditOperation::Create,
+                        None,
+                        Some(value.clone()),
+                    )?;
+                }
+                (Some(previous), Some(value)) => {
+                    info!("Updating {} in environment '{}'", key, environment);
+                    client
+                        .update_environment_variable(environment, key, value)
+                        .await?;
+                    self.audit(
+                        environment,
+                        key,
+                        AuditOperation::Update,
+                        Some(previous.clone()),
+                        Some(value.clone()),
+                    )?;
+                }
+                (Some(previous), None) => {
+                    info!("Deleting {} from environment '{}'", key, environment);
+                    client.delete_environment_variable(environment, key).await?;
+                    self.audit(
+                        environment,
+                        key,
+                        AuditOperation::Delete,
+                        Some(previous.clone()),
+                        None,
+                    )?;
+                }
+                (None, None) => unreachable!("a missing variable already holds its planned value"),
+            }
+
+            if let Some(state) = state.as_deref_mut() {
+                match &operation.value {
+                    Some(value) => state.record(&repository, environment, key, value),
+                    None => state.record_deleted(&repository, environment, key, unix_timestamp()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use color_eyre::{eyre::eyre, Result};
+use tracing::info;
+
+use crate::{audit::AuditOperation, config::ConfigDocument, lock::SyncLock, sync::Syncer};
+
+/// The environment expanded into a preview environment for each pull
+/// request.
+pub const PREVIEW_TEMPLATE: &str = "preview";
+
+/// Replaced with the pull request number in the template's keys and values.
+const PULL_REQUEST_PLACEHOLDER: &str = "${pr}";
+
+/// The name of the preview environment of a pull request.
+pub fn preview_environment(pull_request: u64) -> String {
+    format!("{}-pr-{}", PREVIEW_TEMPLATE, pull_request)
+}
+
+/// The pull request a Github Actions run was triggered by, from
+/// `GITHUB_REF`, which is `refs/pull/<number>/merge` for `pull_request`
+/// events.
+pub fn detect_pull_request() -> Result<u64> {
+    let reference = std::env::var("GITHUB_REF").map_err(|_| {
+        eyre!("GITHUB_REF is not set, so the pull request can't be detected; pass its number")
+    })?;
+
+    reference
+        .strip_prefix("refs/pull/")
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| {
+            eyre!(
+                "GITHUB_REF is {}, which isn't a pull request ref such as refs/pull/123/merge",
+                reference
+            )
+        })
+}
+
+/// Expands the `[preview]` template into a document holding only the
+/// preview environment of `pull_request`, with `${pr}` replaced by its
+/// number in keys and values:
+///
+/// ```toml
+/// [preview]
+/// APP_URL = "https://pr-${pr}.preview.example.com"
+/// ```
+///
+/// The template's owner, expiry and token override carry over to the preview
+/// environment.  Organization secrets are left out.
+pub fn expand_preview(config: &ConfigDocument, pull_request: u64) -> Result<ConfigDocument> {
+    let template = config.environments.get(PREVIEW_TEMPLATE).ok_or_else(|| {
+        eyre!(
+            "The config document has no [{}] environment to expand for pull request {}",
+            PREVIEW_TEMPLATE,
+            pull_request
+        )
+    })?;
+
+    let number = pull_request.to_string();
+    let substitute = |text: &str| text.replace(PULL_REQUEST_PLACEHOLDER, &number);
+    let environment = template
+        .iter()
+        .map(|(key, variable)| {
+            let mut variable = variable.clone();
+            variable.value = substitute(&variable.value);
+            (substitute(key), variable)
+        })
+        .collect();
+
+    let name = preview_environment(pull_request);
+    let mut preview = ConfigDocument {
+        repository: config.repository.clone(),
+        auth: config.auth.clone(),
+        exclusions: config.exclusions.clone(),
+        environments: [(name.clone(), environment)].into_iter().collect(),
+        ..ConfigDocument::default()
+    };
+    if let Some(owner) = config.owners.get(PREVIEW_TEMPLATE) {
+        preview.owners.insert(name.clone(), owner.clone());
+    }
+    if let Some(expiry) = config.expires.get(PREVIEW_TEMPLATE) {
+        preview.expires.insert(name.clone(), *expiry);
+    }
+    if let Some(source) = config.auth.environments.get(PREVIEW_TEMPLATE) {
+        preview.auth.environments.insert(name, source.clone());
+    }
+
+    Ok(preview)
+}
+
+impl Syncer {
+    /// Deletes the given environments, and every variable in them, skipping
+    /// those that don't exist or are excluded (see
+    /// [`SyncOptions::exclusions`]).  Returns the environments deleted, or
+    /// with `dry_run` those that would be.
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub async fn delete_environments(
+        &self,
+        environments: &[String],
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        let remote_environments = self.client().list_environments().await?;
+        let existing = environments
+            .iter()
+            .filter(|environment| {
+                if self.options().exclusions.excludes_environment(environment) {
+                    info!(
+                        "Not deleting environment '{}', which is excluded",
+                        environment
+                    );
+                    return false;
+                }
+
+                let exists = remote_environments.contains(environment);
+                if !exists {
+                    info!("Environment '{}' doesn't exist", environment);
+                }
+                exists
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if dry_run || existing.is_empty() {
+            return Ok(existing);
+        }
+
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let result = self.apply_deletions(&existing).await;
+        let result = lock.release_after(self.client(), result).await;
+
+        result.map(|_| existing)
+    }
+
+    pub(crate) async fn apply_deletions(&self, environments: &[String]) -> Result<()> {
+        for environment in environments {
+            info!("Deleting environment '{}'", environment);
+            self.client_for(environment)
+                .delete_environment(environment)
+                .await?;
+            self.audit(
+                environment,
+                "",
+                AuditOperation::DeleteEnvironment,
+                None,
+                None,
+            )?;
+            self.forget_created(environment, None)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! `all`: runs the syncs of several profiles concurrently, e.g. one per
+//! organization someone administers, and sums up their results.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+use crossterm::style::Color;
+use gh_env_sync::discover::{user_config_dir, PROFILES_FILE};
+use serde::Deserialize;
+use tokio::task::LocalSet;
+use tracing::{error, info, info_span, Instrument};
+
+use crate::{
+    cli::{AllArgs, SyncArgs},
+    outcome::{Outcome, Summary},
+    output::{Cell, Table, Terminal},
+    sync_and_notify,
+};
+
+/// A named set of sync arguments in the profiles file:
+///
+/// ```toml
+/// [work]
+/// args = ["--org", "acme", "--token", "$ACME_GITHUB_TOKEN", "--config-path", "/home/me/acme/github_environments.toml"]
+/// ```
+///
+/// Arguments of the form `$NAME` are replaced with the value of the
+/// environment variable `NAME`, so that tokens aren't written to the file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    args: Vec<String>,
+}
+
+/// Parses a profile's arguments like those of a sync without a subcommand.
+#[derive(Debug, Parser)]
+#[command(name = "gh-env-sync", no_binary_name = true)]
+struct ProfileArgs {
+    #[command(flatten)]
+    sync: SyncArgs,
+}
+
+/// Reads the profiles file from the user config directory.
+fn read_profiles() -> Result<(PathBuf, BTreeMap<String, Profile>)> {
+    let path = user_config_dir()
+        .ok_or_else(|| {
+            eyre!(
+                "Can't find the user config directory holding {}",
+                PROFILES_FILE
+            )
+        })?
+        .join(PROFILES_FILE);
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| eyre!("Error reading profiles file {}: {}", path.display(), e))?;
+    let profiles = toml::from_str(&contents)
+        .map_err(|e| eyre!("Error parsing profiles file {}: {}", path.display(), e))?;
+    Ok((path, profiles))
+}
+
+/// Replaces a `$NAME` argument with the environment variable `NAME`.
+fn expand_arg(arg: &str) -> Result<String> {
+    match arg.strip_prefix('$') {
+        Some(name)
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            std::env::var(name).map_err(|e| eyre!("Error reading ${}: {}", name, e))
+        }
+        _ => Ok(arg.to_string()),
+    }
+}
+
+/// Inserts the arguments of the profile named by `--profile NAME` right
+/// after it, so that any command can take e.g. its --token from a profile.
+/// --profile itself is left for clap, which lists it in --help.
+pub fn insert_profile_args(mut args: Vec<String>) -> Result<Vec<String>> {
+    let Some(position) = args
+        .iter()
+        .position(|arg| arg == "--profile" || arg.starts_with("--profile="))
+    else {
+        return Ok(args);
+    };
+    let (name, end) = match args[position].strip_prefix("--profile=") {
+        Some(name) => (name.to_string(), position + 1),
+        None => match args.get(position + 1) {
+            Some(name) => (name.clone(), position + 2),
+            // Left for clap to report.
+            None => return Ok(args),
+        },
+    };
+
+    let (path, profiles) = read_profiles()?;
+    let profile = profiles
+        .get(&name)
+        .ok_or_else(|| eyre!("{} has no profile '{}'", path.display(), name))?;
+    let profile_args = profile
+        .args
+        .iter()
+        .map(|arg| expand_arg(arg))
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| eyre!("Profile '{}': {}", name, e))?;
+    args.splice(end..end, profile_args);
+    Ok(args)
+}
+
+/// The sync options of a profile.  Tables and JSON events would interleave
+/// with those of the other profiles, so only log lines are written, each
+/// prefixed with the profile's name.
+fn profile_options(name: &str, profile: &Profile, path: &Path, dry_run: bool) -> Result<SyncArgs> {
+    let args = profile
+        .args
+        .iter()
+        .map(|arg| expand_arg(arg))
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| eyre!("Profile '{}': {}", name, e))?;
+    let mut options = ProfileArgs::try_parse_from(args)
+        .map_err(|e| {
+            eyre!(
+                "Profile '{}' of {} has invalid arguments: {}",
+                name,
+                path.display(),
+                e
+            )
+        })?
+        .sync;
+    options
+        .apply_config_defaults()
+        .map_err(|e| eyre!("Profile '{}': {}", name, e))?;
+
+    // Offline or read-only, every write would fail, so only plan the sync.
+    if dry_run || options.connection.refuses_writes() {
+        options.dry_run = Some(true);
+    }
+    options.summary_only = true;
+    Ok(options)
+}
+
+/// Runs the sync of each profile given with --profiles concurrently, then
+/// prints a row per profile and the combined summary line.
+pub async fn run(options: &AllArgs, terminal: Terminal) -> Result<Outcome> {
+    let (path, profiles) = read_profiles()?;
+    let mut selected: Vec<(String, SyncArgs)> = Vec::new();
+    for name in &options.profiles {
+        if selected.iter().any(|(selected, _)| selected == name) {
+            continue;
+        }
+        let profile = profiles
+            .get(name)
+            .ok_or_else(|| eyre!("{} has no profile '{}'", path.display(), name))?;
+        selected.push((
+            name.clone(),
+            profile_options(name, profile, &path, options.dry_run)?,
+        ));
+    }
+
+    info!(
+        "Syncing {} profiles concurrently: {}",
+        selected.len(),
+        selected
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    // Syncs hold non-Send state across awaits, so they share this thread.
+    let results = LocalSet::new()
+        .run_until(async {
+            let tasks = selected
+                .into_iter()
+                .map(|(name, sync)| {
+                    let span = info_span!("profile", name = %name);
+                    let task = tokio::task::spawn_local(
+                        async move { sync_and_notify(&sync, terminal).await }.instrument(span),
+                    );
+                    (name, task)
+                })
+                .collect::<Vec<_>>();
+
+            let mut results = Vec::new();
+            for (name, task) in tasks {
+                let result = match task.await {
+                    Ok(result) => result,
+                    Err(e) => Err(eyre!("The sync panicked: {}", e)),
+                };
+                if let Err(e) = &result {
+                    error!("Error syncing profile '{}': {:#}", name, e);
+                }
+                results.push((name, result));
+            }
+            results
+        })
+        .await;
+
+    print!("{}", profiles_table(&results).render(terminal));
+    let summaries = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok())
+        .collect::<Vec<_>>();
+    let failed = results.len() - summaries.len();
+    if summaries.is_empty() {
+        return Err(eyre!("Failed to sync all {} profiles", failed));
+    }
+
+    let summary = combine(&summaries, failed);
+    println!("{}", summary);
+    Ok(summary.outcome)
+}
+
+/// Adds up the summaries of the profiles that synced.  The outcome is the
+/// worst of theirs, or a partial failure if other profiles failed outright.
+fn combine(summaries: &[&Summary], failed: usize) -> Summary {
+    let total = |count: &dyn Fn(&Summary) -> usize| summaries.iter().map(|s| count(s)).sum();
+    let outcome = match failed {
+        0 => summaries
+            .iter()
+            .map(|summary| summary.outcome)
+            .max()
+            .unwrap_or(Outcome::InSync),
+        _ => Outcome::PartialFailure,
+    };
+
+    Summary {
+        outcome,
+        repositories: total(&|summary| summary.repositories),
+        failed: total(&|summary| summary.failed) + failed,
+        created: total(&|summary| summary.created),
+        updated: total(&|summary| summary.updated),
+        deleted: total(&|summary| summary.deleted),
+        unchanged: total(&|summary| summary.unchanged),
+        kept: total(&|summary| summary.kept),
+        drifted: total(&|summary| summary.drifted),
+    }
+}
+
+/// A row per profile with its totals or error.
+fn profiles_table(results: &[(String, Result<Summary>)]) -> Table {
+    let mut table = Table::new(&[
+        "PROFILE",
+        "RESULT",
+        "REPOSITORIES",
+        "CREATED",
+        "UPDATED",
+        "DELETED",
+    ]);
+    for (name, result) in results {
+        table.push(match result {
+            Ok(summary) => vec![
+                Cell::new(name),
+                Cell::colored(
+                    summary.outcome.name(),
+                    match summary.outcome {
+                        Outcome::InSync | Outcome::Changed => Color::Green,
+                        Outcome::Drift => Color::Yellow,
+                        Outcome::PartialFailure | Outcome::Cancelled => Color::Red,
+                    },
+                ),
+                Cell::new(summary.repositories),
+                Cell::count(summary.created, Color::Green),
+                Cell::count(summary.updated, Color::Yellow),
+                Cell::count(summary.deleted, Color::Red),
+            ],
+            Err(e) => vec![
+                Cell::new(name),
+                Cell::colored(e, Color::Red),
+                Cell::new("-"),
+                Cell::new("-"),
+                Cell::new("-"),
+                Cell::new("-"),
+            ],
+        });
+    }
+
+    table
+}
@@ -0,0 +1,73 @@
+use std::io::{self, BufRead, Write};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_env_sync::{Conflict, ConflictResolver, Resolution};
+
+/// Asks on the terminal what to do with each conflict, similar to
+/// `git add -p`.
+#[derive(Debug)]
+pub struct PromptResolver {
+    /// Print values in full rather than masked.
+    pub show_values: bool,
+}
+
+impl PromptResolver {
+    fn display_value(&self, value: &str) -> String {
+        if self.show_values {
+            format!("{:?}", value)
+        } else {
+            mask_value(value)
+        }
+    }
+}
+
+impl ConflictResolver for PromptResolver {
+    fn resolve(&self, conflict: &Conflict<'_>) -> Result<Resolution> {
+        let mut stderr = io::stderr().lock();
+        writeln!(
+            stderr,
+            "\n{} in environment '{}' changed remotely since the last sync:",
+            conflict.key, conflict.environment
+        )?;
+        writeln!(
+            stderr,
+            "  remote: {}",
+            conflict
+                .remote_value
+                .map(|value| self.display_value(value))
+                .unwrap_or_else(|| "<deleted>".to_string())
+        )?;
+        writeln!(
+            stderr,
+            "  local:  {}",
+            self.display_value(conflict.local_value)
+        )?;
+
+        let stdin = io::stdin();
+        loop {
+            write!(stderr, "Apply the local value [y,n,a,q,?]? ")?;
+            stderr.flush()?;
+
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer)? == 0 {
+                return Err(eyre!("Reached end of input while resolving conflicts"));
+            }
+
+            match answer.trim() {
+                "y" => return Ok(Resolution::Apply),
+                "n" => return Ok(Resolution::Skip),
+                "a" => return Ok(Resolution::ApplyAll),
+                "q" => return Ok(Resolution::Abort),
+                _ => writeln!(
+                    stderr,
+                    "y - apply the local value\nn - keep the remote value\na - apply this and all remaining conflicts\nq - abort the sync\n? - print help"
+                )?,
+            }
+        }
+    }
+}
+
+/// Hides a value while still hinting at its size.
+pub fn mask_value(value: &str) -> String {
+    format!("******** ({} chars)", value.chars().count())
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use color_eyre::{eyre::eyre, Result};
+use tracing::info;
+
+use crate::{config::ConfigDocument, sync::Syncer};
+
+/// Whether a custom deployment protection rule was enabled or disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionRuleAction {
+    Enable,
+    Disable,
+}
+
+impl fmt::Display for ProtectionRuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enable => f.write_str("enable"),
+            Self::Disable => f.write_str("disable"),
+        }
+    }
+}
+
+/// A custom deployment protection rule that a sync enabled or disabled, or
+/// would have in a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtectionRuleChange {
+    pub environment: String,
+    /// The slug of the rule's Github App.
+    pub app: String,
+    pub action: ProtectionRuleAction,
+}
+
+impl Syncer {
+    /// Makes the custom deployment protection rules of each environment
+    /// listed in the config document's `[protection_rules]` section match
+    /// it: listed apps are enabled, and the rules of other apps disabled.
+    /// Apps are matched by slug, ignoring case, and must be installed on the
+    /// repository.  Environments that aren't synced, or aren't listed, are
+    /// left alone.  In a dry run, nothing is written, and environments that
+    /// don't exist yet are expected to get every listed app.
+    pub(crate) async fn sync_protection_rules(
+        &self,
+        config: &ConfigDocument,
+        environment_names: &[&str],
+        remote_environments: &[String],
+    ) -> Result<Vec<ProtectionRuleChange>> {
+        let mut changes = Vec::new();
+        for (environment, apps) in &config.protection_rules {
+            if !environment_names.contains(&environment.as_str()) {
+                continue;
+            }
+            let change = |app: &str, action| ProtectionRuleChange {
+                environment: environment.clone(),
+                app: app.to_string(),
+                action,
+            };
+
+            let client = self.client_for(environment);
+            if self.options().dry_run && !remote_environments.contains(environment) {
+                for app in apps {
+                    info!(
+                        "Would enable deployment protection rule {} for new environment '{}'",
+                        app, environment
+                    );
+                    changes.push(change(app, ProtectionRuleAction::Enable));
+                }
+                continue;
+            }
+
+            let rules = client.list_protection_rules(environment).await?;
+            let missing = apps
+                .iter()
+                .filter(|app| {
+                    !rules
+                        .iter()
+                        .any(|rule| rule.app.slug.eq_ignore_ascii_case(app))
+                })
+                .collect::<Vec<_>>();
+            let available = match missing.is_empty() {
+                true => Vec::new(),
+                false => client.list_protection_rule_apps(environment).await?,
+            };
+
+            for app in missing {
+                let integration = available
+                    .iter()
+                    .find(|available| available.slug.eq_ignore_ascii_case(app))
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Github App '{}' of [protection_rules] can't protect environment '{}'; is it installed on the repository with access to deployments?",
+                            app,
+                            environment
+                        )
+                    })?;
+                match self.options().dry_run {
+                    true => info!(
+                        "Would enable deployment protection rule {} for environment '{}'",
+                        app, environment
+                    ),
+                    false => {
+                        client
+                            .enable_protection_rule(environment, integration.id)
+                            .await?;
+                        info!(
+                            "Enabled deployment protection rule {} for environment '{}'",
+                            app, environment
+                        );
+                    }
+                }
+                changes.push(change(app, ProtectionRuleAction::Enable));
+            }
+
+            for rule in rules.iter().filter(|rule| {
+                !apps
+                    .iter()
+                    .any(|app| rule.app.slug.eq_ignore_ascii_case(app))
+            }) {
+                match self.options().dry_run {
+                    true => info!(
+                        "Would disable deployment protection rule {} for environment '{}'",
+                        rule.app.slug, environment
+                    ),
+                    false => {
+                        client.disable_protection_rule(environment, rule.id).await?;
+                        info!(
+                            "Disabled deployment protection rule {} for environment '{}'",
+                            rule.app.slug, environment
+                        );
+                    }
+                }
+                changes.push(change(&rule.app.slug, ProtectionRuleAction::Disable));
+            }
+        }
+
+        Ok(changes)
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    process::Command,
+    str::FromStr,
+    sync::Arc,
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::config::ConfigDocument;
+
+/// The scheme of `${ref:KEY}` references, which can't be taken by a provider.
+const REFERENCE_SCHEME: &str = "ref";
+
+/// A source of values for `${scheme:argument}` placeholders in the config
+/// document, such as a secret manager:
+///
+/// ```toml
+/// [production]
+/// HOME_DIR = "${env:HOME}"
+/// API_KEY = "${exec:op read op://ci/api/key}"
+/// ```
+///
+/// Implement this to read values from a backend of your own, and add it to
+/// the [`ProviderRegistry`] given to [`crate::Syncer::with_value_providers`].
+pub trait ValueProvider: Send + Sync {
+    /// The scheme of the placeholders this provider resolves, e.g. `env`.
+    fn scheme(&self) -> &str;
+
+    /// The value of a single placeholder's argument.
+    fn resolve(&self, argument: &str) -> Result<String>;
+
+    /// The values of several arguments, in order.  Providers backed by an API
+    /// that can read many values in one request should override this, as
+    /// every argument in a sync is resolved in a single call.
+    fn resolve_batch(&self, arguments: &[&str]) -> Result<Vec<String>> {
+        arguments
+            .iter()
+            .map(|argument| self.resolve(argument))
+            .collect()
+    }
+}
+
+/// Reads the process environment: `${env:NAME}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvProvider;
+
+impl ValueProvider for EnvProvider {
+    fn scheme(&self) -> &str {
+        "env"
+    }
+
+    fn resolve(&self, argument: &str) -> Result<String> {
+        std::env::var(argument)
+            .map_err(|e| eyre!("Error reading environment variable {}: {}", argument, e))
+    }
+}
+
+/// Runs a command with `sh -c` (`cmd /C` on Windows) and takes its output,
+/// without the trailing newline: `${exec:COMMAND}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecProvider;
+
+impl ValueProvider for ExecProvider {
+    fn scheme(&self) -> &str {
+        "exec"
+    }
+
+    fn resolve(&self, argument: &str) -> Result<String> {
+        let output = match cfg!(windows) {
+            true => Command::new("cmd").args(["/C", argument]).output(),
+            false => Command::new("sh").args(["-c", argument]).output(),
+        }
+        .map_err(|e| eyre!("Error running `{}`: {}", argument, e))?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "`{}` failed with {}: {}",
+                argument,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|_| eyre!("`{}` printed a value that isn't UTF-8", argument))?;
+        Ok(stdout
+            .strip_suffix('\n')
+            .map(|value| value.strip_suffix('\r').unwrap_or(value))
+            .unwrap_or(&stdout)
+            .to_string())
+    }
+}
+
+/// The providers that come with gh-env-sync.  They run with the privileges
+/// of the sync, so none are enabled unless asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinProvider {
+    Env,
+    Exec,
+}
+
+impl BuiltinProvider {
+    fn provider(self) -> Arc<dyn ValueProvider> {
+        match self {
+            Self::Env => Arc::new(EnvProvider),
+            Self::Exec => Arc::new(ExecProvider),
+        }
+    }
+}
+
+impl FromStr for BuiltinProvider {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "env" => Ok(Self::Env),
+            "exec" => Ok(Self::Exec),
+            _ => Err(eyre!(
+                "Unknown value provider '{}', expected one of env, exec",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BuiltinProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Env => "env",
+            Self::Exec => "exec",
+        })
+    }
+}
+
+/// The `(scheme, argument)` of every `${scheme:argument}` placeholder in a
+/// value other than `${ref:KEY}` references, whether or not a provider is
+/// registered for its scheme.
+pub fn find_placeholders(value: &str) -> Vec<(&str, &str)> {
+    let mut placeholders = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let inner = &rest[start + 2..];
+        let Some(end) = inner.find('}') else {
+            break;
+        };
+        if let Some((scheme, argument)) = inner[..end].split_once(':') {
+            if scheme != REFERENCE_SCHEME {
+                placeholders.push((scheme, argument));
+            }
+        }
+        rest = &inner[end + 1..];
+    }
+
+    placeholders
+}
+
+/// The value providers available to a sync, keyed by scheme.  Placeholders
+/// whose scheme has no provider are left as they are, so values that merely
+/// look like placeholders, such as shell snippets, sync unchanged.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: BTreeMap<String, Arc<dyn ValueProvider>>,
+}
+
+impl fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.providers.keys()).finish()
+    }
+}
+
+impl ProviderRegistry {
+    /// A registry without any providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a provider, replacing any other provider of the same scheme.
+    pub fn register(&mut self, provider: impl ValueProvider + 'static) -> Result<()> {
+        self.insert(Arc::new(provider))
+    }
+
+    /// Adds one of the providers that come with gh-env-sync.
+    pub fn register_builtin(&mut self, builtin: BuiltinProvider) -> Result<()> {
+        self.insert(builtin.provider())
+    }
+
+    fn insert(&mut self, provider: Arc<dyn ValueProvider>) -> Result<()> {
+        let scheme = provider.scheme().to_string();
+        if scheme == REFERENCE_SCHEME || scheme.is_empty() || scheme.contains([':', '}']) {
+            return Err(eyre!(
+                "'{}' can't be used as a value provider scheme",
+                scheme
+            ));
+        }
+
+        self.providers.insert(scheme, provider);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// The provider registered for a scheme.
+    pub fn provider(&self, scheme: &str) -> Option<&dyn ValueProvider> {
+        self.providers.get(scheme).map(Arc::as_ref)
+    }
+
+    /// Whether any value in the given environments has a placeholder for a
+    /// registered provider.
+    pub fn has_placeholders(&self, config: &ConfigDocument, environment_names: &[&str]) -> bool {
+        !self.is_empty()
+            && environment_names.iter().any(|name| {
+                config.environments[*name]
+                    .values()
+                    .any(|variable| self.has_placeholders_in(&variable.value))
+            })
+    }
+
+    /// Replaces the placeholders in the given environments with the values
+    /// of their providers.  Each distinct placeholder is resolved once, and
+    /// each provider is called once with all of its arguments.
+    pub fn resolve(&self, config: &mut ConfigDocument, environment_names: &[&str]) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut arguments = BTreeMap::<&str, BTreeSet<String>>::new();
+        for name in environment_names {
+            for variable in config.environments[*name].values() {
+                for (scheme, argument) in self.placeholders(&variable.value) {
+                    arguments
+                        .entry(scheme)
+                        .or_default()
+                        .insert(argument.to_string());
+                }
+            }
+        }
+
+        let values = self.resolve_arguments(arguments)?;
+        for name in environment_names {
+            let Some(environment) = config.environments.get_mut(*name) else {
+                continue;
+            };
+            for variable in environment.values_mut() {
+                variable.value = Self::substitute(&variable.value, &values);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the placeholders in a single value, for values that are
+    /// resolved only when they are written (see
+    /// [`crate::SyncOptions::pipeline_depth`]).
+    pub fn resolve_value(&self, value: &str) -> Result<String> {
+        let mut arguments = BTreeMap::<&str, BTreeSet<String>>::new();
+        for (scheme, argument) in self.placeholders(value) {
+            arguments
+                .entry(scheme)
+                .or_default()
+                .insert(argument.to_string());
+        }
+
+        let values = self.resolve_arguments(arguments)?;
+        Ok(Self::substitute(value, &values))
+    }
+
+    /// Whether a value has a placeholder for a registered provider.
+    pub fn has_placeholders_in(&self, value: &str) -> bool {
+        !self.placeholders(value).is_empty()
+    }
+
+    /// Calls each provider once with all of its arguments, returning the
+    /// values keyed by placeholder.
+    fn resolve_arguments(
+        &self,
+        arguments: BTreeMap<&str, BTreeSet<String>>,
+    ) -> Result<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        for (scheme, arguments) in arguments {
+            let arguments = arguments.iter().map(String::as_str).collect::<Vec<_>>();
+            let resolved = self.providers[scheme]
+                .resolve_batch(&arguments)
+                .map_err(|e| eyre!("Error resolving ${{{}:...}} values: {}", scheme, e))?;
+            if resolved.len() != arguments.len() {
+                return Err(eyre!(
+                    "The '{}' value provider returned {} values for {} placeholders",
+                    scheme,
+                    resolved.len(),
+                    arguments.len()
+                ));
+            }
+            for (argument, value) in arguments.into_iter().zip(resolved) {
+                values.insert(format!("${{{}:{}}}", scheme, argument), value);
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// The `(scheme, argument)` of each placeholder in a value whose scheme
+    /// has a provider.
+    fn placeholders<'a>(&self, value: &'a str) -> Vec<(&str, &'a str)> {
+        let mut placeholders = Vec::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let inner = &rest[start + 2..];
+            let Some(end) = inner.find('}') else {
+                break;
+            };
+            if let Some((scheme, argument)) = inner[..end].split_once(':') {
+                if let Some((scheme, _)) = self.providers.get_key_value(scheme) {
+                    placeholders.push((scheme.as_str(), argument));
+                }
+            }
+            rest = &inner[end + 1..];
+        }
+
+        placeholders
+    }
+
+    /// Replaces each placeholder in a value that has a resolved value.
+    /// Resolved values are inserted as they are, so placeholders in them are
+    /// never resolved in turn.
+    fn substitute(value: &str, values: &HashMap<String, String>) -> String {
+        let mut substituted = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let placeholder = &rest[start..start + end + 1];
+            substituted.push_str(&rest[..start]);
+            substituted.push_str(values.get(placeholder).map_or(placeholder, String::as_str));
+            rest = &rest[start + end + 1..];
+        }
+        substituted.push_str(rest);
+
+        substituted
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::config::Environment;
+
+/// The most variables Github allows in a single environment.
+pub const MAX_VARIABLES_PER_ENVIRONMENT: usize = 200;
+
+/// The largest value Github accepts for a single variable, in bytes.
+pub const MAX_VALUE_BYTES: usize = 48 * 1024;
+
+/// The largest combined size Github allows for a repository's variables,
+/// names and values, in bytes.
+pub const MAX_TOTAL_BYTES: usize = 256 * 1024;
+
+/// A way in which a repository's variables would exceed Github's limits for
+/// Actions variables.  See:
+/// https://docs.github.com/en/actions/learn-github-actions/variables#limits-for-configuration-variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaViolation {
+    TooManyVariables {
+        environment: String,
+        count: usize,
+    },
+    ValueTooLarge {
+        environment: String,
+        key: String,
+        bytes: usize,
+    },
+    TotalTooLarge {
+        bytes: usize,
+    },
+}
+
+impl fmt::Display for QuotaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyVariables { environment, count } => write!(
+                f,
+                "environment '{}' would have {} variables, more than the {} allowed",
+                environment, count, MAX_VARIABLES_PER_ENVIRONMENT
+            ),
+            Self::ValueTooLarge {
+                environment,
+                key,
+                bytes,
+            } => write!(
+                f,
+                "{} in environment '{}' is {} bytes, more than the {} allowed; set `chunk = true` on it to split it across several variables",
+                key, environment, bytes, MAX_VALUE_BYTES
+            ),
+            Self::TotalTooLarge { bytes } => write!(
+                f,
+                "the synced environments' variables would total {} bytes, more than the {} allowed for the whole repository",
+                bytes, MAX_TOTAL_BYTES
+            ),
+        }
+    }
+}
+
+/// Adds up the size of the variables of the environments a sync writes, one
+/// environment at a time, checking each against Github's limits.  Other
+/// environments and the repository's own variables aren't read, so they
+/// aren't counted towards [`MAX_TOTAL_BYTES`]: a sync within it can still
+/// be rejected by Github, but one over it never fits.
+#[derive(Debug, Default)]
+pub struct QuotaUsage {
+    total_bytes: usize,
+}
+
+impl QuotaUsage {
+    /// Checks an environment's variables, as they would be after syncing,
+    /// and adds them to the total of this run's environments.  The total is only reported
+    /// by the environment that takes it over the limit.
+    pub fn check(&mut self, environment: &str, variables: &Environment) -> Vec<QuotaViolation> {
+        let mut violations = Vec::new();
+        if variables.len() > MAX_VARIABLES_PER_ENVIRONMENT {
+            violations.push(QuotaViolation::TooManyVariables {
+                environment: environment.to_string(),
+                count: variables.len(),
+            });
+        }
+
+        let mut keys = variables.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let bytes = variables[key].len();
+            if bytes > MAX_VALUE_BYTES {
+                violations.push(QuotaViolation::ValueTooLarge {
+                    environment: environment.to_string(),
+                    key: key.clone(),
+                    bytes,
+                });
+            }
+        }
+
+        let within_total = self.total_bytes <= MAX_TOTAL_BYTES;
+        self.total_bytes += variables
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum::<usize>();
+        if within_total && self.total_bytes > MAX_TOTAL_BYTES {
+            violations.push(QuotaViolation::TotalTooLarge {
+                bytes: self.total_bytes,
+            });
+        }
+
+        violations
+    }
+}
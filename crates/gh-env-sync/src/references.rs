@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::config::EnvironmentConfig;
+
+const REFERENCE_START: &str = "${ref:";
+
+/// Whether any value in the environment references another key.
+pub fn has_references(environment: &EnvironmentConfig) -> bool {
+    environment
+        .values()
+        .any(|variable| variable.value.contains(REFERENCE_START))
+}
+
+/// The keys a value references with `${ref:KEY}`, uppercased, in the order
+/// they appear.  An unterminated reference ends the list.
+pub fn referenced_keys(value: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(REFERENCE_START) {
+        let reference = &rest[start + REFERENCE_START.len()..];
+        let Some(end) = reference.find('}') else {
+            break;
+        };
+        keys.push(reference[..end].trim().to_uppercase());
+        rest = &reference[end + 1..];
+    }
+
+    keys
+}
+
+/// Replaces every `${ref:KEY}` in the environment's values with the value of
+/// `KEY` in the same environment, so that composite values can be built from
+/// other keys:
+///
+/// ```toml
+/// [production]
+/// DB_PASSWORD = "..."
+/// DATABASE_URL = "postgres://user:${ref:DB_PASSWORD}@host/db"
+/// ```
+///
+/// Keys are matched case-insensitively, like Github does.  Referenced values
+/// may contain references themselves, but not cycles.  This runs after
+/// encrypted and generated values are filled in, so they can be referenced.
+pub fn resolve_references(
+    environment_name: &str,
+    environment: &mut EnvironmentConfig,
+) -> Result<()> {
+    if !has_references(environment) {
+        return Ok(());
+    }
+
+    let raw = environment
+        .iter()
+        .map(|(key, variable)| (key.to_uppercase(), variable.value.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut resolver = Resolver {
+        environment_name,
+        raw: &raw,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    };
+
+    for (key, variable) in environment.iter_mut() {
+        variable.value = resolver.resolve(&key.to_uppercase())?;
+    }
+
+    Ok(())
+}
+
+struct Resolver<'a> {
+    environment_name: &'a str,
+    raw: &'a HashMap<String, String>,
+    resolved: HashMap<String, String>,
+    /// The keys being resolved, outermost first, to detect cycles.
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, key: &str) -> Result<String> {
+        if let Some(value) = self.resolved.get(key) {
+            return Ok(value.clone());
+        }
+
+        if let Some(position) = self.stack.iter().position(|stacked| stacked == key) {
+            let mut cycle = self.stack[position..].to_vec();
+            cycle.push(key.to_string());
+            return Err(eyre!(
+                "Reference cycle in environment '{}': {}",
+                self.environment_name,
+                cycle.join(" -> ")
+            ));
+        }
+
+        self.stack.push(key.to_string());
+        let mut value = String::new();
+        let mut rest = self.raw[key].as_str();
+        while let Some(start) = rest.find(REFERENCE_START) {
+            value.push_str(&rest[..start]);
+
+            let reference = &rest[start + REFERENCE_START.len()..];
+            let end = reference.find('}').ok_or_else(|| {
+                eyre!(
+                    "Unterminated ${{ref:...}} in {} in environment '{}'",
+                    key,
+                    self.environment_name
+                )
+            })?;
+            let referenced = reference[..end].trim().to_uppercase();
+            if !self.raw.contains_key(&referenced) {
+                return Err(eyre!(
+                    "{} in environment '{}' references {}, which is not defined there",
+                    key,
+                    self.environment_name,
+                    referenced
+                ));
+            }
+
+            value.push_str(&self.resolve(&referenced)?);
+            rest = &reference[end + 1..];
+        }
+        value.push_str(rest);
+        self.stack.pop();
+
+        self.resolved.insert(key.to_string(), value.clone());
+        Ok(value)
+    }
+}
@@ -0,0 +1,201 @@
+use color_eyre::{eyre::eyre, Result};
+use tracing::{info, warn};
+
+use crate::{audit::AuditOperation, lock::SyncLock, state::State, sync::Syncer};
+
+/// What renaming a key did, or would do, in a single environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameOutcome {
+    /// The new key was created with the old key's value, and the old key
+    /// deleted.
+    Renamed,
+    /// Only the new key exists, e.g. because an earlier rename finished.
+    AlreadyRenamed,
+    /// Neither key exists.
+    Missing,
+}
+
+/// The outcome of renaming a key in a single environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamedKey {
+    pub environment: String,
+    pub outcome: RenameOutcome,
+}
+
+impl Syncer {
+    /// Renames `old_key` to `new_key` in each of the given environments,
+    /// without a window in which neither is set: the new key is created with
+    /// the old key's value and read back to verify it, and only then is the
+    /// old key deleted.  A rename interrupted after creating the new key can
+    /// be rerun to finish it.  What the state file recorded of the old key
+    /// moves to the new one.  Environments where either key is excluded
+    /// (see [`SyncOptions::exclusions`]) are left out.  With `dry_run`,
+    /// nothing is written.
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub async fn rename_key(
+        &self,
+        environments: &[String],
+        old_key: &str,
+        new_key: &str,
+        dry_run: bool,
+    ) -> Result<Vec<RenamedKey>> {
+        let (old_key, new_key) = (old_key.to_uppercase(), new_key.to_uppercase());
+        if old_key == new_key {
+            return Err(eyre!("{} and {} are the same key", old_key, new_key));
+        }
+
+        if dry_run {
+            return self
+                .apply_rename(environments, &old_key, &new_key, true)
+                .await;
+        }
+
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let result = self
+            .apply_rename(environments, &old_key, &new_key, false)
+            .await;
+        lock.release_after(self.client(), result).await
+    }
+
+    async fn apply_rename(
+        &self,
+        environments: &[String],
+        old_key: &str,
+        new_key: &str,
+        dry_run: bool,
+    ) -> Result<Vec<RenamedKey>> {
+        let mut state = match (&self.options().state_file, dry_run) {
+            (Some(path), false) => Some(State::load(path)?),
+            _ => None,
+        };
+        let repository = self.repository();
+        let exclusions = &self.options().exclusions;
+        let mut renamed = Vec::new();
+        let mut result = Ok(());
+        for environment in environments {
+            if exclusions.excludes_key(environment, old_key)
+                || exclusions.excludes_key(environment, new_key)
+            {
+                info!(
+                    "Not renaming {} in environment '{}', where it is excluded",
+                    old_key, environment
+                );
+                continue;
+            }
+
+            let outcome = match self
+                .rename_key_in(environment, old_key, new_key, dry_run)
+                .await
+            {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            // An interrupted rename may have left the state file behind.
+            if outcome != RenameOutcome::Missing {
+                if let Some(state) = state.as_mut() {
+                    state.rename_key(&repository, environment, old_key, new_key);
+                }
+            }
+            renamed.push(RenamedKey {
+                environment: environment.clone(),
+                outcome,
+            });
+        }
+
+        if let (Some(state), Some(path)) = (&state, &self.options().state_file) {
+            state.save(path)?;
+        }
+        result.map(|_| renamed)
+    }
+
+    async fn rename_key_in(
+        &self,
+        environment: &str,
+        old_key: &str,
+        new_key: &str,
+        dry_run: bool,
+    ) -> Result<RenameOutcome> {
+        let client = self.client_for(environment);
+        let old_value = client
+            .get_environment_variable(environment, old_key)
+            .await?;
+        let new_value = client
+            .get_environment_variable(environment, new_key)
+            .await?;
+
+        let value = match (old_value, new_value) {
+            (None, None) => {
+                warn!(
+                    "Neither {} nor {} exists in environment '{}'",
+                    old_key, new_key, environment
+                );
+                return Ok(RenameOutcome::Missing);
+            }
+            (None, Some(_)) => return Ok(RenameOutcome::AlreadyRenamed),
+            (Some(old_value), Some(new_value)) if old_value != new_value => {
+                return Err(eyre!(
+                    "{} already exists in environment '{}' with a different value than {}",
+                    new_key,
+                    environment,
+                    old_key
+                ));
+            }
+            (Some(old_value), new_value) => {
+                if dry_run {
+                    return Ok(RenameOutcome::Renamed);
+                }
+
+                if new_value.is_none() {
+                    info!(
+                        "Creating {} in environment '{}' with the value of {}",
+                        new_key, environment, old_key
+                    );
+                    client
+                        .create_environment_variable(environment, new_key, &old_value)
+                        .await?;
+                    self.audit(
+                        environment,
+                        new_key,
+                        AuditOperation::Create,
+                        None,
+                        Some(old_value.clone()),
+                    )?;
+                }
+                old_value
+            }
+        };
+
+        // Only delete the old key once the new one is known to hold its
+        // value, so that nothing reading either is left without it.
+        let written = client
+            .get_environment_variable(environment, new_key)
+            .await?;
+        if written.as_deref() != Some(value.as_str()) {
+            return Err(eyre!(
+                "{} in environment '{}' doesn't hold the value of {} after creating it, so {} was not deleted",
+                new_key,
+                environment,
+                old_key,
+                old_key
+            ));
+        }
+
+        info!("Deleting {} from environment '{}'", old_key, environment);
+        client
+            .delete_environment_variable(environment, old_key)
+            .await?;
+        self.audit(
+            environment,
+            old_key,
+            AuditOperation::Delete,
+            Some(value),
+            None,
+        )?;
+
+        Ok(RenameOutcome::Renamed)
+    }
+}
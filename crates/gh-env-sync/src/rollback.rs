@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use color_eyre::{eyre::eyre, Result};
+use tracing::{info, warn};
+
+use crate::{
+    audit::{AuditLog, AuditOperation, AuditRecord},
+    lock::SyncLock,
+    sync::Syncer,
+};
+
+/// A write that restores a single variable to an earlier value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollbackOperation {
+    pub environment: String,
+    pub key: String,
+    /// The value to restore, or `None` if the variable didn't exist and
+    /// should be deleted.
+    pub target: Option<String>,
+}
+
+/// Computes the operations which restore every variable written after the
+/// run `to_run_id` to its value at the end of that run.  `records` are the
+/// contents of the audit log, oldest first.
+pub fn plan_rollback(
+    records: &[AuditRecord],
+    repository: &str,
+    to_run_id: &str,
+) -> Result<Vec<RollbackOperation>> {
+    let cutoff = records
+        .iter()
+        .rposition(|record| record.repository == repository && record.run_id == to_run_id)
+        .ok_or_else(|| {
+            eyre!(
+                "Run {} of {} was not found in the audit log",
+                to_run_id,
+                repository
+            )
+        })?;
+
+    // The first write to each variable after the cutoff holds the value it
+    // had at the end of the target run.
+    let mut targets: BTreeMap<(String, String), Option<String>> = BTreeMap::new();
+    for record in records[cutoff + 1..]
+        .iter()
+        .filter(|record| record.repository == repository)
+    {
+        if record.operation == AuditOperation::CreateEnvironment {
+            warn!(
+                "Not deleting environment '{}' created by run {}; delete it by hand if it is no longer needed",
+                record.environment, record.run_id
+            );
+            continue;
+        }
+        if record.operation == AuditOperation::PutSecret {
+            warn!(
+                "Not restoring secret {} in environment '{}' written by run {}; secret values aren't recorded",
+                record.key, record.environment, record.run_id
+            );
+            continue;
+        }
+        if record.operation == AuditOperation::DeleteEnvironment {
+            warn!(
+                "Not recreating environment '{}' deleted by run {}; its variables weren't recorded",
+                record.environment, record.run_id
+            );
+            continue;
+        }
+
+        targets
+            .entry((record.environment.clone(), record.key.to_uppercase()))
+            .or_insert_with(|| record.previous_value.clone());
+    }
+
+    Ok(targets
+        .into_iter()
+        .map(|((environment, key), target)| RollbackOperation {
+            environment,
+            key,
+            target,
+        })
+        .collect())
+}
+
+impl Syncer {
+    /// Undoes every write recorded in the audit log after the run
+    /// `to_run_id`, and returns the operations applied.  Excluded keys (see
+    /// [`SyncOptions::exclusions`]) are left alone.  The rollback is itself
+    /// recorded in the audit log.  With `dry_run`, nothing is written.
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub async fn rollback(&self, to_run_id: &str, dry_run: bool) -> Result<Vec<RollbackOperation>> {
+        let path = self
+            .options()
+            .audit_log
+            .as_ref()
+            .ok_or_else(|| eyre!("Rolling back requires an audit log"))?;
+        let records = AuditLog::new(path).read()?;
+        let exclusions = &self.options().exclusions;
+        let operations = plan_rollback(&records, &self.repository(), to_run_id)?
+            .into_iter()
+            .filter(|operation| {
+                let excluded = exclusions.excludes_key(&operation.environment, &operation.key);
+                if excluded {
+                    info!(
+                        "Not rolling back {} in environment '{}', where it is excluded",
+                        operation.key, operation.environment
+                    );
+                }
+                !excluded
+            })
+            .collect::<Vec<_>>();
+
+        if dry_run {
+            return Ok(operations);
+        }
+
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let result = self.apply_rollback(&operations).await;
+        let result = lock.release_after(self.client(), result).await;
+
+        result.map(|_| operations)
+    }
+
+    async fn apply_rollback(&self, operations: &[RollbackOperation]) -> Result<()> {
+        for operation in operations {
+            let client = self.client_for(&operation.environment);
+            let current = client
+                .get_environment_variable(&operation.environment, &operation.key)
+                .await?;
+
+            match (current, &operation.target) {
+                (current, target) if current.as_ref() == target.as_ref() => {
+                    info!(
+                        "{} in environment '{}' already has its rolled back value",
+                        operation.key, operation.environment
+                    );
+                }
+                (Some(current), Some(target)) => {
+                    info!(
+                        "Restoring previous value of {} in environment '{}'",
+                        operation.key, operation.environment
+                    );
+                    client
+                        .update_environment_variable(&operation.environment, &operation.key, target)
+                        .await?;
+                    self.audit(
+                        &operation.environment,
+                        &operation.key,
+                        AuditOperation::Update,
+                        Some(current),
+                        Some(target.clone()),
+                    )?;
+                }
+                (None, Some(target)) => {
+                    info!(
+                        "Recreating {} in environment '{}'",
+                        operation.key, operation.environment
+                    );
+                    client
+                        .create_environment_variable(&operation.environment, &operation.key, target)
+                        .await?;
+                    self.audit(
+                        &operation.environment,
+                        &operation.key,
+                        AuditOperation::Create,
+                        None,
+                        Some(target.clone()),
+                    )?;
+                }
+                (Some(current), None) => {
+                    info!(
+                        "Deleting {} from environment '{}'",
+                        operation.key, operation.environment
+                    );
+                    client
+                        .delete_environment_variable(&operation.environment, &operation.key)
+                        .await?;
+                    self.audit(
+                        &operation.environment,
+                        &operation.key,
+                        AuditOperation::Delete,
+                        Some(current),
+                        None,
+                    )?;
+                }
+                (None, None) => unreachable!("handled by the equality arm"),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use serde_json::{json, Value};
+
+use crate::{conflict::ConflictStrategy, transform::Transform};
+
+/// A JSON Schema (draft 2020-12) describing the configuration document, for
+/// editors to validate and autocomplete `github_environments.toml`.  With
+/// taplo, reference it from the top of the config file:
+///
+/// ```toml
+/// #:schema ./github_environments.schema.json
+/// ```
+///
+/// This is maintained by hand alongside [`crate::ConfigDocument`], so changes
+/// to the config format must be mirrored here.
+pub fn config_schema() -> Value {
+    let conflict_strategies = [
+        ConflictStrategy::Overwrite,
+        ConflictStrategy::Keep,
+        ConflictStrategy::Fail,
+        ConflictStrategy::Prompt,
+    ]
+    .map(|strategy| strategy.to_string());
+    let transforms = [
+        Transform::Trim,
+        Transform::JsonMinify,
+        Transform::NormalizeNewlines,
+    ]
+    .map(|transform| transform.to_string());
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "https://github.com/michaelhelvey/gh-env-sync/github_environments.schema.json",
+        "title": "gh-env-sync configuration",
+        "description": "Variables to sync to the environments of a Github repository.  Each top-level table is an environment.",
+        "type": "object",
+        "properties": {
+            "repository": {
+                "description": "The owner/repo this file is synced to when it is found with --config-glob.",
+                "type": "string",
+                "pattern": "^[^/]+/[^/]+$"
+            },
+            "auth": {
+                "description": "Tokens to use instead of --token for particular repositories or environments.",
+                "type": "object",
+                "properties": {
+                    "repositories": {
+                        "description": "Keyed by owner/repo.",
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/tokenSource" }
+                    },
+                    "environments": {
+                        "description": "Keyed by environment name.",
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/tokenSource" }
+                    }
+                },
+                "additionalProperties": false
+            },
+            "org_secrets": {
+                "description": "Secrets of the organization that owns the repository.  Only synced when no --environment is given.",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/organizationSecret" }
+            },
+            "owners": {
+                "description": "The team responsible for each environment, keyed by environment name.",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/owner" }
+            },
+            "branches": {
+                "description": "The environment each branch deploys to, keyed by branch name or glob such as feature/*, for --environment-from-branch.",
+                "type": "object",
+                "additionalProperties": { "type": "string" }
+            },
+            "max_changes": {
+                "description": "The most existing variables a sync may update or delete before it aborts, unless overridden by --max-changes.",
+                "type": "integer",
+                "minimum": 0
+            },
+            "naming": {
+                "description": "How variable names must be written, checked before every sync.",
+                "type": "object",
+                "properties": {
+                    "style": {
+                        "description": "'uppercase' forbids lowercase letters, and 'screaming_snake_case' requires names like API_URL.",
+                        "enum": ["any", "uppercase", "screaming_snake_case"],
+                        "default": "any"
+                    },
+                    "max_length": {
+                        "description": "The longest a variable name may be, in characters.",
+                        "type": "integer",
+                        "minimum": 1
+                    }
+                },
+                "additionalProperties": false
+            },
+            "freeze": {
+                "description": "Change freeze windows, during which syncs to their environments are refused without --override-freeze.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "environments": {
+                            "description": "Names or globs of the frozen environments.  Every environment is frozen if left out.",
+                            "type": "array",
+                            "items": { "type": "string" }
+                        },
+                        "from": {
+                            "description": "The first frozen day, UTC.",
+                            "type": "string",
+                            "pattern": "^\\d{4}-\\d{2}-\\d{2}$"
+                        },
+                        "until": {
+                            "description": "The last frozen day, UTC.",
+                            "type": "string",
+                            "pattern": "^\\d{4}-\\d{2}-\\d{2}$"
+                        },
+                        "schedule": {
+                            "description": "A cron-like schedule of minute, hour, day of month, month and day of week, UTC, e.g. '* 16-23 * * 5' for Friday evenings.  Frozen during every matching minute.",
+                            "type": "string"
+                        },
+                        "reason": {
+                            "description": "Why the environments are frozen, shown when a sync is refused.",
+                            "type": "string"
+                        }
+                    },
+                    "additionalProperties": false
+                }
+            },
+            "protection_rules": {
+                "description": "The slugs of the Github Apps to enable as custom deployment protection rules, keyed by environment name.  Other apps' rules are disabled, and environments left out keep their rules.",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "uniqueItems": true
+                }
+            },
+            "cli": {
+                "description": "Defaults for sync flags, so that everyone syncing the file gets the same safety settings.  Flags given on the command line win, e.g. --prune=false over prune = true.",
+                "type": "object",
+                "properties": {
+                    "on_conflict": {
+                        "description": "--on-conflict.",
+                        "enum": conflict_strategies
+                    },
+                    "output": {
+                        "description": "--output.",
+                        "enum": ["text", "json"]
+                    },
+                    "state_file": {
+                        "description": "--state-file, relative to the config file.",
+                        "type": "string"
+                    },
+                    "prune": {
+                        "description": "--prune, which needs a state file.",
+                        "type": "boolean"
+                    },
+                    "check_shadowing": {
+                        "description": "--check-shadowing.",
+                        "type": "boolean"
+                    },
+                    "strict_secrets": {
+                        "description": "--strict-secrets.",
+                        "type": "boolean"
+                    },
+                    "warn_on_quota": {
+                        "description": "--warn-on-quota.",
+                        "type": "boolean"
+                    },
+                    "fail_fast": {
+                        "description": "--fail-fast.",
+                        "type": "boolean"
+                    },
+                    "dry_run": {
+                        "description": "--dry-run, so that syncing only checks for changes unless --dry-run=false is given.",
+                        "type": "boolean"
+                    },
+                    "max_changes": {
+                        "description": "--max-changes, over the document's own max_changes.",
+                        "type": "integer",
+                        "minimum": 0
+                    }
+                },
+                "additionalProperties": false
+            },
+            "__shared": {
+                "description": "Variables added to every environment that doesn't define them itself, keyed by variable name.",
+                "type": "object",
+                "properties": {
+                    "vars": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/sharedVariable" }
+                    },
+                    "secrets": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/sharedVariable" }
+                    }
+                },
+                "additionalProperties": { "$ref": "#/$defs/sharedVariable" }
+            }
+        },
+        "additionalProperties": { "$ref": "#/$defs/environment" },
+        "$defs": {
+            "transform": {
+                "description": "Normalizations applied to the value, in order, before it is compared and uploaded.",
+                "type": "array",
+                "items": { "enum": transforms }
+            },
+            "expires": {
+                "description": "When the entry stops being synced and `cleanup --expired` deletes it: a date such as 2024-12-31, or a TTL such as 7d counted from its first sync (requires --state-file).",
+                "type": "string",
+                "pattern": "^(\\d{4}-\\d{2}-\\d{2}|\\d+[smhdw])$"
+            },
+            "kind": {
+                "description": "Whether the key is synced as a plaintext variable or an encrypted secret.  Overrides the vars or secrets subsection the key is in.",
+                "enum": ["variable", "secret"],
+                "default": "variable"
+            },
+            "chunk": {
+                "description": "Split a value larger than Github allows across KEY__PART1 to KEY__PARTn variables rather than failing the sync.",
+                "type": "boolean",
+                "default": false
+            },
+            "owner": {
+                "description": "The team responsible for the entry.  --owner syncs only the entries of a single owner.",
+                "type": "string"
+            },
+            "tokenSource": {
+                "type": "object",
+                "properties": {
+                    "token_env": {
+                        "description": "The environment variable holding the token.",
+                        "type": "string"
+                    }
+                },
+                "required": ["token_env"],
+                "additionalProperties": false
+            },
+            "environment": {
+                "description": "The variables of a single environment, keyed by variable name.  Keys starting with _ (see --ignore-prefix) are local-only annotations that are never synced.",
+                "type": "object",
+                "properties": {
+                    "__expires": { "$ref": "#/$defs/expires" },
+                    "vars": {
+                        "description": "The environment's plaintext variables, keyed by variable name.",
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/variable" }
+                    },
+                    "secrets": {
+                        "description": "The environment's secrets, keyed by secret name.  Secrets are encrypted before they are uploaded and can't be read back, so unless a state file records what was written they are written on every sync.",
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/variable" }
+                    }
+                },
+                "additionalProperties": { "$ref": "#/$defs/variable" }
+            },
+            "environmentList": {
+                "type": "array",
+                "items": { "type": "string" },
+                "uniqueItems": true
+            },
+            "sharedVariable": {
+                "anyOf": [
+                    { "$ref": "#/$defs/variable" },
+                    {
+                        "description": "A variable added only to some environments, with the options of any other variable.",
+                        "type": "object",
+                        "properties": {
+                            "only": {
+                                "description": "The environments the variable is added to.",
+                                "$ref": "#/$defs/environmentList"
+                            },
+                            "except": {
+                                "description": "The environments the variable isn't added to.",
+                                "$ref": "#/$defs/environmentList"
+                            }
+                        },
+                        "oneOf": [
+                            { "required": ["only"] },
+                            { "required": ["except"] }
+                        ]
+                    }
+                ]
+            },
+            "variable": {
+                "oneOf": [
+                    {
+                        "description": "The variable's value.  ${ref:KEY} is replaced with the value of KEY in the same environment.",
+                        "type": "string"
+                    },
+                    {
+                        "description": "An age encrypted value with per-key options, decrypted with --age-identity.",
+                        "type": "object",
+                        "properties": {
+                            "age": {
+                                "description": "ASCII armored age ciphertext.",
+                                "type": "string",
+                                "pattern": "^\\s*-----BEGIN AGE ENCRYPTED FILE-----"
+                            },
+                            "on_conflict": {
+                                "description": "What to do when the value changed remotely since the last sync and also differs from the config.  Overrides --on-conflict.",
+                                "enum": conflict_strategies
+                            },
+                            "owner": { "$ref": "#/$defs/owner" },
+                            "expires": { "$ref": "#/$defs/expires" },
+                            "transform": { "$ref": "#/$defs/transform" },
+                            "kind": { "$ref": "#/$defs/kind" },
+                            "chunk": { "$ref": "#/$defs/chunk" }
+                        },
+                        "required": ["age"],
+                        "additionalProperties": false
+                    },
+                    {
+                        "description": "A value generated randomly on the first sync and kept in the state file, with per-key options.  Requires --state-file.",
+                        "type": "object",
+                        "properties": {
+                            "generate": {
+                                "type": "object",
+                                "properties": {
+                                    "length": {
+                                        "type": "integer",
+                                        "minimum": 1,
+                                        "default": 32
+                                    },
+                                    "charset": {
+                                        "enum": ["alnum", "alpha", "numeric", "hex", "printable"],
+                                        "default": "alnum"
+                                    }
+                                },
+                                "additionalProperties": false
+                            },
+                            "on_conflict": {
+                                "description": "What to do when the value changed remotely since the last sync and also differs from the config.  Overrides --on-conflict.",
+                                "enum": conflict_strategies
+                            },
+                            "owner": { "$ref": "#/$defs/owner" },
+                            "expires": { "$ref": "#/$defs/expires" },
+                            "transform": { "$ref": "#/$defs/transform" },
+                            "kind": { "$ref": "#/$defs/kind" },
+                            "chunk": { "$ref": "#/$defs/chunk" }
+                        },
+                        "required": ["generate"],
+                        "additionalProperties": false
+                    },
+                    {
+                        "description": "A value read from a local file, with per-key options.  Paths are relative to the config file, which must be local.",
+                        "type": "object",
+                        "properties": {
+                            "file": {
+                                "description": "The file to read, e.g. a certificate or keystore.",
+                                "type": "string"
+                            },
+                            "encoding": {
+                                "description": "How the file is synced: as UTF-8 text, or base64 encoded for binary files.  `import --write-files` decodes the value back into the file.",
+                                "enum": ["utf8", "base64"],
+                                "default": "utf8"
+                            },
+                            "on_conflict": {
+                                "description": "What to do when the value changed remotely since the last sync and also differs from the config.  Overrides --on-conflict.",
+                                "enum": conflict_strategies
+                            },
+                            "owner": { "$ref": "#/$defs/owner" },
+                            "expires": { "$ref": "#/$defs/expires" },
+                            "transform": { "$ref": "#/$defs/transform" },
+                            "kind": { "$ref": "#/$defs/kind" },
+                            "chunk": { "$ref": "#/$defs/chunk" }
+                        },
+                        "required": ["file"],
+                        "additionalProperties": false
+                    },
+                    {
+                        "description": "The variable's value with per-key options.",
+                        "type": "object",
+                        "properties": {
+                            "value": { "type": "string" },
+                            "on_conflict": {
+                                "description": "What to do when the value changed remotely since the last sync and also differs from the config.  Overrides --on-conflict.",
+                                "enum": conflict_strategies
+                            },
+                            "owner": { "$ref": "#/$defs/owner" },
+                            "expires": { "$ref": "#/$defs/expires" },
+                            "transform": { "$ref": "#/$defs/transform" },
+                            "kind": { "$ref": "#/$defs/kind" },
+                            "chunk": { "$ref": "#/$defs/chunk" }
+                        },
+                        "required": ["value"],
+                        "additionalProperties": false
+                    }
+                ]
+            },
+            "organizationSecret": {
+                "type": "object",
+                "properties": {
+                    "value": { "type": "string" },
+                    "visibility": {
+                        "description": "Which repositories in the organization can use the secret.",
+                        "enum": ["all", "private", "selected"],
+                        "default": "private"
+                    },
+                    "repositories": {
+                        "description": "Repositories that can use the secret when visibility is \"selected\", as a name in the organization or an owner/repo pair.",
+                        "type": "array",
+                        "items": { "type": "string" }
+                    }
+                },
+                "required": ["value"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
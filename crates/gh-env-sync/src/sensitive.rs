@@ -0,0 +1,43 @@
+use crate::config::{EnvironmentConfig, VariableKind};
+
+/// Name endings that suggest a key holds a credential rather than ordinary
+/// configuration.
+const SENSITIVE_SUFFIXES: &[&str] = &[
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "SECRET",
+    "PRIVATE_KEY",
+    "API_KEY",
+    "ACCESS_KEY",
+    "CREDENTIAL",
+    "CREDENTIALS",
+];
+
+/// Whether a key's name suggests that its value is a credential, such as
+/// `GITHUB_TOKEN`, `DB_PASSWORD` or `WEBHOOK_SECRET`.  Keys are matched
+/// case-insensitively, on whole `_`-separated words, so `TOKENIZER_MODEL`
+/// isn't sensitive.
+pub fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_uppercase();
+
+    SENSITIVE_SUFFIXES.iter().any(|suffix| {
+        key == *suffix
+            || key
+                .strip_suffix(suffix)
+                .is_some_and(|rest| rest.ends_with('_'))
+    })
+}
+
+/// The keys of an environment's variables whose names look like
+/// credentials, in key order.  Keys declared as secrets are fine.
+pub fn sensitive_keys(environment: &EnvironmentConfig) -> Vec<&str> {
+    let mut keys = environment
+        .iter()
+        .filter(|(key, variable)| variable.kind == VariableKind::Variable && is_sensitive_key(key))
+        .map(|(key, _)| key.as_str())
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    keys
+}
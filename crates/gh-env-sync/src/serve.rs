@@ -0,0 +1,569 @@
+//! Long-running reconciliation: re-reads the config document and syncs it on
+//! a fixed interval, serving health and metrics over HTTP in the meantime.
+//! With a webhook secret, pushes that change the config file in its Github
+//! repository are pulled down and synced immediately.  With a read token, the
+//! variables of the last successful reconciliation are served read-only.
+
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::RateLimit;
+use gh_env_sync::{
+    audit::unix_timestamp,
+    config::{check_strict, environment_values},
+    parse_config,
+    source::{ConfigSource, SourceAuth},
+    sync::VariableChange,
+    ConfigDocument, ConflictStrategy, SyncOptions, SyncReport, Syncer,
+};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use percent_encoding::percent_decode_str;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    cli::ServeArgs,
+    webhook::{verify_signature, PushEvent},
+};
+
+/// Counters describing every reconciliation since the server started.
+#[derive(Debug, Default)]
+struct Metrics {
+    runs: u64,
+    failures: u64,
+    /// Unix timestamps of the most recent run and successful run.
+    last_run: Option<u64>,
+    last_success: Option<u64>,
+    /// Why the most recent run failed, cleared by the next success.
+    last_error: Option<String>,
+    variables_created: u64,
+    variables_updated: u64,
+    variables_drifted: u64,
+    /// Requests sent to Github by every client, including failed ones.
+    api_requests: u64,
+    /// The default token's rate limit as of the most recent response.
+    rate_limit: Option<RateLimit>,
+}
+
+impl Metrics {
+    fn record(&mut self, result: &Result<SyncReport>) {
+        let now = unix_timestamp();
+        self.runs += 1;
+        self.last_run = Some(now);
+
+        match result {
+            Ok(report) => {
+                self.last_success = Some(now);
+                self.last_error = None;
+                self.variables_created += report.count(VariableChange::Create) as u64;
+                self.variables_updated += report.count(VariableChange::Update) as u64;
+                self.variables_drifted += report.drift_count() as u64;
+            }
+            Err(e) => {
+                self.failures += 1;
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Records the requests made by the syncer so far.
+    fn record_requests(&mut self, syncer: &Syncer) {
+        self.api_requests = syncer.request_count() as u64;
+        if let Some(rate_limit) = syncer.client().request_stats().rate_limit {
+            self.rate_limit = Some(rate_limit);
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut output = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: Option<u64>| {
+            let _ = writeln!(output, "# HELP gh_env_sync_{} {}", name, help);
+            let _ = writeln!(output, "# TYPE gh_env_sync_{} {}", name, kind);
+            if let Some(value) = value {
+                let _ = writeln!(output, "gh_env_sync_{} {}", name, value);
+            }
+        };
+
+        metric(
+            "runs_total",
+            "counter",
+            "Reconciliation runs since the server started.",
+            Some(self.runs),
+        );
+        metric(
+            "failures_total",
+            "counter",
+            "Reconciliation runs that failed.",
+            Some(self.failures),
+        );
+        metric(
+            "last_run_timestamp_seconds",
+            "gauge",
+            "Unix time of the most recent run.",
+            self.last_run,
+        );
+        metric(
+            "last_success_timestamp_seconds",
+            "gauge",
+            "Unix time of the most recent successful run.",
+            self.last_success,
+        );
+        metric(
+            "variables_created_total",
+            "counter",
+            "Variables created on Github.",
+            Some(self.variables_created),
+        );
+        metric(
+            "variables_updated_total",
+            "counter",
+            "Variables updated on Github.",
+            Some(self.variables_updated),
+        );
+        metric(
+            "variables_drifted_total",
+            "counter",
+            "Variables found changed outside of gh-env-sync.",
+            Some(self.variables_drifted),
+        );
+        metric(
+            "api_requests_total",
+            "counter",
+            "Requests sent to the Github API, including failed ones.",
+            Some(self.api_requests),
+        );
+        metric(
+            "rate_limit_remaining",
+            "gauge",
+            "Requests left in the token's Github rate limit as of the most recent response.",
+            self.rate_limit.map(|rate_limit| rate_limit.remaining),
+        );
+        metric(
+            "rate_limit_limit",
+            "gauge",
+            "The token's Github rate limit per hour.",
+            self.rate_limit.map(|rate_limit| rate_limit.limit),
+        );
+
+        output
+    }
+}
+
+/// Accepts `push` webhooks for the repository holding the config file.
+#[derive(Debug)]
+struct Webhook {
+    secret: String,
+    /// `owner/repo` of the repository holding the config file.
+    repository: String,
+    /// Path of the config file within that repository.
+    path: String,
+    /// Sends the sha of each push that changed the config file to the
+    /// reconciliation loop.
+    pushes: UnboundedSender<String>,
+}
+
+impl Webhook {
+    async fn handle(&self, request: Request<Body>) -> (StatusCode, String) {
+        let event = header(&request, "X-GitHub-Event").unwrap_or_default();
+        let signature = header(&request, "X-Hub-Signature-256").unwrap_or_default();
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("error reading body: {}", e),
+                )
+            }
+        };
+
+        if !verify_signature(&self.secret, &body, &signature) {
+            warn!("Rejected webhook delivery with an invalid signature");
+            return (StatusCode::UNAUTHORIZED, "invalid signature".to_string());
+        }
+
+        match event.as_str() {
+            "ping" => return (StatusCode::OK, "pong".to_string()),
+            "push" => {}
+            _ => return (StatusCode::OK, format!("ignored '{}' event", event)),
+        }
+
+        let push: PushEvent = match serde_json::from_slice(&body) {
+            Ok(push) => push,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid push payload: {}", e),
+                )
+            }
+        };
+        debug!("Received push webhook: {:?}", push);
+
+        if !push
+            .repository
+            .full_name
+            .eq_ignore_ascii_case(&self.repository)
+        {
+            return (
+                StatusCode::OK,
+                format!("ignored push to {}", push.repository.full_name),
+            );
+        }
+        if !push.is_default_branch() || !push.touches(&self.path) {
+            return (
+                StatusCode::OK,
+                format!("ignored push that doesn't change {}", self.path),
+            );
+        }
+
+        info!("Push {} changed {}, syncing", push.after, self.path);
+        match self.pushes.send(push.after) {
+            Ok(()) => (StatusCode::ACCEPTED, "sync triggered".to_string()),
+            Err(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server is shutting down".to_string(),
+            ),
+        }
+    }
+}
+
+fn header(request: &Request<Body>, name: &str) -> Option<String> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Serves the variables of the last successful reconciliation to other
+/// tools, read-only.
+#[derive(Debug, Default)]
+struct ReadApi {
+    token: String,
+    /// `owner/repo` of the synced repository.
+    repository: String,
+    /// Resolved variables keyed by environment name, empty until a
+    /// reconciliation succeeds.  Secrets are left out.
+    environments: Mutex<Option<BTreeMap<String, BTreeMap<String, String>>>>,
+}
+
+impl ReadApi {
+    /// Replaces the served variables with those of a resolved config.
+    fn publish(&self, config: &ConfigDocument) {
+        let environments = config
+            .environments
+            .iter()
+            .map(|(name, environment)| {
+                (
+                    name.clone(),
+                    environment_values(environment).into_iter().collect(),
+                )
+            })
+            .collect();
+        *self.environments.lock().unwrap_or_else(|e| e.into_inner()) = Some(environments);
+    }
+
+    /// Answers `GET /repos/:owner/:repo/environments/:env`.
+    fn handle(&self, request: &Request<Body>) -> (StatusCode, String) {
+        let authorization = header(request, "Authorization").unwrap_or_default();
+        let authorized = authorization
+            .strip_prefix("Bearer ")
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes()));
+        if !authorized {
+            return (StatusCode::UNAUTHORIZED, "invalid token".to_string());
+        }
+
+        let path = request.uri().path();
+        let Some((repository, environment_name)) = path
+            .strip_prefix("/repos/")
+            .and_then(|rest| rest.split_once("/environments/"))
+        else {
+            return (StatusCode::NOT_FOUND, "not found".to_string());
+        };
+        // Environment names may contain spaces and other characters clients
+        // percent-encode.
+        let Ok(environment_name) = percent_decode_str(environment_name).decode_utf8() else {
+            return (
+                StatusCode::BAD_REQUEST,
+                "environment name is not valid UTF-8".to_string(),
+            );
+        };
+        if !repository.eq_ignore_ascii_case(&self.repository) {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("{} is not synced by this server", repository),
+            );
+        }
+
+        let environments = self.environments.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(environments) = environments.as_ref() else {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "no reconciliation has succeeded yet".to_string(),
+            );
+        };
+        let Some(variables) = environments.get(environment_name.as_ref()) else {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("environment '{}' is not in the config", environment_name),
+            );
+        };
+
+        let body = serde_json::json!({
+            "repository": self.repository,
+            "environment": environment_name,
+            "variables": variables,
+        });
+        (StatusCode::OK, body.to_string())
+    }
+}
+
+/// Compares two byte strings in time that depends only on their lengths, so
+/// that response times don't reveal how much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// State shared between the reconciliation loop and the HTTP server.
+#[derive(Debug, Default)]
+struct ServerState {
+    metrics: Mutex<Metrics>,
+    webhook: Option<Webhook>,
+    read_api: Option<ReadApi>,
+}
+
+impl ServerState {
+    fn metrics(&self) -> std::sync::MutexGuard<'_, Metrics> {
+        self.metrics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn handle(request: Request<Body>, state: Arc<ServerState>) -> Response<Body> {
+    let response = Response::builder();
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/healthz") => match &state.metrics().last_error {
+            None => response.status(StatusCode::OK).body(Body::from("ok\n")),
+            Some(e) => response
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(Body::from(format!("last reconciliation failed: {}\n", e))),
+        },
+        (&Method::GET, "/metrics") => response
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(state.metrics().render())),
+        (&Method::POST, "/webhook") if state.webhook.is_some() => {
+            let webhook = state.webhook.as_ref().expect("checked by the match guard");
+            let (status, message) = webhook.handle(request).await;
+            response.status(status).body(Body::from(message + "\n"))
+        }
+        (&Method::GET, path) if path.starts_with("/repos/") && state.read_api.is_some() => {
+            let read_api = state.read_api.as_ref().expect("checked by the match guard");
+            match read_api.handle(&request) {
+                (StatusCode::OK, body) => response
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body + "\n")),
+                (status, message) => response.status(status).body(Body::from(message + "\n")),
+            }
+        }
+        _ => response
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("not found\n")),
+    };
+
+    response.expect("responses are built from valid parts")
+}
+
+async fn serve_http(address: SocketAddr, state: Arc<ServerState>) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(request, state).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .map_err(|e| eyre!("Error listening on {}: {}", address, e))?
+        .serve(make_service);
+    info!("Serving /healthz and /metrics on http://{}", address);
+
+    server
+        .await
+        .map_err(|e| eyre!("Error serving HTTP on {}: {}", address, e))
+}
+
+/// Re-reads the config document and syncs every environment in it, then
+/// publishes the resolved variables to the read API, if it is enabled.
+async fn reconcile(
+    syncer: &Syncer,
+    options: &ServeArgs,
+    read_api: Option<&ReadApi>,
+) -> Result<SyncReport> {
+    let source: ConfigSource = options.config_path.parse()?;
+    let auth = SourceAuth::from_env(Some(syncer.client()));
+    let mut config = match options.connection.strict_config {
+        true => source.load_strict(&auth).await?,
+        false => source.load(&auth).await?,
+    };
+    options.connection.decrypt_config(&mut config)?;
+    let report = syncer.sync(&config, None).await?;
+
+    if let Some(read_api) = read_api {
+        // The sync recorded any generated values in the state file, so this
+        // reads them back rather than generating new ones.
+        syncer.resolve_values(&mut config)?;
+        read_api.publish(&config);
+    }
+
+    Ok(report)
+}
+
+/// Replaces a local config file with its contents at the pushed commit, so
+/// that later interval runs also use it.  Remote sources are re-read on
+/// every run anyway.
+async fn pull_config(syncer: &Syncer, options: &ServeArgs, sha: &str) -> Result<()> {
+    if !options.config_path.parse::<ConfigSource>()?.is_local() {
+        return Ok(());
+    }
+
+    let repository = options.config_repository()?;
+    let (owner, name) = repository.split_once('/').ok_or_else(|| {
+        eyre!("Expected --config-repository to be a owner/repo_name pair, e.g. rust-lang/rust-lang")
+    })?;
+    let path = options.config_repository_path();
+    let contents = syncer
+        .client()
+        .get_repository_file(owner, name, &path, sha)
+        .await?
+        .ok_or_else(|| eyre!("{} was removed from {}/{} in {}", path, owner, name, sha))?;
+
+    // Don't replace a working config with one that can't be synced.
+    if options.connection.strict_config {
+        check_strict(&contents)?;
+    }
+    parse_config(&contents)?;
+    std::fs::write(&options.config_path, contents)
+        .map_err(|e| eyre!("Error writing config file {}: {}", options.config_path, e))
+}
+
+/// Reconciles on the configured interval until the HTTP server fails.
+pub async fn run(options: &ServeArgs) -> Result<()> {
+    if options.on_conflict == ConflictStrategy::Prompt {
+        return Err(eyre!(
+            "The 'prompt' conflict strategy can't be used by `serve`, which runs unattended"
+        ));
+    }
+
+    // Token overrides are only read at startup, since clients can't be
+    // swapped out of a running syncer.
+    let auth = options
+        .connection
+        .load_config(&options.config_path)
+        .await?
+        .auth;
+    let syncer = options
+        .connection
+        .syncer(
+            &auth,
+            SyncOptions {
+                graphql: options.graphql,
+                lock: options.write.lock,
+                state_file: options.state_file.clone(),
+                on_conflict: options.on_conflict,
+                audit_log: options.write.audit_log.clone(),
+                ignore_prefix: Some(options.ignore_prefix.clone()),
+                apply_strategy: options.apply_strategy,
+                fail_fast: options.fail_fast,
+                dry_run: false,
+                strict_secrets: options.strict_secrets,
+                warn_on_quota: options.warn_on_quota,
+                missing_environments: options.missing_environments(),
+                prune: options.prune,
+                max_changes: options.max_changes,
+                checkpoint_file: None,
+                check_shadowing: false,
+                override_freeze: false,
+                pipeline_depth: None,
+                exclusions: options.connection.exclusions_for(&options.config_path)?,
+            },
+            None,
+        )
+        .await?
+        .with_event_handler(crate::log_event);
+
+    let (pushes, mut pushed) = unbounded_channel();
+    let config_repository = options.config_repository()?;
+    let webhook = options.webhook_secret.as_ref().map(|secret| Webhook {
+        secret: secret.clone(),
+        repository: config_repository,
+        path: options.config_repository_path(),
+        pushes,
+    });
+    let read_api = match &options.read_token {
+        Some(token) => Some(ReadApi {
+            token: token.clone(),
+            repository: options.connection.repository()?.to_string(),
+            ..ReadApi::default()
+        }),
+        None => None,
+    };
+    let state = Arc::new(ServerState {
+        webhook,
+        read_api,
+        ..ServerState::default()
+    });
+    let mut server = tokio::spawn(serve_http(options.listen, state.clone()));
+
+    let mut ticks = interval(options.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            // Without a webhook the sender is dropped, which disables this
+            // branch.
+            Some(sha) = pushed.recv() => {
+                if let Err(e) = pull_config(&syncer, options, &sha).await {
+                    error!("Error pulling config from push {}: {}", sha, e);
+                    state.metrics().record(&Err(e));
+                    continue;
+                }
+                // The push was just synced, so wait a full interval.
+                ticks.reset();
+            }
+            result = &mut server => {
+                return result.map_err(|e| eyre!("HTTP server task failed: {}", e))?;
+            }
+        }
+
+        info!("Reconciling {}", options.config_path);
+        let result = reconcile(&syncer, options, state.read_api.as_ref()).await;
+        match &result {
+            Ok(report) => info!(
+                "Reconciled ({} created, {} updated, {} drifted remotely); next run in {:?}",
+                report.count(VariableChange::Create),
+                report.count(VariableChange::Update),
+                report.drift_count(),
+                options.interval
+            ),
+            // Keep running so that a bad config push or a Github outage is
+            // retried on the next tick.
+            Err(e) => error!("Reconciliation failed: {}", e),
+        }
+        let mut metrics = state.metrics();
+        metrics.record(&result);
+        metrics.record_requests(&syncer);
+    }
+}
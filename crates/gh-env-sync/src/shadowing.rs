@@ -0,0 +1,89 @@
+use std::{collections::HashMap, fmt};
+
+use crate::config::Environment;
+
+/// Where an Actions variable is defined.  A workflow job that uses an
+/// environment sees the value of the most specific level that defines it:
+/// the environment's, then the repository's, then the organization's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VariableLevel {
+    Environment,
+    Repository,
+    Organization,
+}
+
+impl fmt::Display for VariableLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Environment => "environment",
+            Self::Repository => "repository",
+            Self::Organization => "organization",
+        })
+    }
+}
+
+/// The repository and organization variables a repository's workflows
+/// inherit, keyed by name as Github returns them, uppercased.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InheritedVariables {
+    pub repository: HashMap<String, String>,
+    /// The organization variables shared with the repository.
+    pub organization: HashMap<String, String>,
+}
+
+/// A configured environment variable that is also defined at the
+/// repository or organization level, so which value a workflow sees depends
+/// on whether its job uses the environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowedVariable {
+    pub environment: String,
+    pub key: String,
+    /// Every level defining the variable with its value, most specific
+    /// first, so the first is the value jobs using the environment see.
+    pub levels: Vec<(VariableLevel, String)>,
+}
+
+impl ShadowedVariable {
+    /// The levels whose values jobs using the environment don't see.
+    pub fn shadowed_levels(&self) -> impl Iterator<Item = VariableLevel> + '_ {
+        self.levels.iter().skip(1).map(|(level, _)| *level)
+    }
+
+    /// Whether the levels disagree on the value, so that it matters which
+    /// one a job sees.
+    pub fn differs(&self) -> bool {
+        self.levels
+            .iter()
+            .any(|(_, value)| *value != self.levels[0].1)
+    }
+}
+
+/// Finds the environment's configured variables that are also defined at
+/// the repository or organization level, in key order.
+pub fn find_shadowed(
+    environment: &str,
+    values: &Environment,
+    inherited: &InheritedVariables,
+) -> Vec<ShadowedVariable> {
+    let mut keys = values.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let name = key.to_uppercase();
+            let mut levels = vec![(VariableLevel::Environment, values[key].clone())];
+            if let Some(value) = inherited.repository.get(&name) {
+                levels.push((VariableLevel::Repository, value.clone()));
+            }
+            if let Some(value) = inherited.organization.get(&name) {
+                levels.push((VariableLevel::Organization, value.clone()));
+            }
+
+            (levels.len() > 1).then(|| ShadowedVariable {
+                environment: environment.to_string(),
+                key: key.clone(),
+                levels,
+            })
+        })
+        .collect()
+}
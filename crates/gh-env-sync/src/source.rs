@@ -0,0 +1,358 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use gh_client::GithubEnvClient;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::{
+    audit::unix_timestamp,
+    config::{check_strict, load_config, parse_config, ConfigDocument},
+    expiry::civil_from_days,
+    files::{config_dir, read_files, reject_files},
+};
+
+/// Where a configuration document is read from.  Parsed from `--config-path`:
+///
+/// * `path/to/file.toml` - a local file
+/// * `https://example.com/config.toml` - fetched over HTTP(S)
+/// * `s3://bucket/path/to/config.toml` - an S3 object
+/// * `git::owner/repo@ref:path/to/config.toml` - a file in a Github
+///   repository at a branch, tag or commit.  `@ref` may be left out to use
+///   the default branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    File(PathBuf),
+    Url(String),
+    S3 {
+        bucket: String,
+        key: String,
+    },
+    Git {
+        owner: String,
+        repository: String,
+        reference: Option<String>,
+        path: String,
+    },
+}
+
+impl FromStr for ConfigSource {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with("https://") || s.starts_with("http://") {
+            return Ok(ConfigSource::Url(s.to_string()));
+        }
+
+        if let Some(location) = s.strip_prefix("s3://") {
+            let (bucket, key) = location
+                .split_once('/')
+                .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+                .ok_or_else(|| {
+                    eyre!(
+                        "Expected an S3 config source like s3://bucket/key, got '{}'",
+                        s
+                    )
+                })?;
+
+            return Ok(ConfigSource::S3 {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+
+        if let Some(location) = s.strip_prefix("git::") {
+            let invalid = || {
+                eyre!(
+                    "Expected a git config source like git::owner/repo@ref:path/to/file, got '{}'",
+                    s
+                )
+            };
+            let (repository, path) = location.split_once(':').ok_or_else(invalid)?;
+            let (repository, reference) = match repository.split_once('@') {
+                Some((repository, reference)) => (repository, Some(reference.to_string())),
+                None => (repository, None),
+            };
+            let (owner, repository) = repository.split_once('/').ok_or_else(invalid)?;
+            if owner.is_empty() || repository.is_empty() || path.is_empty() {
+                return Err(invalid());
+            }
+
+            return Ok(ConfigSource::Git {
+                owner: owner.to_string(),
+                repository: repository.to_string(),
+                reference,
+                path: path.to_string(),
+            });
+        }
+
+        Ok(ConfigSource::File(PathBuf::from(s)))
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Url(url) => write!(f, "{}", url),
+            ConfigSource::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+            ConfigSource::Git {
+                owner,
+                repository,
+                reference,
+                path,
+            } => {
+                write!(f, "git::{}/{}", owner, repository)?;
+                if let Some(reference) = reference {
+                    write!(f, "@{}", reference)?;
+                }
+                write!(f, ":{}", path)
+            }
+        }
+    }
+}
+
+/// AWS credentials for `s3://` sources.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    pub region: String,
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("region", &self.region)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AwsCredentials {
+    /// Reads credentials from the standard `AWS_*` environment variables.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            region: std::env::var("AWS_REGION")
+                .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|_| "us-east-1".to_string()),
+        })
+    }
+}
+
+/// Credentials for each kind of remote config source.  Sources are fetched
+/// anonymously when their credentials are missing.
+#[derive(Debug, Default)]
+pub struct SourceAuth<'a> {
+    /// Sent as a bearer token to `https://` sources.
+    pub http_bearer_token: Option<String>,
+    /// Signs requests for `s3://` sources.
+    pub aws: Option<AwsCredentials>,
+    /// Reads `git::` sources, which can't be fetched without one.
+    pub github: Option<&'a GithubEnvClient>,
+}
+
+impl<'a> SourceAuth<'a> {
+    /// Reads `https://` and `s3://` credentials from the environment:
+    /// `GH_ENV_SYNC_CONFIG_TOKEN` and the standard `AWS_*` variables.
+    pub fn from_env(github: Option<&'a GithubEnvClient>) -> Self {
+        Self {
+            http_bearer_token: std::env::var("GH_ENV_SYNC_CONFIG_TOKEN").ok(),
+            aws: AwsCredentials::from_env(),
+            github,
+        }
+    }
+}
+
+impl ConfigSource {
+    /// Whether the source is a local file.
+    pub fn is_local(&self) -> bool {
+        matches!(self, ConfigSource::File(_))
+    }
+
+    /// Reads and parses the configuration document from this source.  Only
+    /// local files may have `file` values, which are read relative to them.
+    pub async fn load(&self, auth: &SourceAuth<'_>) -> Result<ConfigDocument> {
+        match self {
+            ConfigSource::File(path) => load_config(path),
+            _ => self.read_files(parse_config(&self.fetch(auth).await?)?),
+        }
+    }
+
+    /// Like [`ConfigSource::load`], but first rejects documents that fail
+    /// [`check_strict`].
+    pub async fn load_strict(&self, auth: &SourceAuth<'_>) -> Result<ConfigDocument> {
+        let contents = self.fetch(auth).await?;
+        check_strict(&contents).map_err(|e| eyre!("{}: {}", self, e))?;
+        self.read_files(parse_config(&contents)?)
+    }
+
+    fn read_files(&self, mut config: ConfigDocument) -> Result<ConfigDocument> {
+        match self {
+            ConfigSource::File(path) => read_files(&mut config, config_dir(path))?,
+            _ => reject_files(&config, self)?,
+        }
+        Ok(config)
+    }
+
+    /// Reads the configuration document from this source without parsing
+    /// it.
+    pub async fn fetch(&self, auth: &SourceAuth<'_>) -> Result<String> {
+        debug!("Fetching config document from {}", self);
+
+        match self {
+            ConfigSource::File(path) => std::fs::read_to_string(path)
+                .map_err(|e| eyre!("Error reading config file {}: {}", path.display(), e)),
+            ConfigSource::Url(url) => {
+                let mut request = Client::new().get(url);
+                if let Some(token) = &auth.http_bearer_token {
+                    request = request.bearer_auth(token);
+                }
+
+                let response = request.send().await?;
+                match response.error_for_status() {
+                    Ok(response) => Ok(response.text().await?),
+                    Err(e) => Err(eyre!("Error fetching config from {}: {}", url, e)),
+                }
+            }
+            ConfigSource::S3 { bucket, key } => fetch_s3(bucket, key, auth.aws.as_ref()).await,
+            ConfigSource::Git {
+                owner,
+                repository,
+                reference,
+                path,
+            } => {
+                let client = auth.github.ok_or_else(|| {
+                    eyre!("A Github client is required to read config from {}", self)
+                })?;
+                let reference = reference.as_deref().unwrap_or("HEAD");
+
+                client
+                    .get_repository_file(owner, repository, path, reference)
+                    .await?
+                    .ok_or_else(|| eyre!("Config file {} does not exist", self))
+            }
+        }
+    }
+}
+
+/// Gets an S3 object, signing the request with AWS Signature Version 4 when
+/// credentials are available.  See:
+/// https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html
+async fn fetch_s3(bucket: &str, key: &str, credentials: Option<&AwsCredentials>) -> Result<String> {
+    let region = credentials.map_or("us-east-1", |credentials| credentials.region.as_str());
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+    let path = format!("/{}", uri_encode(key));
+    let url = format!("https://{}{}", host, path);
+
+    let mut request = Client::new().get(&url);
+    if let Some(credentials) = credentials {
+        // The body of a GET is empty.
+        let payload_hash = hex(&Sha256::digest(b""));
+        let (date_time, date) = amz_date(unix_timestamp());
+
+        let mut headers = vec![
+            ("host", host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", date_time.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect::<String>();
+        let canonical_request = format!(
+            "GET\n{}\n\n{}\n{}\n{}",
+            path, canonical_headers, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            date_time,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [date.as_str(), region, "s3", "aws4_request"].iter().fold(
+            format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        request = request.header(
+            "Authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key_id, scope, signed_headers, signature
+            ),
+        );
+    }
+
+    let response = request.send().await?;
+    match response.error_for_status() {
+        Ok(response) => Ok(response.text().await?),
+        Err(e) => Err(eyre!(
+            "Error fetching config from s3://{}/{}: {}",
+            bucket,
+            key,
+            e
+        )),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Percent-encodes an S3 key, leaving `/` separators alone.
+fn uri_encode(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Formats a unix timestamp as the `YYYYMMDD'T'HHMMSS'Z'` date-time and
+/// `YYYYMMDD` date used by AWS signatures.
+fn amz_date(timestamp: u64) -> (String, String) {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    let seconds = timestamp % 86_400;
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let date_time = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    );
+    (date_time, date)
+}
@@ -0,0 +1,407 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+/// Hashes of the values applied to a single environment, keyed by uppercased
+/// variable name.
+pub type EnvironmentState = BTreeMap<String, String>;
+
+/// A variable a sync deleted, e.g. with `--prune`, so that `undelete` can
+/// tell what was deleted and when.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    /// The hash of the value last applied, if the tool applied one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_hash: Option<String>,
+    /// Seconds since the unix epoch.
+    pub deleted_at: u64,
+}
+
+/// A secret the tool wrote.  Secret values can't be read back, so a secret
+/// is only written again once its configured value changes, or once it was
+/// updated on Github since.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedSecret {
+    pub value_hash: String,
+    /// When Github last updated the secret after it was written, as an ISO
+    /// 8601 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+impl AppliedSecret {
+    pub fn new(value: &str, updated_at: Option<String>) -> Self {
+        Self {
+            value_hash: hash_value(value),
+            updated_at,
+        }
+    }
+
+    /// Whether writing `value` again would change nothing: it's the value
+    /// written, and the remote secret, last updated at `remote_updated_at`,
+    /// wasn't updated since.
+    pub fn is_current(&self, value: &str, remote_updated_at: Option<&str>) -> bool {
+        match (&self.updated_at, remote_updated_at) {
+            // ISO 8601 timestamps in UTC sort chronologically.
+            (Some(updated_at), Some(remote_updated_at)) => {
+                self.value_hash == hash_value(value) && remote_updated_at <= updated_at.as_str()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What the tool last applied to a single repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryState {
+    pub environments: BTreeMap<String, EnvironmentState>,
+    /// Values of `generate`d variables, keyed by environment and then by
+    /// uppercased variable name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub generated: BTreeMap<String, BTreeMap<String, String>>,
+    /// When the tool first applied each environment, in seconds since the
+    /// unix epoch, which `__expires` TTLs count from.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub created: BTreeMap<String, u64>,
+    /// When the tool first applied each variable with an `expires` TTL,
+    /// keyed by environment and then by uppercased variable name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub created_variables: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Variables deleted by syncs and not recreated since, keyed by
+    /// environment and then by uppercased variable name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tombstones: BTreeMap<String, BTreeMap<String, Tombstone>>,
+    /// Environment secrets written by syncs, keyed by environment and then
+    /// by uppercased secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, BTreeMap<String, AppliedSecret>>,
+    /// Secrets of the repository's organization written by syncs, keyed by
+    /// uppercased secret name.  Their hashes also cover the visibility and
+    /// selected repositories.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub organization_secrets: BTreeMap<String, AppliedSecret>,
+}
+
+/// A record of the values applied by previous syncs, used to tell changes
+/// made outside the tool ("drift") apart from changes made in the config.
+/// Only SHA-256 hashes of configured values are stored, but generated values
+/// are stored as-is so that later syncs can reuse them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    /// Keyed by `owner/repo`.
+    pub repositories: BTreeMap<String, RepositoryState>,
+}
+
+impl State {
+    /// Reads the state file at the given path.  A missing file is treated as
+    /// empty state, since there is nothing recorded before the first sync.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            debug!("No state file at {}, starting fresh", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eyre!("Error reading state file {}: {}", path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| eyre!("Error parsing state file {}: {}", path.display(), e))
+    }
+
+    /// Writes the state to the given path.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        debug!("Writing state file {}", path.display());
+
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .map_err(|e| eyre!("Error writing state file {}: {}", path.display(), e))
+    }
+
+    /// The recorded state of a single environment, if any.
+    pub fn environment(&self, repository: &str, environment: &str) -> Option<&EnvironmentState> {
+        self.repositories
+            .get(repository)
+            .and_then(|repository| repository.environments.get(environment))
+    }
+
+    /// Records that the given value was applied, which buries any tombstone
+    /// of the variable.
+    pub fn record(&mut self, repository: &str, environment: &str, key: &str, value: &str) {
+        let repository = self.repositories.entry(repository.to_string()).or_default();
+        if let Some(tombstones) = repository.tombstones.get_mut(environment) {
+            tombstones.remove(&key.to_uppercase());
+        }
+        repository
+            .environments
+            .entry(environment.to_string())
+            .or_default()
+            .insert(key.to_uppercase(), hash_value(value));
+    }
+
+    /// Forgets a deleted variable like [`State::forget`], but leaves a
+    /// tombstone with the hash of its last applied value.
+    pub fn record_deleted(
+        &mut self,
+        repository: &str,
+        environment: &str,
+        key: &str,
+        timestamp: u64,
+    ) {
+        let value_hash = self
+            .environment(repository, environment)
+            .and_then(|applied| applied.get(&key.to_uppercase()))
+            .cloned();
+        self.forget(repository, environment, Some(key));
+        self.repositories
+            .entry(repository.to_string())
+            .or_default()
+            .tombstones
+            .entry(environment.to_string())
+            .or_default()
+            .insert(
+                key.to_uppercase(),
+                Tombstone {
+                    value_hash,
+                    deleted_at: timestamp,
+                },
+            );
+    }
+
+    /// The tombstone of a deleted variable, if any.
+    pub fn tombstone(&self, repository: &str, environment: &str, key: &str) -> Option<&Tombstone> {
+        self.repositories
+            .get(repository)?
+            .tombstones
+            .get(environment)?
+            .get(&key.to_uppercase())
+    }
+
+    /// The environments of a repository holding a tombstone of `key`.
+    pub fn tombstoned_environments(&self, repository: &str, key: &str) -> Vec<String> {
+        self.repositories
+            .get(repository)
+            .map(|repository| {
+                repository
+                    .tombstones
+                    .iter()
+                    .filter(|(_, tombstones)| tombstones.contains_key(&key.to_uppercase()))
+                    .map(|(environment, _)| environment.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes the tombstone of a variable that was restored.
+    pub fn remove_tombstone(&mut self, repository: &str, environment: &str, key: &str) {
+        if let Some(tombstones) = self
+            .repositories
+            .get_mut(repository)
+            .and_then(|repository| repository.tombstones.get_mut(environment))
+        {
+            tombstones.remove(&key.to_uppercase());
+        }
+    }
+
+    /// Records when an environment, or with `key` one of its variables, was
+    /// first applied.  Later calls leave the recorded time alone.
+    pub fn record_created(
+        &mut self,
+        repository: &str,
+        environment: &str,
+        key: Option<&str>,
+        timestamp: u64,
+    ) {
+        let repository = self.repositories.entry(repository.to_string()).or_default();
+        match key {
+            Some(key) => repository
+                .created_variables
+                .entry(environment.to_string())
+                .or_default()
+                .entry(key.to_uppercase())
+                .or_insert(timestamp),
+            None => repository
+                .created
+                .entry(environment.to_string())
+                .or_insert(timestamp),
+        };
+    }
+
+    /// When an environment, or with `key` one of its variables, was first
+    /// applied, if recorded.
+    pub fn created(&self, repository: &str, environment: &str, key: Option<&str>) -> Option<u64> {
+        let repository = self.repositories.get(repository)?;
+        match key {
+            Some(key) => repository
+                .created_variables
+                .get(environment)?
+                .get(&key.to_uppercase())
+                .copied(),
+            None => repository.created.get(environment).copied(),
+        }
+    }
+
+    /// Forgets everything recorded about a deleted environment, or with
+    /// `key` a deleted variable.
+    pub fn forget(&mut self, repository: &str, environment: &str, key: Option<&str>) {
+        let Some(repository) = self.repositories.get_mut(repository) else {
+            return;
+        };
+
+        match key {
+            Some(key) => {
+                let key = key.to_uppercase();
+                if let Some(applied) = repository.environments.get_mut(environment) {
+                    applied.remove(&key);
+                }
+                if let Some(generated) = repository.generated.get_mut(environment) {
+                    generated.remove(&key);
+                }
+                if let Some(created) = repository.created_variables.get_mut(environment) {
+                    created.remove(&key);
+                }
+                if let Some(secrets) = repository.secrets.get_mut(environment) {
+                    secrets.remove(&key);
+                }
+            }
+            None => {
+                repository.environments.remove(environment);
+                repository.generated.remove(environment);
+                repository.created.remove(environment);
+                repository.created_variables.remove(environment);
+                repository.tombstones.remove(environment);
+                repository.secrets.remove(environment);
+            }
+        }
+    }
+
+    /// Moves what was recorded about a variable to its new name after it was
+    /// renamed on Github, so that the next sync neither reports the new key
+    /// as drift nor leaves it out of `--prune`.
+    pub fn rename_key(
+        &mut self,
+        repository: &str,
+        environment: &str,
+        old_key: &str,
+        new_key: &str,
+    ) {
+        let Some(repository) = self.repositories.get_mut(repository) else {
+            return;
+        };
+
+        let (old_key, new_key) = (old_key.to_uppercase(), new_key.to_uppercase());
+        move_entry(
+            repository.environments.get_mut(environment),
+            &old_key,
+            &new_key,
+        );
+        move_entry(
+            repository.generated.get_mut(environment),
+            &old_key,
+            &new_key,
+        );
+        move_entry(
+            repository.created_variables.get_mut(environment),
+            &old_key,
+            &new_key,
+        );
+    }
+
+    /// The secrets of an environment written by previous syncs, keyed by
+    /// uppercased secret name.
+    pub fn secrets(
+        &self,
+        repository: &str,
+        environment: &str,
+    ) -> Option<&BTreeMap<String, AppliedSecret>> {
+        self.repositories
+            .get(repository)
+            .and_then(|repository| repository.secrets.get(environment))
+    }
+
+    /// Records that an environment secret was written.
+    pub fn record_secret(
+        &mut self,
+        repository: &str,
+        environment: &str,
+        key: &str,
+        secret: AppliedSecret,
+    ) {
+        self.repositories
+            .entry(repository.to_string())
+            .or_default()
+            .secrets
+            .entry(environment.to_string())
+            .or_default()
+            .insert(key.to_uppercase(), secret);
+    }
+
+    /// An organization secret written by a previous sync, if any.
+    pub fn organization_secret(&self, repository: &str, name: &str) -> Option<&AppliedSecret> {
+        self.repositories
+            .get(repository)?
+            .organization_secrets
+            .get(&name.to_uppercase())
+    }
+
+    /// Records that an organization secret was written.
+    pub fn record_organization_secret(
+        &mut self,
+        repository: &str,
+        name: &str,
+        secret: AppliedSecret,
+    ) {
+        self.repositories
+            .entry(repository.to_string())
+            .or_default()
+            .organization_secrets
+            .insert(name.to_uppercase(), secret);
+    }
+
+    /// The value previously generated for a variable, if any.
+    pub fn generated(&self, repository: &str, environment: &str, key: &str) -> Option<&str> {
+        self.repositories
+            .get(repository)
+            .and_then(|repository| repository.generated.get(environment))
+            .and_then(|environment| environment.get(&key.to_uppercase()))
+            .map(String::as_str)
+    }
+
+    /// Records a newly generated value for reuse by later syncs.
+    pub fn record_generated(
+        &mut self,
+        repository: &str,
+        environment: &str,
+        key: &str,
+        value: &str,
+    ) {
+        self.repositories
+            .entry(repository.to_string())
+            .or_default()
+            .generated
+            .entry(environment.to_string())
+            .or_default()
+            .insert(key.to_uppercase(), value.to_string());
+    }
+}
+
+/// Hex encoded SHA-256 hash of a value.
+pub fn hash_value(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Moves the entry of `old_key`, if there is one, to `new_key`.
+fn move_entry<T>(entries: Option<&mut BTreeMap<String, T>>, old_key: &str, new_key: &str) {
+    let Some(entries) = entries else {
+        return;
+    };
+    if let Some(entry) = entries.remove(old_key) {
+        entries.insert(new_key.to_string(), entry);
+    }
+}
@@ -0,0 +1,150 @@
+//! Secrets whose values come from providers, streamed through a bounded
+//! pipeline while an environment is written, rather than resolved up front
+//! (see [`crate::SyncOptions::pipeline_depth`]).
+
+use std::collections::{BTreeMap, HashSet};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::sync::mpsc::{channel, Receiver};
+
+use crate::{
+    config::{EnvironmentConfig, VariableConfig, VariableKind},
+    providers::ProviderRegistry,
+    references::referenced_keys,
+};
+
+/// The secrets of an environment whose placeholders are still to be
+/// resolved, in the order they are written.
+pub type StreamedSecrets = BTreeMap<String, VariableConfig>;
+
+/// Removes the secrets that can be streamed from an environment: those with
+/// a placeholder for a registered provider that aren't generated, split into
+/// chunks, or part of a `${ref:KEY}` reference.  Nothing but writing them
+/// needs their values.
+pub fn take_streamed_secrets(
+    environment: &mut EnvironmentConfig,
+    providers: &ProviderRegistry,
+) -> StreamedSecrets {
+    let referenced = environment
+        .values()
+        .flat_map(|variable| referenced_keys(&variable.value))
+        .collect::<HashSet<_>>();
+    let keys = environment
+        .iter()
+        .filter(|(key, variable)| {
+            variable.kind == VariableKind::Secret
+                && variable.generate.is_none()
+                && !variable.chunk
+                && !referenced.contains(&key.to_uppercase())
+                && referenced_keys(&variable.value).is_empty()
+                && providers.has_placeholders_in(&variable.value)
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+
+    keys.into_iter()
+        .filter_map(|key| environment.remove_entry(&key))
+        .collect()
+}
+
+/// Streamed secrets as they are resolved, see [`resolve_streamed_secrets`].
+#[derive(Debug)]
+pub struct ResolvedSecrets {
+    environment_name: String,
+    receiver: Receiver<(String, Result<String>)>,
+    total: usize,
+    received: usize,
+}
+
+impl ResolvedSecrets {
+    /// The next secret's key and value, in key order, or `None` once every
+    /// secret was received.  Fails if the secret couldn't be resolved, or if
+    /// resolving stopped before every secret was, e.g. because a provider
+    /// panicked.
+    pub async fn next(&mut self) -> Result<Option<(String, String)>> {
+        match self.receiver.recv().await {
+            Some((key, value)) => {
+                self.received += 1;
+                Ok(Some((key, value?)))
+            }
+            None if self.received < self.total => Err(eyre!(
+                "Resolving the secrets of environment '{}' stopped after {} of {}",
+                self.environment_name,
+                self.received,
+                self.total
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// How many secrets were received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// How many secrets are streamed in all.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+/// Resolves and transforms streamed secrets one by one on a blocking thread,
+/// so that the first of them can be written while providers are still
+/// looking up the rest.  At most `depth` resolved values wait to be received
+/// at a time.  Resolving stops at the first error, or once the
+/// [`ResolvedSecrets`] are dropped.
+pub fn resolve_streamed_secrets(
+    environment_name: &str,
+    secrets: StreamedSecrets,
+    providers: ProviderRegistry,
+    depth: usize,
+) -> ResolvedSecrets {
+    let (sender, receiver) = channel(depth.max(1));
+    let total = secrets.len();
+    let thread_environment_name = environment_name.to_string();
+    tokio::task::spawn_blocking(move || {
+        for (key, variable) in secrets {
+            let value = resolve_secret(&thread_environment_name, &key, &variable, &providers);
+            let failed = value.is_err();
+            if sender.blocking_send((key, value)).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    ResolvedSecrets {
+        environment_name: environment_name.to_string(),
+        receiver,
+        total,
+        received: 0,
+    }
+}
+
+fn resolve_secret(
+    environment_name: &str,
+    key: &str,
+    variable: &VariableConfig,
+    providers: &ProviderRegistry,
+) -> Result<String> {
+    let mut value = providers.resolve_value(&variable.value).map_err(|e| {
+        eyre!(
+            "Error resolving {} in environment '{}': {}",
+            key,
+            environment_name,
+            e
+        )
+    })?;
+    for transform in &variable.transform {
+        value = transform.apply(&value).map_err(|e| {
+            eyre!(
+                "Error applying {} to {} in environment '{}': {}",
+                transform,
+                key,
+                environment_name,
+                e
+            )
+        })?;
+    }
+
+    Ok(value)
+}
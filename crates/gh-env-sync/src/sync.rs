@@ -0,0 +1,2329 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use gh_client::{GithubEnvClient, OrganizationSecretVisibility};
+use tracing::{debug, info, warn};
+
+use crate::{
+    audit::{new_run_id, unix_timestamp, AuditLog, AuditOperation, AuditRecord},
+    checkpoint::{Checkpoint, CompletedWrite, RepositoryCheckpoint},
+    chunk::{has_chunked, split_chunked, stale_chunks},
+    config::{
+        environment_secrets, environment_values, ConfigDocument, Environment, EnvironmentConfig,
+        OrganizationSecretConfig,
+    },
+    conflict::{Conflict, ConflictResolver, ConflictStrategy, Resolution},
+    encryption::is_encrypted,
+    events::{EventSink, SyncEvent, SyncEventHandler},
+    exclusions::Exclusions,
+    lock::{LockMode, SyncLock},
+    protection::ProtectionRuleChange,
+    providers::ProviderRegistry,
+    quota::{QuotaUsage, QuotaViolation},
+    references::{has_references, resolve_references},
+    sensitive::sensitive_keys,
+    shadowing::{find_shadowed, InheritedVariables, ShadowedVariable},
+    state::{hash_value, AppliedSecret, EnvironmentState, State},
+    stream::{resolve_streamed_secrets, take_streamed_secrets, StreamedSecrets},
+    transform::{apply_transforms, has_transforms},
+    validation::{environment_name_problem, write_problem, Rejection},
+};
+
+/// Options controlling how a [`Syncer`] reads remote state.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// List environments through Github's GraphQL API.  It has no field for
+    /// environment variables, so those are still read through the REST API.
+    pub graphql: bool,
+    /// How to guard against concurrent syncs of the same repository.
+    pub lock: LockMode,
+    /// Where to record applied values between runs for drift detection.
+    pub state_file: Option<PathBuf>,
+    /// What to do with values that drifted remotely and also differ from the
+    /// config, unless overridden per key.
+    pub on_conflict: ConflictStrategy,
+    /// Where to append a record of every write made to Github.
+    pub audit_log: Option<PathBuf>,
+    /// Keys starting with this prefix are local-only annotations, such as
+    /// descriptions, owners or ticket links, and are never synced.  An empty
+    /// prefix ignores nothing.
+    pub ignore_prefix: Option<String>,
+    /// When environments are read and validated relative to writing them.
+    pub apply_strategy: ApplyStrategy,
+    /// With [`ApplyStrategy::Transactional`], stop at the first environment
+    /// that fails to apply rather than applying the rest.
+    pub fail_fast: bool,
+    /// Read and diff every environment without writing anything, taking the
+    /// lock, or updating the state file.  Conflicts aren't resolved, and
+    /// organization secrets are skipped since their values can't be read
+    /// back.
+    pub dry_run: bool,
+    /// Fail rather than warn when a variable's key looks like a credential,
+    /// such as `*_TOKEN` or `*_PASSWORD`.
+    pub strict_secrets: bool,
+    /// Warn rather than fail when a sync would exceed Github's limits on the
+    /// number and size of variables.  Dry runs only ever warn.
+    pub warn_on_quota: bool,
+    /// What to do with configured environments that don't exist on Github.
+    pub missing_environments: MissingEnvironments,
+    /// Delete remote variables that the state file records as applied but
+    /// which are no longer in the config.  Variables the tool never applied
+    /// are never deleted.  Does nothing without a state file.
+    pub prune: bool,
+    /// Abort before writing anything when a sync would update or delete
+    /// more than this many existing variables, e.g. because it was pointed
+    /// at the wrong config or repository.  Created variables don't count.
+    /// Overrides the config document's `max_changes`.  Dry runs only warn.
+    pub max_changes: Option<usize>,
+    /// Where a cancelled sync records the writes it completed, for
+    /// [`Syncer::resuming`] to skip.  A finished sync clears its repository
+    /// from the file.
+    pub checkpoint_file: Option<PathBuf>,
+    /// Read the repository and organization variables too, and warn about
+    /// configured variables that shadow them (see
+    /// [`SyncReport::shadowed`]).
+    pub check_shadowing: bool,
+    /// Sync environments even during one of the config document's freeze
+    /// windows (see [`crate::freeze::FreezeWindow`]), with a warning.
+    pub override_freeze: bool,
+    /// Resolve the provider placeholders of environment secrets only as
+    /// each is written, holding at most this many resolved values at a
+    /// time, rather than resolving every value before anything is written.
+    /// Memory stays flat for environments with hundreds of secrets, and the
+    /// first uploads start while slow providers are still looking up the
+    /// rest.  Secrets that are generated, chunked or referenced are still
+    /// resolved up front, as are all values in dry runs and with
+    /// [`ApplyStrategy::Transactional`], which validates everything before
+    /// writing.
+    pub pipeline_depth: Option<usize>,
+    /// Keys and environments that are never read, written or pruned, on top
+    /// of those of the config document being synced (see
+    /// [`ConfigDocument::exclude`]), e.g. for a config built from another
+    /// repository or the process environment.
+    pub exclusions: Exclusions,
+}
+
+impl SyncOptions {
+    /// Whether a key is a local-only annotation per
+    /// [`SyncOptions::ignore_prefix`].
+    pub fn is_ignored(&self, key: &str) -> bool {
+        self.ignore_prefix
+            .as_deref()
+            .is_some_and(|prefix| !prefix.is_empty() && key.starts_with(prefix))
+    }
+}
+
+/// When environments are read and validated relative to writing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApplyStrategy {
+    /// Each environment is read, validated and written in turn, stopping at
+    /// the first error.  Earlier environments may already have been written
+    /// when a later one turns out to be invalid.
+    #[default]
+    Interleaved,
+    /// Every environment is read and validated, including conflict
+    /// resolution, before any of them is written.  Environments are then
+    /// written one by one.
+    Transactional,
+}
+
+impl FromStr for ApplyStrategy {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "interleaved" => Ok(Self::Interleaved),
+            "transactional" => Ok(Self::Transactional),
+            _ => Err(eyre!(
+                "Unknown apply strategy '{}', expected one of interleaved, transactional",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ApplyStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Interleaved => "interleaved",
+            Self::Transactional => "transactional",
+        })
+    }
+}
+
+/// What to do with configured environments that don't exist on Github yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingEnvironments {
+    /// Create them, without protection rules.
+    #[default]
+    Create,
+    /// Fail before writing anything, for when environments and their
+    /// protection rules are created by some other process.
+    Fail,
+    /// Leave them out of the sync.
+    Skip,
+}
+
+impl FromStr for MissingEnvironments {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "create" => Ok(Self::Create),
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            _ => Err(eyre!(
+                "Unknown missing environments strategy '{}', expected one of create, fail, skip",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MissingEnvironments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Create => "create",
+            Self::Fail => "fail",
+            Self::Skip => "skip",
+        })
+    }
+}
+
+/// The change required to bring a single remote variable in line with the
+/// config document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableChange {
+    Create,
+    Update,
+    Unchanged,
+    /// The remote value conflicted with the config and was left in place per
+    /// the conflict strategy.
+    Kept,
+    /// The variable was applied by the tool but is no longer in the config,
+    /// and is deleted per [`SyncOptions::prune`].
+    Delete,
+}
+
+/// The outcome of syncing a single variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableReport {
+    pub key: String,
+    pub change: VariableChange,
+    /// Whether the remote value was changed outside the tool since it was
+    /// last applied.  Only known when a state file is in use.
+    pub drifted: bool,
+}
+
+impl VariableReport {
+    /// Whether the remote value drifted and also differs from the config.
+    pub fn is_conflict(&self) -> bool {
+        self.drifted && matches!(self.change, VariableChange::Create | VariableChange::Update)
+    }
+}
+
+/// The outcome of syncing a single environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentReport {
+    pub name: String,
+    /// Which token synced the environment, when it wasn't the default one.
+    pub identity: Option<String>,
+    /// Whether the environment had to be created on Github.
+    pub created: bool,
+    pub variables: Vec<VariableReport>,
+    /// The environment secrets written.  Secret values can't be read back,
+    /// so with a state file they are written only when their value changed
+    /// or they were updated on Github since the last write, and without one
+    /// on every sync.  Empty in a dry run.
+    pub secrets: Vec<String>,
+    /// What to record in the state file of each secret written, keyed by
+    /// secret name.
+    pub applied_secrets: BTreeMap<String, AppliedSecret>,
+}
+
+impl EnvironmentReport {
+    /// Number of variables with the given change.
+    pub fn count(&self, change: VariableChange) -> usize {
+        self.variables
+            .iter()
+            .filter(|variable| variable.change == change)
+            .count()
+    }
+
+    /// Whether the environment or any of its variables were created or
+    /// modified on Github.
+    pub fn has_changes(&self) -> bool {
+        self.created
+            || !self.secrets.is_empty()
+            || self.count(VariableChange::Create) > 0
+            || self.count(VariableChange::Update) > 0
+            || self.count(VariableChange::Delete) > 0
+    }
+}
+
+/// A value that a dry run would write, with the remote value it replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedValue {
+    pub environment: String,
+    pub key: String,
+    /// [`VariableChange::Create`], [`VariableChange::Update`] or
+    /// [`VariableChange::Delete`].
+    pub change: VariableChange,
+    /// Empty for deletions.
+    pub local_value: String,
+    pub remote_value: Option<String>,
+    /// When the remote value was last written, as an ISO 8601 timestamp.
+    pub remote_updated_at: Option<String>,
+    /// Whether the remote value was changed outside the tool since it was
+    /// last applied (see [`VariableReport::drifted`]).
+    pub drifted: bool,
+    /// Why Github would reject the write, e.g. a name it doesn't allow.
+    pub rejection: Option<String>,
+}
+
+/// Time spent in each phase of a sync, summed across environments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncTimings {
+    /// Reading environments and their variables from Github.
+    pub listing: Duration,
+    /// Comparing the config against the remote values and resolving
+    /// conflicts, including time spent waiting on prompts.
+    pub diff: Duration,
+    /// Writing changes to Github.
+    pub apply: Duration,
+}
+
+/// The outcome of a sync across every requested environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub environments: Vec<EnvironmentReport>,
+    /// Names of the organization secrets that were written, which with a
+    /// state file leaves out those unchanged since they were last written.
+    pub organization_secrets: Vec<String>,
+    /// With [`SyncOptions::dry_run`], every value that would have been
+    /// written, in environment and key order.
+    pub planned: Vec<PlannedValue>,
+    /// With [`SyncOptions::check_shadowing`], configured variables also
+    /// defined at the repository or organization level.
+    pub shadowed: Vec<ShadowedVariable>,
+    /// Custom deployment protection rules enabled or disabled per the
+    /// config document's `[protection_rules]` section.
+    pub protection_rules: Vec<ProtectionRuleChange>,
+    /// With [`SyncOptions::dry_run`], the writes Github would reject, so
+    /// that applying the plan would fail part way through.
+    pub rejections: Vec<Rejection>,
+    pub timings: SyncTimings,
+}
+
+impl SyncReport {
+    /// Number of variables across all environments with the given change.
+    pub fn count(&self, change: VariableChange) -> usize {
+        self.environments
+            .iter()
+            .map(|environment| environment.count(change))
+            .sum()
+    }
+
+    /// Number of variables across all environments which drifted remotely.
+    pub fn drift_count(&self) -> usize {
+        self.environments
+            .iter()
+            .flat_map(|environment| &environment.variables)
+            .filter(|variable| variable.drifted)
+            .count()
+    }
+
+    /// Whether the sync created or modified anything on Github.
+    pub fn has_changes(&self) -> bool {
+        !self.organization_secrets.is_empty()
+            || !self.protection_rules.is_empty()
+            || self.environments.iter().any(EnvironmentReport::has_changes)
+    }
+}
+
+/// Returned by [`Syncer::sync`] when [`ApplyStrategy::Transactional`] failed
+/// to apply some environments after applying the others, so that callers can
+/// tell a partial failure apart from one where nothing was written.
+#[derive(Debug)]
+pub struct PartialApplyError {
+    /// The environments that failed to apply.
+    pub failed: Vec<String>,
+    /// What was applied to the other environments.
+    pub report: SyncReport,
+}
+
+impl fmt::Display for PartialApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to apply environments {}; the others were applied",
+            self.failed.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for PartialApplyError {}
+
+/// Returned by [`Syncer::sync`] when it was stopped through
+/// [`Syncer::with_cancellation`], after the in-flight request finished.
+#[derive(Debug)]
+pub struct CancelledError {
+    /// The checkpoint file recording what was written, if anything was.
+    pub checkpoint: Option<PathBuf>,
+}
+
+impl fmt::Display for CancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.checkpoint {
+            Some(path) => write!(
+                f,
+                "Sync cancelled; the writes made so far are recorded in {}",
+                path.display()
+            ),
+            None => write!(f, "Sync cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for CancelledError {}
+
+/// The variables to delete with [`SyncOptions::prune`]: those recorded in
+/// `applied` by a previous sync that still exist remotely but are no longer
+/// in the config, in key order.  Keys are uppercased, as Github and the state
+/// file store them.
+pub fn prunable_variables(
+    local: &Environment,
+    remote: &Environment,
+    applied: Option<&EnvironmentState>,
+) -> Vec<VariableReport> {
+    let Some(applied) = applied else {
+        return Vec::new();
+    };
+
+    applied
+        .keys()
+        .filter(|key| remote.contains_key(*key))
+        .filter(|key| {
+            !local
+                .keys()
+                .any(|local_key| &local_key.to_uppercase() == *key)
+        })
+        .map(|key| VariableReport {
+            key: key.clone(),
+            change: VariableChange::Delete,
+            drifted: false,
+        })
+        .collect()
+}
+
+/// Computes the change required for every local variable given the current
+/// remote variables, in key order.  Github stores variable names uppercased,
+/// so `remote` is expected to be keyed by uppercased name.
+///
+/// When `applied` holds the state recorded by the previous sync, variables
+/// whose remote value no longer matches what was applied are marked as
+/// drifted.
+pub fn diff_environment(
+    local: &Environment,
+    remote: &Environment,
+    applied: Option<&EnvironmentState>,
+) -> Vec<VariableReport> {
+    let mut keys = local.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    keys.into_iter()
+        .map(|key| {
+            let remote_value = remote.get(&key.to_uppercase());
+            let change = match remote_value {
+                Some(remote_value) if remote_value == &local[key] => VariableChange::Unchanged,
+                Some(_) => VariableChange::Update,
+                None => VariableChange::Create,
+            };
+
+            let drifted = applied
+                .and_then(|applied| applied.get(&key.to_uppercase()))
+                .is_some_and(|applied_hash| {
+                    remote_value.map(|value| hash_value(value)).as_ref() != Some(applied_hash)
+                });
+
+            VariableReport {
+                key: key.clone(),
+                change,
+                drifted,
+            }
+        })
+        .collect()
+}
+
+/// Github pages environment variables 30 at a time.
+const VARIABLES_PER_PAGE: usize = 30;
+
+/// The variable recording who last changed an environment, written when a
+/// sync with an actor changes it.  It isn't part of the config.
+pub const LAST_ACTOR_VARIABLE: &str = "GH_ENV_SYNC_LAST_ACTOR";
+
+/// An upper bound on the number of REST requests a sync of the config
+/// document makes, not counting the one made to initialize the client.  It
+/// assumes that every environment needs creating and every variable needs
+/// writing.
+pub fn estimate_requests(
+    config: &ConfigDocument,
+    environment: Option<&str>,
+    options: &SyncOptions,
+) -> usize {
+    let environments = config
+        .environments
+        .iter()
+        .filter(|(name, _)| environment.is_none_or(|environment| environment == name.as_str()))
+        .map(|(_, environment)| {
+            environment
+                .keys()
+                .filter(|key| !options.is_ignored(key))
+                .count()
+        })
+        .collect::<Vec<_>>();
+
+    // Listing environments, 100 to a page.
+    let mut requests = environments.len().div_ceil(100).max(1);
+    for variables in environments {
+        // Creating the environment, listing its variables, and writing them.
+        requests += 1 + variables.div_ceil(VARIABLES_PER_PAGE).max(1) + variables;
+    }
+
+    if options.lock == LockMode::Remote {
+        // Checking for, creating and deleting the lock variable.
+        requests += 3;
+    }
+
+    if environment.is_none() && !config.org_secrets.is_empty() {
+        // The organization's public key, then each secret and its
+        // repositories.
+        requests += 1;
+        for secret in config.org_secrets.values() {
+            requests += 1;
+            if secret.visibility == OrganizationSecretVisibility::Selected {
+                requests += 1 + secret.repositories.len();
+            }
+        }
+    }
+
+    // Listing each protected environment's rules, the apps that can be
+    // enabled, and enabling each one at worst.
+    for (name, apps) in &config.protection_rules {
+        if environment.is_none_or(|environment| environment == name.as_str()) {
+            requests += 2 + apps.len();
+        }
+    }
+
+    requests
+}
+
+/// Everything read and decided about an environment before writing to it.
+struct EnvironmentPlan<'a> {
+    name: &'a str,
+    identity: Option<String>,
+    /// Whether the environment doesn't exist on Github yet.
+    create: bool,
+    values: Environment,
+    remote_variables: Environment,
+    /// When each remote variable was last written, keyed by uppercased name.
+    remote_updated_at: HashMap<String, String>,
+    variables: Vec<VariableReport>,
+    /// Secrets to write, which are never compared with Github.
+    secrets: BTreeMap<String, String>,
+}
+
+impl EnvironmentPlan<'_> {
+    /// How many existing variables applying the plan would update or delete.
+    fn destructive_changes(&self) -> usize {
+        self.variables
+            .iter()
+            .filter(|variable| {
+                matches!(
+                    variable.change,
+                    VariableChange::Update | VariableChange::Delete
+                )
+            })
+            .count()
+    }
+
+    /// The values that applying the plan would write or delete.
+    fn planned_values(&self) -> Vec<PlannedValue> {
+        self.variables
+            .iter()
+            .filter(|variable| {
+                matches!(
+                    variable.change,
+                    VariableChange::Create | VariableChange::Update | VariableChange::Delete
+                )
+            })
+            .map(|variable| {
+                let local_value = self.values.get(&variable.key).cloned().unwrap_or_default();
+                PlannedValue {
+                    environment: self.name.to_string(),
+                    key: variable.key.clone(),
+                    change: variable.change,
+                    rejection: match variable.change {
+                        VariableChange::Delete => None,
+                        _ => write_problem(&variable.key, &local_value),
+                    },
+                    local_value,
+                    remote_value: self
+                        .remote_variables
+                        .get(&variable.key.to_uppercase())
+                        .cloned(),
+                    remote_updated_at: self
+                        .remote_updated_at
+                        .get(&variable.key.to_uppercase())
+                        .cloned(),
+                    drifted: variable.drifted,
+                }
+            })
+            .collect()
+    }
+
+    /// What Github would reject of creating the environment and writing its
+    /// secrets.  Rejected variables are marked in [`Self::planned_values`].
+    fn rejections(&self) -> Vec<Rejection> {
+        let rejection = |key: Option<&String>, problem| Rejection {
+            environment: Some(self.name.to_string()),
+            key: key.cloned(),
+            problem,
+        };
+
+        let mut rejections = Vec::new();
+        if self.create {
+            if let Some(problem) = environment_name_problem(self.name) {
+                rejections.push(rejection(None, problem));
+            }
+        }
+        for (key, value) in &self.secrets {
+            if let Some(problem) = write_problem(key, value) {
+                rejections.push(rejection(Some(key), problem));
+            }
+        }
+        rejections
+    }
+
+    /// The environment's variables as they would be after applying the
+    /// plan, keyed by uppercased name like the remote variables.
+    fn post_sync_variables(&self) -> Environment {
+        let mut variables = self.remote_variables.clone();
+        for variable in &self.variables {
+            match variable.change {
+                VariableChange::Create | VariableChange::Update => {
+                    variables.insert(
+                        variable.key.to_uppercase(),
+                        self.values[&variable.key].clone(),
+                    );
+                }
+                VariableChange::Delete => {
+                    variables.remove(&variable.key.to_uppercase());
+                }
+                VariableChange::Unchanged | VariableChange::Kept => {}
+            }
+        }
+
+        variables
+    }
+
+    /// Reports the plan as if it had been applied.
+    fn into_report(self) -> EnvironmentReport {
+        EnvironmentReport {
+            name: self.name.to_string(),
+            identity: self.identity,
+            created: self.create,
+            variables: self.variables,
+            secrets: Vec::new(),
+            applied_secrets: BTreeMap::new(),
+        }
+    }
+}
+
+/// What the state file hashes of an organization secret, so that changing
+/// its visibility or selected repositories writes it again too.
+fn organization_secret_fingerprint(secret: &OrganizationSecretConfig) -> String {
+    serde_json::json!([secret.value, secret.visibility, secret.repositories]).to_string()
+}
+
+/// Whether any of the given environments have `generate`d variables.
+fn has_generated_values(config: &ConfigDocument, environment_names: &[&str]) -> bool {
+    environment_names
+        .iter()
+        .filter_map(|name| config.environments.get(*name))
+        .flat_map(|environment| environment.values())
+        .any(|variable| variable.generate.is_some())
+}
+
+/// Syncs configuration documents to the environments of a single repository.
+pub struct Syncer {
+    client: GithubEnvClient,
+    /// Labels changes made with `client`.
+    identity: Option<String>,
+    /// The person changes are attributed to, whichever client makes them.
+    actor: Option<String>,
+    /// Clients authenticated with per-environment tokens, and their
+    /// identities.
+    environment_clients: HashMap<String, (GithubEnvClient, String)>,
+    options: SyncOptions,
+    events: EventSink,
+    resolver: Option<Box<dyn ConflictResolver>>,
+    /// Resolve `${scheme:argument}` placeholders in values.
+    providers: ProviderRegistry,
+    /// Set once a resolver answers [`Resolution::ApplyAll`] during a run.
+    apply_all_conflicts: AtomicBool,
+    run_id: String,
+    /// Set to stop the sync before its next write.
+    cancellation: Option<Arc<AtomicBool>>,
+    /// What the interrupted run being resumed had written.
+    resumed: Option<RepositoryCheckpoint>,
+    /// What this run has written, for the checkpoint if it is cancelled.
+    completed: Mutex<Vec<CompletedWrite>>,
+}
+
+impl fmt::Debug for Syncer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Syncer")
+            .field("client", &self.client)
+            .field("identity", &self.identity)
+            .field("actor", &self.actor)
+            .field("environment_clients", &self.environment_clients)
+            .field("options", &self.options)
+            .field("events", &self.events)
+            .field("resolver", &self.resolver.as_ref().map(|_| "<resolver>"))
+            .field("providers", &self.providers)
+            .field("run_id", &self.run_id)
+            .field("cancellation", &self.cancellation)
+            .field("resumed", &self.resumed)
+            .finish()
+    }
+}
+
+impl Syncer {
+    /// Creates a syncer.  Its run id is the client's correlation id, if it
+    /// has one, so that audit records match the requests that made them.
+    pub fn new(client: GithubEnvClient, options: SyncOptions) -> Self {
+        let run_id = client
+            .correlation_id()
+            .map_or_else(new_run_id, str::to_string);
+
+        Self {
+            client,
+            identity: None,
+            actor: None,
+            environment_clients: HashMap::new(),
+            options,
+            events: EventSink::default(),
+            resolver: None,
+            providers: ProviderRegistry::default(),
+            apply_all_conflicts: AtomicBool::new(false),
+            run_id,
+            cancellation: None,
+            resumed: None,
+            completed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Identifies writes made by this syncer in the audit log.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Decides conflicts with the given resolver when the conflict strategy
+    /// is [`ConflictStrategy::Prompt`].
+    pub fn with_conflict_resolver(mut self, resolver: impl ConflictResolver + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Resolves `${scheme:argument}` placeholders in values with the given
+    /// providers before anything else reads them, so that `${ref:KEY}`
+    /// references and transforms see the resolved values.
+    pub fn with_value_providers(mut self, providers: ProviderRegistry) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Reports progress to the given handler as the sync runs.
+    pub fn with_event_handler(mut self, handler: impl SyncEventHandler + 'static) -> Self {
+        self.events = EventSink::new(handler);
+        self
+    }
+
+    /// Stops the sync before its next write once the flag is set, e.g. by a
+    /// signal handler, so that no request is cut off half way.  The writes
+    /// made until then are recorded in [`SyncOptions::checkpoint_file`].
+    pub fn with_cancellation(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancellation = Some(cancelled);
+        self
+    }
+
+    /// Resumes a cancelled sync: writes it already made with the same value
+    /// are neither made nor audited again, and the audit log records both
+    /// under the run id of the cancelled one.
+    pub fn resuming(mut self, checkpoint: RepositoryCheckpoint) -> Self {
+        self.run_id = checkpoint.run_id.clone();
+        self.resumed = Some(checkpoint);
+        self
+    }
+
+    /// Labels changes made with the default client, e.g. in the audit log.
+    pub fn with_identity(mut self, identity: impl Into<String>) -> Self {
+        self.identity = Some(identity.into());
+        self
+    }
+
+    /// Attributes changes to a person, for when several people share a
+    /// token.  The actor is recorded in the audit log and, in each changed
+    /// environment, in [`LAST_ACTOR_VARIABLE`].
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    /// Reads and writes the given environment with its own client, e.g. one
+    /// authenticated with a token scoped to that environment.  `identity`
+    /// labels the changes it makes.
+    pub fn with_environment_client(
+        mut self,
+        environment: impl Into<String>,
+        client: GithubEnvClient,
+        identity: impl Into<String>,
+    ) -> Self {
+        self.environment_clients
+            .insert(environment.into(), (client, identity.into()));
+        self
+    }
+
+    /// The client used to read and write the given environment.
+    pub fn client_for(&self, environment: &str) -> &GithubEnvClient {
+        self.environment_clients
+            .get(environment)
+            .map_or(&self.client, |(client, _)| client)
+    }
+
+    /// The number of requests sent by every client of this syncer, including
+    /// those for individual environments.
+    pub fn request_count(&self) -> usize {
+        self.client.request_stats().requests
+            + self
+                .environment_clients
+                .values()
+                .map(|(client, _)| client.request_stats().requests)
+                .sum::<usize>()
+    }
+
+    /// Which token changes to the given environment are made with, when it
+    /// isn't the default one.
+    pub fn identity_for(&self, environment: &str) -> Option<&str> {
+        self.environment_clients
+            .get(environment)
+            .map(|(_, identity)| identity.as_str())
+            .or(self.identity.as_deref())
+    }
+
+    /// The options this syncer was created with.
+    pub fn options(&self) -> &SyncOptions {
+        &self.options
+    }
+
+    /// The client used to talk to Github.
+    pub fn client(&self) -> &GithubEnvClient {
+        &self.client
+    }
+
+    /// Syncs the environments defined in the given configuration document to
+    /// Github.  If `environment` is given, only that environment is synced.
+    ///
+    /// The repository is locked according to [`SyncOptions::lock`] for the
+    /// duration of the sync, unless it is a [`SyncOptions::dry_run`].
+    pub async fn sync(
+        &self,
+        config: &ConfigDocument,
+        environment: Option<&str>,
+    ) -> Result<SyncReport> {
+        self.apply_all_conflicts.store(false, Ordering::Relaxed);
+        self.completed.lock().expect("lock poisoned").clear();
+        let excluded = self.without_excluded(config);
+        let config = excluded.as_ref().unwrap_or(config);
+        let unexpired = self.without_expired(config)?;
+        let config = unexpired.as_ref().unwrap_or(config);
+        if self.options.dry_run {
+            return self.sync_unlocked(config, environment).await;
+        }
+
+        let lock = SyncLock::acquire(&self.client, self.options.lock).await?;
+        let result = self.sync_unlocked(config, environment).await;
+        let result = lock.release_after(&self.client, result).await;
+
+        if let (Ok(_), Some(path)) = (&result, &self.options.checkpoint_file) {
+            Checkpoint::clear(path, &self.repository())?;
+        }
+        result
+    }
+
+    /// Leaves the entries excluded by [`SyncOptions::exclusions`] out of a
+    /// config document before syncing it.
+    fn without_excluded(&self, config: &ConfigDocument) -> Option<ConfigDocument> {
+        if self.options.exclusions.is_empty() {
+            return None;
+        }
+
+        let mut excluded = config.clone();
+        excluded.exclude(self.options.exclusions.clone());
+        Some(excluded)
+    }
+
+    async fn sync_unlocked(
+        &self,
+        config: &ConfigDocument,
+        environment: Option<&str>,
+    ) -> Result<SyncReport> {
+        let environment_names = match environment {
+            Some(environment) => {
+                info!(
+                    "Found single environment '{}' to sync based on --environment argument",
+                    environment
+                );
+
+                if config.exclusions.excludes_environment(environment) {
+                    return Err(eyre!(
+                        "Environment '{}' is excluded by the ignore file",
+                        environment
+                    ));
+                }
+                if !config.environments.contains_key(environment) {
+                    return Err(eyre!(
+                        "Environment '{}' is not defined in the config document",
+                        environment
+                    ));
+                }
+
+                vec![environment]
+            }
+            None => {
+                let mut all_envs = config
+                    .environments
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                all_envs.sort();
+
+                info!(
+                    "Syncing all environments ({:?}) because no --environment argument was given",
+                    all_envs
+                );
+
+                all_envs
+            }
+        };
+
+        self.check_sensitive_keys(config, &environment_names)?;
+        self.check_naming(config, &environment_names)?;
+        self.check_freeze(config, &environment_names)?;
+
+        // Uploading ciphertext would silently break whatever reads the
+        // variable, so refuse to sync anything that wasn't decrypted.
+        for environment_name in &environment_names {
+            if let Some(key) = config.environments[*environment_name]
+                .iter()
+                .find_map(|(key, variable)| is_encrypted(&variable.value).then_some(key))
+            {
+                return Err(eyre!(
+                    "{} in environment '{}' is age encrypted and must be decrypted before syncing (see --age-identity)",
+                    key,
+                    environment_name
+                ));
+            }
+        }
+        if environment.is_none() {
+            if let Some(name) = config
+                .org_secrets
+                .iter()
+                .find_map(|(name, secret)| is_encrypted(&secret.value).then_some(name))
+            {
+                return Err(eyre!(
+                    "Organization secret {} is age encrypted and must be decrypted before syncing (see --age-identity)",
+                    name
+                ));
+            }
+        }
+
+        let repository = self.repository();
+        let mut state = match &self.options.state_file {
+            Some(path) => Some(State::load(path)?),
+            None => None,
+        };
+        if let (Some(state), Some(resumed)) = (state.as_mut(), &self.resumed) {
+            resumed.record_in(state, &repository);
+        }
+
+        let resolved;
+        let mut streamed = HashMap::new();
+        let config = if self.needs_resolution(config, &environment_names) {
+            let mut document = config.clone();
+            streamed =
+                self.resolve_values_in(&mut document, &environment_names, state.as_mut(), true)?;
+            resolved = document;
+            &resolved
+        } else {
+            config
+        };
+
+        let mut report = SyncReport::default();
+
+        let started = Instant::now();
+        let remote_environments = if self.options.graphql {
+            self.client.list_environments_graphql().await
+        } else {
+            self.client.list_environments().await
+        }
+        .inspect_err(|e| {
+            self.events.emit(SyncEvent::Error {
+                environment: None,
+                key: None,
+                message: e.to_string(),
+            })
+        })?;
+        let inherited = match self.options.check_shadowing {
+            true => Some(self.inherited_variables().await),
+            false => None,
+        };
+        report.timings.listing += started.elapsed();
+        let environment_names =
+            self.select_missing_environments(environment_names, &remote_environments)?;
+        let synced_names = environment_names.clone();
+
+        // Checked before writing, rather than failing part way through with
+        // a 422 from Github.
+        let mut quota = QuotaUsage::default();
+        let max_changes = self.options.max_changes.or(config.max_changes);
+        if self.options.dry_run {
+            let mut changes = 0;
+            for environment_name in environment_names {
+                self.check_cancelled()?;
+                let applied = state
+                    .as_ref()
+                    .and_then(|state| state.environment(&repository, environment_name));
+                let plan = self
+                    .plan_environment(
+                        environment_name,
+                        &config.environments[environment_name],
+                        &config.exclusions,
+                        &remote_environments,
+                        applied,
+                        &mut report.timings,
+                    )
+                    .await?;
+                let violations = quota.check(plan.name, &plan.post_sync_variables());
+                // Values too large are rejected with their planned value
+                // instead.
+                report.rejections.extend(
+                    violations
+                        .iter()
+                        .filter(|violation| {
+                            !matches!(violation, QuotaViolation::ValueTooLarge { .. })
+                        })
+                        .map(Rejection::quota),
+                );
+                self.enforce_quota(violations)?;
+                self.report_shadowed(&plan, inherited.as_ref(), &mut report);
+
+                changes += plan.destructive_changes();
+                let planned = plan.planned_values();
+                let mut rejections = plan.rejections();
+                rejections.extend(planned.iter().filter_map(|value| {
+                    Some(Rejection {
+                        environment: Some(value.environment.clone()),
+                        key: Some(value.key.clone()),
+                        problem: value.rejection.clone()?,
+                    })
+                }));
+                for rejection in &rejections {
+                    warn!("Github would reject: {}", rejection);
+                }
+                report.rejections.extend(rejections);
+                report.planned.extend(planned);
+                if !plan.secrets.is_empty() {
+                    info!(
+                        "Skipping {} secrets of environment '{}', whose values can't be compared in a dry run",
+                        plan.secrets.len(),
+                        plan.name
+                    );
+                }
+                report.environments.push(plan.into_report());
+            }
+            self.enforce_max_changes(changes, max_changes)?;
+
+            if environment.is_none() && !config.org_secrets.is_empty() {
+                info!(
+                    "Skipping {} organization secrets, whose values can't be compared in a dry run",
+                    config.org_secrets.len()
+                );
+            }
+            report.protection_rules = self
+                .sync_protection_rules(config, &synced_names, &remote_environments)
+                .await?;
+            return Ok(report);
+        }
+
+        match self.options.apply_strategy {
+            // The total number of changes is only known once every
+            // environment is planned, so with a limit, interleaved syncs plan
+            // everything first too.
+            ApplyStrategy::Interleaved if max_changes.is_none() => {
+                for environment_name in environment_names {
+                    self.check_cancelled()?;
+                    let applied = state
+                        .as_ref()
+                        .and_then(|state| state.environment(&repository, environment_name));
+                    let plan = self
+                        .plan_environment(
+                            environment_name,
+                            &config.environments[environment_name],
+                            &config.exclusions,
+                            &remote_environments,
+                            applied,
+                            &mut report.timings,
+                        )
+                        .await?;
+                    self.enforce_quota(quota.check(plan.name, &plan.post_sync_variables()))?;
+                    self.report_shadowed(&plan, inherited.as_ref(), &mut report);
+
+                    let streamed = streamed.remove(environment_name).unwrap_or_default();
+                    let applied_secrets = state
+                        .as_ref()
+                        .and_then(|state| state.secrets(&repository, environment_name))
+                        .cloned()
+                        .unwrap_or_default();
+                    let environment_report = self
+                        .apply_environment(plan, streamed, &applied_secrets, &mut report.timings)
+                        .await?;
+                    self.record_applied(&mut state, config, &environment_report)?;
+                    report.environments.push(environment_report);
+                }
+            }
+            ApplyStrategy::Interleaved | ApplyStrategy::Transactional => {
+                let mut plans = Vec::new();
+                for environment_name in environment_names {
+                    let applied = state
+                        .as_ref()
+                        .and_then(|state| state.environment(&repository, environment_name));
+                    let plan = self
+                        .plan_environment(
+                            environment_name,
+                            &config.environments[environment_name],
+                            &config.exclusions,
+                            &remote_environments,
+                            applied,
+                            &mut report.timings,
+                        )
+                        .await?;
+                    self.report_shadowed(&plan, inherited.as_ref(), &mut report);
+                    plans.push(plan);
+                }
+
+                self.enforce_quota(
+                    plans
+                        .iter()
+                        .flat_map(|plan| quota.check(plan.name, &plan.post_sync_variables()))
+                        .collect(),
+                )?;
+                self.enforce_max_changes(
+                    plans.iter().map(EnvironmentPlan::destructive_changes).sum(),
+                    max_changes,
+                )?;
+
+                let mut failed = Vec::new();
+                for plan in plans {
+                    self.check_cancelled()?;
+                    let environment_name = plan.name.to_string();
+                    let streamed = streamed.remove(&environment_name).unwrap_or_default();
+                    let applied_secrets = state
+                        .as_ref()
+                        .and_then(|state| state.secrets(&repository, &environment_name))
+                        .cloned()
+                        .unwrap_or_default();
+                    match self
+                        .apply_environment(plan, streamed, &applied_secrets, &mut report.timings)
+                        .await
+                    {
+                        Ok(environment_report) => {
+                            self.record_applied(&mut state, config, &environment_report)?;
+                            report.environments.push(environment_report);
+                        }
+                        Err(e)
+                            if self.options.fail_fast
+                                || self.options.apply_strategy == ApplyStrategy::Interleaved
+                                || e.is::<CancelledError>() =>
+                        {
+                            return Err(e)
+                        }
+                        Err(e) => {
+                            warn!("Error applying environment '{}': {}", environment_name, e);
+                            failed.push(environment_name);
+                        }
+                    }
+                }
+
+                if !failed.is_empty() {
+                    return Err(PartialApplyError { failed, report }.into());
+                }
+            }
+        }
+
+        report.protection_rules = self
+            .sync_protection_rules(config, &synced_names, &remote_environments)
+            .await?;
+
+        // Organization secrets aren't tied to an environment, so they're only
+        // synced when syncing everything.
+        if environment.is_none() {
+            report.organization_secrets = self
+                .sync_organization_secrets(&config.org_secrets, &mut state)
+                .await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Applies [`SyncOptions::missing_environments`] to the environments to
+    /// sync, returning those that are left.
+    fn select_missing_environments<'a>(
+        &self,
+        environment_names: Vec<&'a str>,
+        remote_environments: &[String],
+    ) -> Result<Vec<&'a str>> {
+        let (existing, missing): (Vec<_>, Vec<_>) = environment_names
+            .iter()
+            .copied()
+            .partition(|name| remote_environments.iter().any(|env| env == *name));
+        if missing.is_empty() {
+            return Ok(environment_names);
+        }
+
+        match self.options.missing_environments {
+            MissingEnvironments::Create => Ok(environment_names),
+            MissingEnvironments::Fail => Err(eyre!(
+                "Environments {:?} don't exist on Github, and creating them is disabled (see --create-missing-environments)",
+                missing
+            )),
+            MissingEnvironments::Skip => {
+                warn!(
+                    "Skipping environments {:?}, which don't exist on Github",
+                    missing
+                );
+                Ok(existing)
+            }
+        }
+    }
+
+    /// Fails if a sync would exceed Github's variable limits, or warns with
+    /// [`SyncOptions::warn_on_quota`] and in dry runs.
+    fn enforce_quota(&self, violations: Vec<QuotaViolation>) -> Result<()> {
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        if self.options.warn_on_quota || self.options.dry_run {
+            for violation in &violations {
+                warn!("Exceeds Github's variable limits: {}", violation);
+            }
+            return Ok(());
+        }
+
+        Err(eyre!(
+            "Not syncing because it would exceed Github's variable limits: {}",
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ))
+    }
+
+    /// Fails when an environment is inside one of the config document's
+    /// freeze windows, unless [`SyncOptions::override_freeze`] is set, or
+    /// only warns in a dry run.
+    fn check_freeze(&self, config: &ConfigDocument, environment_names: &[&str]) -> Result<()> {
+        let now = unix_timestamp();
+        let frozen = environment_names
+            .iter()
+            .filter_map(|name| {
+                config
+                    .freeze
+                    .iter()
+                    .find(|window| window.freezes(name, now))
+                    .map(|window| format!("'{}' is {}", name, window))
+            })
+            .collect::<Vec<_>>();
+        if frozen.is_empty() {
+            return Ok(());
+        }
+
+        let frozen = frozen.join("; ");
+        if self.options.override_freeze {
+            warn!("Overriding the change freeze: {}", frozen);
+            return Ok(());
+        }
+        if self.options.dry_run {
+            warn!(
+                "A sync would be refused during the change freeze: {}",
+                frozen
+            );
+            return Ok(());
+        }
+
+        Err(eyre!(
+            "Not syncing during the change freeze: {}; pass --override-freeze if the change can't wait",
+            frozen
+        ))
+    }
+
+    /// Fails when a sync would update or delete more existing variables than
+    /// allowed, or only warns in a dry run.
+    fn enforce_max_changes(&self, changes: usize, max_changes: Option<usize>) -> Result<()> {
+        let Some(max_changes) = max_changes else {
+            return Ok(());
+        };
+        if changes <= max_changes {
+            return Ok(());
+        }
+
+        if self.options.dry_run {
+            warn!(
+                "Would update or delete {} existing variables, more than the maximum of {}",
+                changes, max_changes
+            );
+            return Ok(());
+        }
+
+        Err(eyre!(
+            "Not syncing because it would update or delete {} existing variables, more than the maximum of {} (see --max-changes); check that the config and repository are the intended ones",
+            changes,
+            max_changes
+        ))
+    }
+
+    /// Warns about variables whose keys look like credentials, or fails with
+    /// [`SyncOptions::strict_secrets`].  Variables are readable in plaintext
+    /// by anyone with read access to the repository, unlike secrets.
+    fn check_sensitive_keys(
+        &self,
+        config: &ConfigDocument,
+        environment_names: &[&str],
+    ) -> Result<()> {
+        let mut sensitive = Vec::new();
+        for environment_name in environment_names {
+            for key in sensitive_keys(&config.environments[*environment_name]) {
+                if !self.is_ignored(key) {
+                    sensitive.push(format!("{} in '{}'", key, environment_name));
+                }
+            }
+        }
+
+        if sensitive.is_empty() {
+            return Ok(());
+        }
+        if self.options.strict_secrets {
+            return Err(eyre!(
+                "Refusing to sync variables that look like credentials with --strict-secrets: {}.  Variables are visible in plaintext to anyone with read access to the repository, so declare these as secrets with kind = \"secret\" or under [<environment>.secrets] instead",
+                sensitive.join(", ")
+            ));
+        }
+        for variable in sensitive {
+            warn!(
+                "{} looks like a credential, but variables are visible in plaintext to anyone with read access to the repository; consider declaring it as a secret with kind = \"secret\" or under [<environment>.secrets] instead",
+                variable
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The repository and organization variables the repository's workflows
+    /// inherit.  Either can fail to be read, e.g. without permission to read
+    /// organization variables, which only costs the warnings about it.
+    async fn inherited_variables(&self) -> InheritedVariables {
+        let repository = self
+            .client
+            .list_repository_variables()
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Not checking for shadowed repository variables: {}", e);
+                HashMap::new()
+            });
+        let organization = self
+            .client
+            .list_organization_variables()
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Not checking for shadowed organization variables: {}", e);
+                HashMap::new()
+            });
+
+        InheritedVariables {
+            repository,
+            organization,
+        }
+    }
+
+    /// Warns about the planned environment's variables that shadow
+    /// repository or organization variables, and adds them to the report.
+    fn report_shadowed(
+        &self,
+        plan: &EnvironmentPlan<'_>,
+        inherited: Option<&InheritedVariables>,
+        report: &mut SyncReport,
+    ) {
+        let Some(inherited) = inherited else {
+            return;
+        };
+
+        for shadowed in find_shadowed(plan.name, &plan.values, inherited) {
+            let levels = shadowed
+                .shadowed_levels()
+                .map(|level| level.to_string())
+                .collect::<Vec<_>>()
+                .join(" and ");
+            match shadowed.differs() {
+                true => warn!(
+                    "{} of environment '{}' shadows the {} variable of the same name; jobs using the environment see a different value than those that don't",
+                    shadowed.key, shadowed.environment, levels
+                ),
+                false => info!(
+                    "{} of environment '{}' shadows the {} variable of the same name, with the same value",
+                    shadowed.key, shadowed.environment, levels
+                ),
+            }
+            report.shadowed.push(shadowed);
+        }
+    }
+
+    /// Fails before anything is written if a variable name breaks the config
+    /// document's naming policy, suggesting names that would follow it.
+    fn check_naming(&self, config: &ConfigDocument, environment_names: &[&str]) -> Result<()> {
+        let violations = environment_names
+            .iter()
+            .flat_map(|environment_name| {
+                config.naming.check(
+                    environment_name,
+                    config.environments[*environment_name]
+                        .keys()
+                        .map(String::as_str)
+                        .filter(|key| !self.is_ignored(key)),
+                )
+            })
+            .map(|violation| violation.to_string())
+            .collect::<Vec<_>>();
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(eyre!(
+            "Variable names break the naming policy: {}",
+            violations.join("; ")
+        ))
+    }
+
+    /// Fills in generated values and resolves `${ref:KEY}` references in
+    /// every environment of the config document, generating and recording
+    /// any values that the state file doesn't have yet.  [`Syncer::sync`]
+    /// does this itself, but callers that display local values need it done
+    /// up front.
+    pub fn resolve_values(&self, config: &mut ConfigDocument) -> Result<()> {
+        let environment_names = config.environments.keys().cloned().collect::<Vec<_>>();
+        let environment_names = environment_names
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        let mut state = match &self.options.state_file {
+            Some(path) => Some(State::load(path)?),
+            None => None,
+        };
+        self.resolve_values_in(config, &environment_names, state.as_mut(), false)?;
+        Ok(())
+    }
+
+    /// Whether a key is a local-only annotation per
+    /// [`SyncOptions::ignore_prefix`].
+    pub fn is_ignored(&self, key: &str) -> bool {
+        self.options.is_ignored(key)
+    }
+
+    /// Whether [`Syncer::resolve_values_in`] has anything to do.
+    fn needs_resolution(&self, config: &ConfigDocument, environment_names: &[&str]) -> bool {
+        has_generated_values(config, environment_names)
+            || self.providers.has_placeholders(config, environment_names)
+            || environment_names.iter().any(|name| {
+                let environment = &config.environments[*name];
+                has_references(environment)
+                    || has_transforms(environment)
+                    || has_chunked(environment)
+                    || environment.keys().any(|key| self.is_ignored(key))
+            })
+    }
+
+    /// Fills in the values of the given environments.  With `stream`, the
+    /// secrets left to be resolved as they are written per
+    /// [`SyncOptions::pipeline_depth`] are taken out of them and returned,
+    /// keyed by environment; otherwise every value is resolved.  Nothing is
+    /// streamed with [`ApplyStrategy::Transactional`], so that a failing
+    /// provider stops the sync before any environment is written.
+    fn resolve_values_in(
+        &self,
+        config: &mut ConfigDocument,
+        environment_names: &[&str],
+        state: Option<&mut State>,
+        stream: bool,
+    ) -> Result<HashMap<String, StreamedSecrets>> {
+        // Annotations are dropped first, so they're never generated or
+        // referenced.
+        for environment_name in environment_names {
+            if let Some(environment) = config.environments.get_mut(*environment_name) {
+                environment.retain(|key, _| !self.is_ignored(key));
+            }
+        }
+
+        if has_generated_values(config, environment_names) {
+            self.fill_generated_values(config, environment_names, state)?;
+        }
+        let mut streamed = HashMap::new();
+        if stream
+            && self.options.pipeline_depth.is_some()
+            && !self.options.dry_run
+            && self.options.apply_strategy != ApplyStrategy::Transactional
+        {
+            for environment_name in environment_names {
+                if let Some(environment) = config.environments.get_mut(*environment_name) {
+                    let secrets = take_streamed_secrets(environment, &self.providers);
+                    if !secrets.is_empty() {
+                        streamed.insert(environment_name.to_string(), secrets);
+                    }
+                }
+            }
+        }
+        self.providers.resolve(config, environment_names)?;
+
+        // References are resolved last so that they can refer to generated
+        // values.
+        for environment_name in environment_names {
+            if let Some(environment) = config.environments.get_mut(*environment_name) {
+                resolve_references(environment_name, environment)?;
+                apply_transforms(environment_name, environment)?;
+                split_chunked(environment);
+            }
+        }
+
+        Ok(streamed)
+    }
+
+    fn fill_generated_values(
+        &self,
+        config: &mut ConfigDocument,
+        environment_names: &[&str],
+        state: Option<&mut State>,
+    ) -> Result<()> {
+        let (Some(state), Some(path)) = (state, &self.options.state_file) else {
+            return Err(eyre!(
+                "Generated values are remembered in the state file, so syncing them requires --state-file"
+            ));
+        };
+
+        let repository = self.repository();
+        let mut generated_count = 0;
+        for environment_name in environment_names {
+            let Some(environment) = config.environments.get_mut(*environment_name) else {
+                continue;
+            };
+
+            for (key, variable) in environment.iter_mut() {
+                let Some(spec) = variable.generate else {
+                    continue;
+                };
+
+                variable.value = match state.generated(&repository, environment_name, key) {
+                    Some(value) => value.to_string(),
+                    None => {
+                        info!("Generating a value for {} in '{}'", key, environment_name);
+                        let value = spec.generate();
+                        state.record_generated(&repository, environment_name, key, &value);
+                        generated_count += 1;
+                        value
+                    }
+                };
+            }
+        }
+
+        // Save new values before they're applied anywhere, so that a failed
+        // sync can't lose a value that was already written to Github.  A dry
+        // run writes nothing, so it shows values that the next sync will
+        // generate afresh.
+        if generated_count > 0 && !self.options.dry_run {
+            state.save(path)?;
+        }
+
+        Ok(())
+    }
+
+    async fn sync_organization_secrets(
+        &self,
+        secrets: &BTreeMap<String, OrganizationSecretConfig>,
+        state: &mut Option<State>,
+    ) -> Result<Vec<String>> {
+        if secrets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        info!(
+            "Syncing {} organization secrets to {}",
+            secrets.len(),
+            self.client.repository_owner()
+        );
+
+        // Several secrets are usually shared with the same repositories, so
+        // only look each one up once.
+        let repository = self.repository();
+        let mut repository_ids = HashMap::new();
+        let mut updated = Vec::new();
+        for (name, secret) in secrets {
+            let write =
+                CompletedWrite::new("", name, AuditOperation::PutSecret, Some(&secret.value));
+            if self.already_completed(&write) {
+                debug!(
+                    "Skipping organization secret {}, which the resumed sync already wrote",
+                    name
+                );
+                continue;
+            }
+            let applied = state
+                .as_ref()
+                .and_then(|state| state.organization_secret(&repository, name))
+                .cloned();
+            self.check_cancelled()?;
+            let result = self
+                .sync_organization_secret(name, secret, applied.as_ref(), &mut repository_ids)
+                .await;
+            if let Err(e) = &result {
+                self.events.emit(SyncEvent::Error {
+                    environment: None,
+                    key: Some(name.clone()),
+                    message: e.to_string(),
+                });
+            }
+            let Some(applied) = result? else {
+                debug!("Skipping unchanged organization secret {}", name);
+                continue;
+            };
+            self.record_completed(write);
+            if let (Some(state), Some(path)) = (state.as_mut(), &self.options.state_file) {
+                state.record_organization_secret(&repository, name, applied);
+                state.save(path)?;
+            }
+
+            self.events
+                .emit(SyncEvent::OrganizationSecretUpdated { name: name.clone() });
+            updated.push(name.clone());
+        }
+
+        Ok(updated)
+    }
+
+    /// Writes an organization secret unless it's unchanged since the write
+    /// recorded in `applied`, returning what to record of it in the state
+    /// file, or `None` if it was skipped.
+    async fn sync_organization_secret(
+        &self,
+        name: &str,
+        secret: &OrganizationSecretConfig,
+        applied: Option<&AppliedSecret>,
+        repository_ids: &mut HashMap<String, usize>,
+    ) -> Result<Option<AppliedSecret>> {
+        let fingerprint = organization_secret_fingerprint(secret);
+        if let Some(applied) =
+            applied.filter(|applied| applied.value_hash == hash_value(&fingerprint))
+        {
+            let remote = self.client.get_organization_secret(name).await?;
+            if applied.is_current(
+                &fingerprint,
+                remote
+                    .as_ref()
+                    .and_then(|secret| secret.updated_at.as_deref()),
+            ) {
+                return Ok(None);
+            }
+        }
+
+        // Resolve repositories before writing anything, so that a typo in a
+        // repository name doesn't leave the secret half configured.
+        let mut selected_ids = Vec::with_capacity(secret.repositories.len());
+        for repository in &secret.repositories {
+            let id = match repository_ids.get(repository) {
+                Some(id) => *id,
+                None => {
+                    let (owner, repository_name) = repository
+                        .split_once('/')
+                        .unwrap_or((self.client.repository_owner(), repository));
+                    let id = self
+                        .client
+                        .get_repository_id(owner, repository_name)
+                        .await?;
+                    repository_ids.insert(repository.clone(), id);
+                    id
+                }
+            };
+            selected_ids.push(id);
+        }
+
+        self.client
+            .put_organization_secret(name, &secret.value, secret.visibility)
+            .await?;
+
+        if secret.visibility == OrganizationSecretVisibility::Selected {
+            self.client
+                .set_organization_secret_repositories(name, &selected_ids)
+                .await?;
+        }
+
+        let updated_at = match self.options.state_file {
+            Some(_) => self
+                .client
+                .get_organization_secret(name)
+                .await?
+                .and_then(|secret| secret.updated_at),
+            None => None,
+        };
+
+        Ok(Some(AppliedSecret::new(&fingerprint, updated_at)))
+    }
+
+    /// The `owner/repo` name of the repository being synced.
+    pub(crate) fn repository(&self) -> String {
+        format!(
+            "{}/{}",
+            self.client.repository_owner(),
+            self.client.repository_name()
+        )
+    }
+
+    /// Reads an environment's remote variables and decides what to write,
+    /// without writing anything.
+    async fn plan_environment<'a>(
+        &self,
+        environment_name: &'a str,
+        environment: &EnvironmentConfig,
+        exclusions: &Exclusions,
+        remote_environments: &[String],
+        applied: Option<&EnvironmentState>,
+        timings: &mut SyncTimings,
+    ) -> Result<EnvironmentPlan<'a>> {
+        info!(
+            "Syncing {} variables to environment '{}'",
+            environment.len(),
+            environment_name
+        );
+        let client = self.client_for(environment_name);
+        let identity = self.identity_for(environment_name).map(str::to_string);
+        self.events.emit(SyncEvent::EnvironmentStarted {
+            environment: environment_name.to_string(),
+            variable_count: environment.len(),
+            identity: identity.clone(),
+        });
+
+        // Read every remote variable for the environment up front rather than
+        // issuing a GET per key, so that we only write the keys that changed.
+        let create = !remote_environments
+            .iter()
+            .any(|env| env == environment_name);
+        let started = Instant::now();
+        let remote_details = if create {
+            Vec::new()
+        } else {
+            client
+                .list_environment_variable_details(environment_name)
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, None, e))?
+        };
+        let mut remote_variables = Environment::new();
+        let mut remote_updated_at = HashMap::new();
+        for variable in remote_details {
+            // Excluded variables belong to someone else, so they're neither
+            // compared, reported as drift nor pruned.
+            if exclusions.excludes_key(environment_name, &variable.name) {
+                debug!(
+                    "Ignoring remote variable {} excluded by the ignore file",
+                    variable.name
+                );
+                continue;
+            }
+            if let Some(updated_at) = variable.updated_at {
+                remote_updated_at.insert(variable.name.clone(), updated_at);
+            }
+            remote_variables.insert(variable.name, variable.value);
+        }
+        timings.listing += started.elapsed();
+
+        let started = Instant::now();
+        let values = environment_values(environment);
+        let mut variables = diff_environment(&values, &remote_variables, applied);
+        if self.options.prune {
+            variables.extend(prunable_variables(&values, &remote_variables, applied));
+        }
+        for stale in stale_chunks(environment, &remote_variables) {
+            if !variables.iter().any(|variable| variable.key == stale.key) {
+                variables.push(stale);
+            }
+        }
+        if !self.options.dry_run {
+            self.resolve_conflicts(
+                environment_name,
+                environment,
+                &remote_variables,
+                &mut variables,
+            )?;
+        }
+        timings.diff += started.elapsed();
+
+        Ok(EnvironmentPlan {
+            name: environment_name,
+            identity,
+            create,
+            values,
+            remote_variables,
+            remote_updated_at,
+            variables,
+            secrets: environment_secrets(environment),
+        })
+    }
+
+    /// Writes a planned environment to Github, followed by its streamed
+    /// secrets as they are resolved.
+    async fn apply_environment(
+        &self,
+        plan: EnvironmentPlan<'_>,
+        streamed: StreamedSecrets,
+        applied_secrets: &BTreeMap<String, AppliedSecret>,
+        timings: &mut SyncTimings,
+    ) -> Result<EnvironmentReport> {
+        let environment_name = plan.name;
+        let client = self.client_for(environment_name);
+
+        let started = Instant::now();
+        let write = CompletedWrite::new(
+            environment_name,
+            "",
+            AuditOperation::CreateEnvironment,
+            None,
+        );
+        if plan.create && !self.already_completed(&write) {
+            self.check_cancelled()?;
+            client
+                .upsert_environment(environment_name)
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, None, e))?;
+            self.audit(
+                environment_name,
+                "",
+                AuditOperation::CreateEnvironment,
+                None,
+                None,
+            )?;
+            self.record_completed(write);
+            self.events.emit(SyncEvent::EnvironmentCreated {
+                environment: environment_name.to_string(),
+            });
+        }
+
+        let values = &plan.values;
+        let remote_variables = &plan.remote_variables;
+        for variable in &plan.variables {
+            if variable.drifted {
+                self.events.emit(SyncEvent::DriftDetected {
+                    environment: environment_name.to_string(),
+                    key: variable.key.clone(),
+                });
+            }
+
+            let environment = environment_name.to_string();
+            let key = variable.key.clone();
+
+            let event = match variable.change {
+                VariableChange::Unchanged => {
+                    debug!("Skipping unchanged environment variable {}", variable.key);
+                    SyncEvent::VariableSkipped { environment, key }
+                }
+                VariableChange::Kept => {
+                    debug!("Keeping conflicting remote value of {}", variable.key);
+                    SyncEvent::VariableSkipped { environment, key }
+                }
+                VariableChange::Update => {
+                    let value = &values[&variable.key];
+                    let write = CompletedWrite::new(
+                        environment_name,
+                        &key,
+                        AuditOperation::Update,
+                        Some(value),
+                    );
+                    if self.already_completed(&write) {
+                        self.events
+                            .emit(SyncEvent::VariableSkipped { environment, key });
+                        continue;
+                    }
+                    self.check_cancelled()?;
+                    client
+                        .update_environment_variable(environment_name, &variable.key, value)
+                        .await
+                        .inspect_err(|e| self.emit_error(environment_name, Some(&key), e))?;
+                    self.audit(
+                        environment_name,
+                        &key,
+                        AuditOperation::Update,
+                        remote_variables.get(&key.to_uppercase()).cloned(),
+                        Some(value.clone()),
+                    )?;
+                    self.record_completed(write);
+                    SyncEvent::VariableUpdated { environment, key }
+                }
+                VariableChange::Create => {
+                    let value = &values[&variable.key];
+                    let write = CompletedWrite::new(
+                        environment_name,
+                        &key,
+                        AuditOperation::Create,
+                        Some(value),
+                    );
+                    if self.already_completed(&write) {
+                        self.events
+                            .emit(SyncEvent::VariableSkipped { environment, key });
+                        continue;
+                    }
+                    self.check_cancelled()?;
+                    client
+                        .create_environment_variable(environment_name, &variable.key, value)
+                        .await
+                        .inspect_err(|e| self.emit_error(environment_name, Some(&key), e))?;
+                    self.audit(
+                        environment_name,
+                        &key,
+                        AuditOperation::Create,
+                        None,
+                        Some(value.clone()),
+                    )?;
+                    self.record_completed(write);
+                    SyncEvent::VariableCreated { environment, key }
+                }
+                VariableChange::Delete => {
+                    let write =
+                        CompletedWrite::new(environment_name, &key, AuditOperation::Delete, None);
+                    if self.already_completed(&write) {
+                        self.events
+                            .emit(SyncEvent::VariableSkipped { environment, key });
+                        continue;
+                    }
+                    self.check_cancelled()?;
+                    client
+                        .delete_environment_variable(environment_name, &variable.key)
+                        .await
+                        .inspect_err(|e| self.emit_error(environment_name, Some(&key), e))?;
+                    self.audit(
+                        environment_name,
+                        &key,
+                        AuditOperation::Delete,
+                        remote_variables.get(&key.to_uppercase()).cloned(),
+                        None,
+                    )?;
+                    self.record_completed(write);
+                    SyncEvent::VariableDeleted { environment, key }
+                }
+            };
+            self.events.emit(event);
+        }
+        let mut secrets = BTreeMap::new();
+        for (key, value) in &plan.secrets {
+            if let Some(secret) = self
+                .put_secret(environment_name, key, value, applied_secrets)
+                .await?
+            {
+                secrets.insert(key.clone(), secret);
+            }
+        }
+        if !streamed.is_empty() {
+            let depth = self.options.pipeline_depth.unwrap_or_default();
+            info!(
+                "Streaming {} secrets to environment '{}' as they are resolved",
+                streamed.len(),
+                environment_name
+            );
+            let mut resolved =
+                resolve_streamed_secrets(environment_name, streamed, self.providers.clone(), depth);
+            while let Some((key, value)) = resolved
+                .next()
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, None, e))?
+            {
+                if let Some(secret) = self
+                    .put_secret(environment_name, &key, &value, applied_secrets)
+                    .await?
+                {
+                    secrets.insert(key, secret);
+                }
+                debug!(
+                    "Streamed {} of {} secrets to environment '{}'",
+                    resolved.received(),
+                    resolved.total(),
+                    environment_name
+                );
+            }
+        }
+        let changed = plan.create
+            || !secrets.is_empty()
+            || plan.variables.iter().any(|variable| {
+                matches!(
+                    variable.change,
+                    VariableChange::Create | VariableChange::Update | VariableChange::Delete
+                )
+            });
+        if changed {
+            self.record_actor(environment_name, remote_variables)
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, None, e))?;
+        }
+        timings.apply += started.elapsed();
+
+        self.events.emit(SyncEvent::EnvironmentFinished {
+            environment: environment_name.to_string(),
+        });
+
+        let mut report = plan.into_report();
+        report.secrets = secrets.keys().cloned().collect();
+        report.applied_secrets = secrets;
+
+        Ok(report)
+    }
+
+    /// Writes an environment secret, returning what to record of it in the
+    /// state file, or `None` if it was skipped: because it's unchanged since
+    /// the write recorded in `applied_secrets`, or because the resumed sync
+    /// already wrote it.
+    async fn put_secret(
+        &self,
+        environment_name: &str,
+        key: &str,
+        value: &str,
+        applied_secrets: &BTreeMap<String, AppliedSecret>,
+    ) -> Result<Option<AppliedSecret>> {
+        let write = CompletedWrite::new(
+            environment_name,
+            key,
+            AuditOperation::PutSecret,
+            Some(value),
+        );
+        if self.already_completed(&write) {
+            debug!(
+                "Skipping secret {}, which the resumed sync already wrote",
+                key
+            );
+            return Ok(None);
+        }
+
+        let client = self.client_for(environment_name);
+        if let Some(applied) = applied_secrets
+            .get(&key.to_uppercase())
+            .filter(|applied| applied.value_hash == hash_value(value))
+        {
+            let remote = client
+                .get_environment_secret(environment_name, key)
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, Some(key), e))?;
+            if applied.is_current(
+                value,
+                remote
+                    .as_ref()
+                    .and_then(|secret| secret.updated_at.as_deref()),
+            ) {
+                debug!("Skipping unchanged environment secret {}", key);
+                self.events.emit(SyncEvent::VariableSkipped {
+                    environment: environment_name.to_string(),
+                    key: key.to_string(),
+                });
+                return Ok(None);
+            }
+        }
+
+        self.check_cancelled()?;
+        client
+            .put_environment_secret(environment_name, key, value)
+            .await
+            .inspect_err(|e| self.emit_error(environment_name, Some(key), e))?;
+        self.audit(environment_name, key, AuditOperation::PutSecret, None, None)?;
+        self.record_completed(write);
+        self.events.emit(SyncEvent::SecretUpdated {
+            environment: environment_name.to_string(),
+            key: key.to_string(),
+        });
+
+        // The next sync compares against when Github last updated the
+        // secret, which is only worth looking up if it's recorded.
+        let updated_at = match self.options.state_file {
+            Some(_) => client
+                .get_environment_secret(environment_name, key)
+                .await
+                .inspect_err(|e| self.emit_error(environment_name, Some(key), e))?
+                .and_then(|secret| secret.updated_at),
+            None => None,
+        };
+
+        Ok(Some(AppliedSecret::new(value, updated_at)))
+    }
+
+    /// Sets an environment's [`LAST_ACTOR_VARIABLE`] to the actor, if there
+    /// is one.  The marker isn't audited, as it only describes other writes.
+    async fn record_actor(
+        &self,
+        environment_name: &str,
+        remote_variables: &Environment,
+    ) -> Result<()> {
+        let Some(actor) = &self.actor else {
+            return Ok(());
+        };
+
+        let client = self.client_for(environment_name);
+        match remote_variables.get(LAST_ACTOR_VARIABLE) {
+            Some(previous) if previous == actor => Ok(()),
+            Some(_) => {
+                client
+                    .update_environment_variable(environment_name, LAST_ACTOR_VARIABLE, actor)
+                    .await
+            }
+            None => {
+                client
+                    .create_environment_variable(environment_name, LAST_ACTOR_VARIABLE, actor)
+                    .await
+            }
+        }
+    }
+
+    /// Records an applied environment in the state file, if one is in use.
+    /// Each environment is recorded as soon as it is applied, so that a later
+    /// failure doesn't lose track of what was written.
+    fn record_applied(
+        &self,
+        state: &mut Option<State>,
+        config: &ConfigDocument,
+        report: &EnvironmentReport,
+    ) -> Result<()> {
+        let (Some(state), Some(path)) = (state, &self.options.state_file) else {
+            return Ok(());
+        };
+
+        let repository = self.repository();
+        let now = unix_timestamp();
+        let environment = &config.environments[&report.name];
+        state.record_created(&repository, &report.name, None, now);
+        for variable in &report.variables {
+            match variable.change {
+                VariableChange::Delete => {
+                    state.record_deleted(&repository, &report.name, &variable.key, now);
+                }
+                // Kept values weren't applied, so the recorded hash stays at
+                // whatever was last applied.
+                VariableChange::Kept => {}
+                _ => {
+                    let variable_config = &environment[&variable.key];
+                    state.record(
+                        &repository,
+                        &report.name,
+                        &variable.key,
+                        &variable_config.value,
+                    );
+                    if variable_config.expires.is_some() {
+                        state.record_created(&repository, &report.name, Some(&variable.key), now);
+                    }
+                }
+            }
+        }
+        for (key, secret) in &report.applied_secrets {
+            state.record_secret(&repository, &report.name, key, secret.clone());
+        }
+
+        state.save(path)
+    }
+
+    /// Applies the conflict strategy to every conflicting variable, failing
+    /// before anything is written if any of them is set to fail or aborted.
+    fn resolve_conflicts(
+        &self,
+        environment_name: &str,
+        environment: &EnvironmentConfig,
+        remote_variables: &Environment,
+        variables: &mut [VariableReport],
+    ) -> Result<()> {
+        let mut failed = Vec::new();
+
+        for variable in variables
+            .iter_mut()
+            .filter(|variable| variable.is_conflict())
+        {
+            let strategy = environment[&variable.key]
+                .on_conflict
+                .unwrap_or(self.options.on_conflict);
+            debug!(
+                "Resolving conflict on {} in environment '{}' with strategy {}",
+                variable.key, environment_name, strategy
+            );
+
+            match strategy {
+                ConflictStrategy::Overwrite => {}
+                ConflictStrategy::Keep => variable.change = VariableChange::Kept,
+                ConflictStrategy::Fail => failed.push(variable.key.clone()),
+                ConflictStrategy::Prompt => {
+                    if self.apply_all_conflicts.load(Ordering::Relaxed) {
+                        continue;
+                    }
+
+                    let resolver = self.resolver.as_ref().ok_or_else(|| {
+                        eyre!("The 'prompt' conflict strategy requires a conflict resolver")
+                    })?;
+                    let conflict = Conflict {
+                        environment: environment_name,
+                        key: &variable.key,
+                        local_value: &environment[&variable.key].value,
+                        remote_value: remote_variables
+                            .get(&variable.key.to_uppercase())
+                            .map(String::as_str),
+                    };
+
+                    match resolver.resolve(&conflict)? {
+                        Resolution::Apply => {}
+                        Resolution::Skip => variable.change = VariableChange::Kept,
+                        Resolution::ApplyAll => {
+                            self.apply_all_conflicts.store(true, Ordering::Relaxed)
+                        }
+                        Resolution::Abort => {
+                            let error = eyre!(
+                                "Sync aborted while resolving conflict on {} in environment '{}'",
+                                variable.key,
+                                environment_name
+                            );
+                            self.emit_error(environment_name, Some(&variable.key), &error);
+                            return Err(error);
+                        }
+                    }
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        let error = eyre!(
+            "Refusing to overwrite {} in environment '{}', which changed remotely since the last sync and differ from the config",
+            failed.join(", "),
+            environment_name
+        );
+        self.emit_error(environment_name, None, &error);
+
+        Err(error)
+    }
+
+    /// Records a write in the audit log, if one is configured.
+    pub(crate) fn audit(
+        &self,
+        environment_name: &str,
+        key: &str,
+        operation: AuditOperation,
+        previous_value: Option<String>,
+        value: Option<String>,
+    ) -> Result<()> {
+        let Some(path) = &self.options.audit_log else {
+            return Ok(());
+        };
+
+        AuditLog::new(path).append(&AuditRecord {
+            run_id: self.run_id.clone(),
+            timestamp: unix_timestamp(),
+            repository: self.repository(),
+            environment: environment_name.to_string(),
+            key: key.to_string(),
+            operation,
+            previous_value,
+            value,
+            identity: self.identity_for(environment_name).map(str::to_string),
+            actor: self.actor.clone(),
+        })
+    }
+
+    /// Whether the interrupted run being resumed already made the write.
+    fn already_completed(&self, write: &CompletedWrite) -> bool {
+        self.resumed
+            .as_ref()
+            .is_some_and(|resumed| resumed.contains(write))
+    }
+
+    fn record_completed(&self, write: CompletedWrite) {
+        self.completed.lock().expect("lock poisoned").push(write);
+    }
+
+    /// Fails with [`CancelledError`] once the sync was cancelled, recording
+    /// the writes made so far, including those of the run being resumed, in
+    /// the checkpoint file.  Called before each write, so that the request
+    /// in flight when the sync was cancelled is always finished.
+    fn check_cancelled(&self) -> Result<()> {
+        if !self
+            .cancellation
+            .as_ref()
+            .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+        {
+            return Ok(());
+        }
+
+        let mut completed = self
+            .resumed
+            .as_ref()
+            .map(|resumed| resumed.completed.clone())
+            .unwrap_or_default();
+        completed.extend(
+            self.completed
+                .lock()
+                .expect("lock poisoned")
+                .iter()
+                .cloned(),
+        );
+        let checkpoint = match &self.options.checkpoint_file {
+            Some(path) if !completed.is_empty() => {
+                Checkpoint::record(
+                    path,
+                    &self.repository(),
+                    RepositoryCheckpoint {
+                        run_id: self.run_id.clone(),
+                        completed,
+                    },
+                )?;
+                Some(path.clone())
+            }
+            _ => None,
+        };
+
+        Err(CancelledError { checkpoint }.into())
+    }
+
+    fn emit_error(&self, environment_name: &str, key: Option<&str>, error: &Report) {
+        self.events.emit(SyncEvent::Error {
+            environment: Some(environment_name.to_string()),
+            key: key.map(str::to_string),
+            message: error.to_string(),
+        });
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashSet};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+use crate::config::Environment;
+
+const ENVIRONMENT_RESOURCE: &str = "github_repository_environment";
+const VARIABLE_RESOURCE: &str = "github_actions_environment_variable";
+
+/// Renders environments and their variables as resources of the Github
+/// Terraform provider, for moving them to Terraform:
+///
+/// ```hcl
+/// resource "github_repository_environment" "production" {
+///   repository  = "octo-repo"
+///   environment = "production"
+/// }
+///
+/// resource "github_actions_environment_variable" "production_api_url" {
+///   repository    = "octo-repo"
+///   environment   = github_repository_environment.production.environment
+///   variable_name = "API_URL"
+///   value         = "https://example.com"
+/// }
+/// ```
+///
+/// With `import_blocks`, each resource is followed by an `import` block, so
+/// that `terraform apply` adopts what already exists on Github instead of
+/// failing to create it.
+pub fn export_terraform(
+    repository_name: &str,
+    environments: &BTreeMap<String, Environment>,
+    import_blocks: bool,
+) -> String {
+    let mut names = ResourceNames::default();
+    let mut output = String::new();
+
+    for (environment_name, variables) in environments {
+        let environment_resource = names.unique(ENVIRONMENT_RESOURCE, environment_name);
+        output.push_str(&format!(
+            "resource \"{}\" \"{}\" {{\n  repository  = {}\n  environment = {}\n}}\n\n",
+            ENVIRONMENT_RESOURCE,
+            environment_resource,
+            quote(repository_name),
+            quote(environment_name)
+        ));
+        if import_blocks {
+            output.push_str(&import_block(
+                ENVIRONMENT_RESOURCE,
+                &environment_resource,
+                &format!("{}:{}", repository_name, environment_name),
+            ));
+        }
+
+        let mut keys = variables.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let variable_resource =
+                names.unique(VARIABLE_RESOURCE, &format!("{}_{}", environment_name, key));
+            output.push_str(&format!(
+                "resource \"{}\" \"{}\" {{\n  repository    = {}\n  environment   = {}.{}.environment\n  variable_name = {}\n  value         = {}\n}}\n\n",
+                VARIABLE_RESOURCE,
+                variable_resource,
+                quote(repository_name),
+                ENVIRONMENT_RESOURCE,
+                environment_resource,
+                quote(key),
+                quote(&variables[key])
+            ));
+            if import_blocks {
+                output.push_str(&import_block(
+                    VARIABLE_RESOURCE,
+                    &variable_resource,
+                    &format!("{}:{}:{}", repository_name, environment_name, key),
+                ));
+            }
+        }
+    }
+
+    output.truncate(output.trim_end().len());
+    output.push('\n');
+    output
+}
+
+fn import_block(resource_type: &str, resource_name: &str, id: &str) -> String {
+    format!(
+        "import {{\n  to = {}.{}\n  id = {}\n}}\n\n",
+        resource_type,
+        resource_name,
+        quote(id)
+    )
+}
+
+/// Hands out Terraform resource names, which may only contain letters,
+/// digits, underscores and dashes, and must be unique per resource type.
+#[derive(Debug, Default)]
+struct ResourceNames {
+    used: HashSet<(String, String)>,
+}
+
+impl ResourceNames {
+    fn unique(&mut self, resource_type: &str, name: &str) -> String {
+        let mut base = name
+            .to_lowercase()
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+                true => c,
+                false => '_',
+            })
+            .collect::<String>();
+        if !base.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            base.insert(0, '_');
+        }
+
+        let mut candidate = base.clone();
+        let mut suffix = 2;
+        while !self
+            .used
+            .insert((resource_type.to_string(), candidate.clone()))
+        {
+            candidate = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+
+        candidate
+    }
+}
+
+/// Quotes a value as an HCL string, escaping template sequences so that
+/// values are taken literally.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                quoted.push(c);
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// The parts of a Terraform state file (format version 4) needed to read
+/// environment variables out of it.
+#[derive(Debug, Deserialize)]
+struct TerraformState {
+    version: u32,
+    #[serde(default)]
+    resources: Vec<StateResource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StateResource {
+    mode: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    #[serde(default)]
+    instances: Vec<StateInstance>,
+}
+
+/// Attributes are only parsed for the resources that are read, as every
+/// resource type has different ones.
+#[derive(Debug, Deserialize)]
+struct StateInstance {
+    attributes: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct VariableAttributes {
+    repository: String,
+    environment: String,
+    variable_name: String,
+    value: String,
+}
+
+/// Reads the `github_actions_environment_variable` resources of a
+/// repository, by name, out of a Terraform state file, keyed by environment
+/// name, for adopting variables that Terraform managed until now.
+pub fn variables_from_state(
+    contents: &str,
+    repository_name: &str,
+) -> Result<BTreeMap<String, Environment>> {
+    let state: TerraformState = serde_json::from_str(contents)
+        .map_err(|e| eyre!("Error parsing Terraform state: {}", e))?;
+    if state.version != 4 {
+        return Err(eyre!(
+            "Unsupported Terraform state version {}, expected 4",
+            state.version
+        ));
+    }
+
+    let mut environments = BTreeMap::<String, Environment>::new();
+    for resource in state.resources.into_iter().filter(|resource| {
+        resource.mode == "managed" && resource.resource_type == VARIABLE_RESOURCE
+    }) {
+        for instance in resource.instances {
+            let attributes: VariableAttributes = serde_json::from_value(instance.attributes)
+                .map_err(|e| {
+                    eyre!(
+                        "Error parsing {} in Terraform state: {}",
+                        VARIABLE_RESOURCE,
+                        e
+                    )
+                })?;
+            if !attributes.repository.eq_ignore_ascii_case(repository_name) {
+                continue;
+            }
+
+            environments
+                .entry(attributes.environment)
+                .or_default()
+                .insert(attributes.variable_name, attributes.value);
+        }
+    }
+
+    Ok(environments)
+}
@@ -0,0 +1,122 @@
+use std::{fmt, str::FromStr};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+use crate::config::EnvironmentConfig;
+
+/// A normalization applied to a value before it is compared and uploaded,
+/// so that formatting that is convenient in TOML doesn't cause spurious
+/// diffs or break whatever reads the variable:
+///
+/// ```toml
+/// [production]
+/// CERTIFICATE = { value = """
+/// -----BEGIN CERTIFICATE-----
+/// ...
+/// """, transform = ["trim"] }
+/// FEATURES = { value = '{ "beta": true }', transform = ["json_minify"] }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Strips leading and trailing whitespace, including newlines.
+    Trim,
+    /// Removes whitespace outside of strings from a JSON value, failing if
+    /// the value isn't valid JSON.  Keys keep their order.
+    JsonMinify,
+    /// Replaces `\r\n` and lone `\r` line endings with `\n`.
+    NormalizeNewlines,
+}
+
+impl Transform {
+    /// Applies the transformation to a value.
+    pub fn apply(&self, value: &str) -> Result<String> {
+        match self {
+            Self::Trim => Ok(value.trim().to_string()),
+            Self::JsonMinify => minify_json(value),
+            Self::NormalizeNewlines => Ok(value.replace("\r\n", "\n").replace('\r', "\n")),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "trim" => Ok(Self::Trim),
+            "json_minify" => Ok(Self::JsonMinify),
+            "normalize_newlines" => Ok(Self::NormalizeNewlines),
+            _ => Err(eyre!(
+                "Unknown transform '{}', expected one of trim, json_minify, normalize_newlines",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Trim => "trim",
+            Self::JsonMinify => "json_minify",
+            Self::NormalizeNewlines => "normalize_newlines",
+        })
+    }
+}
+
+/// Whether any variable in the environment has transformations.
+pub fn has_transforms(environment: &EnvironmentConfig) -> bool {
+    environment
+        .values()
+        .any(|variable| !variable.transform.is_empty())
+}
+
+/// Applies each variable's transformations to its value, in the order they
+/// are listed.  This runs after references are resolved, so composite values
+/// are transformed as a whole.
+pub fn apply_transforms(environment_name: &str, environment: &mut EnvironmentConfig) -> Result<()> {
+    for (key, variable) in environment.iter_mut() {
+        for transform in &variable.transform {
+            variable.value = transform.apply(&variable.value).map_err(|e| {
+                eyre!(
+                    "Error applying {} to {} in environment '{}': {}",
+                    transform,
+                    key,
+                    environment_name,
+                    e
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Drops the whitespace between JSON tokens.  Values are validated by
+/// parsing, but not re-serialized, so numbers and key order are untouched.
+fn minify_json(value: &str) -> Result<String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(value)
+        .map_err(|e| eyre!("value is not valid JSON: {}", e))?;
+
+    let mut minified = String::with_capacity(value.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if in_string {
+            minified.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if !c.is_whitespace() {
+            in_string = c == '"';
+            minified.push(c);
+        }
+    }
+
+    Ok(minified)
+}
@@ -0,0 +1,570 @@
+//! An interactive terminal UI for browsing a repository's environments,
+//! comparing them against the config document, and applying selected
+//! changes.
+
+use std::{collections::BTreeMap, io, time::Duration};
+
+use color_eyre::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use gh_client::EnvironmentVariable;
+use gh_env_sync::{
+    config::EnvironmentConfig, ConfigDocument, Environment, SyncOptions, Syncer, VariableConfig,
+    VariableKind,
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+
+use crate::{cli::TuiArgs, output::short_timestamp};
+
+/// How a variable differs between the config document and Github.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowStatus {
+    Create,
+    Update,
+    Unchanged,
+    /// Defined on Github but not in the config document.
+    RemoteOnly,
+}
+
+impl RowStatus {
+    fn label(self) -> &'static str {
+        match self {
+            RowStatus::Create => "create",
+            RowStatus::Update => "update",
+            RowStatus::Unchanged => "",
+            RowStatus::RemoteOnly => "remote only",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            RowStatus::Create => Color::Green,
+            RowStatus::Update => Color::Yellow,
+            RowStatus::Unchanged => Color::Gray,
+            RowStatus::RemoteOnly => Color::Red,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct VariableRow {
+    /// Uppercased, as Github stores it.
+    key: String,
+    local: Option<String>,
+    remote: Option<String>,
+    /// When the remote value was last written, as an ISO 8601 timestamp.
+    remote_updated_at: Option<String>,
+    staged: bool,
+}
+
+impl VariableRow {
+    fn status(&self) -> RowStatus {
+        match (&self.local, &self.remote) {
+            (Some(local), Some(remote)) if local == remote => RowStatus::Unchanged,
+            (Some(_), Some(_)) => RowStatus::Update,
+            (Some(_), None) => RowStatus::Create,
+            (None, _) => RowStatus::RemoteOnly,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EnvironmentView {
+    name: String,
+    exists_remotely: bool,
+    rows: Vec<VariableRow>,
+}
+
+impl EnvironmentView {
+    fn new(name: String, local: &Environment, remote: Option<Vec<EnvironmentVariable>>) -> Self {
+        let exists_remotely = remote.is_some();
+        let remote = remote.unwrap_or_default();
+
+        let mut rows = BTreeMap::new();
+        for (key, value) in local {
+            rows.insert(
+                key.to_uppercase(),
+                VariableRow {
+                    key: key.to_uppercase(),
+                    local: Some(value.clone()),
+                    remote: None,
+                    remote_updated_at: None,
+                    staged: false,
+                },
+            );
+        }
+        for variable in remote {
+            let row = rows
+                .entry(variable.name.clone())
+                .or_insert_with(|| VariableRow {
+                    key: variable.name.clone(),
+                    local: None,
+                    remote: None,
+                    remote_updated_at: None,
+                    staged: false,
+                });
+            row.remote = Some(variable.value);
+            row.remote_updated_at = variable.updated_at;
+        }
+
+        Self {
+            name,
+            exists_remotely,
+            rows: rows.into_values().collect(),
+        }
+    }
+
+    fn staged_count(&self) -> usize {
+        self.rows.iter().filter(|row| row.staged).count()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Environments,
+    Variables,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Browse,
+    /// Editing the local value of the selected variable.
+    Edit(String),
+}
+
+struct App {
+    syncer: Syncer,
+    /// `owner/repo`.
+    repository: String,
+    environments: Vec<EnvironmentView>,
+    environment_state: ListState,
+    variable_state: TableState,
+    focus: Focus,
+    mode: Mode,
+    status: String,
+}
+
+impl App {
+    async fn load(syncer: Syncer, config: &ConfigDocument) -> Result<Self> {
+        let client = syncer.client();
+        let remote_environments = client.list_environments().await?;
+
+        let mut names = config
+            .environments
+            .keys()
+            .chain(&remote_environments)
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+
+        let mut environments = Vec::with_capacity(names.len());
+        for name in names {
+            let local = config
+                .environments
+                .get(&name)
+                .map(gh_env_sync::config::environment_values)
+                .unwrap_or_default();
+            let remote = if remote_environments.contains(&name) {
+                Some(
+                    syncer
+                        .client_for(&name)
+                        .list_environment_variable_details(&name)
+                        .await?,
+                )
+            } else {
+                None
+            };
+            environments.push(EnvironmentView::new(name, &local, remote));
+        }
+
+        let mut environment_state = ListState::default();
+        environment_state.select((!environments.is_empty()).then_some(0));
+
+        let repository = format!("{}/{}", client.repository_owner(), client.repository_name());
+
+        Ok(Self {
+            syncer,
+            repository,
+            environments,
+            environment_state,
+            variable_state: TableState::default(),
+            focus: Focus::Environments,
+            mode: Mode::Browse,
+            status: "Tab: switch pane  j/k: move  space: stage  e: edit  a: apply  q: quit"
+                .to_string(),
+        })
+    }
+
+    fn environment(&self) -> Option<&EnvironmentView> {
+        self.environment_state
+            .selected()
+            .and_then(|index| self.environments.get(index))
+    }
+
+    fn row_mut(&mut self) -> Option<&mut VariableRow> {
+        let environment = self.environment_state.selected()?;
+        let row = self.variable_state.selected()?;
+        self.environments.get_mut(environment)?.rows.get_mut(row)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (state_len, selected) = match self.focus {
+            Focus::Environments => (self.environments.len(), self.environment_state.selected()),
+            Focus::Variables => (
+                self.environment().map_or(0, |env| env.rows.len()),
+                self.variable_state.selected(),
+            ),
+        };
+        if state_len == 0 {
+            return;
+        }
+
+        let next = selected
+            .map_or(0, |index| index as isize + delta)
+            .clamp(0, state_len as isize - 1) as usize;
+        match self.focus {
+            Focus::Environments => {
+                self.environment_state.select(Some(next));
+                self.variable_state.select(None);
+            }
+            Focus::Variables => self.variable_state.select(Some(next)),
+        }
+    }
+
+    fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Environments if self.environment().is_some_and(|env| !env.rows.is_empty()) => {
+                if self.variable_state.selected().is_none() {
+                    self.variable_state.select(Some(0));
+                }
+                Focus::Variables
+            }
+            _ => Focus::Environments,
+        };
+    }
+
+    fn toggle_staged(&mut self) {
+        let Some(row) = self.row_mut() else {
+            return;
+        };
+
+        match row.status() {
+            RowStatus::RemoteOnly => {
+                self.status = format!(
+                    "{} isn't in the config document, so there is nothing to apply",
+                    row.key
+                )
+            }
+            RowStatus::Unchanged => self.status = format!("{} is already up to date", row.key),
+            RowStatus::Create | RowStatus::Update => row.staged = !row.staged,
+        }
+    }
+
+    fn start_edit(&mut self) {
+        if let Some(row) = self.row_mut() {
+            let value = row.local.clone().unwrap_or_default();
+            self.mode = Mode::Edit(value);
+        }
+    }
+
+    fn finish_edit(&mut self, value: String) {
+        self.mode = Mode::Browse;
+        if let Some(row) = self.row_mut() {
+            row.local = Some(value);
+            row.staged = row.status() != RowStatus::Unchanged;
+            let key = row.key.clone();
+            self.status = format!("Edited {} (not saved to the config file)", key);
+        }
+    }
+
+    /// Applies the staged variables of the selected environment through the
+    /// syncer, so that locking and the audit log behave as in a normal sync.
+    async fn apply(&mut self) -> Result<()> {
+        let Some(index) = self.environment_state.selected() else {
+            return Ok(());
+        };
+        let environment = &self.environments[index];
+        if environment.staged_count() == 0 {
+            self.status = format!("Nothing staged in '{}'", environment.name);
+            return Ok(());
+        }
+
+        let staged: EnvironmentConfig = environment
+            .rows
+            .iter()
+            .filter(|row| row.staged)
+            .filter_map(|row| {
+                row.local.clone().map(|value| {
+                    (
+                        row.key.clone(),
+                        VariableConfig {
+                            value,
+                            on_conflict: None,
+                            generate: None,
+                            file: None,
+                            owner: None,
+                            expires: None,
+                            transform: Vec::new(),
+                            kind: VariableKind::Variable,
+                            chunk: false,
+                        },
+                    )
+                })
+            })
+            .collect();
+        let name = environment.name.clone();
+        let document = ConfigDocument {
+            environments: [(name.clone(), staged)].into_iter().collect(),
+            ..ConfigDocument::default()
+        };
+
+        self.status = match self.syncer.sync(&document, Some(&name)).await {
+            Ok(report) => {
+                let remote = self
+                    .syncer
+                    .client_for(&name)
+                    .list_environment_variable_details(&name)
+                    .await?;
+                let environment = &mut self.environments[index];
+                environment.exists_remotely = true;
+                for row in &mut environment.rows {
+                    let variable = remote.iter().find(|variable| variable.name == row.key);
+                    row.remote = variable.map(|variable| variable.value.clone());
+                    row.remote_updated_at =
+                        variable.and_then(|variable| variable.updated_at.clone());
+                    row.staged = false;
+                }
+                format!(
+                    "Applied {} variables to '{}'",
+                    report
+                        .environments
+                        .iter()
+                        .map(|environment| environment.variables.len())
+                        .sum::<usize>(),
+                    name
+                )
+            }
+            Err(e) => format!("Error applying '{}': {}", name, e),
+        };
+
+        Ok(())
+    }
+
+    /// Handles a key press, returning false when the app should exit.
+    async fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if let Mode::Edit(value) = &mut self.mode {
+            match key.code {
+                KeyCode::Enter => {
+                    let value = std::mem::take(value);
+                    self.finish_edit(value);
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    value.pop();
+                }
+                KeyCode::Char(c) => value.push(c),
+                _ => {}
+            }
+            return Ok(true);
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => self.toggle_focus(),
+            KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char(' ') if self.focus == Focus::Variables => self.toggle_staged(),
+            KeyCode::Char('e') if self.focus == Focus::Variables => self.start_edit(),
+            KeyCode::Char('a') => self.apply().await?,
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = split(
+            frame.size(),
+            Direction::Vertical,
+            [Constraint::Min(3), Constraint::Length(3)],
+        );
+        let [sidebar, variables] = split(
+            main,
+            Direction::Horizontal,
+            [Constraint::Percentage(30), Constraint::Percentage(70)],
+        );
+        let [repositories, environments] = split(
+            sidebar,
+            Direction::Vertical,
+            [Constraint::Length(3), Constraint::Min(3)],
+        );
+
+        let repository = Paragraph::new(self.repository.as_str())
+            .block(Block::default().borders(Borders::ALL).title("Repository"));
+        frame.render_widget(repository, repositories);
+
+        let items = self
+            .environments
+            .iter()
+            .map(|environment| {
+                let mut label = environment.name.clone();
+                if !environment.exists_remotely {
+                    label.push_str(" (new)");
+                }
+                if environment.staged_count() > 0 {
+                    label.push_str(&format!(" [{} staged]", environment.staged_count()));
+                }
+                ListItem::new(label)
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(pane("Environments", self.focus == Focus::Environments))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, environments, &mut self.environment_state);
+
+        let rows = self
+            .environment()
+            .map(|environment| {
+                environment
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let status = row.status();
+                        Row::new(vec![
+                            Cell::from(if row.staged { "*" } else { " " }),
+                            Cell::from(row.key.clone()),
+                            Cell::from(row.local.clone().unwrap_or_default()),
+                            Cell::from(row.remote.clone().unwrap_or_default()),
+                            Cell::from(
+                                row.remote_updated_at
+                                    .as_deref()
+                                    .map(short_timestamp)
+                                    .unwrap_or_default(),
+                            ),
+                            Cell::from(status.label()),
+                        ])
+                        .style(Style::default().fg(status.color()))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(1),
+                Constraint::Percentage(20),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+                Constraint::Length(16),
+                Constraint::Percentage(15),
+            ],
+        )
+        .header(
+            Row::new(vec!["", "Key", "Local", "Remote", "Updated", ""])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(pane("Variables", self.focus == Focus::Variables))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, variables, &mut self.variable_state);
+
+        let status_line = match &self.mode {
+            Mode::Browse => Line::from(self.status.as_str()),
+            Mode::Edit(value) => Line::from(format!(
+                "New value (Enter to save, Esc to cancel): {}",
+                value
+            )),
+        };
+        let status_bar = Paragraph::new(status_line).block(Block::default().borders(Borders::ALL));
+        frame.render_widget(status_bar, status);
+    }
+}
+
+fn split<const N: usize>(
+    area: ratatui::layout::Rect,
+    direction: Direction,
+    constraints: [Constraint; N],
+) -> [ratatui::layout::Rect; N] {
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    std::array::from_fn(|index| chunks[index])
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title)
+}
+
+/// Runs the terminal UI until the user quits.
+pub async fn run(config: &ConfigDocument, options: &TuiArgs) -> Result<()> {
+    let syncer = options
+        .connection
+        .syncer(
+            &config.auth,
+            SyncOptions {
+                lock: options.write.lock,
+                state_file: options.state_file.clone(),
+                audit_log: options.write.audit_log.clone(),
+                ignore_prefix: Some(options.ignore_prefix.clone()),
+                ..SyncOptions::default()
+            },
+            None,
+        )
+        .await?;
+
+    // Show generated and referencing values as they'll be applied.
+    let mut config = config.clone();
+    syncer.resolve_values(&mut config)?;
+    let mut app = App::load(syncer, &config).await?;
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let result = event_loop(&mut terminal, &mut app).await;
+
+    // Restore the terminal even if the loop failed, so that the error is
+    // readable.
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+async fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key).await? {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use color_eyre::{eyre::eyre, Result};
+use tracing::{info, warn};
+
+use crate::{
+    audit::{AuditLog, AuditOperation, AuditRecord},
+    lock::SyncLock,
+    state::{hash_value, State},
+    sync::Syncer,
+};
+
+/// What undeleting a key did, or would do, in a single environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndeleteOutcome {
+    /// The key was recreated with its value from before it was deleted.
+    Restored,
+    /// The key exists again, e.g. because a later sync recreated it, so it
+    /// was left alone.
+    AlreadyExists,
+}
+
+/// The outcome of undeleting a key in a single environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndeletedKey {
+    pub environment: String,
+    pub outcome: UndeleteOutcome,
+    /// When the key was deleted, in seconds since the unix epoch.
+    pub deleted_at: u64,
+    /// The run that deleted it.
+    pub run_id: String,
+}
+
+/// The latest deletion of `key` from `environment` recorded in the audit log
+/// along with the value it deleted.  `records` are the contents of the audit
+/// log, oldest first.
+pub fn last_deletion<'a>(
+    records: &'a [AuditRecord],
+    repository: &str,
+    environment: &str,
+    key: &str,
+) -> Option<&'a AuditRecord> {
+    records.iter().rev().find(|record| {
+        record.repository == repository
+            && record.environment == environment
+            && record.operation == AuditOperation::Delete
+            && record.key.eq_ignore_ascii_case(key)
+            && record.previous_value.is_some()
+    })
+}
+
+/// The environments to undelete `key` in without `--environment`: those
+/// with a tombstone of it in the state file, or else those it was deleted
+/// from according to the audit log.
+pub fn deleted_from(
+    records: &[AuditRecord],
+    state: Option<&State>,
+    repository: &str,
+    key: &str,
+) -> Vec<String> {
+    let tombstoned = state
+        .map(|state| state.tombstoned_environments(repository, key))
+        .unwrap_or_default();
+    if !tombstoned.is_empty() {
+        return tombstoned;
+    }
+
+    let mut environments = records
+        .iter()
+        .filter(|record| {
+            record.repository == repository
+                && record.operation == AuditOperation::Delete
+                && record.key.eq_ignore_ascii_case(key)
+        })
+        .map(|record| record.environment.clone())
+        .collect::<Vec<_>>();
+    environments.sort();
+    environments.dedup();
+    environments
+}
+
+impl Syncer {
+    /// Recreates `key` in each of `environments`, or wherever it was deleted,
+    /// with the value it had before its latest deletion, which the audit log
+    /// keeps as a backup.  The key's tombstone is removed from the state
+    /// file, but no applied value is recorded, so that `--prune` leaves the
+    /// key alone until it is added back to the config.  Environments where
+    /// the key is excluded (see [`SyncOptions::exclusions`]) are left out.
+    /// With `dry_run`, nothing is written.
+    ///
+    /// [`SyncOptions::exclusions`]: crate::sync::SyncOptions::exclusions
+    pub async fn undelete(
+        &self,
+        key: &str,
+        environments: Option<&[String]>,
+        dry_run: bool,
+    ) -> Result<Vec<UndeletedKey>> {
+        let path = self
+            .options()
+            .audit_log
+            .as_ref()
+            .ok_or_else(|| eyre!("Undeleting requires the audit log of the deleting sync"))?;
+        let records = AuditLog::new(path).read()?;
+        let mut state = match &self.options().state_file {
+            Some(path) => Some(State::load(path)?),
+            None => None,
+        };
+
+        let key = key.to_uppercase();
+        let repository = self.repository();
+        let environments = match environments {
+            Some(environments) => environments.to_vec(),
+            None => deleted_from(&records, state.as_ref(), &repository, &key),
+        };
+        if environments.is_empty() {
+            return Err(eyre!(
+                "{} has no tombstone or recorded deletion in {}",
+                key,
+                repository
+            ));
+        }
+
+        let mut deletions = Vec::new();
+        for environment in &environments {
+            if self.options().exclusions.excludes_key(environment, &key) {
+                info!(
+                    "Not undeleting {} in environment '{}', where it is excluded",
+                    key, environment
+                );
+                continue;
+            }
+
+            let deletion =
+                last_deletion(&records, &repository, environment, &key).ok_or_else(|| {
+                    eyre!(
+                        "The audit log records no value of {} deleted from environment '{}'",
+                        key,
+                        environment
+                    )
+                })?;
+            let value = deletion
+                .previous_value
+                .as_ref()
+                .expect("deletions are found by their value");
+            let value_hash = state
+                .as_ref()
+                .and_then(|state| state.tombstone(&repository, environment, &key))
+                .and_then(|tombstone| tombstone.value_hash.as_ref());
+            if value_hash.is_some_and(|hash| *hash != hash_value(value)) {
+                warn!(
+                    "{} in environment '{}' had been changed outside of gh-env-sync when it was deleted; restoring the value it had then",
+                    key, environment
+                );
+            }
+            deletions.push((environment, deletion));
+        }
+
+        if dry_run {
+            let mut undeleted = Vec::new();
+            for (environment, deletion) in deletions {
+                let exists = self
+                    .client_for(environment)
+                    .get_environment_variable(environment, &key)
+                    .await?
+                    .is_some();
+                undeleted.push(UndeletedKey {
+                    environment: environment.clone(),
+                    outcome: match exists {
+                        true => UndeleteOutcome::AlreadyExists,
+                        false => UndeleteOutcome::Restored,
+                    },
+                    deleted_at: deletion.timestamp,
+                    run_id: deletion.run_id.clone(),
+                });
+            }
+            return Ok(undeleted);
+        }
+
+        let lock = SyncLock::acquire(self.client(), self.options().lock).await?;
+        let mut undeleted = Vec::new();
+        let mut result = Ok(());
+        for (environment, deletion) in deletions {
+            match self.undelete_in(environment, &key, deletion).await {
+                Ok(outcome) => {
+                    if let Some(state) = state.as_mut() {
+                        state.remove_tombstone(&repository, environment, &key);
+                    }
+                    undeleted.push(UndeletedKey {
+                        environment: environment.clone(),
+                        outcome,
+                        deleted_at: deletion.timestamp,
+                        run_id: deletion.run_id.clone(),
+                    });
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        let result = lock.release_after(self.client(), result).await;
+
+        if let (Some(state), Some(path)) = (&state, &self.options().state_file) {
+            state.save(path)?;
+        }
+        result.map(|_| undeleted)
+    }
+
+    async fn undelete_in(
+        &self,
+        environment: &str,
+        key: &str,
+        deletion: &AuditRecord,
+    ) -> Result<UndeleteOutcome> {
+        let client = self.client_for(environment);
+        if client
+            .get_environment_variable(environment, key)
+            .await?
+            .is_some()
+        {
+            info!(
+                "{} exists in environment '{}' again, leaving it alone",
+                key, environment
+            );
+            return Ok(UndeleteOutcome::AlreadyExists);
+        }
+
+        let value = deletion
+            .previous_value
+            .clone()
+            .expect("deletions are found by their value");
+        info!(
+            "Restoring {} in environment '{}' as deleted by run {}",
+            key, environment, deletion.run_id
+        );
+        client
+            .create_environment_variable(environment, key, &value)
+            .await?;
+        self.audit(environment, key, AuditOperation::Create, None, Some(value))?;
+
+        Ok(UndeleteOutcome::Restored)
+    }
+}
@@ -0,0 +1,195 @@
+//! `self-update`: replaces the running binary with the build of a Github
+//! release of gh-env-sync for this platform, after checking it against the
+//! release's checksums.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::{download_release_asset, get_release, HttpOptions, ReleaseAsset};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::cli::SelfUpdateArgs;
+
+/// The repository gh-env-sync is released from.
+const RELEASE_OWNER: &str = "michaelhelvey";
+const RELEASE_REPOSITORY: &str = "gh-env-sync";
+
+/// The release asset listing the SHA-256 checksum of every other asset, in
+/// the format of `sha256sum`.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// The name of the release asset built for this platform.  Linux builds are
+/// static musl binaries, so that they run on any distribution.
+fn asset_name() -> Result<String> {
+    let arch = std::env::consts::ARCH;
+    match std::env::consts::OS {
+        "linux" => Ok(format!("gh-env-sync-{}-unknown-linux-musl", arch)),
+        "macos" => Ok(format!("gh-env-sync-{}-apple-darwin", arch)),
+        "windows" => Ok(format!("gh-env-sync-{}-pc-windows-msvc.exe", arch)),
+        os => Err(eyre!("gh-env-sync isn't released for {} on {}", arch, os)),
+    }
+}
+
+/// The numeric parts of a version or tag such as `v1.2.3`.  Pre-release and
+/// build suffixes are ignored.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Whether a release tag is a newer version than `current`.
+fn is_newer(tag: &str, current: &str) -> bool {
+    match (parse_version(tag), parse_version(current)) {
+        (Some(tag), Some(current)) => tag > current,
+        _ => false,
+    }
+}
+
+/// The checksum of `name` in the contents of a [`CHECKSUMS_ASSET`].
+fn expected_checksum<'a>(checksums: &'a str, name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (checksum, file) = line.split_once(char::is_whitespace)?;
+        // sha256sum marks files read in binary mode with a `*`.
+        (file.trim_start().trim_start_matches('*') == name).then_some(checksum)
+    })
+}
+
+async fn download(asset: &ReleaseAsset, token: Option<&str>) -> Result<Vec<u8>> {
+    info!("Downloading {} ({} bytes)", asset.name, asset.size);
+    download_release_asset(asset, token, &HttpOptions::default()).await
+}
+
+/// Replaces the executable at `path` with `contents`.  The new binary is
+/// written next to it and renamed over it, so that a failed write leaves the
+/// old one in place.  Windows can't replace a running executable, but can
+/// rename it, so there the old binary is moved aside to `<name>.old`.
+fn replace_executable(path: &Path, contents: &[u8]) -> Result<()> {
+    let sibling = |extension: &str| -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(extension);
+        path.with_file_name(name)
+    };
+
+    let staged = sibling(".new");
+    std::fs::write(&staged, contents)
+        .map_err(|e| eyre!("Error writing {}: {}", staged.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| eyre!("Error making {} executable: {}", staged.display(), e))?;
+    }
+
+    if cfg!(windows) {
+        let old = sibling(".old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(path, &old)
+            .map_err(|e| eyre!("Error moving {} aside: {}", path.display(), e))?;
+    }
+    std::fs::rename(&staged, path).map_err(|e| {
+        eyre!(
+            "Error replacing {} with {}: {}",
+            path.display(),
+            staged.display(),
+            e
+        )
+    })
+}
+
+/// Updates the running binary to the latest release, or to --version.
+pub async fn run(options: &SelfUpdateArgs) -> Result<()> {
+    let token = options
+        .token
+        .clone()
+        .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    let token = token.as_deref();
+    let current = env!("CARGO_PKG_VERSION");
+
+    let release = get_release(
+        RELEASE_OWNER,
+        RELEASE_REPOSITORY,
+        options.version.as_deref(),
+        token,
+        &HttpOptions::default(),
+    )
+    .await?;
+    if options.version.is_none() && !is_newer(&release.tag_name, current) {
+        info!(
+            "gh-env-sync {} is up to date (the latest release is {})",
+            current, release.tag_name
+        );
+        return Ok(());
+    }
+    if options.check {
+        info!(
+            "gh-env-sync {} is available (this is {}); run `gh-env-sync self-update` to install it",
+            release.tag_name, current
+        );
+        return Ok(());
+    }
+
+    let name = asset_name()?;
+    let asset = release.asset(&name).ok_or_else(|| {
+        eyre!(
+            "Release {} has no {} build for this platform",
+            release.tag_name,
+            name
+        )
+    })?;
+    let checksums_asset = release.asset(CHECKSUMS_ASSET).ok_or_else(|| {
+        eyre!(
+            "Release {} has no {} to verify the download with",
+            release.tag_name,
+            CHECKSUMS_ASSET
+        )
+    })?;
+    let checksums = String::from_utf8(download(checksums_asset, token).await?).map_err(|_| {
+        eyre!(
+            "{} of release {} isn't text",
+            CHECKSUMS_ASSET,
+            release.tag_name
+        )
+    })?;
+    let expected = expected_checksum(&checksums, &name).ok_or_else(|| {
+        eyre!(
+            "{} of release {} has no checksum for {}",
+            CHECKSUMS_ASSET,
+            release.tag_name,
+            name
+        )
+    })?;
+
+    let binary = download(asset, token).await?;
+    let actual = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(eyre!(
+            "The checksum of the downloaded {} is {}, but {} lists {}; not installing it",
+            name,
+            actual,
+            CHECKSUMS_ASSET,
+            expected
+        ));
+    }
+
+    let path = std::env::current_exe()
+        .map_err(|e| eyre!("Error finding the running executable: {}", e))?;
+    replace_executable(&path, &binary)?;
+    info!(
+        "Updated gh-env-sync at {} from {} to {}",
+        path.display(),
+        current,
+        release.tag_name
+    );
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::{
+    config::ConfigDocument,
+    encryption::is_encrypted,
+    quota::{QuotaViolation, MAX_VALUE_BYTES},
+};
+
+/// The longest environment name Github accepts, in characters.
+pub const MAX_ENVIRONMENT_NAME_LENGTH: usize = 255;
+
+/// The prefix of variable and secret names that Github keeps for its own.
+pub const RESERVED_NAME_PREFIX: &str = "GITHUB_";
+
+/// A write that a dry run found Github would reject, so that a plan that
+/// can't fully apply stands out before it is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    /// `None` for limits of the whole repository, and for organization
+    /// secrets.
+    pub environment: Option<String>,
+    /// `None` when the environment itself would be rejected.
+    pub key: Option<String>,
+    /// Why, e.g. `can only contain letters, digits and underscores`.
+    pub problem: String,
+}
+
+impl Rejection {
+    /// A repository-wide quota violation.
+    pub fn quota(violation: &QuotaViolation) -> Self {
+        Self {
+            environment: None,
+            key: None,
+            problem: violation.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.environment, &self.key) {
+            (Some(environment), Some(key)) => write!(
+                f,
+                "{} in environment '{}' {}",
+                key, environment, self.problem
+            ),
+            (Some(environment), None) => {
+                write!(f, "environment '{}' {}", environment, self.problem)
+            }
+            (None, Some(key)) => write!(f, "organization secret {} {}", key, self.problem),
+            (None, None) => f.write_str(&self.problem),
+        }
+    }
+}
+
+/// Why Github would reject a variable or secret named `key`, per its naming
+/// rules:
+/// https://docs.github.com/en/actions/learn-github-actions/variables#naming-conventions-for-configuration-variables
+pub fn name_problem(key: &str) -> Option<String> {
+    if key.is_empty() {
+        return Some("has an empty name".to_string());
+    }
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Some("can only contain letters, digits and underscores".to_string());
+    }
+    if key.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some("can't start with a digit".to_string());
+    }
+    if key.to_uppercase().starts_with(RESERVED_NAME_PREFIX) {
+        return Some(format!(
+            "can't start with {}, which Github reserves",
+            RESERVED_NAME_PREFIX
+        ));
+    }
+    None
+}
+
+/// Why Github would reject writing `value` to a variable or secret named
+/// `key`.
+pub fn write_problem(key: &str, value: &str) -> Option<String> {
+    name_problem(key).or_else(|| {
+        (value.len() > MAX_VALUE_BYTES).then(|| {
+            format!(
+                "is {} bytes, more than the {} allowed",
+                value.len(),
+                MAX_VALUE_BYTES
+            )
+        })
+    })
+}
+
+/// Why Github would refuse to create an environment named `name`.
+pub fn environment_name_problem(name: &str) -> Option<String> {
+    let length = name.chars().count();
+    if name.trim().is_empty() {
+        return Some("has an empty name".to_string());
+    }
+    (length > MAX_ENVIRONMENT_NAME_LENGTH).then(|| {
+        format!(
+            "has a name {} characters long, more than the {} allowed",
+            length, MAX_ENVIRONMENT_NAME_LENGTH
+        )
+    })
+}
+
+/// Every environment name, key and value of a config document that Github
+/// would reject, found without contacting it.  Keys starting with
+/// `ignore_prefix` aren't synced, so they aren't checked, and neither are the
+/// sizes of encrypted values, which are only known once decrypted.
+pub fn config_rejections(config: &ConfigDocument, ignore_prefix: &str) -> Vec<Rejection> {
+    let mut rejections = Vec::new();
+    let mut environment_names = config.environments.keys().collect::<Vec<_>>();
+    environment_names.sort();
+    for name in environment_names {
+        if let Some(problem) = environment_name_problem(name) {
+            rejections.push(Rejection {
+                environment: Some(name.clone()),
+                key: None,
+                problem,
+            });
+        }
+
+        let environment = &config.environments[name];
+        let mut keys = environment
+            .keys()
+            .filter(|key| ignore_prefix.is_empty() || !key.starts_with(ignore_prefix))
+            .collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let value = &environment[key].value;
+            let problem = match is_encrypted(value) {
+                true => name_problem(key),
+                false => write_problem(key, value),
+            };
+            if let Some(problem) = problem {
+                rejections.push(Rejection {
+                    environment: Some(name.clone()),
+                    key: Some(key.clone()),
+                    problem,
+                });
+            }
+        }
+    }
+
+    for (name, secret) in &config.org_secrets {
+        let problem = match is_encrypted(&secret.value) {
+            true => name_problem(name),
+            false => write_problem(name, &secret.value),
+        };
+        if let Some(problem) = problem {
+            rejections.push(Rejection {
+                environment: None,
+                key: Some(name.clone()),
+                problem,
+            });
+        }
+    }
+
+    rejections
+}
@@ -0,0 +1,112 @@
+use color_eyre::{eyre::eyre, Result};
+use tracing::info;
+
+use crate::{
+    config::{environment_values, ConfigDocument, Environment},
+    state::hash_value,
+    sync::Syncer,
+};
+
+/// How a configured variable compares to the repository's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    /// The remote value hashes the same as the local one.
+    Match,
+    /// The remote value hashes differently.
+    Mismatch,
+    /// The variable doesn't exist in the repository.
+    Missing,
+}
+
+/// The outcome of verifying a single variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedKey {
+    pub environment: String,
+    pub key: String,
+    pub outcome: VerifyOutcome,
+}
+
+/// Compares the SHA-256 hashes of an environment's local and remote values,
+/// in key order.  Like [`crate::sync::diff_environment`], `remote` is
+/// expected to be keyed by uppercased name.  Only hashes are compared, so
+/// the outcome never depends on, or reveals, anything but equality.
+pub fn verify_environment(
+    environment_name: &str,
+    local: &Environment,
+    remote: &Environment,
+) -> Vec<VerifiedKey> {
+    let mut keys = local.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    keys.into_iter()
+        .map(|key| {
+            let outcome = match remote.get(&key.to_uppercase()) {
+                Some(remote_value) if hash_value(remote_value) == hash_value(&local[key]) => {
+                    VerifyOutcome::Match
+                }
+                Some(_) => VerifyOutcome::Mismatch,
+                None => VerifyOutcome::Missing,
+            };
+
+            VerifiedKey {
+                environment: environment_name.to_string(),
+                key: key.clone(),
+                outcome,
+            }
+        })
+        .collect()
+}
+
+impl Syncer {
+    /// Verifies that every configured variable, or every variable of a
+    /// single environment, holds its configured value in the repository,
+    /// without writing anything.  Generated and referencing values are
+    /// resolved first, as [`Syncer::sync`] would.
+    pub async fn verify(
+        &self,
+        config: &ConfigDocument,
+        environment: Option<&str>,
+    ) -> Result<Vec<VerifiedKey>> {
+        let mut environment_names = match environment {
+            Some(environment) if !config.environments.contains_key(environment) => {
+                return Err(eyre!(
+                    "Environment '{}' is not defined in the config document",
+                    environment
+                ));
+            }
+            Some(environment) => vec![environment.to_string()],
+            None => config.environments.keys().cloned().collect(),
+        };
+        environment_names.sort();
+
+        let mut config = config.clone();
+        self.resolve_values(&mut config)?;
+
+        let remote_environments = self.client().list_environments().await?;
+        let mut verified = Vec::new();
+        for environment_name in &environment_names {
+            let mut remote_variables = if remote_environments.contains(environment_name) {
+                self.client_for(environment_name)
+                    .list_environment_variables(environment_name)
+                    .await?
+            } else {
+                info!(
+                    "Environment '{}' does not exist in the repository",
+                    environment_name
+                );
+                Environment::new()
+            };
+            remote_variables
+                .retain(|key, _| !config.exclusions.excludes_key(environment_name, key));
+
+            let values = environment_values(&config.environments[environment_name]);
+            verified.extend(verify_environment(
+                environment_name,
+                &values,
+                &remote_variables,
+            ));
+        }
+
+        Ok(verified)
+    }
+}
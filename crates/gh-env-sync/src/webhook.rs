@@ -0,0 +1,86 @@
+//! Verification and parsing of Github `push` webhooks, which `serve` uses to
+//! sync as soon as a change to the config file is merged.
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+/// The parts of a `push` webhook payload that decide whether to sync.  See:
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#push
+#[derive(Debug, Deserialize)]
+pub struct PushEvent {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    /// The commit sha after the push.
+    pub after: String,
+    /// Whether the push deleted the ref.
+    #[serde(default)]
+    pub deleted: bool,
+    pub repository: PushRepository,
+    #[serde(default)]
+    pub commits: Vec<PushCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushRepository {
+    /// `owner/repo`.
+    pub full_name: String,
+    pub default_branch: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushCommit {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+impl PushEvent {
+    /// Whether the push was to the repository's default branch.
+    pub fn is_default_branch(&self) -> bool {
+        !self.deleted && self.reference == format!("refs/heads/{}", self.repository.default_branch)
+    }
+
+    /// Whether any commit in the push added, modified or removed `path`.
+    /// Github separates directories with `/`, so a Windows path's `\` are
+    /// taken as `/` too.
+    pub fn touches(&self, path: &str) -> bool {
+        let path = path.replace('\\', "/");
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+
+        self.commits.iter().any(|commit| {
+            commit
+                .added
+                .iter()
+                .chain(&commit.modified)
+                .chain(&commit.removed)
+                .any(|changed| changed == path)
+        })
+    }
+}
+
+/// Checks the `X-Hub-Signature-256` header, an HMAC-SHA256 of the body keyed
+/// by the webhook secret, in constant time.  See:
+/// https://docs.github.com/en/webhooks/using-webhooks/validating-webhook-deliveries
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(signature) = signature.strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Decodes a hex string, failing on odd lengths and non-hex characters.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
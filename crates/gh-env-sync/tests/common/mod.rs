@@ -0,0 +1,147 @@
+//! Syncers for the integration tests whose client replays the Github
+//! responses a test lists, rather than calling Github.
+
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use gh_client::{Cassette, GithubEnvClient, HttpOptions};
+use serde_json::{json, Value};
+
+/// The repository every replayed client is for.
+pub const REPOSITORY: &str = "octo-org/octo-repo";
+
+/// The API URL of an environment of the repository.
+pub fn environment_url(environment: &str, path: &str) -> String {
+    format!(
+        "https://api.github.com/repositories/1296269/environments/{}{}",
+        environment, path
+    )
+}
+
+/// A recorded request and its successful response.
+pub fn interaction(method: &str, url: &str, body: Value) -> Value {
+    json!({
+        "request": { "method": method, "url": url },
+        "response": {
+            "status": 200,
+            "headers": { "content-type": "application/json; charset=utf-8" },
+            "body": body.to_string(),
+        },
+    })
+}
+
+/// A recorded write with its JSON request body, if it has one, and Github's
+/// empty response.
+pub fn write(method: &str, url: &str, body: Option<Value>, status: u16) -> Value {
+    json!({
+        "request": { "method": method, "url": url, "body": body },
+        "response": { "status": status, "body": "" },
+    })
+}
+
+/// Getting a single variable of an environment, which is a 404 once it
+/// doesn't exist.
+pub fn get_variable(environment: &str, key: &str, value: Option<&str>) -> Value {
+    let url = environment_url(environment, &format!("/variables/{}", key));
+    match value {
+        Some(value) => interaction(
+            "GET",
+            &url,
+            json!({
+                "name": key,
+                "value": value,
+                "created_at": "2023-09-01T12:00:00Z",
+                "updated_at": "2023-09-01T12:00:00Z",
+            }),
+        ),
+        None => json!({
+            "request": { "method": "GET", "url": url },
+            "response": { "status": 404, "body": "{\"message\":\"Not Found\"}" },
+        }),
+    }
+}
+
+/// Listing the repository's environments.
+pub fn list_environments(names: &[&str]) -> Value {
+    let environments = names
+        .iter()
+        .map(|name| json!({ "name": name }))
+        .collect::<Vec<_>>();
+    interaction(
+        "GET",
+        "https://api.github.com/repos/octo-org/octo-repo/environments?per_page=100&page=1",
+        json!({ "total_count": names.len(), "environments": environments }),
+    )
+}
+
+/// Listing the variables of an environment.
+pub fn list_variables(environment: &str, variables: &[(&str, &str)]) -> Value {
+    let variables = variables
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": value,
+                "created_at": "2023-09-01T12:00:00Z",
+                "updated_at": "2023-09-01T12:00:00Z",
+            })
+        })
+        .collect::<Vec<_>>();
+    interaction(
+        "GET",
+        &environment_url(environment, "/variables?per_page=30&page=1"),
+        json!({ "total_count": variables.len(), "variables": variables }),
+    )
+}
+
+/// Listing the names of an environment's secrets.
+pub fn list_secrets(environment: &str, names: &[&str]) -> Value {
+    let secrets = names
+        .iter()
+        .map(|name| json!({ "name": name }))
+        .collect::<Vec<_>>();
+    interaction(
+        "GET",
+        &environment_url(environment, "/secrets?per_page=100&page=1"),
+        json!({ "total_count": secrets.len(), "secrets": secrets }),
+    )
+}
+
+/// A path in the temp directory unique to this test process.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("gh-env-sync-{}-{}", std::process::id(), name))
+}
+
+/// A client for [`REPOSITORY`] that answers requests from `interactions`,
+/// and fails any other request.  Read-only clients refuse every write.
+pub async fn replay_client(name: &str, interactions: &[Value], read_only: bool) -> GithubEnvClient {
+    let mut recorded = vec![interaction(
+        "GET",
+        "https://api.github.com/repos/octo-org/octo-repo",
+        json!({
+            "id": 1296269,
+            "name": "octo-repo",
+            "owner": { "login": "octo-org" },
+            "default_branch": "main",
+        }),
+    )];
+    recorded.extend_from_slice(interactions);
+    let path = temp_path(&format!("{}.json", name));
+    std::fs::write(&path, Value::Array(recorded).to_string()).unwrap();
+
+    GithubEnvClient::init(
+        None,
+        "not-a-real-token".to_string(),
+        "octo-org",
+        "octo-repo",
+        &HttpOptions {
+            cassette: Some(Cassette::replay(&path).unwrap()),
+            read_only,
+            ..HttpOptions::default()
+        },
+    )
+    .await
+    .unwrap()
+}
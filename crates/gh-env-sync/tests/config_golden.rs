@@ -0,0 +1,102 @@
+//! Golden tests for config parsing: each `tests/fixtures/config/*.toml`
+//! document is parsed and rendered, and compared with the `.golden` file
+//! next to it.  Run with `UPDATE_GOLDEN=1` to rewrite the golden files after
+//! an intended change, and review their diff.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use gh_env_sync::{parse_config, ConfigDocument};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config");
+
+/// Renders the parts of a parsed document that parsing decides, with
+/// environments and keys in order.
+fn render(config: &ConfigDocument) -> String {
+    let mut out = String::new();
+    if let Some(repository) = &config.repository {
+        writeln!(out, "repository = {:?}", repository).unwrap();
+    }
+    if let Some(max_changes) = config.max_changes {
+        writeln!(out, "max_changes = {}", max_changes).unwrap();
+    }
+
+    for (name, environment) in config.environments.iter().collect::<BTreeMap<_, _>>() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        writeln!(out, "[{}]", name).unwrap();
+        if let Some(expires) = config.expires.get(name) {
+            writeln!(out, "# expires: {:?}", expires).unwrap();
+        }
+        if let Some(shared) = config.shared_keys.get(name) {
+            let shared = shared.iter().cloned().collect::<Vec<_>>();
+            writeln!(out, "# shared: {}", shared.join(", ")).unwrap();
+        }
+
+        for (key, variable) in environment.iter().collect::<BTreeMap<_, _>>() {
+            write!(out, "{} = {:?} ({})", key, variable.value, variable.kind).unwrap();
+            if let Some(owner) = &variable.owner {
+                write!(out, " owner={}", owner).unwrap();
+            }
+            if variable.chunk {
+                out.push_str(" chunk");
+            }
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut fixtures = std::fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    fixtures
+}
+
+fn check(fixture: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(fixture).unwrap();
+    let rendered = match parse_config(&contents) {
+        Ok(config) => render(&config),
+        Err(e) => format!("error: {}\n", e),
+    };
+
+    let golden = fixture.with_extension("golden");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden, &rendered).unwrap();
+        return None;
+    }
+    let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+    (rendered != expected).then(|| {
+        format!(
+            "{} doesn't match {}:\n--- expected\n{}--- rendered\n{}",
+            fixture.display(),
+            golden.display(),
+            expected,
+            rendered
+        )
+    })
+}
+
+#[test]
+fn config_documents_match_golden_files() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", FIXTURES);
+
+    let mismatches = fixtures
+        .iter()
+        .filter_map(|fixture| check(fixture))
+        .collect::<Vec<_>>();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
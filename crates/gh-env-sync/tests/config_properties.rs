@@ -0,0 +1,138 @@
+//! Properties of config parsing that hold for any document, checked against
+//! generated ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use gh_env_sync::{parse_config, ConfigDocument, VariableKind};
+use proptest::prelude::*;
+
+/// Environments keyed by name, each with its variables keyed by name.
+type Environments = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Top-level names that are reserved sections rather than environments.
+const RESERVED: [&str; 10] = [
+    "repository",
+    "org_secrets",
+    "auth",
+    "owners",
+    "branches",
+    "max_changes",
+    "naming",
+    "freeze",
+    "protection_rules",
+    "cli",
+];
+
+fn environments() -> impl Strategy<Value = Environments> {
+    let name = "[a-z][a-z0-9_-]{0,8}".prop_filter("reserved section", |name| {
+        !RESERVED.contains(&name.as_str())
+    });
+    let variables = prop::collection::btree_map("[A-Z][A-Z0-9_]{0,10}", "\\PC{0,20}", 1..6);
+    prop::collection::btree_map(name, variables, 1..4)
+}
+
+/// The configured values and kinds of a parsed document.
+fn parsed(config: &ConfigDocument) -> BTreeMap<String, BTreeMap<String, (String, VariableKind)>> {
+    config
+        .environments
+        .iter()
+        .map(|(name, environment)| {
+            let variables = environment
+                .iter()
+                .map(|(key, variable)| (key.clone(), (variable.value.clone(), variable.kind)))
+                .collect();
+            (name.clone(), variables)
+        })
+        .collect()
+}
+
+fn expected(
+    environments: &Environments,
+    kind: VariableKind,
+) -> BTreeMap<String, BTreeMap<String, (String, VariableKind)>> {
+    environments
+        .iter()
+        .map(|(name, variables)| {
+            let variables = variables
+                .iter()
+                .map(|(key, value)| (key.clone(), (value.clone(), kind)))
+                .collect();
+            (name.clone(), variables)
+        })
+        .collect()
+}
+
+/// The same environments with their keys moved into a subsection.
+fn in_subsection(
+    environments: &Environments,
+    subsection: &str,
+) -> BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>> {
+    environments
+        .iter()
+        .map(|(name, variables)| {
+            let section = BTreeMap::from([(subsection.to_string(), variables.clone())]);
+            (name.clone(), section)
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn plain_values_parse_as_variables(environments in environments()) {
+        let contents = toml::to_string(&environments).unwrap();
+        let config = parse_config(&contents).unwrap();
+
+        prop_assert_eq!(parsed(&config), expected(&environments, VariableKind::Variable));
+    }
+
+    #[test]
+    fn vars_subsections_parse_like_plain_keys(environments in environments()) {
+        let plain = parse_config(&toml::to_string(&environments).unwrap()).unwrap();
+        let contents = toml::to_string(&in_subsection(&environments, "vars")).unwrap();
+        let grouped = parse_config(&contents).unwrap();
+
+        prop_assert_eq!(parsed(&grouped), parsed(&plain));
+    }
+
+    #[test]
+    fn secrets_subsections_parse_as_secrets(environments in environments()) {
+        let contents = toml::to_string(&in_subsection(&environments, "secrets")).unwrap();
+        let config = parse_config(&contents).unwrap();
+
+        prop_assert_eq!(parsed(&config), expected(&environments, VariableKind::Secret));
+    }
+
+    #[test]
+    fn shared_keys_fill_in_missing_keys(
+        environments in environments(),
+        shared in prop::collection::btree_map("[A-Z][A-Z0-9_]{0,10}", "\\PC{0,20}", 1..4),
+    ) {
+        let toml::Value::Table(mut document) = toml::Value::try_from(&environments).unwrap() else {
+            panic!("environments serialize as a table");
+        };
+        document.insert("__shared".to_string(), toml::Value::try_from(&shared).unwrap());
+        let config = parse_config(&toml::to_string(&document).unwrap()).unwrap();
+
+        for (name, variables) in &environments {
+            let environment = &config.environments[name];
+            let mut copied = BTreeSet::new();
+            for (key, value) in &shared {
+                match variables.keys().find(|own| own.eq_ignore_ascii_case(key)) {
+                    // An environment's own value wins over the shared one.
+                    Some(own) => {
+                        prop_assert_eq!(&environment[own].value, &variables[own]);
+                    }
+                    None => {
+                        prop_assert_eq!(&environment[key].value, value);
+                        copied.insert(key.clone());
+                    }
+                }
+            }
+            prop_assert_eq!(environment.len(), variables.len() + copied.len());
+            prop_assert_eq!(
+                config.shared_keys.get(name).cloned().unwrap_or_default(),
+                copied
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Tests for flag defaults read from the `[cli]` section.
+
+use gh_env_sync::{defaults::OutputFormat, parse_config};
+
+#[test]
+fn switches_left_out_of_the_cli_section_stay_unset() {
+    let config = parse_config(
+        r#"
+        [cli]
+        output = "json"
+        prune = false
+        check_shadowing = true
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.cli.output, Some(OutputFormat::Json));
+    assert_eq!(config.cli.prune, Some(false));
+    assert_eq!(config.cli.check_shadowing, Some(true));
+    assert_eq!(config.cli.fail_fast, None);
+    assert_eq!(config.cli.dry_run, None);
+}
+
+#[test]
+fn check_mode_and_change_limit_can_be_defaulted() {
+    let config = parse_config(
+        r#"
+        max_changes = 50
+
+        [cli]
+        dry_run = true
+        max_changes = 10
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.cli.dry_run, Some(true));
+    assert_eq!(config.cli.max_changes, Some(10));
+    assert_eq!(config.max_changes, Some(50));
+}
+
+#[test]
+fn unknown_output_is_an_error() {
+    let error = parse_config(
+        r#"
+        [cli]
+        output = "yaml"
+        "#,
+    )
+    .unwrap_err()
+    .to_string();
+
+    assert!(error.contains("yaml"), "{}", error);
+}
@@ -0,0 +1,154 @@
+//! Tests for what syncs delete from Github, with `--prune` and once chunked
+//! keys change shape, and for where `undelete` restores deleted keys from.
+
+use gh_env_sync::{
+    audit::{AuditOperation, AuditRecord},
+    chunk::{split_chunked, stale_chunks},
+    config::Environment,
+    parse_config,
+    state::EnvironmentState,
+    sync::{prunable_variables, VariableChange, VariableReport},
+    undelete::{deleted_from, last_deletion},
+    State,
+};
+
+fn environment(variables: &[(&str, &str)]) -> Environment {
+    variables
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn deleted_keys(reports: Vec<VariableReport>) -> Vec<String> {
+    reports
+        .into_iter()
+        .inspect(|report| assert_eq!(report.change, VariableChange::Delete))
+        .map(|report| report.key)
+        .collect()
+}
+
+#[test]
+fn prunes_only_keys_recorded_in_the_state_file() {
+    let local = environment(&[("api_url", "https://example.com")]);
+    let remote = environment(&[
+        ("API_URL", "https://example.com"),
+        ("OLD_FLAG", "on"),
+        ("HAND_MANAGED", "yes"),
+    ]);
+    let applied = ["API_URL", "OLD_FLAG", "GONE_ALREADY"]
+        .into_iter()
+        .map(|key| (key.to_string(), "hash".to_string()))
+        .collect::<EnvironmentState>();
+
+    assert_eq!(
+        deleted_keys(prunable_variables(&local, &remote, Some(&applied))),
+        vec!["OLD_FLAG"]
+    );
+}
+
+#[test]
+fn prunes_nothing_without_a_state_file() {
+    let remote = environment(&[("OLD_FLAG", "on")]);
+
+    assert!(prunable_variables(&Environment::new(), &remote, None).is_empty());
+}
+
+#[test]
+fn deletes_parts_left_over_from_a_longer_value() {
+    let mut config = parse_config(&format!(
+        "[production]\nSERVICE_MAP = {{ value = \"{}\", chunk = true }}\n",
+        "x".repeat(60 * 1024)
+    ))
+    .unwrap();
+    let production = config.environments.get_mut("production").unwrap();
+    split_chunked(production);
+    let remote = environment(&[
+        ("SERVICE_MAP", "whole"),
+        ("SERVICE_MAP__PART1", "a"),
+        ("SERVICE_MAP__PART2", "b"),
+        ("SERVICE_MAP__PART3", "c"),
+        ("UNRELATED__PART3", "d"),
+    ]);
+
+    assert_eq!(
+        deleted_keys(stale_chunks(production, &remote)),
+        vec!["SERVICE_MAP", "SERVICE_MAP__PART3"]
+    );
+}
+
+#[test]
+fn deletes_parts_once_a_value_fits_again() {
+    let config =
+        parse_config("[production]\nSERVICE_MAP = { value = \"small\", chunk = true }\n").unwrap();
+    let remote = environment(&[
+        ("SERVICE_MAP__PART1", "a"),
+        ("SERVICE_MAP__PART2", "b"),
+        ("OTHER", "c"),
+    ]);
+
+    assert_eq!(
+        deleted_keys(stale_chunks(&config.environments["production"], &remote)),
+        vec!["SERVICE_MAP__PART1", "SERVICE_MAP__PART2"]
+    );
+}
+
+fn deletion(run_id: &str, environment: &str, key: &str, previous_value: &str) -> AuditRecord {
+    AuditRecord {
+        run_id: run_id.to_string(),
+        timestamp: 1_700_000_000,
+        repository: "owner/repo".to_string(),
+        environment: environment.to_string(),
+        key: key.to_string(),
+        operation: AuditOperation::Delete,
+        previous_value: Some(previous_value.to_string()),
+        value: None,
+        identity: None,
+        actor: None,
+    }
+}
+
+#[test]
+fn undeletes_the_value_of_the_latest_deletion() {
+    let mut recreated = deletion("run-2", "production", "API_URL", "unused");
+    recreated.operation = AuditOperation::Create;
+    let records = vec![
+        deletion("run-1", "production", "API_URL", "https://old.example.com"),
+        deletion("run-2", "production", "API_URL", "https://example.com"),
+        recreated,
+        deletion("run-3", "staging", "API_URL", "https://staging.example.com"),
+    ];
+
+    let latest = last_deletion(&records, "owner/repo", "production", "api_url").unwrap();
+    assert_eq!(latest.run_id, "run-2");
+    assert_eq!(
+        latest.previous_value.as_deref(),
+        Some("https://example.com")
+    );
+    assert!(last_deletion(&records, "owner/other", "production", "API_URL").is_none());
+}
+
+#[test]
+fn undeletes_where_tombstones_are_before_the_audit_log() {
+    let records = vec![
+        deletion("run-1", "staging", "API_URL", "a"),
+        deletion("run-2", "production", "API_URL", "b"),
+        deletion("run-3", "staging", "API_URL", "c"),
+    ];
+    assert_eq!(
+        deleted_from(&records, None, "owner/repo", "api_url"),
+        vec!["production", "staging"]
+    );
+
+    let mut state = State::default();
+    state.record("owner/repo", "qa", "API_URL", "c");
+    state.record_deleted("owner/repo", "qa", "API_URL", 1_700_000_000);
+    assert_eq!(
+        deleted_from(&records, Some(&state), "owner/repo", "API_URL"),
+        vec!["qa"]
+    );
+    assert!(state
+        .tombstone("owner/repo", "qa", "API_URL")
+        .unwrap()
+        .value_hash
+        .is_some());
+}
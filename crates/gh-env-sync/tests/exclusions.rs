@@ -0,0 +1,153 @@
+#![cfg(feature = "cassette")]
+//! Tests that every command writing to Github leaves the keys and
+//! environments of `.ghenvignore` alone.  The replayed clients are
+//! read-only, so that any write to an excluded key fails the test.
+
+use std::path::PathBuf;
+
+use gh_client::GithubEnvClient;
+use gh_env_sync::{
+    audit::{AuditLog, AuditOperation, AuditRecord},
+    env_prefix::config_from_env,
+    exclusions::Exclusions,
+    LockMode, SyncOptions, Syncer,
+};
+
+use common::{list_environments, list_variables, replay_client, temp_path};
+
+mod common;
+
+fn syncer(client: GithubEnvClient, exclusions: &str, audit_log: Option<PathBuf>) -> Syncer {
+    Syncer::new(
+        client,
+        SyncOptions {
+            lock: LockMode::None,
+            audit_log,
+            exclusions: Exclusions::parse(exclusions).unwrap(),
+            ..SyncOptions::default()
+        },
+    )
+}
+
+/// An audit log holding `records`, for the commands that replay it.
+fn audit_log(name: &str, records: &[AuditRecord]) -> PathBuf {
+    let path = temp_path(name);
+    let _ = std::fs::remove_file(&path);
+    let log = AuditLog::new(&path);
+    for record in records {
+        log.append(record).unwrap();
+    }
+    path
+}
+
+fn record(
+    run_id: &str,
+    operation: AuditOperation,
+    previous_value: Option<&str>,
+    value: Option<&str>,
+) -> AuditRecord {
+    AuditRecord {
+        run_id: run_id.to_string(),
+        timestamp: 1_700_000_000,
+        repository: "octo-org/octo-repo".to_string(),
+        environment: "production".to_string(),
+        key: "DEPLOY_TOKEN".to_string(),
+        operation,
+        previous_value: previous_value.map(str::to_string),
+        value: value.map(str::to_string),
+        identity: None,
+        actor: None,
+    }
+}
+
+#[tokio::test]
+async fn syncs_from_the_process_environment_skip_excluded_keys() {
+    let config = config_from_env(
+        &["CI_PROD_=production".parse().unwrap()],
+        [
+            ("CI_PROD_API_URL".to_string(), "https://api".to_string()),
+            ("CI_PROD_DEPLOY_TOKEN".to_string(), "abc".to_string()),
+        ],
+    )
+    .unwrap();
+    let client = replay_client(
+        "exclusions-env-prefix",
+        &[
+            list_environments(&["production"]),
+            list_variables("production", &[("API_URL", "https://api")]),
+        ],
+        true,
+    )
+    .await;
+
+    let report = syncer(client, "DEPLOY_*", None)
+        .sync(&config, None)
+        .await
+        .unwrap();
+    assert!(!report.has_changes(), "{:?}", report);
+}
+
+#[tokio::test]
+async fn cleanup_leaves_excluded_preview_environments() {
+    let client = replay_client(
+        "exclusions-cleanup",
+        &[list_environments(&["production", "preview-pr-7"])],
+        true,
+    )
+    .await;
+
+    let deleted = syncer(client, "preview-*/", None)
+        .delete_environments(&["preview-pr-7".to_string()], false)
+        .await
+        .unwrap();
+    assert!(deleted.is_empty(), "{:?}", deleted);
+}
+
+#[tokio::test]
+async fn rename_leaves_environments_where_a_key_is_excluded() {
+    let client = replay_client("exclusions-rename", &[], true).await;
+
+    let renamed = syncer(client, "production/DEPLOY_*", None)
+        .rename_key(
+            &["production".to_string()],
+            "DEPLOY_TOKEN",
+            "API_TOKEN",
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(renamed.is_empty(), "{:?}", renamed);
+}
+
+#[tokio::test]
+async fn undelete_leaves_excluded_keys_deleted() {
+    let path = audit_log(
+        "exclusions-undelete.jsonl",
+        &[record("run-1", AuditOperation::Delete, Some("abc"), None)],
+    );
+    let client = replay_client("exclusions-undelete", &[], true).await;
+
+    let undeleted = syncer(client, "DEPLOY_TOKEN", Some(path))
+        .undelete("DEPLOY_TOKEN", None, false)
+        .await
+        .unwrap();
+    assert!(undeleted.is_empty(), "{:?}", undeleted);
+}
+
+#[tokio::test]
+async fn rollback_leaves_excluded_keys_alone() {
+    let path = audit_log(
+        "exclusions-rollback.jsonl",
+        &[
+            record("run-1", AuditOperation::Create, None, Some("abc")),
+            record("run-2", AuditOperation::Update, Some("abc"), Some("def")),
+        ],
+    );
+    let client = replay_client("exclusions-rollback", &[], true).await;
+
+    let operations = syncer(client, "production/", Some(path))
+        .rollback("run-1", false)
+        .await
+        .unwrap();
+    assert!(operations.is_empty(), "{:?}", operations);
+}
@@ -0,0 +1,83 @@
+//! Tests for the report of remote names that `pull` wouldn't import as they
+//! are, and for how importing treats them.
+
+use std::collections::BTreeMap;
+
+use gh_env_sync::{
+    config::Environment,
+    import::{import_variables, normalization_report},
+};
+
+fn remote(environments: &[(&str, &[(&str, &str)])]) -> BTreeMap<String, Environment> {
+    environments
+        .iter()
+        .map(|(name, variables)| {
+            let variables = variables
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            (name.to_string(), variables)
+        })
+        .collect()
+}
+
+fn report(contents: &str, remote: &BTreeMap<String, Environment>) -> Vec<String> {
+    normalization_report(contents, remote, "_")
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn names_that_round_trip_are_not_reported() {
+    let remote = remote(&[("production", &[("API_URL", "https://example.com")])]);
+
+    assert!(report("", &remote).is_empty());
+    assert!(report("[production]\napi_url = \"https://example.com\"\n", &remote).is_empty());
+}
+
+#[test]
+fn reports_reserved_and_ignored_names() {
+    let remote = remote(&[
+        ("naming", &[("STYLE", "any")]),
+        (
+            "production",
+            &[("__EXPIRES", "never"), ("_NOTE", "hi"), ("OK", "yes")],
+        ),
+    ]);
+
+    assert_eq!(
+        report("", &remote),
+        vec![
+            "environment 'naming' is a section of the config document, not imported",
+            "_NOTE in environment 'production' starts with the ignore prefix _, so syncs skip it",
+            "__EXPIRES in environment 'production' starts with __, which the config keeps for its own keys",
+        ]
+    );
+}
+
+#[test]
+fn reports_keys_breaking_the_naming_policy() {
+    let remote = remote(&[("production", &[("API__URL", "https://example.com")])]);
+    let contents = "[naming]\nstyle = \"screaming_snake_case\"\n";
+
+    assert_eq!(report(contents, &remote), vec!["API__URL in environment 'production' isn't SCREAMING_SNAKE_CASE (rename it to API_URL)"]);
+}
+
+#[test]
+fn environments_in_different_case_are_imported_as_new_tables() {
+    let remote = remote(&[("production", &[("API_URL", "https://example.com")])]);
+    let contents = "[Production]\nAPI_URL = \"https://old.example.com\"\n";
+
+    assert_eq!(
+        report(contents, &remote),
+        vec!["environment 'production' is [Production] in the config, which syncs as another environment, so it is imported as a new table"]
+    );
+
+    let (updated, _) = import_variables(contents, &remote).unwrap();
+    assert_eq!(
+        updated,
+        "[Production]\nAPI_URL = \"https://old.example.com\"\n\n[production]\nAPI_URL = \"https://example.com\"\n"
+    );
+}
@@ -0,0 +1,261 @@
+#![cfg(feature = "cassette")]
+//! Tests for plan files and `apply` against replayed Github responses.
+
+use std::{path::Path, process::Command};
+
+use gh_env_sync::{
+    plan::{ApplyOutcome, Plan, PlanOperation, SignatureKind},
+    state::hash_value,
+    sync::VariableChange,
+    LockMode, PlannedValue, State, SyncOptions, Syncer,
+};
+use serde_json::json;
+
+use common::{environment_url, list_environments, list_variables, replay_client, temp_path, write};
+
+mod common;
+
+fn operation(
+    environment: &str,
+    key: &str,
+    previous: Option<&str>,
+    value: Option<&str>,
+) -> PlanOperation {
+    PlanOperation {
+        environment: environment.to_string(),
+        key: key.to_string(),
+        previous: previous.map(str::to_string),
+        value: value.map(str::to_string),
+    }
+}
+
+fn plan(operations: Vec<PlanOperation>) -> Plan {
+    Plan {
+        version: 1,
+        repository: "octo-org/octo-repo".to_string(),
+        config_hash: "9f86d08".to_string(),
+        created_at: 1_700_000_000,
+        operations,
+    }
+}
+
+#[test]
+fn plans_hold_the_planned_writes_of_a_dry_run() {
+    let planned = |key: &str, change, local: &str, remote: Option<&str>| PlannedValue {
+        environment: "production".to_string(),
+        key: key.to_string(),
+        change,
+        local_value: local.to_string(),
+        remote_value: remote.map(str::to_string),
+        remote_updated_at: None,
+        drifted: false,
+        rejection: None,
+    };
+
+    let plan = Plan::new(
+        "octo-org/octo-repo",
+        "9f86d08".to_string(),
+        &[
+            planned("api_url", VariableChange::Create, "https://api", None),
+            planned("DEBUG", VariableChange::Update, "0", Some("1")),
+            planned("OLD_URL", VariableChange::Delete, "", Some("https://old")),
+        ],
+    );
+    assert_eq!(
+        plan.operations,
+        vec![
+            operation("production", "API_URL", None, Some("https://api")),
+            operation("production", "DEBUG", Some("1"), Some("0")),
+            operation("production", "OLD_URL", Some("https://old"), None),
+        ]
+    );
+
+    let path = temp_path("plan.json");
+    plan.save(&path).unwrap();
+    assert_eq!(Plan::load(&path).unwrap(), plan);
+}
+
+#[test]
+fn partial_plans_leave_the_other_operations_for_later() {
+    let plan = plan(vec![
+        operation("staging", "FEATURE_SEARCH", None, Some("1")),
+        operation(
+            "staging",
+            "API_URL",
+            Some("https://old"),
+            Some("https://api"),
+        ),
+        operation("production", "FEATURE_SEARCH", None, Some("1")),
+        operation("production-eu", "FEATURE_CHAT", Some("0"), None),
+    ]);
+
+    let (selected, residual) =
+        plan.partition(&["Production*".to_string()], &["feature_*".to_string()]);
+    assert_eq!(selected.operations, plan.operations[2..].to_vec());
+    assert_eq!(residual.operations, plan.operations[..2].to_vec());
+    assert_eq!(residual.config_hash, plan.config_hash);
+    assert_eq!(residual.created_at, plan.created_at);
+
+    let (selected, residual) = plan.partition(&["staging".to_string()], &[]);
+    assert_eq!(selected.operations, plan.operations[..2].to_vec());
+    assert_eq!(residual.operations, plan.operations[2..].to_vec());
+}
+
+#[tokio::test]
+async fn applying_refuses_variables_changed_since_the_plan() {
+    let client = replay_client(
+        "plan-stale",
+        &[
+            list_environments(&["production"]),
+            list_variables("production", &[("API_URL", "https://api"), ("DEBUG", "2")]),
+        ],
+        true,
+    )
+    .await;
+    let syncer = Syncer::new(
+        client,
+        SyncOptions {
+            lock: LockMode::None,
+            ..SyncOptions::default()
+        },
+    );
+
+    let error = syncer
+        .apply_plan(
+            &plan(vec![
+                operation("production", "API_URL", None, Some("https://api")),
+                operation("production", "DEBUG", Some("1"), Some("0")),
+            ]),
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("DEBUG in environment 'production'"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn applying_makes_the_planned_writes_not_yet_made() {
+    let client = replay_client(
+        "plan-apply",
+        &[
+            list_environments(&["production"]),
+            // API_URL was created by an earlier, interrupted apply.
+            list_variables(
+                "production",
+                &[
+                    ("API_URL", "https://api"),
+                    ("DEBUG", "1"),
+                    ("OLD_URL", "https://old"),
+                ],
+            ),
+            write(
+                "PATCH",
+                &environment_url("production", "/variables/DEBUG"),
+                Some(json!({ "value": "0" })),
+                204,
+            ),
+            write(
+                "DELETE",
+                &environment_url("production", "/variables/OLD_URL"),
+                None,
+                204,
+            ),
+        ],
+        false,
+    )
+    .await;
+    let path = temp_path("plan-state.json");
+    let _ = std::fs::remove_file(&path);
+    let syncer = Syncer::new(
+        client,
+        SyncOptions {
+            lock: LockMode::None,
+            state_file: Some(path.clone()),
+            ..SyncOptions::default()
+        },
+    );
+
+    let applied = syncer
+        .apply_plan(
+            &plan(vec![
+                operation("production", "API_URL", None, Some("https://api")),
+                operation("production", "DEBUG", Some("1"), Some("0")),
+                operation("production", "OLD_URL", Some("https://old"), None),
+            ]),
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        applied
+            .iter()
+            .map(|applied| applied.outcome)
+            .collect::<Vec<_>>(),
+        vec![
+            ApplyOutcome::AlreadyApplied,
+            ApplyOutcome::Applied,
+            ApplyOutcome::Applied
+        ]
+    );
+
+    let state = State::load(&path).unwrap();
+    let environment = state
+        .environment("octo-org/octo-repo", "production")
+        .unwrap();
+    assert_eq!(environment.get("DEBUG"), Some(&hash_value("0")));
+    assert!(state
+        .tombstone("octo-org/octo-repo", "production", "OLD_URL")
+        .is_some());
+}
+
+/// Runs gpg with a keyring of its own, so that the test neither needs nor
+/// touches the user's.
+fn gpg(home: &Path, args: &[&str]) -> bool {
+    Command::new("gpg")
+        .env("GNUPGHOME", home)
+        .args(["--batch", "--pinentry-mode", "loopback", "--passphrase", ""])
+        .args(args)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+fn gpg_signatures_prove_the_plan_is_unchanged() {
+    let home = temp_path("gnupg");
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    if !gpg(
+        &home,
+        &[
+            "--quick-generate-key",
+            "plans@example.com",
+            "default",
+            "default",
+            "never",
+        ],
+    ) {
+        eprintln!("Skipping, since gpg isn't installed");
+        return;
+    }
+    // `sign` and `verify` run gpg with the default GNUPGHOME.
+    std::env::set_var("GNUPGHOME", &home);
+
+    let path = temp_path("signed-plan.json");
+    plan(vec![operation("production", "DEBUG", Some("1"), Some("0"))])
+        .save(&path)
+        .unwrap();
+    let signature = SignatureKind::Gpg.sign(&path).unwrap();
+    assert_eq!(signature, SignatureKind::Gpg.signature_path(&path));
+    SignatureKind::Gpg.verify(&path, None).unwrap();
+
+    plan(vec![operation("production", "DEBUG", Some("1"), Some("2"))])
+        .save(&path)
+        .unwrap();
+    assert!(SignatureKind::Gpg.verify(&path, None).is_err());
+}
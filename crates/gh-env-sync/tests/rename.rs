@@ -0,0 +1,81 @@
+#![cfg(feature = "cassette")]
+//! Tests for `rename-key` against replayed Github responses.
+
+use gh_env_sync::{rename::RenameOutcome, LockMode, State, SyncOptions, Syncer};
+use serde_json::json;
+
+use common::{environment_url, get_variable, replay_client, temp_path, write};
+
+mod common;
+
+#[tokio::test]
+async fn renaming_moves_the_state_of_the_old_key() {
+    let client = replay_client(
+        "rename",
+        &[
+            get_variable("production", "OLD_URL", Some("https://api")),
+            get_variable("production", "API_URL", None),
+            write(
+                "POST",
+                &environment_url("production", "/variables"),
+                Some(json!({ "name": "API_URL", "value": "https://api" })),
+                201,
+            ),
+            get_variable("production", "API_URL", Some("https://api")),
+            write(
+                "DELETE",
+                &environment_url("production", "/variables/OLD_URL"),
+                None,
+                204,
+            ),
+            // Renamed by an earlier run that was interrupted before saving
+            // the state file.
+            get_variable("staging", "OLD_URL", None),
+            get_variable("staging", "API_URL", Some("https://staging")),
+        ],
+        false,
+    )
+    .await;
+    let path = temp_path("rename-state.json");
+    let mut state = State::default();
+    for environment in ["production", "staging"] {
+        state.record("octo-org/octo-repo", environment, "OLD_URL", "https://api");
+        state.record("octo-org/octo-repo", environment, "DEBUG", "1");
+    }
+    state.save(&path).unwrap();
+    let syncer = Syncer::new(
+        client,
+        SyncOptions {
+            lock: LockMode::None,
+            state_file: Some(path.clone()),
+            ..SyncOptions::default()
+        },
+    );
+
+    let renamed = syncer
+        .rename_key(
+            &["production".to_string(), "staging".to_string()],
+            "old_url",
+            "api_url",
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        renamed.iter().map(|key| key.outcome).collect::<Vec<_>>(),
+        vec![RenameOutcome::Renamed, RenameOutcome::AlreadyRenamed]
+    );
+
+    let state = State::load(&path).unwrap();
+    for environment in ["production", "staging"] {
+        let applied = state
+            .environment("octo-org/octo-repo", environment)
+            .unwrap();
+        assert_eq!(
+            applied.keys().collect::<Vec<_>>(),
+            vec!["API_URL", "DEBUG"],
+            "{}",
+            environment
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Tests for what the state file records of written secrets.
+
+use gh_env_sync::{AppliedSecret, State};
+
+#[test]
+fn secret_is_current_until_its_value_or_remote_changes() {
+    let secret = AppliedSecret::new("hunter2", Some("2024-03-01T12:00:00Z".to_string()));
+
+    assert!(secret.is_current("hunter2", Some("2024-03-01T12:00:00Z")));
+    assert!(!secret.is_current("hunter3", Some("2024-03-01T12:00:00Z")));
+    assert!(!secret.is_current("hunter2", Some("2024-03-01T12:00:01Z")));
+    // Deleted on Github since.
+    assert!(!secret.is_current("hunter2", None));
+}
+
+#[test]
+fn secret_without_a_recorded_update_is_never_current() {
+    let secret = AppliedSecret::new("hunter2", None);
+
+    assert!(!secret.is_current("hunter2", Some("2024-03-01T12:00:00Z")));
+}
+
+#[test]
+fn forgetting_a_key_forgets_its_secret() {
+    let mut state = State::default();
+    let secret = AppliedSecret::new("hunter2", Some("2024-03-01T12:00:00Z".to_string()));
+    state.record_secret("owner/repo", "production", "api_token", secret.clone());
+    state.record_secret("owner/repo", "production", "DB_PASSWORD", secret.clone());
+
+    assert_eq!(
+        state.secrets("owner/repo", "production").unwrap()["API_TOKEN"],
+        secret
+    );
+
+    state.forget("owner/repo", "production", Some("API_TOKEN"));
+    let secrets = state.secrets("owner/repo", "production").unwrap();
+    assert_eq!(secrets.keys().collect::<Vec<_>>(), vec!["DB_PASSWORD"]);
+
+    state.forget("owner/repo", "production", None);
+    assert!(state.secrets("owner/repo", "production").is_none());
+}
+
+#[test]
+fn organization_secrets_are_recorded_per_repository() {
+    let mut state = State::default();
+    let secret = AppliedSecret::new("hunter2", Some("2024-03-01T12:00:00Z".to_string()));
+    state.record_organization_secret("owner/repo", "npm_token", secret.clone());
+
+    assert_eq!(
+        state.organization_secret("owner/repo", "NPM_TOKEN"),
+        Some(&secret)
+    );
+    assert_eq!(state.organization_secret("owner/other", "NPM_TOKEN"), None);
+}
@@ -0,0 +1,152 @@
+//! Tests for streaming secrets through the bounded pipeline of
+//! `--pipeline-depth`: which secrets are streamed, the order they arrive in,
+//! and how resolving stops.
+
+use std::sync::{Arc, Mutex};
+
+use color_eyre::{eyre::eyre, Result};
+use gh_env_sync::{
+    parse_config,
+    stream::{resolve_streamed_secrets, take_streamed_secrets, StreamedSecrets},
+    ProviderRegistry, ValueProvider,
+};
+
+/// Resolves `${test:ARG}` to the lowercased argument, recording each call.
+/// `fail` is an error and `panic` panics.
+#[derive(Clone, Default)]
+struct TestProvider {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl ValueProvider for TestProvider {
+    fn scheme(&self) -> &str {
+        "test"
+    }
+
+    fn resolve(&self, argument: &str) -> Result<String> {
+        self.calls.lock().unwrap().push(argument.to_string());
+        match argument {
+            "fail" => Err(eyre!("lookup failed")),
+            "panic" => panic!("provider panicked"),
+            _ => Ok(argument.to_lowercase()),
+        }
+    }
+}
+
+fn registry() -> (ProviderRegistry, Arc<Mutex<Vec<String>>>) {
+    let provider = TestProvider::default();
+    let calls = provider.calls.clone();
+    let mut providers = ProviderRegistry::new();
+    providers.register(provider).unwrap();
+    (providers, calls)
+}
+
+/// The streamable secrets of the `production` environment of `contents`.
+fn streamed(contents: &str, providers: &ProviderRegistry) -> StreamedSecrets {
+    let mut config = parse_config(contents).unwrap();
+    let environment = config.environments.get_mut("production").unwrap();
+    take_streamed_secrets(environment, providers)
+}
+
+#[test]
+fn streams_only_secrets_nothing_else_needs() {
+    let (providers, _) = registry();
+    let mut config = parse_config(
+        r#"
+        [production]
+        PLAIN = { value = "${test:A}" }
+        LITERAL = { value = "hunter2", kind = "secret" }
+        STREAMED = { value = "${test:B}", kind = "secret" }
+        REFERENCED = { value = "${test:C}", kind = "secret" }
+        COMPOSITE = { value = "${ref:REFERENCED}", kind = "secret" }
+        CHUNKED = { value = "${test:D}", kind = "secret", chunk = true }
+        "#,
+    )
+    .unwrap();
+    let environment = config.environments.get_mut("production").unwrap();
+
+    let secrets = take_streamed_secrets(environment, &providers);
+    assert_eq!(secrets.keys().collect::<Vec<_>>(), vec!["STREAMED"]);
+    assert!(!environment.contains_key("STREAMED"));
+    assert_eq!(environment.len(), 5);
+}
+
+#[tokio::test]
+async fn resolves_secrets_in_key_order() {
+    let (providers, calls) = registry();
+    let secrets = streamed(
+        r#"
+        [production]
+        C_SECRET = { value = "${test:C}", kind = "secret" }
+        A_SECRET = { value = "prefix-${test:A}", kind = "secret" }
+        B_SECRET = { value = "  ${test:B}  ", kind = "secret", transform = ["trim"] }
+        "#,
+        &providers,
+    );
+
+    let mut resolved = resolve_streamed_secrets("production", secrets, providers, 1);
+    let mut received = Vec::new();
+    while let Some(secret) = resolved.next().await.unwrap() {
+        received.push(secret);
+    }
+
+    assert_eq!(
+        received,
+        vec![
+            ("A_SECRET".to_string(), "prefix-a".to_string()),
+            ("B_SECRET".to_string(), "b".to_string()),
+            ("C_SECRET".to_string(), "c".to_string()),
+        ]
+    );
+    assert_eq!(*calls.lock().unwrap(), vec!["A", "B", "C"]);
+    assert_eq!(resolved.received(), 3);
+}
+
+#[tokio::test]
+async fn stops_at_the_first_provider_error() {
+    let (providers, calls) = registry();
+    let secrets = streamed(
+        r#"
+        [production]
+        A_SECRET = { value = "${test:A}", kind = "secret" }
+        B_SECRET = { value = "${test:fail}", kind = "secret" }
+        C_SECRET = { value = "${test:C}", kind = "secret" }
+        "#,
+        &providers,
+    );
+
+    let mut resolved = resolve_streamed_secrets("production", secrets, providers, 4);
+    assert_eq!(
+        resolved.next().await.unwrap(),
+        Some(("A_SECRET".to_string(), "a".to_string()))
+    );
+    let error = resolved.next().await.unwrap_err().to_string();
+    assert!(error.contains("B_SECRET"), "{}", error);
+    assert!(error.contains("production"), "{}", error);
+
+    // Nothing after the error is resolved.
+    assert!(resolved.next().await.is_err());
+    assert_eq!(*calls.lock().unwrap(), vec!["A", "fail"]);
+}
+
+#[tokio::test]
+async fn fails_when_resolving_stops_early() {
+    let (providers, _) = registry();
+    let secrets = streamed(
+        r#"
+        [production]
+        A_SECRET = { value = "${test:A}", kind = "secret" }
+        B_SECRET = { value = "${test:panic}", kind = "secret" }
+        C_SECRET = { value = "${test:C}", kind = "secret" }
+        "#,
+        &providers,
+    );
+
+    let mut resolved = resolve_streamed_secrets("production", secrets, providers, 1);
+    assert!(resolved.next().await.unwrap().is_some());
+    let error = resolved.next().await.unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "Resolving the secrets of environment 'production' stopped after 1 of 3"
+    );
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "cassette")]
+//! Tests for syncs against replayed Github responses.
+
+use color_eyre::{eyre::eyre, Result};
+use gh_client::ReadOnlyError;
+use gh_env_sync::{
+    exclusions::Exclusions, mirror::config_from_remote, parse_config, sync::ApplyStrategy,
+    ProviderRegistry, SyncOptions, Syncer, ValueProvider,
+};
+
+use common::{list_environments, list_secrets, list_variables, replay_client};
+
+mod common;
+
+/// Resolves `${test:ARG}` to the lowercased argument; `fail` is an error.
+#[derive(Debug)]
+struct TestProvider;
+
+impl ValueProvider for TestProvider {
+    fn scheme(&self) -> &str {
+        "test"
+    }
+
+    fn resolve(&self, argument: &str) -> Result<String> {
+        match argument {
+            "fail" => Err(eyre!("lookup failed")),
+            _ => Ok(argument.to_lowercase()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn transactional_sync_resolves_streamed_secrets_before_writing() {
+    // A read-only client, so that any write fails the sync.
+    let client = replay_client(
+        "transactional",
+        &[
+            list_environments(&["development", "production"]),
+            list_variables("development", &[]),
+            list_variables("production", &[]),
+        ],
+        true,
+    )
+    .await;
+    let mut providers = ProviderRegistry::new();
+    providers.register(TestProvider).unwrap();
+    let syncer = Syncer::new(
+        client,
+        SyncOptions {
+            apply_strategy: ApplyStrategy::Transactional,
+            pipeline_depth: Some(1),
+            ..SyncOptions::default()
+        },
+    )
+    .with_value_providers(providers);
+    let config = parse_config(
+        r#"
+        [development]
+        API_TOKEN = { value = "${test:A}", kind = "secret" }
+
+        [production]
+        API_TOKEN = { value = "${test:fail}", kind = "secret" }
+        "#,
+    )
+    .unwrap();
+
+    let error = syncer.sync(&config, None).await.unwrap_err();
+    assert!(
+        error.downcast_ref::<ReadOnlyError>().is_none(),
+        "{:?}",
+        error
+    );
+    assert!(
+        format!("{:#}", error).contains("lookup failed"),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn excluded_keys_of_a_mirrored_config_are_not_written() {
+    let source = replay_client(
+        "mirror-source",
+        &[
+            list_environments(&["production"]),
+            list_variables("production", &[("API_URL", "https://api"), ("DEBUG", "1")]),
+            list_secrets("production", &[]),
+        ],
+        false,
+    )
+    .await;
+    let config = config_from_remote(&source, &[], &[]).await.unwrap();
+    assert!(config.environments["production"].contains_key("DEBUG"));
+
+    // A read-only client, so that writing DEBUG would fail the sync.
+    let target = replay_client(
+        "mirror-target",
+        &[
+            list_environments(&["production"]),
+            list_variables("production", &[("API_URL", "https://api")]),
+        ],
+        true,
+    )
+    .await;
+    let syncer = Syncer::new(
+        target,
+        SyncOptions {
+            exclusions: Exclusions::parse("production/DEBUG").unwrap(),
+            ..SyncOptions::default()
+        },
+    );
+
+    let report = syncer.sync(&config, None).await.unwrap();
+    assert!(!report.has_changes(), "{:?}", report);
+}
@@ -0,0 +1,47 @@
+//! Tests for `validate`'s checks of a config document against Github's rules
+//! for names and values.
+
+use gh_env_sync::{parse_config, validation::config_rejections};
+
+fn rejections(contents: &str) -> Vec<String> {
+    config_rejections(&parse_config(contents).unwrap(), "_")
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn rejects_names_github_refuses() {
+    assert_eq!(
+        rejections(
+            r#"
+            [production]
+            GITHUB_SHA = "abc"
+            "1ST_KEY" = "a"
+            OK = "b"
+            _NOTE = "not synced, so not checked"
+
+            [org_secrets]
+            "NPM-TOKEN" = { value = "hunter2" }
+            "#
+        ),
+        vec![
+            "1ST_KEY in environment 'production' can't start with a digit",
+            "GITHUB_SHA in environment 'production' can't start with GITHUB_, which Github reserves",
+            "organization secret NPM-TOKEN can only contain letters, digits and underscores",
+        ]
+    );
+}
+
+#[test]
+fn rejects_values_too_large() {
+    let contents = format!("[production]\nBIG = \"{}\"\n", "x".repeat(49 * 1024));
+    let rejections = rejections(&contents);
+    assert_eq!(rejections.len(), 1);
+    assert!(rejections[0].starts_with("BIG in environment 'production' is"));
+}
+
+#[test]
+fn accepts_a_valid_document() {
+    assert!(rejections("[production]\nAPI_URL = \"https://example.com\"\n").is_empty());
+}